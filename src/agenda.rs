@@ -0,0 +1,262 @@
+//! Today's events from iCalendar feeds (`CALENDAR_URLS`), for the briefing.
+//!
+//! Times with a `TZID` are taken as local time. Daily, weekly, monthly and yearly
+//! recurrences are expanded with `INTERVAL`, `UNTIL`, `COUNT`, weekly `BYDAY` and
+//! `EXDATE`; other rule parts are ignored.
+
+use crate::error::JobError;
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Weekday};
+
+/// The start or end of an event.
+#[derive(Clone, Copy)]
+enum When {
+    /// An all-day event's date.
+    Day(NaiveDate),
+    Time(NaiveDateTime),
+}
+
+impl When {
+    fn date(self) -> NaiveDate {
+        match self {
+            When::Day(date) => date,
+            When::Time(time) => time.date(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+struct Rule {
+    frequency: Frequency,
+    interval: i64,
+    by_day: Vec<Weekday>,
+    until: Option<NaiveDate>,
+    count: Option<u32>,
+}
+
+#[derive(Default)]
+struct Event {
+    summary: String,
+    location: Option<String>,
+    start: Option<When>,
+    end: Option<When>,
+    rule: Option<Rule>,
+    exdates: Vec<NaiveDate>,
+}
+
+/// One line of the agenda.
+pub struct Entry {
+    /// `09:00-09:30`, or `All day`.
+    pub when: String,
+    /// The summary, with the location after an `@`.
+    pub what: String,
+    sort_key: Option<NaiveTime>,
+}
+
+/// Today's events from every feed in `urls`, all-day ones first. Fails if any feed does.
+pub async fn today(urls: &[String]) -> Result<Vec<Entry>, JobError> {
+    let today = Local::now().date_naive();
+    let mut tasks = tokio::task::JoinSet::new();
+    for url in urls {
+        let url = url.clone();
+        tasks.spawn(async move { fetch(&url).await });
+    }
+    let mut entries = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let body = joined.map_err(|e| JobError::Upstream(format!("calendar fetch failed: {}", e)))??;
+        entries.extend(parse(&body).iter().filter(|event| occurs_on(event, today)).map(entry));
+    }
+    entries.sort_by_key(|entry| entry.sort_key);
+    Ok(entries)
+}
+
+async fn fetch(url: &str) -> Result<String, JobError> {
+    reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .text()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))
+}
+
+fn entry(event: &Event) -> Entry {
+    let (when, sort_key) = match (event.start, event.end) {
+        (Some(When::Time(start)), Some(When::Time(end))) if end.date() == start.date() => {
+            (format!("{}-{}", start.format("%H:%M"), end.format("%H:%M")), Some(start.time()))
+        }
+        (Some(When::Time(start)), _) => (start.format("%H:%M").to_string(), Some(start.time())),
+        _ => ("All day".to_owned(), None),
+    };
+    let what = match &event.location {
+        Some(location) => format!("{} @ {}", event.summary, location),
+        None => event.summary.clone(),
+    };
+    Entry { when, what, sort_key }
+}
+
+/// Joins folded lines (RFC 5545 section 3.1) back together.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("\\n", " ").replace("\\N", " ").replace("\\,", ",").replace("\\;", ";").replace("\\\\", "\\")
+}
+
+/// `20261016` as a day, `20261016T090000Z` as local time, and `20261016T090000` as is.
+fn parse_when(params: &str, value: &str) -> Option<When> {
+    if (params.contains("VALUE=DATE") && !params.contains("VALUE=DATE-TIME")) || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(When::Day);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(When::Time(time.and_utc().with_timezone(&Local).naive_local()));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok().map(When::Time)
+}
+
+fn weekday(code: &str) -> Option<Weekday> {
+    // Weekly rules don't use ordinals like the `1` in `1MO`, so they're dropped.
+    match code.trim_start_matches(|c: char| c.is_ascii_digit() || c == '+' || c == '-') {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_rule(value: &str) -> Option<Rule> {
+    let mut rule = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        by_day: Vec::new(),
+        until: None,
+        count: None,
+    };
+    let mut frequency = None;
+    for part in value.split(';') {
+        let (name, value) = part.split_once('=')?;
+        match name {
+            "FREQ" => {
+                frequency = match value {
+                    "DAILY" => Some(Frequency::Daily),
+                    "WEEKLY" => Some(Frequency::Weekly),
+                    "MONTHLY" => Some(Frequency::Monthly),
+                    "YEARLY" => Some(Frequency::Yearly),
+                    _ => None,
+                }
+            }
+            "INTERVAL" => rule.interval = value.parse().ok().filter(|&n: &i64| n > 0)?,
+            "BYDAY" => rule.by_day = value.split(',').filter_map(weekday).collect(),
+            "UNTIL" => rule.until = NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok(),
+            "COUNT" => rule.count = value.parse().ok(),
+            _ => {}
+        }
+    }
+    rule.frequency = frequency?;
+    Some(rule)
+}
+
+fn parse(text: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    for line in unfold(text) {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, params) = name.split_once(';').unwrap_or((name, ""));
+        match (name, event.as_mut()) {
+            ("BEGIN", None) if value == "VEVENT" => event = Some(Event::default()),
+            ("END", Some(_)) if value == "VEVENT" => events.extend(event.take()),
+            ("SUMMARY", Some(event)) => event.summary = unescape(value),
+            ("LOCATION", Some(event)) => event.location = Some(unescape(value)).filter(|l| !l.is_empty()),
+            ("DTSTART", Some(event)) => event.start = parse_when(params, value),
+            ("DTEND", Some(event)) => event.end = parse_when(params, value),
+            ("RRULE", Some(event)) => event.rule = parse_rule(value),
+            ("EXDATE", Some(event)) => {
+                event.exdates.extend(value.split(',').filter_map(|value| parse_when(params, value)).map(When::date))
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Whether `day` is in the `rule`'s pattern starting at `first`, ignoring its end.
+fn in_pattern(rule: &Rule, first: NaiveDate, day: NaiveDate) -> bool {
+    let monday = |date: NaiveDate| date - TimeDelta::days(date.weekday().num_days_from_monday() as i64);
+    let months = |date: NaiveDate| date.year() as i64 * 12 + date.month0() as i64;
+    match rule.frequency {
+        Frequency::Daily => (day - first).num_days() % rule.interval == 0,
+        Frequency::Weekly => {
+            let weeks = (monday(day) - monday(first)).num_days() / 7;
+            let weekday_matches = if rule.by_day.is_empty() {
+                day.weekday() == first.weekday()
+            } else {
+                rule.by_day.contains(&day.weekday())
+            };
+            weeks % rule.interval == 0 && weekday_matches
+        }
+        Frequency::Monthly => (months(day) - months(first)) % rule.interval == 0 && day.day() == first.day(),
+        Frequency::Yearly => {
+            (day.year() - first.year()) as i64 % rule.interval == 0 && (day.month(), day.day()) == (first.month(), first.day())
+        }
+    }
+}
+
+fn occurs_on(event: &Event, day: NaiveDate) -> bool {
+    let Some(start) = event.start else {
+        return false;
+    };
+    let first = start.date();
+    if day < first {
+        return false;
+    }
+    let Some(rule) = &event.rule else {
+        return match (start, event.end) {
+            // All-day events end the day before their DTEND.
+            (When::Day(_), Some(end)) => day < end.date().max(first.succ_opt().unwrap_or(first)),
+            _ => day == first,
+        };
+    };
+    // Walk the occurrences up to `day` so `COUNT` is honoured.
+    let mut occurrences = 0;
+    let mut date = first;
+    while date <= day {
+        if rule.until.is_some_and(|until| date > until) {
+            return false;
+        }
+        if date == first || in_pattern(rule, first, date) {
+            occurrences += 1;
+            if rule.count.is_some_and(|count| occurrences > count) {
+                return false;
+            }
+            if date == day {
+                return !event.exdates.contains(&day);
+            }
+        }
+        let Some(next) = date.succ_opt() else {
+            return false;
+        };
+        date = next;
+    }
+    false
+}
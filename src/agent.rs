@@ -0,0 +1,410 @@
+//! Printers behind NAT, fed by a `print-jobber agent` running next to them: the agent
+//! long-polls `GET /agent/{printer}/next` on this server for each job's ESC/POS bytes,
+//! prints them and reports back, so the server can live in the cloud without a port
+//! forwarded to the printer. On the server such a printer is `PRINTERS=kitchen=agent`,
+//! or `PRINTER_AGENT` for the main one; it's laid out with the server's `PRINTER_PROFILE`.
+//!
+//! `print-jobber agent [--server URL] [--key KEY] [--printer NAME]` is the agent, printing
+//! on the printer its own environment configures.
+
+use crate::{AppState, config::Config, diagnostics::Diagnostics, printer::{Device, MAIN_PRINTER, PrinterSlot}};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use escpos::{driver::Driver, errors::Result as EscposResult};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+use utoipa::{IntoParams, ToSchema};
+
+const USAGE: &str = "usage: print-jobber agent [--server URL] [--key KEY] [--printer NAME]";
+/// Header carrying the number to report a job's outcome under.
+const JOB_HEADER: &str = "x-agent-job";
+/// How long an agent's poll is held open when there's no job, at most.
+const MAX_WAIT_SECONDS: u64 = 60;
+/// What the agent asks to wait; NAT gateways tend to drop idle connections after a minute.
+const POLL_SECONDS: u64 = 30;
+/// An agent between polls, e.g. reporting on a job or reconnecting, still counts as
+/// there for this long. Jobs for a printer whose agent is gone fail straight away, so
+/// failover and retries kick in.
+const RECONNECT_GRACE: Duration = Duration::from_secs(10);
+/// How long a job waits for the agent to take and print it.
+const JOB_TIMEOUT: Duration = Duration::from_secs(60);
+/// Pause before polling again after the server couldn't be reached.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Inbox {
+    /// Bytes written since the last flush.
+    pending: Vec<u8>,
+    /// Numbers the jobs, so a late report can't settle a newer one.
+    last_job: u64,
+    /// A job waiting for the agent to take it.
+    waiting: Option<(u64, Vec<u8>)>,
+    /// The job the agent took and hasn't reported on yet.
+    taken: Option<u64>,
+    outcome: Option<Result<(), String>>,
+    /// Polls open right now.
+    polls: usize,
+    last_poll: Option<Instant>,
+}
+
+impl Inbox {
+    fn connected(&self) -> bool {
+        self.polls > 0 || self.last_poll.is_some_and(|at| at.elapsed() < RECONNECT_GRACE)
+    }
+}
+
+struct Shared {
+    printer: String,
+    inbox: Mutex<Inbox>,
+    /// Wakes the printing job when the agent reports.
+    reported: Condvar,
+    /// Wakes a poll when a job comes in.
+    posted: Notify,
+}
+
+/// The printer end of an agent: each flush hands the receipt to the agent and waits
+/// until it says how printing went.
+#[derive(Clone)]
+pub struct AgentDriver {
+    shared: Arc<Shared>,
+}
+
+/// Counts an open poll, until the request ends or its client goes away.
+struct Polling<'a>(&'a AgentDriver);
+
+impl Drop for Polling<'_> {
+    fn drop(&mut self) {
+        let mut inbox = self.0.inbox();
+        inbox.polls -= 1;
+        inbox.last_poll = Some(Instant::now());
+    }
+}
+
+impl AgentDriver {
+    pub fn new(printer: &str) -> Self {
+        AgentDriver {
+            shared: Arc::new(Shared {
+                printer: printer.to_owned(),
+                inbox: Mutex::new(Inbox::default()),
+                reported: Condvar::new(),
+                posted: Notify::new(),
+            }),
+        }
+    }
+
+    fn inbox(&self) -> MutexGuard<'_, Inbox> {
+        self.shared.inbox.lock().unwrap()
+    }
+
+    /// The next job's number and bytes, once there is one, or `None` after `wait`.
+    async fn take(&self, wait: Duration) -> Option<(u64, Vec<u8>)> {
+        let deadline = tokio::time::Instant::now() + wait;
+        self.inbox().polls += 1;
+        let _polling = Polling(self);
+        loop {
+            {
+                let mut inbox = self.inbox();
+                if let Some((job, bytes)) = inbox.waiting.take() {
+                    inbox.taken = Some(job);
+                    return Some((job, bytes));
+                }
+            }
+            // A job posted since the check has left a permit, so it isn't missed.
+            tokio::time::timeout_at(deadline, self.shared.posted.notified()).await.ok()?;
+        }
+    }
+
+    /// Settles the job numbered `job`; `false` if it isn't the one the agent took, e.g.
+    /// because it timed out meanwhile.
+    fn report(&self, job: u64, outcome: Result<(), String>) -> bool {
+        let mut inbox = self.inbox();
+        if inbox.taken != Some(job) {
+            return false;
+        }
+        inbox.taken = None;
+        inbox.outcome = Some(outcome);
+        self.shared.reported.notify_all();
+        true
+    }
+}
+
+impl Driver for AgentDriver {
+    fn name(&self) -> String {
+        format!("Agent ({})", self.shared.printer)
+    }
+
+    fn write(&self, data: &[u8]) -> EscposResult<()> {
+        self.inbox().pending.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Nothing to answer queries with, e.g. for the model name.
+    fn read(&self, _buf: &mut [u8]) -> EscposResult<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> EscposResult<()> {
+        let mut inbox = self.inbox();
+        let bytes = std::mem::take(&mut inbox.pending);
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if !inbox.connected() {
+            let message = format!("no agent is polling for printer {}", self.shared.printer);
+            return Err(io::Error::new(io::ErrorKind::NotConnected, message).into());
+        }
+        inbox.last_job += 1;
+        let job = inbox.last_job;
+        inbox.waiting = Some((job, bytes));
+        inbox.outcome = None;
+        self.shared.posted.notify_one();
+        let (mut inbox, _) = self.shared.reported.wait_timeout_while(inbox, JOB_TIMEOUT, |inbox| inbox.outcome.is_none()).unwrap();
+        inbox.waiting = None;
+        inbox.taken = None;
+        match inbox.outcome.take() {
+            Some(Ok(())) => Ok(()),
+            Some(Err(e)) => Err(io::Error::other(format!("agent for printer {} failed: {}", self.shared.printer, e)).into()),
+            None => {
+                let message = format!("agent for printer {} didn't print job {} in time", self.shared.printer, job);
+                Err(io::Error::new(io::ErrorKind::TimedOut, message).into())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct NextParams {
+    /// Seconds to hold the request open for a job, up to 60.
+    #[serde(default = "default_wait")]
+    #[param(default = default_wait, maximum = 60)]
+    wait: u64,
+}
+
+fn default_wait() -> u64 {
+    POLL_SECONDS
+}
+
+/// `GET /agent/{printer}/next`: the next job's ESC/POS bytes, with its number in
+/// [`JOB_HEADER`], or 204 if none came in while waiting.
+#[utoipa::path(
+    get,
+    path = "/agent/{printer}/next",
+    tag = "admin",
+    summary = "Wait for a job for a printer behind an agent",
+    description = "Long-polled by `print-jobber agent` running next to the printer, so the printer needs no open \
+        port. Report how printing went to `/agent/{printer}/jobs/{job}`. A printer with no agent polling fails \
+        its jobs at once.",
+    params(
+        ("printer" = String, Path, description = "A printer set up as `agent` in `PRINTERS`, or `default` with `PRINTER_AGENT`."),
+        NextParams,
+    ),
+    responses(
+        (status = 200, description = "The job's ESC/POS bytes.", body = Vec<u8>, content_type = "application/octet-stream",
+            headers(("x-agent-job" = u64, description = "The job's number."))),
+        (status = 204, description = "No job came in while waiting."),
+        (status = 404, description = "No such agent printer."),
+    )
+)]
+pub async fn next(State(state): State<AppState>, Path(printer): Path<String>, Query(params): Query<NextParams>) -> Result<Response, StatusCode> {
+    let Some(agent) = state.agents.get(&printer) else {
+        eprintln!("Agent polled for {:?}, which isn't an agent printer", printer);
+        return Err(StatusCode::NOT_FOUND);
+    };
+    match agent.take(Duration::from_secs(params.wait.min(MAX_WAIT_SECONDS))).await {
+        Some((job, bytes)) => {
+            eprintln!("Agent took job {} for printer {} ({} bytes)", job, printer, bytes.len());
+            let headers = [(CONTENT_TYPE, "application/octet-stream".to_owned()), (JOB_HEADER.parse().unwrap(), job.to_string())];
+            Ok((headers, bytes).into_response())
+        }
+        None => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+/// How printing a job went, as the agent reports it.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Outcome {
+    /// Why the job didn't print; absent if it did.
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// `POST /agent/{printer}/jobs/{job}`: the agent's report on a job it took.
+#[utoipa::path(
+    post,
+    path = "/agent/{printer}/jobs/{job}",
+    tag = "admin",
+    summary = "Report how printing a job went",
+    params(
+        ("printer" = String, Path, description = "A printer set up as `agent` in `PRINTERS`, or `default` with `PRINTER_AGENT`."),
+        ("job" = u64, Path, description = "The number from `x-agent-job`."),
+    ),
+    responses(
+        (status = 204, description = "Recorded."),
+        (status = 404, description = "No such agent printer, or the job isn't waiting on a report, e.g. because it \
+            timed out."),
+    )
+)]
+pub async fn report(State(state): State<AppState>, Path((printer, job)): Path<(String, u64)>, Json(outcome): Json<Outcome>) -> StatusCode {
+    let Some(agent) = state.agents.get(&printer) else {
+        return StatusCode::NOT_FOUND;
+    };
+    if let Some(error) = &outcome.error {
+        eprintln!("Agent for printer {} failed job {}: {}", printer, job, error);
+    }
+    match agent.report(job, outcome.error.map_or(Ok(()), Err)) {
+        true => StatusCode::NO_CONTENT,
+        false => {
+            eprintln!("Agent reported on job {} for printer {}, which isn't waiting", job, printer);
+            StatusCode::NOT_FOUND
+        }
+    }
+}
+
+struct AgentArgs {
+    server: Option<String>,
+    key: Option<String>,
+    printer: String,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<AgentArgs, String> {
+    let mut parsed = AgentArgs {
+        server: std::env::var("PRINT_JOBBER_SERVER").ok(),
+        key: std::env::var("PRINT_JOBBER_KEY").ok(),
+        printer: MAIN_PRINTER.to_owned(),
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--server" => parsed.server = Some(args.next().ok_or("--server needs a URL")?),
+            "--key" => parsed.key = Some(args.next().ok_or("--key needs an API key")?),
+            "--printer" => parsed.printer = args.next().ok_or("--printer needs a printer name")?,
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            other => return Err(format!("unknown argument {}\n{}", other, USAGE)),
+        }
+    }
+    Ok(parsed)
+}
+
+enum Failure {
+    /// The server is unreachable or having trouble; worth trying again.
+    Retry(String),
+    /// The server turned the agent away, e.g. for a wrong key or printer name.
+    Fatal(String),
+}
+
+async fn poll(client: &reqwest::Client, url: &str, key: Option<&str>) -> Result<Option<(u64, Bytes)>, Failure> {
+    let mut request = client.get(url).timeout(Duration::from_secs(POLL_SECONDS + 15));
+    if let Some(key) = key {
+        request = request.bearer_auth(key);
+    }
+    let response = request.send().await.map_err(|e| Failure::Retry(format!("can't reach {}: {}", url, e)))?;
+    match response.status() {
+        StatusCode::NO_CONTENT => Ok(None),
+        StatusCode::OK => {
+            let job = response
+                .headers()
+                .get(JOB_HEADER)
+                .and_then(|value| value.to_str().ok()?.parse().ok())
+                .ok_or_else(|| Failure::Retry(format!("job from {} has no {} header", url, JOB_HEADER)))?;
+            let bytes = response.bytes().await.map_err(|e| Failure::Retry(format!("job {} broke off: {}", job, e)))?;
+            Ok(Some((job, bytes)))
+        }
+        status @ (StatusCode::UNAUTHORIZED | StatusCode::NOT_FOUND) => Err(Failure::Fatal(format!("server refused the agent: {}", status))),
+        status => Err(Failure::Retry(format!("server answered {}", status))),
+    }
+}
+
+async fn send_outcome(client: &reqwest::Client, url: &str, key: Option<&str>, outcome: &Outcome) -> Result<(), String> {
+    let mut request = client.post(url).json(outcome);
+    if let Some(key) = key {
+        request = request.bearer_auth(key);
+    }
+    let response = request.send().await.map_err(|e| format!("can't reach {}: {}", url, e))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("server didn't take the report: {}", status)),
+    }
+}
+
+/// Sends a job's bytes as they are: the server already laid them out for this printer.
+fn print_job(slot: &PrinterSlot, bytes: &[u8]) -> Result<(), String> {
+    let Some(mut printer) = slot.acquire() else {
+        return Err("printer not connected".to_owned());
+    };
+    printer.custom(bytes).and_then(|printer| printer.print()).map(|_| ()).map_err(|e| {
+        slot.disconnect();
+        e.to_string()
+    })
+}
+
+/// Runs the `agent` subcommand with the arguments after `agent`. Polls until the server
+/// turns it away, returning the exit code.
+pub async fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 2;
+        }
+    };
+    let Some(server) = args.server else {
+        eprintln!("--server or PRINT_JOBBER_SERVER is needed\n{}", USAGE);
+        return 2;
+    };
+    let config = Config::from_env();
+    let device = crate::printer_device(&config);
+    if matches!(device, Device::Agent) {
+        eprintln!("PRINTER_AGENT is set here too: the agent needs a printer of its own");
+        return 2;
+    }
+    let slot = match tokio::task::spawn_blocking(move || PrinterSlot::new(device, &config.printer_profile, Diagnostics::default())).await {
+        Ok(slot) => slot,
+        Err(e) => {
+            eprintln!("can't open the printer: {}", e);
+            return 1;
+        }
+    };
+
+    let base = format!("{}/agent/{}", server.trim_end_matches('/'), args.printer);
+    let next = format!("{}/next?wait={}", base, POLL_SECONDS);
+    let key = args.key.as_deref();
+    let client = reqwest::Client::new();
+    eprintln!("Polling {} for printer {}", server, args.printer);
+    loop {
+        let (job, bytes) = match poll(&client, &next, key).await {
+            Ok(Some(job)) => job,
+            Ok(None) => continue,
+            Err(Failure::Retry(message)) => {
+                eprintln!("{}", message);
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+            Err(Failure::Fatal(message)) => {
+                eprintln!("{}", message);
+                return 1;
+            }
+        };
+        eprintln!("Printing job {} ({} bytes)", job, bytes.len());
+        let printing = slot.clone();
+        let printed = tokio::task::spawn_blocking(move || print_job(&printing, &bytes))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+        if let Err(e) = &printed {
+            eprintln!("Job {} failed: {}", job, e);
+        }
+        let outcome = Outcome { error: printed.err() };
+        if let Err(message) = send_outcome(&client, &format!("{}/jobs/{}", base, job), key, &outcome).await {
+            eprintln!("{}", message);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    MAX_TAB_WIDTH, PrintParams,
+    receipt::{Receipt, Style},
+};
+
+/// A run of text in one style.
+struct Span {
+    text: String,
+    style: Style,
+}
+
+/// Applies an SGR (`ESC [ ... m`) parameter list. Colours are dropped, including the
+/// extended `38;5;n` and `38;2;r;g;b` forms, whose arguments would otherwise read as styles.
+fn apply_sgr(style: &mut Style, params: &str) {
+    let codes: Vec<u32> = params.split(';').map(|code| code.parse().unwrap_or(0)).collect();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 => *style = Style::default(),
+            1 => style.bold = true,
+            22 => style.bold = false,
+            4 => style.underline = true,
+            24 => style.underline = false,
+            7 => style.inverse = true,
+            27 => style.inverse = false,
+            38 | 48 | 58 => match codes.get(i + 1) {
+                Some(5) => i += 2,
+                Some(2) => i += 4,
+                _ => {}
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+/// Splits one line of terminal output into styled spans, carrying `style` over from the
+/// previous line. Other escape sequences (cursor movement, hyperlinks) and control
+/// characters are stripped; tabs expand to `tab_width` stops.
+fn parse_line(line: &str, style: &mut Style, tab_width: usize) -> Vec<Span> {
+    let mut spans = vec![Span {
+        text: String::new(),
+        style: *style,
+    }];
+    let mut column = 0;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to a final byte in @..~
+                Some('[') => {
+                    let mut params = String::new();
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            if c == 'm' {
+                                apply_sgr(style, &params);
+                                spans.push(Span {
+                                    text: String::new(),
+                                    style: *style,
+                                });
+                            }
+                            break;
+                        }
+                        params.push(c);
+                    }
+                }
+                // OSC: up to BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' => {
+                let spaces = tab_width - column % tab_width;
+                spans.last_mut().unwrap().text.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            c if c.is_control() => {}
+            c => {
+                spans.last_mut().unwrap().text.push(c);
+                column += 1;
+            }
+        }
+    }
+    spans.retain(|span| !span.text.is_empty());
+    spans
+}
+
+/// Prints terminal output with bold, underline and inverse mapped to the printer's styles.
+/// Lines longer than the paper are broken at the edge (unless `raw`) rather than word-wrapped,
+/// since CLI output is usually laid out in columns.
+pub fn render(receipt: &mut Receipt, text: &str, params: &PrintParams) {
+    let mut style = Style::default();
+    for line in text.lines() {
+        let mut column = 0;
+        for span in parse_line(line, &mut style, params.tab_width.clamp(1, MAX_TAB_WIDTH)) {
+            let mut text = String::with_capacity(span.text.len());
+            for c in span.text.chars() {
+                if column == receipt.width() && !params.raw {
+                    text.push('\n');
+                    column = 0;
+                }
+                text.push(c);
+                column += 1;
+            }
+            receipt.style(span.style);
+            receipt.text(&text);
+        }
+        receipt.style(Style::default());
+        receipt.text("\n");
+    }
+}
@@ -0,0 +1,162 @@
+//! `/apod`: NASA's Astronomy Picture of the Day, dithered to the paper width, with the
+//! explanation cut to `APOD_MAX_CHARS`.
+
+use crate::{
+    AppState,
+    error::JobError,
+    raster::{self, Dither, ImageOptions},
+    receipt::{Receipt, wrap},
+    source::Source,
+    timefmt,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+const API_BASE: &str = "https://api.nasa.gov";
+
+#[derive(Deserialize, IntoParams)]
+pub struct ApodParams {
+    /// An earlier day's picture, e.g. `2024-01-15`; today's when missing.
+    date: Option<NaiveDate>,
+    /// How to turn the picture's greys into dots.
+    #[serde(default = "default_dither")]
+    #[param(inline, default = "floyd-steinberg")]
+    dither: Dither,
+}
+
+fn default_dither() -> Dither {
+    Dither::FloydSteinberg
+}
+
+#[derive(Deserialize)]
+struct Apod {
+    date: NaiveDate,
+    title: String,
+    explanation: String,
+    /// `image` or `video`.
+    media_type: String,
+    url: String,
+    /// Set for videos when asked for with `thumbs=true`.
+    thumbnail_url: Option<String>,
+    copyright: Option<String>,
+}
+
+/// The API base, or `NASA_URL` when set.
+fn api_base(state: &AppState) -> &str {
+    state.config.nasa_url.as_deref().unwrap_or(API_BASE).trim_end_matches('/')
+}
+
+#[utoipa::path(
+    get,
+    path = "/apod",
+    tag = "printing",
+    summary = "Print NASA's Astronomy Picture of the Day",
+    description = "The title, the picture dithered to the paper width (a video's thumbnail and a QR code linking to \
+        it), and the explanation cut at a word to `APOD_MAX_CHARS` (600 by default; 0 prints it whole). Needs \
+        `NASA_API_KEY`. Also available as the `apod` scheduled job.",
+    params(ApodParams),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 502, description = "NASA failed, has no picture for `date`, or the picture can't be decoded."),
+        (status = 503, description = "`NASA_API_KEY` isn't set, or the printer is down for maintenance; see `Retry-After`."),
+    )
+)]
+pub async fn apod(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<ApodParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_apod(&state, &source, params.date, params.dither).await?)
+}
+
+/// Errors leave out the URL, which carries the API key.
+async fn get(url: &str) -> Result<reqwest::Response, JobError> {
+    reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))
+}
+
+/// `text` cut to at most `max` characters at a word boundary, with "..." when cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_owned();
+    }
+    let mut cut = String::new();
+    for word in text.split_whitespace() {
+        if cut.chars().count() + word.chars().count() + 4 > max {
+            break;
+        }
+        if !cut.is_empty() {
+            cut.push(' ');
+        }
+        cut.push_str(word);
+    }
+    format!("{}...", cut.trim_end_matches([',', ';', ':', '.']))
+}
+
+pub async fn print_apod(state: &AppState, source: &Source, date: Option<NaiveDate>, dither: Dither) -> Result<(), JobError> {
+    let api_key = state.config.nasa_api_key.as_deref().ok_or(JobError::NotConfigured("NASA_API_KEY"))?;
+    eprintln!("APOD request for {}", date.map_or("today".to_owned(), |date| date.to_string()));
+    let mut url = format!("{}/planetary/apod?api_key={}&thumbs=true", api_base(state), api_key);
+    if let Some(date) = date {
+        url.push_str(&format!("&date={}", date));
+    }
+    let fetched = async {
+        let apod = get(&url).await?.json::<Apod>().await.map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+        let image_url = match apod.media_type.as_str() {
+            "image" => Some(&apod.url),
+            _ => apod.thumbnail_url.as_ref(),
+        };
+        let image = match image_url {
+            Some(url) => Some(get(url).await?.bytes().await.map_err(|e| JobError::Upstream(e.to_string()))?),
+            None => None,
+        };
+        Ok((apod, image))
+    };
+    let (apod, image) = fetched.await.inspect_err(|e: &JobError| {
+        eprintln!("Failed to fetch APOD: {}", e);
+        state.diagnostics.record_error("apod", e.to_string());
+    })?;
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("ASTRONOMY PICTURE OF THE DAY");
+    receipt.line_center(&timefmt::long_date(&state.config, apod.date));
+    receipt.divider();
+    for line in wrap(&apod.title, receipt.width()) {
+        receipt.line_center(&line);
+    }
+    if let Some(image) = image {
+        let raster_width = state.config.printer_profile.raster_width as u32;
+        let options = ImageOptions {
+            width: Some(raster_width),
+            paper_width: Some(raster_width),
+            dither,
+            ..Default::default()
+        };
+        let bitmap = raster::decode_image(&image, &options)
+            .map_err(|e| JobError::Upstream(format!("undecodable APOD image for {}: {}", apod.date, e)))?;
+        receipt.image(&bitmap, &format!("[picture: {}]", apod.title));
+    }
+    if apod.media_type != "image" {
+        // Videos can't be printed; link to them instead.
+        receipt.line_center("Watch the video:");
+        receipt.qr_code(&apod.url, 4);
+    }
+    if let Some(copyright) = &apod.copyright {
+        receipt.line_center(&format!("(c) {}", copyright.trim()));
+    }
+    receipt.divider();
+    match state.config.apod_max_chars {
+        Some(max) => receipt.wrapped(&truncate(&apod.explanation, max)),
+        None => receipt.wrapped(&apod.explanation),
+    }
+
+    state.print(receipt, "apod", source);
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! With `ARCHIVE_JOBS`, every job's receipt is written to `archive/` in the data
+//! directory as it was rendered, so `/jobs/{id}/content` and reprints work after a
+//! restart and reprint byte for byte. Receipts are stored once per SHA-256 under
+//! `objects/`; `index.jsonl` maps each job ID to its receipt's hash.
+
+use crate::{
+    history::{JobRecord, JobStatus},
+    receipt::Receipt,
+    source::Source,
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchivedJob {
+    pub id: u64,
+    /// RFC 3339 local time the job was submitted.
+    pub at: String,
+    pub namespace: String,
+    pub job: String,
+    pub source: String,
+    #[serde(default)]
+    pub lines: usize,
+    /// SHA-256 of the receipt, naming its file under `objects/`.
+    pub hash: String,
+}
+
+impl ArchivedJob {
+    /// The job as `/jobs` lists it; how it printed isn't archived.
+    pub fn to_record(&self) -> JobRecord {
+        JobRecord {
+            id: self.id,
+            at: self.at.clone(),
+            namespace: self.namespace.clone(),
+            job: self.job.clone(),
+            source: self.source.clone(),
+            lines: self.lines,
+            status: JobStatus::Archived,
+            scheduled_for: None,
+            error: None,
+            paper_mm: None,
+            targets: Vec::new(),
+            failover: None,
+            upstream_id: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct JobArchive {
+    /// `None` when archiving is off.
+    dir: Option<PathBuf>,
+    index: Arc<Mutex<Vec<ArchivedJob>>>,
+}
+
+impl JobArchive {
+    /// Reads the index in `dir`, or archives nothing when `dir` is `None`.
+    pub fn load(dir: Option<PathBuf>) -> Self {
+        let index = dir
+            .as_ref()
+            .map(|dir| {
+                std::fs::read_to_string(dir.join("index.jsonl"))
+                    .unwrap_or_default()
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        JobArchive {
+            dir,
+            index: Arc::new(Mutex::new(index)),
+        }
+    }
+
+    /// The highest archived job ID, for numbering new jobs after a restart.
+    pub fn last_id(&self) -> u64 {
+        self.index.lock().unwrap().iter().map(|job| job.id).max().unwrap_or(0)
+    }
+
+    fn object_path(dir: &Path, hash: &str) -> PathBuf {
+        dir.join("objects").join(&hash[..2]).join(format!("{}.json", hash))
+    }
+
+    /// Writes `receipt` unless an identical one is already archived, and indexes it as job `id`.
+    pub fn store(&self, id: u64, job: &str, source: &Source, lines: usize, receipt: &Receipt) {
+        let Some(dir) = &self.dir else { return };
+        let entry = || -> io::Result<ArchivedJob> {
+            let json = serde_json::to_vec(receipt)?;
+            let hash: String = Sha256::digest(&json).iter().map(|b| format!("{:02x}", b)).collect();
+            let path = Self::object_path(dir, &hash);
+            if !path.exists() {
+                std::fs::create_dir_all(path.parent().unwrap_or(dir))?;
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, &json)?;
+                std::fs::rename(&tmp, &path)?;
+            }
+            let entry = ArchivedJob {
+                id,
+                at: Local::now().to_rfc3339(),
+                namespace: source.namespace().to_owned(),
+                job: job.to_owned(),
+                source: source.to_string(),
+                lines,
+                hash,
+            };
+            let mut index = std::fs::OpenOptions::new().create(true).append(true).open(dir.join("index.jsonl"))?;
+            writeln!(index, "{}", serde_json::to_string(&entry)?)?;
+            Ok(entry)
+        };
+        match entry() {
+            Ok(entry) => self.index.lock().unwrap().push(entry),
+            Err(e) => eprintln!("Failed to archive job #{} in {}: {}", id, dir.display(), e),
+        }
+    }
+
+    /// Archived jobs in `namespace`, oldest first.
+    pub fn jobs(&self, namespace: &str) -> Vec<ArchivedJob> {
+        self.index.lock().unwrap().iter().filter(|job| job.namespace == namespace).cloned().collect()
+    }
+
+    /// The archived receipt with SHA-256 `hash`.
+    pub fn content(&self, hash: &str) -> Option<Receipt> {
+        let path = Self::object_path(self.dir.as_ref()?, hash);
+        std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+            .inspect_err(|e| eprintln!("Failed to read archived receipt {}: {}", path.display(), e))
+            .ok()
+    }
+
+    /// The receipt of job `id` in `namespace`, or of its latest job when `id` is `None`.
+    pub fn receipt(&self, namespace: &str, id: Option<u64>) -> Option<(u64, Receipt)> {
+        let entry = self
+            .index
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|job| id.is_none_or(|id| id == job.id) && job.namespace == namespace)
+            .cloned()?;
+        Some((entry.id, self.content(&entry.hash)?))
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    AppState,
+    error::JobError,
+    html,
+    receipt::{Receipt, wrap},
+    source::Source,
+};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Lines per "page", marked with a centered page number so long articles are easy to tear up.
+const PAGE_LINES: usize = 60;
+/// Paragraphs shorter than this are usually captions, share buttons or bylines.
+const MIN_PARAGRAPH_CHARS: usize = 40;
+const BOILERPLATE: &[&str] = &[
+    "script", "style", "noscript", "nav", "header", "footer", "aside", "form", "figure", "svg", "button",
+];
+
+#[derive(Deserialize, ToSchema)]
+pub struct ArticleRequest {
+    url: String,
+    /// Stop after this many characters of body text.
+    max_chars: Option<usize>,
+}
+
+struct Article {
+    title: String,
+    byline: Option<String>,
+    paragraphs: Vec<String>,
+}
+
+/// A readability-style pass: drop boilerplate elements, then keep the reasonably
+/// long paragraphs of the most specific content container on the page.
+fn extract_article(page: &str) -> Article {
+    let title = html::meta_content(page, "og:title")
+        .or_else(|| html::elements(page, "title").first().map(|t| html::to_text(t)))
+        .filter(|t| !t.is_empty())
+        .unwrap_or("Untitled".to_owned());
+    let byline = html::meta_content(page, "author").or_else(|| html::meta_content(page, "article:author"));
+
+    let cleaned = html::without_elements(page, BOILERPLATE);
+    let paragraphs_in = |container: &str| -> Vec<String> {
+        html::elements(container, "p")
+            .into_iter()
+            .map(html::to_text)
+            .filter(|p| p.chars().count() >= MIN_PARAGRAPH_CHARS)
+            .collect()
+    };
+
+    let paragraphs = ["article", "main", "body"]
+        .iter()
+        .filter_map(|tag| html::elements(&cleaned, tag).into_iter().max_by_key(|c| paragraphs_in(c).len()))
+        .map(paragraphs_in)
+        .find(|paragraphs| !paragraphs.is_empty())
+        .unwrap_or_else(|| paragraphs_in(&cleaned));
+
+    Article {
+        title,
+        byline,
+        paragraphs,
+    }
+}
+
+/// Cuts the body after `max_chars`, ending on a word boundary. Returns how many characters were dropped.
+fn truncate(paragraphs: &mut Vec<String>, max_chars: usize) -> usize {
+    let total: usize = paragraphs.iter().map(|p| p.chars().count()).sum();
+    let mut budget = max_chars;
+    let mut kept = Vec::new();
+    for paragraph in paragraphs.drain(..) {
+        let len = paragraph.chars().count();
+        if len <= budget {
+            budget -= len;
+            kept.push(paragraph);
+            continue;
+        }
+        if budget > 0 {
+            let cut: String = paragraph.chars().take(budget).collect();
+            let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+            kept.push(format!("{}...", cut));
+        }
+        break;
+    }
+    *paragraphs = kept;
+    total.saturating_sub(max_chars)
+}
+
+#[utoipa::path(
+    post,
+    path = "/article",
+    tag = "printing",
+    summary = "Print the main text of a web article",
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn article(
+    State(state): State<AppState>,
+    source: Source,
+    Json(request): Json<ArticleRequest>,
+) -> Result<(), StatusCode> {
+    Ok(print_article(&state, &source, &request.url, request.max_chars).await?)
+}
+
+pub async fn print_article(state: &AppState, source: &Source, url: &str, max_chars: Option<usize>) -> Result<(), JobError> {
+    eprintln!("Article request for {}", url);
+    let page = html::fetch_page(url).await.inspect_err(|e| {
+        eprintln!("Failed to fetch article: {}", e);
+    })?;
+    let mut article = extract_article(&page);
+    if article.paragraphs.is_empty() {
+        eprintln!("No article text found at {}", url);
+        return Err(JobError::Unprocessable(format!("no article text found at {}", url)));
+    }
+    let dropped = max_chars.map_or(0, |max| truncate(&mut article.paragraphs, max));
+
+    let mut receipt = Receipt::for_config(&state.config);
+    for line in wrap(&article.title, receipt.width()) {
+        receipt.line_center(&line);
+    }
+    if let Some(byline) = &article.byline {
+        receipt.line_center(&format!("by {}", byline));
+    }
+    receipt.divider();
+
+    let mut lines = 0;
+    let mut page = 1;
+    for paragraph in &article.paragraphs {
+        for line in wrap(paragraph, receipt.width()) {
+            if lines == PAGE_LINES {
+                page += 1;
+                lines = 0;
+                receipt.line_center(&format!("- {} -", page));
+            }
+            receipt.line_left(&line);
+            lines += 1;
+        }
+        receipt.line_left("");
+        lines += 1;
+    }
+
+    if dropped > 0 {
+        receipt.line_center(&format!("[{} more characters online]", dropped));
+    }
+    receipt.divider();
+    receipt.wrapped(url);
+
+    state.print(receipt, "article", source);
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use crate::{
+    AppState,
+    raster::{self, Bitmap, Dither, ImageOptions},
+    receipt::Receipt,
+    source::Source,
+    storage,
+};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Deserialize, IntoParams)]
+pub struct AssetParams {
+    /// Width in dots to store the logo at; defaults to the image's width, at most the paper width.
+    width: Option<u32>,
+    /// How to turn the image's greys into dots.
+    #[serde(default)]
+    #[param(inline, default = "threshold")]
+    dither: Dither,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AssetInfo {
+    name: String,
+    width: usize,
+    height: usize,
+    /// Key of the copy in the printer's NV graphics memory, if it has been downloaded there.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nv_key: Option<String>,
+}
+
+/// Named logos, stored already converted to black and white as PNGs under
+/// `assets/<namespace>/` in the data directory.
+#[derive(Clone)]
+pub struct Assets {
+    dir: PathBuf,
+    /// Two-character NV graphics keys by `namespace/name`, saved as `nv.json` so the
+    /// server remembers what the printer holds across restarts.
+    nv: Arc<Mutex<HashMap<String, String>>>,
+}
+
+/// Names end up in file paths, so keep them to a safe alphabet.
+fn valid_name(name: &str) -> bool {
+    (1..=64).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Assets {
+    pub fn new(dir: PathBuf) -> Self {
+        Assets {
+            nv: Arc::new(Mutex::new(storage::load_json(&dir.join("nv.json")))),
+            dir,
+        }
+    }
+
+    fn path(&self, namespace: &str, name: &str) -> PathBuf {
+        self.dir.join(namespace).join(format!("{}.png", name))
+    }
+
+    /// The stored logo, if there is one by that name.
+    pub fn load(&self, namespace: &str, name: &str) -> Option<Bitmap> {
+        if !valid_name(name) {
+            return None;
+        }
+        let image = image::open(self.path(namespace, name)).ok()?;
+        Some(Bitmap::from_gray(&image.to_luma8()))
+    }
+
+    /// The key the logo is stored under in the printer's NV graphics memory, if any.
+    pub fn nv_key(&self, namespace: &str, name: &str) -> Option<[u8; 2]> {
+        let nv = self.nv.lock().unwrap();
+        nv.get(&format!("{}/{}", namespace, name)).and_then(|key| key_bytes(key))
+    }
+
+    /// Changes the logo's NV key with `f` and saves the table, returning the old and new keys.
+    fn update_nv(
+        &self,
+        namespace: &str,
+        name: &str,
+        f: impl FnOnce(&HashMap<String, String>) -> Option<String>,
+    ) -> Result<(Option<String>, Option<String>), StatusCode> {
+        let mut nv = self.nv.lock().unwrap();
+        let id = format!("{}/{}", namespace, name);
+        let key = f(&nv);
+        let old = match &key {
+            Some(key) => nv.insert(id, key.clone()),
+            None => nv.remove(&id),
+        };
+        let path = self.dir.join("nv.json");
+        storage::save_json(&path, &*nv).map_err(|e| {
+            eprintln!("Failed to save NV graphics keys to {}: {}", path.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok((old, key))
+    }
+}
+
+/// The logo's existing key, or the first of `A0`..`Z9` no other logo uses.
+fn allocate_key(nv: &HashMap<String, String>, id: &str) -> Option<String> {
+    if let Some(key) = nv.get(id) {
+        return Some(key.clone());
+    }
+    (b'A'..=b'Z')
+        .flat_map(|a| (b'0'..=b'9').map(move |b| String::from_utf8(vec![a, b]).unwrap()))
+        .find(|key| !nv.values().any(|used| used == key))
+}
+
+fn key_bytes(key: &str) -> Option<[u8; 2]> {
+    key.as_bytes().try_into().ok()
+}
+
+/// Queues a job that changes the printer's NV graphics memory without printing anything.
+fn send_nv_command(state: &AppState, source: &Source, data: Vec<u8>, alt: &str) {
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.nv_command(data, alt);
+    receipt.set_plain();
+    receipt.without_cut();
+    state.print(receipt, "nv-graphics", source);
+}
+
+/// Deletes the printer's copy of the logo, e.g. when it was replaced or removed and would print stale.
+fn forget_nv(state: &AppState, source: &Source, name: &str) -> Result<(), StatusCode> {
+    let (old, _) = state.assets.update_nv(source.namespace(), name, |_| None)?;
+    if let Some((key, bytes)) = old.as_deref().and_then(|key| Some((key, key_bytes(key)?))) {
+        eprintln!("Removing NV graphic {} for {}", key, name);
+        send_nv_command(state, source, raster::nv_delete_command(bytes), &format!("[deleted NV graphic {}]", key));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/assets",
+    tag = "assets",
+    summary = "Stored logos",
+    responses((status = 200, description = "OK", body = Vec<AssetInfo>))
+)]
+pub async fn list(State(state): State<AppState>, source: Source) -> Json<Vec<AssetInfo>> {
+    let dir = state.assets.dir.join(source.namespace());
+    let mut assets: Vec<AssetInfo> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_stem()?.to_str()?.to_owned();
+            let (width, height) = image::image_dimensions(&path).ok()?;
+            let nv_key = state
+                .assets
+                .nv_key(source.namespace(), &name)
+                .map(|key| String::from_utf8_lossy(&key).into_owned());
+            Some(AssetInfo {
+                name,
+                width: width as usize,
+                height: height as usize,
+                nv_key,
+            })
+        })
+        .collect();
+    assets.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(assets)
+}
+
+/// Stores the image in the body under `name`, replacing any logo already there.
+#[utoipa::path(
+    put,
+    path = "/assets/{name}",
+    tag = "assets",
+    summary = "Store an image as a logo",
+    description = "Converts the image to black and white and stores it for `?logo=<name>` to print above jobs.",
+    params(
+        ("name" = String, Path, description = "The logo's name: letters, digits, `-` and `_`."),
+        AssetParams,
+    ),
+    request_body(content = Vec<u8>, content_type = "image/*"),
+    responses(
+        (status = 201, description = "Stored.", body = AssetInfo),
+        (status = 400, description = "Invalid name."),
+        (status = 422, description = "The body isn't an image that can be decoded."),
+    )
+)]
+pub async fn upload(
+    State(state): State<AppState>,
+    source: Source,
+    Path(name): Path<String>,
+    Query(params): Query<AssetParams>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<AssetInfo>), StatusCode> {
+    if !valid_name(&name) {
+        eprintln!("Invalid asset name {:?}", name);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let options = ImageOptions {
+        width: params.width,
+        paper_width: Some(state.config.printer_profile.raster_width as u32),
+        dither: params.dither,
+        ..Default::default()
+    };
+    let bitmap = raster::decode_image(&body, &options).map_err(|e| {
+        eprintln!("Failed to decode asset {}: {}", name, e);
+        StatusCode::UNPROCESSABLE_ENTITY
+    })?;
+
+    let path = state.assets.path(source.namespace(), &name);
+    let saved = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .map_err(image::ImageError::IoError)
+        .and_then(|()| bitmap.to_gray().save_with_format(&path, image::ImageFormat::Png));
+    if let Err(e) = saved {
+        eprintln!("Failed to save asset to {}: {}", path.display(), e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    eprintln!("Stored asset {} ({}x{})", name, bitmap.width, bitmap.height);
+    forget_nv(&state, &source, &name)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(AssetInfo {
+            name,
+            width: bitmap.width,
+            height: bitmap.height,
+            nv_key: None,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/assets/{name}",
+    tag = "assets",
+    summary = "Delete a logo",
+    params(("name" = String, Path, description = "The logo's name: letters, digits, `-` and `_`.")),
+    responses(
+        (status = 204, description = "Deleted."),
+        (status = 404, description = "No such logo."),
+    )
+)]
+pub async fn delete(State(state): State<AppState>, source: Source, Path(name): Path<String>) -> Result<StatusCode, StatusCode> {
+    if !valid_name(&name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if std::fs::remove_file(state.assets.path(source.namespace(), &name)).is_err() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    forget_nv(&state, &source, &name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NvGraphic {
+    name: String,
+    nv_key: String,
+}
+
+/// Downloads the logo into the printer's NV graphics memory; from then on `?logo=` prints
+/// it by key instead of streaming the raster data with every job. NV memory wears out,
+/// so this is meant for logos that rarely change.
+#[utoipa::path(
+    put,
+    path = "/assets/{name}/nv",
+    tag = "assets",
+    summary = "Download a logo into the printer's NV graphics memory",
+    description = "From then on `?logo=` prints it by key instead of sending the image with every job. NV memory wears \
+        out, so this is meant for logos that rarely change.",
+    params(("name" = String, Path, description = "The logo's name: letters, digits, `-` and `_`.")),
+    responses(
+        (status = 202, description = "Queued to download.", body = NvGraphic),
+        (status = 404, description = "No such logo."),
+        (status = 507, description = "No free NV graphics keys."),
+    )
+)]
+pub async fn store_nv(State(state): State<AppState>, source: Source, Path(name): Path<String>) -> Result<(StatusCode, Json<NvGraphic>), StatusCode> {
+    let bitmap = state.assets.load(source.namespace(), &name).ok_or(StatusCode::NOT_FOUND)?;
+    let id = format!("{}/{}", source.namespace(), name);
+    let (_, key) = state.assets.update_nv(source.namespace(), &name, |nv| allocate_key(nv, &id))?;
+    let Some((key, bytes)) = key.and_then(|key| Some((key.clone(), key_bytes(&key)?))) else {
+        eprintln!("No free NV graphics keys for {}", name);
+        return Err(StatusCode::INSUFFICIENT_STORAGE);
+    };
+    eprintln!("Downloading {} ({}x{}) to NV graphics as {}", name, bitmap.width, bitmap.height, key);
+    send_nv_command(&state, &source, bitmap.to_nv_definition(bytes), &format!("[stored {} as NV graphic {}]", name, key));
+    Ok((StatusCode::ACCEPTED, Json(NvGraphic { name, nv_key: key })))
+}
+
+/// Deletes the printer's copy of the logo; the logo itself stays in the library.
+#[utoipa::path(
+    delete,
+    path = "/assets/{name}/nv",
+    tag = "assets",
+    summary = "Remove a logo from NV graphics memory",
+    params(("name" = String, Path, description = "The logo's name: letters, digits, `-` and `_`.")),
+    responses(
+        (status = 204, description = "Removed."),
+        (status = 404, description = "Not in NV memory."),
+    )
+)]
+pub async fn delete_nv(State(state): State<AppState>, source: Source, Path(name): Path<String>) -> Result<StatusCode, StatusCode> {
+    if state.assets.nv_key(source.namespace(), &name).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    forget_nv(&state, &source, &name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
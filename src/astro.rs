@@ -0,0 +1,401 @@
+//! Low-precision sun and moon positions, after Vladimir Agafonkin's suncalc
+//! (itself based on http://aa.quae.nl/en/reken/hemelpositie.html formulas).
+//! Sunrise and sunset use NOAA's solar calculator equations instead.
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use std::f64::consts::PI;
+
+const RAD: f64 = PI / 180.0;
+const J1970: f64 = 2440588.0;
+const J2000: f64 = 2451545.0;
+/// Obliquity of the Earth
+const E: f64 = RAD * 23.4397;
+
+fn to_days(date: DateTime<Utc>) -> f64 {
+    date.timestamp_millis() as f64 / 86_400_000.0 - 0.5 + J1970 - J2000
+}
+
+fn right_ascension(l: f64, b: f64) -> f64 {
+    (l.sin() * E.cos() - b.tan() * E.sin()).atan2(l.cos())
+}
+
+fn declination(l: f64, b: f64) -> f64 {
+    (b.sin() * E.cos() + b.cos() * E.sin() * l.sin()).asin()
+}
+
+fn altitude(h: f64, phi: f64, dec: f64) -> f64 {
+    (phi.sin() * dec.sin() + phi.cos() * dec.cos() * h.cos()).asin()
+}
+
+fn sidereal_time(d: f64, lw: f64) -> f64 {
+    RAD * (280.16 + 360.9856235 * d) - lw
+}
+
+fn astro_refraction(h: f64) -> f64 {
+    // The formula works for positive altitudes only
+    let h = h.max(0.0);
+    0.0002967 / (h + 0.00312536 / (h + 0.08901179)).tan()
+}
+
+struct Coords {
+    ra: f64,
+    dec: f64,
+    dist: f64,
+}
+
+fn sun_coords(d: f64) -> Coords {
+    let m = RAD * (357.5291 + 0.98560028 * d);
+    let c = RAD * (1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin());
+    let l = m + c + RAD * 102.9372 + PI;
+    Coords {
+        ra: right_ascension(l, 0.0),
+        dec: declination(l, 0.0),
+        dist: 149_598_000.0,
+    }
+}
+
+fn moon_coords(d: f64) -> Coords {
+    let l = RAD * (218.316 + 13.176396 * d);
+    let m = RAD * (134.963 + 13.064993 * d);
+    let f = RAD * (93.272 + 13.229350 * d);
+
+    let lng = l + RAD * 6.289 * m.sin();
+    let lat = RAD * 5.128 * f.sin();
+    Coords {
+        ra: right_ascension(lng, lat),
+        dec: declination(lng, lat),
+        dist: 385_001.0 - 20_905.0 * m.cos(),
+    }
+}
+
+/// Moon altitude above the horizon in radians, corrected for refraction.
+fn moon_altitude(date: DateTime<Utc>, lat: f64, lon: f64) -> f64 {
+    let d = to_days(date);
+    let c = moon_coords(d);
+    let h = altitude(sidereal_time(d, RAD * -lon) - c.ra, RAD * lat, c.dec);
+    h + astro_refraction(h)
+}
+
+pub struct MoonIllumination {
+    /// Illuminated fraction of the disc, 0.0 to 1.0.
+    pub fraction: f64,
+    /// Position in the cycle: 0.0 new, 0.25 first quarter, 0.5 full, 0.75 last quarter.
+    pub phase: f64,
+}
+
+pub fn moon_illumination(date: DateTime<Utc>) -> MoonIllumination {
+    let d = to_days(date);
+    let s = sun_coords(d);
+    let m = moon_coords(d);
+
+    let phi = (s.dec.sin() * m.dec.sin() + s.dec.cos() * m.dec.cos() * (s.ra - m.ra).cos()).acos();
+    let inc = (s.dist * phi.sin()).atan2(m.dist - s.dist * phi.cos());
+    let angle = (s.dec.cos() * (s.ra - m.ra).sin())
+        .atan2(s.dec.sin() * m.dec.cos() - s.dec.cos() * m.dec.sin() * (s.ra - m.ra).cos());
+
+    MoonIllumination {
+        fraction: (1.0 + inc.cos()) / 2.0,
+        phase: 0.5 + 0.5 * inc * angle.signum() / PI,
+    }
+}
+
+pub fn moon_phase_name(phase: f64) -> &'static str {
+    match phase {
+        p if !(0.03..=0.97).contains(&p) => "New Moon",
+        p if p < 0.22 => "Waxing Crescent",
+        p if p < 0.28 => "First Quarter",
+        p if p < 0.47 => "Waxing Gibbous",
+        p if p < 0.53 => "Full Moon",
+        p if p < 0.72 => "Waning Gibbous",
+        p if p < 0.78 => "Last Quarter",
+        _ => "Waning Crescent",
+    }
+}
+
+/// ASCII stand-in for the phase icon when printing to stdout.
+pub fn moon_phase_symbol(phase: f64) -> &'static str {
+    match moon_phase_name(phase) {
+        "New Moon" => "@",
+        "Waxing Crescent" => ")",
+        "First Quarter" | "Waxing Gibbous" => "D",
+        "Full Moon" => "O",
+        "Waning Gibbous" | "Last Quarter" => "C",
+        _ => "(",
+    }
+}
+
+pub struct MoonTimes {
+    pub rise: Option<DateTime<Utc>>,
+    pub set: Option<DateTime<Utc>>,
+}
+
+/// Moonrise and moonset during the 24 hours after `start` (usually local midnight).
+/// Either can be missing: the moon doesn't rise or set every calendar day.
+pub fn moon_times(start: DateTime<Utc>, lat: f64, lon: f64) -> MoonTimes {
+    let hc = 0.133 * RAD;
+    let (rise, set) = horizon_crossings(start, |t| moon_altitude(t, lat, lon) - hc);
+    MoonTimes { rise, set }
+}
+
+/// When `altitude` (radians above the horizon at a time) turns positive and negative
+/// during the 24 hours after `start`.
+fn horizon_crossings(start: DateTime<Utc>, altitude: impl Fn(DateTime<Utc>) -> f64) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let at = |hours: f64| start + TimeDelta::seconds((hours * 3600.0) as i64);
+    let alt = |hours: f64| altitude(at(hours));
+
+    let mut rise = None;
+    let mut set = None;
+    let mut h0 = alt(0.0);
+
+    // Fit a parabola through each pair of hours and look for horizon crossings.
+    let mut i = 1.0;
+    while i <= 24.0 {
+        let h1 = alt(i);
+        let h2 = alt(i + 1.0);
+
+        let a = (h0 + h2) / 2.0 - h1;
+        let b = (h2 - h0) / 2.0;
+        let xe = -b / (2.0 * a);
+        let ye = (a * xe + b) * xe + h1;
+        let d = b * b - 4.0 * a * h1;
+
+        if d >= 0.0 {
+            let dx = d.sqrt() / (a.abs() * 2.0);
+            let mut x1 = xe - dx;
+            let x2 = xe + dx;
+            let roots = [x1, x2].iter().filter(|x| x.abs() <= 1.0).count();
+            if x1 < -1.0 {
+                x1 = x2;
+            }
+
+            if roots == 1 {
+                if h0 < 0.0 {
+                    rise = Some(i + x1);
+                } else {
+                    set = Some(i + x1);
+                }
+            } else if roots == 2 {
+                rise = Some(i + if ye < 0.0 { x2 } else { x1 });
+                set = Some(i + if ye < 0.0 { x1 } else { x2 });
+            }
+        }
+
+        if rise.is_some() && set.is_some() {
+            break;
+        }
+        h0 = h2;
+        i += 2.0;
+    }
+
+    (rise.map(at), set.map(at))
+}
+
+/// The naked-eye planets.
+#[derive(Clone, Copy)]
+pub enum Planet {
+    Mercury,
+    Venus,
+    Mars,
+    Jupiter,
+    Saturn,
+}
+
+impl Planet {
+    pub const ALL: [Planet; 5] = [Planet::Mercury, Planet::Venus, Planet::Mars, Planet::Jupiter, Planet::Saturn];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Planet::Mercury => "Mercury",
+            Planet::Venus => "Venus",
+            Planet::Mars => "Mars",
+            Planet::Jupiter => "Jupiter",
+            Planet::Saturn => "Saturn",
+        }
+    }
+}
+
+/// Keplerian elements at `d` days from 2000 Jan 0.0, in degrees and AU.
+struct Orbit {
+    /// Longitude of the ascending node.
+    n: f64,
+    /// Inclination to the ecliptic.
+    i: f64,
+    /// Argument of perihelion.
+    w: f64,
+    a: f64,
+    e: f64,
+    /// Mean anomaly.
+    m: f64,
+}
+
+/// Orbital elements from Paul Schlyter's "How to compute planetary positions"
+/// (https://stjarnhimlen.se/comp/ppcomp.html), good to a degree or two without the
+/// perturbation terms, which is plenty for rise and set times.
+fn orbit(planet: Option<Planet>, d: f64) -> Orbit {
+    match planet {
+        // The Earth's orbit, as the sun's apparent one.
+        None => Orbit {
+            n: 0.0,
+            i: 0.0,
+            w: 282.9404 + 4.70935e-5 * d,
+            a: 1.0,
+            e: 0.016709 - 1.151e-9 * d,
+            m: 356.0470 + 0.9856002585 * d,
+        },
+        Some(Planet::Mercury) => Orbit {
+            n: 48.3313 + 3.24587e-5 * d,
+            i: 7.0047 + 5.00e-8 * d,
+            w: 29.1241 + 1.01444e-5 * d,
+            a: 0.387098,
+            e: 0.205635 + 5.59e-10 * d,
+            m: 168.6562 + 4.0923344368 * d,
+        },
+        Some(Planet::Venus) => Orbit {
+            n: 76.6799 + 2.46590e-5 * d,
+            i: 3.3946 + 2.75e-8 * d,
+            w: 54.8910 + 1.38374e-5 * d,
+            a: 0.723330,
+            e: 0.006773 - 1.302e-9 * d,
+            m: 48.0052 + 1.6021302244 * d,
+        },
+        Some(Planet::Mars) => Orbit {
+            n: 49.5574 + 2.11081e-5 * d,
+            i: 1.8497 - 1.78e-8 * d,
+            w: 286.5016 + 2.92961e-5 * d,
+            a: 1.523688,
+            e: 0.093405 + 2.516e-9 * d,
+            m: 18.6021 + 0.5240207766 * d,
+        },
+        Some(Planet::Jupiter) => Orbit {
+            n: 100.4542 + 2.76854e-5 * d,
+            i: 1.3030 - 1.557e-7 * d,
+            w: 273.8777 + 1.64505e-5 * d,
+            a: 5.20256,
+            e: 0.048498 + 4.469e-9 * d,
+            m: 19.8950 + 0.0830853001 * d,
+        },
+        Some(Planet::Saturn) => Orbit {
+            n: 113.6634 + 2.38980e-5 * d,
+            i: 2.4886 - 1.081e-7 * d,
+            w: 339.3939 + 2.97661e-5 * d,
+            a: 9.55475,
+            e: 0.055546 - 9.499e-9 * d,
+            m: 316.9670 + 0.0334442282 * d,
+        },
+    }
+}
+
+/// Heliocentric ecliptic x, y, z in AU.
+fn heliocentric(orbit: &Orbit) -> [f64; 3] {
+    let m = RAD * orbit.m.rem_euclid(360.0);
+    // Kepler's equation by Newton's method.
+    let mut ecc = m + orbit.e * m.sin() * (1.0 + orbit.e * m.cos());
+    for _ in 0..5 {
+        ecc -= (ecc - orbit.e * ecc.sin() - m) / (1.0 - orbit.e * ecc.cos());
+    }
+    let xv = orbit.a * (ecc.cos() - orbit.e);
+    let yv = orbit.a * (1.0 - orbit.e * orbit.e).sqrt() * ecc.sin();
+    let v = yv.atan2(xv);
+    let r = xv.hypot(yv);
+
+    let (n, i, vw) = (RAD * orbit.n, RAD * orbit.i, v + RAD * orbit.w);
+    [
+        r * (n.cos() * vw.cos() - n.sin() * vw.sin() * i.cos()),
+        r * (n.sin() * vw.cos() + n.cos() * vw.sin() * i.cos()),
+        r * vw.sin() * i.sin(),
+    ]
+}
+
+fn planet_coords(planet: Planet, d: f64) -> Coords {
+    // Schlyter counts days from 2000 Jan 0.0, a day and a half before J2000.
+    let d = d + 1.5;
+    let [xp, yp, zp] = heliocentric(&orbit(Some(planet), d));
+    // Adding the sun's position as seen from the Earth moves the origin to the Earth.
+    let [xs, ys, _] = heliocentric(&orbit(None, d));
+    let (x, y, z) = (xp + xs, yp + ys, zp);
+    let lng = y.atan2(x);
+    let lat = z.atan2(x.hypot(y));
+    Coords {
+        ra: right_ascension(lng, lat),
+        dec: declination(lng, lat),
+        dist: (x * x + y * y + z * z).sqrt(),
+    }
+}
+
+/// A planet's altitude above the horizon in radians, corrected for refraction.
+pub fn planet_altitude(planet: Planet, date: DateTime<Utc>, lat: f64, lon: f64) -> f64 {
+    let d = to_days(date);
+    let c = planet_coords(planet, d);
+    let h = altitude(sidereal_time(d, RAD * -lon) - c.ra, RAD * lat, c.dec);
+    h + astro_refraction(h)
+}
+
+pub struct PlanetTimes {
+    pub rise: Option<DateTime<Utc>>,
+    pub set: Option<DateTime<Utc>>,
+}
+
+/// A planet's rise and set during the 24 hours after `start`.
+pub fn planet_times(planet: Planet, start: DateTime<Utc>, lat: f64, lon: f64) -> PlanetTimes {
+    let (rise, set) = horizon_crossings(start, |t| planet_altitude(planet, t, lat, lon));
+    PlanetTimes { rise, set }
+}
+
+pub struct SunTimes {
+    /// Start of morning civil twilight (sun 6° below the horizon).
+    pub dawn: Option<DateTime<Utc>>,
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    /// End of evening civil twilight.
+    pub dusk: Option<DateTime<Utc>>,
+}
+
+/// Sunrise, sunset and civil twilight for `date` using NOAA's solar calculator
+/// equations (https://gml.noaa.gov/grad/solcalc/calcdetails.html), good to about
+/// a minute. Times are `None` when the sun never crosses that altitude that day.
+pub fn sun_times(date: NaiveDate, lat: f64, lon: f64) -> SunTimes {
+    let Some(midnight) = date.and_hms_opt(0, 0, 0).map(|t| t.and_utc()) else {
+        return SunTimes { dawn: None, sunrise: None, sunset: None, dusk: None };
+    };
+    // Evaluate the sun's position around local solar noon.
+    let jd = to_days(midnight) + J2000 + 0.5 - lon / 360.0;
+    let t = (jd - J2000) / 36525.0;
+
+    let l0 = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0) * RAD;
+    let m = (357.52911 + t * (35999.05029 - 0.0001537 * t)) * RAD;
+    let e = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let c = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t) * RAD;
+    let lambda = l0 + (c - 0.00569 - 0.00478 * omega.sin()) * RAD;
+    let eps0 = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let eps = (eps0 + 0.00256 * omega.cos()) * RAD;
+    let dec = (eps.sin() * lambda.sin()).asin();
+
+    let y = (eps / 2.0).tan().powi(2);
+    let eq_time = 4.0
+        / RAD
+        * (y * (2.0 * l0).sin() - 2.0 * e * m.sin() + 4.0 * e * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * e * e * (2.0 * m).sin());
+    let solar_noon = 720.0 - 4.0 * lon - eq_time;
+
+    // Minutes either side of solar noon at which the sun's center reaches `zenith` degrees.
+    let half_day = |zenith: f64| {
+        let phi = lat * RAD;
+        let cos_ha = (zenith * RAD).cos() / (phi.cos() * dec.cos()) - phi.tan() * dec.tan();
+        (-1.0..=1.0).contains(&cos_ha).then(|| 4.0 * cos_ha.acos() / RAD)
+    };
+    let at = |minutes: f64| midnight + TimeDelta::seconds((minutes * 60.0) as i64);
+
+    // 90.833° allows for refraction and the radius of the solar disc.
+    let day = half_day(90.833);
+    let civil = half_day(96.0);
+    SunTimes {
+        dawn: civil.map(|ha| at(solar_noon - ha)),
+        sunrise: day.map(|ha| at(solar_noon - ha)),
+        sunset: day.map(|ha| at(solar_noon + ha)),
+        dusk: civil.map(|ha| at(solar_noon + ha)),
+    }
+}
@@ -0,0 +1,302 @@
+//! An append-only record of print requests, including ones turned away. Each entry
+//! carries the SHA-256 of the one before it, so editing or deleting a line in the
+//! middle of `audit.jsonl` breaks the chain and shows up in `GET /audit/verify`.
+//! gRPC print calls, and gRPC calls refused for their API key, are recorded too.
+
+use crate::{
+    AppState,
+    redact::{self, Redaction},
+    source::{self, Peer},
+};
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{ConnectInfo, Query, Request, State},
+    http::{
+        StatusCode,
+        header::{CONTENT_TYPE, TRANSFER_ENCODING},
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio_stream::StreamExt;
+use utoipa::{IntoParams, ToSchema};
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Bodies are buffered to count and preview them; anything larger is refused.
+const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+const FIRST_LINE_CHARS: usize = 80;
+/// How much of a streamed body is kept to preview it.
+const STREAM_HEAD_BYTES: usize = 4096;
+/// Entries returned by `GET /audit` unless `limit` says otherwise.
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub seq: u64,
+    /// RFC 3339 local time the request finished.
+    #[schema(format = DateTime)]
+    pub at: String,
+    /// `cert:<common name>` for a client certificate, `key:<name>` for a valid API key,
+    /// otherwise `http:<ip>`.
+    pub who: String,
+    pub method: String,
+    pub path: String,
+    pub bytes: usize,
+    pub first_line: Option<String>,
+    pub status: u16,
+    prev_hash: String,
+    hash: String,
+}
+
+impl AuditEntry {
+    fn digest(&self) -> String {
+        let unsigned = AuditEntry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&unsigned).unwrap_or_default();
+        Sha256::digest(&json).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+struct Tail {
+    seq: u64,
+    hash: String,
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    tail: Arc<Mutex<Tail>>,
+    path: PathBuf,
+}
+
+/// Marks responses from printing endpoints, which are the ones worth auditing.
+#[derive(Clone)]
+struct PrintRoute;
+
+fn read_entries(path: &PathBuf) -> Vec<AuditEntry> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+impl AuditLog {
+    /// Picks up the chain where the log on disk left off.
+    pub fn load(path: PathBuf) -> Self {
+        let last = read_entries(&path).pop();
+        AuditLog {
+            tail: Arc::new(Mutex::new(Tail {
+                seq: last.as_ref().map_or(0, |entry| entry.seq),
+                hash: last.map(|entry| entry.hash).unwrap_or_default(),
+            })),
+            path,
+        }
+    }
+
+    fn append(&self, mut entry: AuditEntry) {
+        let mut tail = self.tail.lock().unwrap();
+        entry.seq = tail.seq + 1;
+        entry.prev_hash = tail.hash.clone();
+        entry.hash = entry.digest();
+
+        let written = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::OpenOptions::new().create(true).append(true).open(&self.path))
+            .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(&entry).unwrap_or_default()));
+        match written {
+            Ok(()) => {
+                tail.seq = entry.seq;
+                tail.hash = entry.hash;
+            }
+            Err(e) => eprintln!("Failed to write audit log {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Route layer for the printing endpoints; see [`PrintRoute`].
+pub async fn mark_print_route(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    response.extensions_mut().insert(PrintRoute);
+    response
+}
+
+/// The body's first non-blank line for the log, with `redactions` masked as on paper.
+fn first_line(content_type: &str, body: &[u8], redactions: &[Redaction]) -> Option<String> {
+    if content_type.starts_with("multipart/") {
+        return Some("[multipart upload]".to_owned());
+    }
+    let text = std::str::from_utf8(body).ok()?;
+    let line = text.lines().map(str::trim).find(|line| !line.is_empty())?;
+    Some(redact::redact(line, redactions).chars().take(FIRST_LINE_CHARS).collect())
+}
+
+/// Outermost layer: records requests that reached a printing endpoint or were refused for a
+/// missing or wrong API key. Runs before authentication so refused attempts are seen too.
+pub async fn record(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let peer = req.extensions().get::<ConnectInfo<Peer>>().map(|ConnectInfo(peer)| peer);
+    let who = match (peer, source::presented_key(&state.config, req.headers())) {
+        (Some(peer), _) if peer.cert_name.is_some() => peer.origin(),
+        (_, Some(key)) => format!("key:{}", key.name),
+        (Some(peer), None) => peer.origin(),
+        (None, None) => "http".to_owned(),
+    };
+    let method = req.method().to_string();
+    let path = req.uri().path().to_owned();
+    let content_type = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+
+    let (parts, body) = req.into_parts();
+    let chunked = parts
+        .headers
+        .get(TRANSFER_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("chunked"));
+    let (bytes, body, response) = if chunked {
+        // Passed on as it arrives, for bodies that print as they stream in (see
+        // crate::stream); only its size and start are kept.
+        let seen = Arc::new(Mutex::new((0, Vec::new())));
+        let counted = seen.clone();
+        let stream = body.into_data_stream().map(move |chunk| {
+            if let Ok(chunk) = &chunk {
+                let (bytes, head) = &mut *counted.lock().unwrap();
+                *bytes += chunk.len();
+                head.extend_from_slice(&chunk[..chunk.len().min(STREAM_HEAD_BYTES.saturating_sub(head.len()))]);
+            }
+            chunk
+        });
+        let response = next.run(Request::from_parts(parts, Body::from_stream(stream))).await;
+        let (bytes, head) = std::mem::take(&mut *seen.lock().unwrap());
+        (bytes, head.into(), response)
+    } else {
+        match to_bytes(body, MAX_BODY_BYTES).await {
+            Ok(body) => (body.len(), body.clone(), next.run(Request::from_parts(parts, Body::from(body))).await),
+            Err(_) => (0, Default::default(), StatusCode::PAYLOAD_TOO_LARGE.into_response()),
+        }
+    };
+
+    let status = response.status();
+    if response.extensions().get::<PrintRoute>().is_some() || status == StatusCode::UNAUTHORIZED {
+        state.audit.append(AuditEntry {
+            seq: 0,
+            at: Local::now().to_rfc3339(),
+            who,
+            method,
+            path,
+            bytes,
+            first_line: first_line(&content_type, &body, &state.config.redactions),
+            status: status.as_u16(),
+            prev_hash: String::new(),
+            hash: String::new(),
+        });
+    }
+    response
+}
+
+/// Records a gRPC call, which doesn't pass through [`record`]: `path` is the method's,
+/// `body` the text it was asked to print, and `status` what the REST API would have
+/// answered.
+pub fn record_grpc(state: &AppState, who: String, path: &str, body: &str, status: StatusCode) {
+    state.audit.append(AuditEntry {
+        seq: 0,
+        at: Local::now().to_rfc3339(),
+        who,
+        method: "POST".to_owned(),
+        path: path.to_owned(),
+        bytes: body.len(),
+        first_line: first_line("application/grpc", body.as_bytes(), &state.config.redactions),
+        status: status.as_u16(),
+        prev_hash: String::new(),
+        hash: String::new(),
+    });
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuditParams {
+    /// RFC 3339 lower bound on when the request was made.
+    from: Option<DateTime<chrono::FixedOffset>>,
+    /// RFC 3339 upper bound on when the request was made.
+    to: Option<DateTime<chrono::FixedOffset>>,
+    /// API key name, or `http:<ip>` for unauthenticated requests.
+    key: Option<String>,
+    /// Entries to return.
+    #[param(default = 100)]
+    limit: Option<usize>,
+}
+
+/// The newest matching entries, newest first.
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "admin",
+    summary = "Audit log of requests, newest first",
+    params(AuditParams),
+    responses((status = 200, description = "OK", body = Vec<AuditEntry>))
+)]
+pub async fn list(State(state): State<AppState>, Query(params): Query<AuditParams>) -> Json<Vec<AuditEntry>> {
+    let key = params.key.map(|key| if key.contains(':') { key } else { format!("key:{}", key) });
+    let entries = read_entries(&state.audit.path)
+        .into_iter()
+        .rev()
+        .filter(|entry| {
+            let at = DateTime::parse_from_rfc3339(&entry.at).ok();
+            params.from.is_none_or(|from| at.is_some_and(|at| at >= from))
+                && params.to.is_none_or(|to| at.is_some_and(|at| at <= to))
+                && key.as_ref().is_none_or(|key| &entry.who == key)
+        })
+        .take(params.limit.unwrap_or(DEFAULT_LIMIT))
+        .collect();
+    Json(entries)
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Verification {
+    entries: u64,
+    valid: bool,
+    /// First entry whose hash or link to the previous entry doesn't match.
+    broken_at: Option<u64>,
+}
+
+/// Walks the hash chain from the start of the log.
+#[utoipa::path(
+    get,
+    path = "/audit/verify",
+    tag = "admin",
+    summary = "Check the audit log's hash chain",
+    responses((status = 200, description = "OK", body = Verification))
+)]
+pub async fn verify(State(state): State<AppState>) -> Json<Verification> {
+    let entries = read_entries(&state.audit.path);
+    let mut prev_hash = String::new();
+    let mut broken_at = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.seq != i as u64 + 1 || entry.prev_hash != prev_hash || entry.hash != entry.digest() {
+            broken_at = Some(i as u64 + 1);
+            break;
+        }
+        prev_hash = entry.hash.clone();
+    }
+    if let Some(seq) = broken_at {
+        eprintln!("Audit log chain broken at entry {}", seq);
+    }
+    Json(Verification {
+        entries: entries.len() as u64,
+        valid: broken_at.is_none(),
+        broken_at,
+    })
+}
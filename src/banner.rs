@@ -0,0 +1,77 @@
+use crate::{
+    AppState,
+    receipt::{Receipt, wrap},
+    source::Source,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+/// ESC/POS character scaling goes up to 8x; beyond 4 lines a banner stops being a sign.
+const MAX_SCALE: usize = 8;
+const MAX_LINES: usize = 4;
+
+#[derive(Deserialize, IntoParams)]
+pub struct BannerParams {
+    /// Frame the text with rules above and below.
+    #[serde(default)]
+    #[param(default = false)]
+    border: bool,
+}
+
+/// The largest scale at which every word fits on a line and the text takes at most
+/// `MAX_LINES` lines of a `columns` wide receipt, with the text wrapped for it.
+fn fit(text: &str, columns: usize) -> (usize, Vec<String>) {
+    for scale in (2..=MAX_SCALE).rev() {
+        let width = columns / scale;
+        let words_fit = text.split_whitespace().all(|word| word.chars().count() <= width);
+        if !words_fit {
+            continue;
+        }
+        let lines: Vec<String> = text.lines().flat_map(|line| wrap(line, width)).collect();
+        if lines.len() <= MAX_LINES {
+            return (scale, lines);
+        }
+    }
+    (1, text.lines().flat_map(|line| wrap(line, columns)).collect())
+}
+
+/// Prints the body as big as it will go, e.g. `BACK IN 5 MIN`.
+#[utoipa::path(
+    post,
+    path = "/banner",
+    tag = "printing",
+    summary = "Print text as large as fits the paper",
+    request_body(content = String, content_type = "text/plain"),
+    params(BannerParams),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 400, description = "No text."),
+    )
+)]
+pub async fn banner(State(state): State<AppState>, source: Source, Query(params): Query<BannerParams>, body: String) -> Result<(), StatusCode> {
+    let text = body.trim();
+    if text.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut receipt = Receipt::for_config(&state.config);
+    let (scale, lines) = fit(text, receipt.width());
+    eprintln!("Banner at {}x: {:?}", scale, text);
+
+    if params.border {
+        receipt.line_left(&"#".repeat(receipt.width()));
+        receipt.line_left("");
+    }
+    for line in &lines {
+        receipt.line_large(line, scale as u8);
+    }
+    if params.border {
+        receipt.line_left("");
+        receipt.line_left(&"#".repeat(receipt.width()));
+    }
+    state.print(receipt, "banner", &source);
+    Ok(())
+}
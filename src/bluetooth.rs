@@ -0,0 +1,130 @@
+//! Bluetooth Classic printers, which show up as a serial port (SPP) over an RFCOMM
+//! channel. Most portable 58mm printers only have Bluetooth.
+
+use escpos::{driver::Driver, errors::Result};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A device address as `bluetoothctl` shows it, e.g. `66:22:B3:4C:1A:0F`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct BtAddr(pub [u8; 6]);
+
+impl fmt::Display for BtAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}", a, b, c, d, e, g)
+    }
+}
+
+impl FromStr for BtAddr {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, ()> {
+        let mut bytes = [0; 6];
+        let mut parts = s.trim().split(':');
+        for byte in &mut bytes {
+            let part = parts.next().filter(|p| p.len() == 2).ok_or(())?;
+            *byte = u8::from_str_radix(part, 16).map_err(|_| ())?;
+        }
+        if parts.next().is_some() {
+            return Err(());
+        }
+        Ok(BtAddr(bytes))
+    }
+}
+
+const BTPROTO_RFCOMM: libc::c_int = 3;
+/// How long a query, e.g. for the model name, waits for an answer.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `struct sockaddr_rc` from BlueZ.
+#[repr(C)]
+struct SockaddrRc {
+    family: libc::sa_family_t,
+    /// Little-endian, i.e. the reverse of how the address is written.
+    bdaddr: [u8; 6],
+    channel: u8,
+}
+
+fn connect(address: BtAddr, channel: u8) -> io::Result<File> {
+    // SAFETY: plain socket(2), connect(2) and setsockopt(2) calls; the descriptor is
+    // owned by `OwnedFd` as soon as it's created, so it's closed on every error path.
+    unsafe {
+        let fd = libc::socket(libc::AF_BLUETOOTH, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, BTPROTO_RFCOMM);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+        let mut bdaddr = address.0;
+        bdaddr.reverse();
+        let addr = SockaddrRc {
+            family: libc::AF_BLUETOOTH as libc::sa_family_t,
+            bdaddr,
+            channel,
+        };
+        let result = libc::connect(
+            fd.as_raw_fd(),
+            &addr as *const SockaddrRc as *const libc::sockaddr,
+            size_of::<SockaddrRc>() as libc::socklen_t,
+        );
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let timeout = libc::timeval {
+            tv_sec: READ_TIMEOUT.as_secs() as libc::time_t,
+            tv_usec: 0,
+        };
+        let result = libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const libc::timeval as *const libc::c_void,
+            size_of::<libc::timeval>() as libc::socklen_t,
+        );
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from(fd))
+    }
+}
+
+/// An open RFCOMM connection to a printer.
+#[derive(Clone)]
+pub struct BluetoothDriver {
+    address: BtAddr,
+    socket: Arc<Mutex<File>>,
+}
+
+impl BluetoothDriver {
+    pub fn open(address: BtAddr, channel: u8) -> io::Result<Self> {
+        Ok(BluetoothDriver {
+            address,
+            socket: Arc::new(Mutex::new(connect(address, channel)?)),
+        })
+    }
+}
+
+impl Driver for BluetoothDriver {
+    fn name(&self) -> String {
+        format!("Bluetooth ({})", self.address)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<()> {
+        Ok(self.socket.lock().unwrap().write_all(data)?)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.socket.lock().unwrap().read(buf)?)
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(self.socket.lock().unwrap().flush()?)
+    }
+}
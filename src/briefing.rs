@@ -0,0 +1,305 @@
+//! `GET /briefing`: the morning's sections on one receipt, as listed in
+//! `BRIEFING_SECTIONS` (see [`parse_section`]). Sections are fetched at the same time;
+//! one that fails is noted as unavailable rather than failing the whole briefing.
+
+use crate::{
+    AppState, agenda, countdowns, error::JobError, exec, feeds, hn, markdown, plugins, quote, receipt::Receipt, source::Source, transit, weather,
+};
+use axum::{extract::State, http::StatusCode};
+use chrono::Local;
+use std::{collections::HashMap, path::PathBuf};
+
+/// Where a section's content comes from, with its options.
+#[derive(Clone)]
+pub enum Provider {
+    Weather { outlook: bool },
+    /// iCalendar feeds; `CALENDAR_URLS` when empty.
+    Agenda { urls: Vec<String> },
+    /// `TODO_URL` when `None`.
+    Todo { url: Option<String> },
+    /// Hacker News.
+    Headlines { count: usize },
+    /// `COUNTDOWNS` and `BIRTHDAYS_FILE` events this many days away or closer.
+    Countdowns { within: i64 },
+    Feed { url: String, count: usize },
+    Quote { file: Option<PathBuf>, url: Option<String> },
+    Transit { stop: String, count: usize, url: Option<String> },
+    /// A command from `EXEC_COMMANDS`.
+    Exec { name: String },
+    /// A Lua plugin, given the section's other keys as its parameters.
+    Plugin { name: String, params: HashMap<String, String> },
+}
+
+#[derive(Clone)]
+pub struct Section {
+    pub title: String,
+    pub provider: Provider,
+}
+
+impl Section {
+    fn new(title: &str, provider: Provider) -> Self {
+        Section {
+            title: title.to_owned(),
+            provider,
+        }
+    }
+}
+
+/// Parses a `BRIEFING_SECTIONS` entry, `provider:key=value:...`, e.g.
+/// `feeds:url=https://example.com/rss:count=3:title=News`. Every section takes `title`;
+/// the providers and their keys are `weather` (`outlook`), `agenda` (`url`, repeatable),
+/// `todo` (`url`), `headlines` (`count`), `countdowns` (`within`), `feeds` (`url`,
+/// `count`), `quote` (`file`, `url`), `transit` (`stop`, `count`, `url`), `exec` (`name`)
+/// and `plugin` (`name`, plus any others).
+pub fn parse_section(entry: &str) -> Option<Section> {
+    let mut parts = entry.split(':').map(str::trim);
+    let name = parts.next()?;
+    let mut settings: Vec<(&str, String)> = Vec::new();
+    for part in parts {
+        match part.split_once('=') {
+            Some((key, value)) => settings.push((key, value.to_owned())),
+            // A colon inside the previous value, e.g. in a URL.
+            None => {
+                let (_, value) = settings.last_mut()?;
+                value.push(':');
+                value.push_str(part);
+            }
+        }
+    }
+    let get = |key: &str| settings.iter().find(|(k, _)| *k == key).map(|(_, value)| value.clone());
+    let count = || match get("count") {
+        Some(count) => count.parse().ok().filter(|&n: &usize| n > 0),
+        None => Some(5),
+    };
+
+    let (title, provider, keys): (&str, Provider, &[&str]) = match name {
+        "weather" => (
+            "WEATHER",
+            Provider::Weather {
+                outlook: get("outlook").is_some_and(|v| matches!(v.as_str(), "1" | "true" | "yes")),
+            },
+            &["outlook"],
+        ),
+        "agenda" => (
+            "AGENDA",
+            Provider::Agenda {
+                urls: settings.iter().filter(|(k, _)| *k == "url").map(|(_, url)| url.clone()).collect(),
+            },
+            &["url"],
+        ),
+        "todo" => ("TO DO", Provider::Todo { url: get("url") }, &["url"]),
+        "headlines" => ("HEADLINES", Provider::Headlines { count: count()? }, &["count"]),
+        "countdowns" => (
+            "COUNTDOWNS",
+            Provider::Countdowns {
+                within: match get("within") {
+                    Some(within) => within.parse().ok().filter(|&n: &i64| n >= 0)?,
+                    None => countdowns::default_within(),
+                },
+            },
+            &["within"],
+        ),
+        "feeds" => (
+            "NEWS",
+            Provider::Feed {
+                url: get("url")?,
+                count: count()?,
+            },
+            &["url", "count"],
+        ),
+        "quote" => (
+            "QUOTE",
+            Provider::Quote {
+                file: get("file").map(PathBuf::from),
+                url: get("url"),
+            },
+            &["file", "url"],
+        ),
+        "transit" => (
+            "DEPARTURES",
+            Provider::Transit {
+                stop: get("stop")?,
+                count: count()?,
+                url: get("url"),
+            },
+            &["stop", "count", "url"],
+        ),
+        "exec" => {
+            let name = get("name")?;
+            if settings.iter().any(|(key, _)| !matches!(*key, "name" | "title")) {
+                return None;
+            }
+            return Some(Section {
+                title: get("title").unwrap_or_else(|| name.clone()).to_uppercase(),
+                provider: Provider::Exec { name },
+            });
+        }
+        "plugin" => {
+            let name = get("name")?;
+            let params = settings.iter().filter(|(k, _)| !matches!(*k, "name" | "title")).map(|(k, v)| (k.to_string(), v.clone()));
+            let title = name.to_uppercase();
+            return Some(Section {
+                title: get("title").unwrap_or(title).to_uppercase(),
+                provider: Provider::Plugin {
+                    name,
+                    params: params.collect(),
+                },
+            });
+        }
+        _ => return None,
+    };
+    if settings.iter().any(|(key, _)| *key != "title" && !keys.contains(key)) {
+        return None;
+    }
+    Some(Section {
+        title: get("title").map_or_else(|| title.to_owned(), |title| title.to_uppercase()),
+        provider,
+    })
+}
+
+/// The sections without `BRIEFING_SECTIONS`: the weather, then whichever of the agenda,
+/// to-dos and headlines are configured.
+fn default_sections(state: &AppState) -> Vec<Section> {
+    let config = &state.config;
+    let mut sections = vec![Section::new("WEATHER", Provider::Weather { outlook: false })];
+    if !config.calendar_urls.is_empty() {
+        sections.push(Section::new("AGENDA", Provider::Agenda { urls: Vec::new() }));
+    }
+    if config.todo_url.is_some() {
+        sections.push(Section::new("TO DO", Provider::Todo { url: None }));
+    }
+    if config.briefing_headlines > 0 {
+        sections.push(Section::new("HEADLINES", Provider::Headlines { count: config.briefing_headlines }));
+    }
+    sections
+}
+
+#[utoipa::path(
+    get,
+    path = "/briefing",
+    tag = "printing",
+    summary = "Print the morning briefing",
+    description = "The sections listed in `BRIEFING_SECTIONS` (weather, agenda, todo, headlines, countdowns, feeds, \
+        quote, transit, exec, plugin, each with its own options) on one receipt. Without it: weather, today's \
+        events from `CALENDAR_URLS`, open items from `TODO_URL` and the top `BRIEFING_HEADLINES` Hacker News \
+        stories, leaving out the unconfigured ones. Sections are fetched concurrently; one that fails prints \
+        as \"(unavailable)\". Also available as the `briefing` scheduled job.",
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn briefing(State(state): State<AppState>, source: Source) -> Result<(), StatusCode> {
+    Ok(print_briefing(&state, &source).await?)
+}
+
+pub async fn print_briefing(state: &AppState, source: &Source) -> Result<(), JobError> {
+    let sections = match &state.config.briefing_sections {
+        sections if sections.is_empty() => default_sections(state),
+        sections => sections.clone(),
+    };
+    eprintln!("Briefing request for {} section(s)", sections.len());
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, section) in sections.iter().enumerate() {
+        let state = state.clone();
+        let provider = section.provider.clone();
+        tasks.spawn(async move { (i, render(&state, provider).await) });
+    }
+    let mut bodies: Vec<Option<Result<Receipt, JobError>>> = sections.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((i, body)) => bodies[i] = Some(body),
+            // The section prints as unavailable; the others still print.
+            Err(e) => eprintln!("Briefing section task failed: {}", e),
+        }
+    }
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("GOOD MORNING");
+    receipt.line_center(&Local::now().format_localized("%A, %-d %B", state.config.locale).to_string());
+    for (section, body) in sections.iter().zip(bodies) {
+        receipt.divider();
+        match body {
+            Some(Ok(body)) => {
+                receipt.line_center(&section.title);
+                receipt.append(body);
+            }
+            Some(Err(e)) => {
+                eprintln!("Briefing section {} failed: {}", section.title, e);
+                state.diagnostics.record_error("briefing", format!("{}: {}", section.title.to_lowercase(), e));
+                receipt.line_center(&format!("{} (unavailable)", section.title));
+            }
+            None => receipt.line_center(&format!("{} (unavailable)", section.title)),
+        }
+    }
+
+    state.print(receipt, "briefing", source);
+    Ok(())
+}
+
+/// Fetches and lays out one section's content, without its title.
+async fn render(state: &AppState, provider: Provider) -> Result<Receipt, JobError> {
+    let mut receipt = Receipt::for_config(&state.config);
+    match provider {
+        Provider::Weather { outlook } => return weather::briefing_receipt(state, outlook).await,
+        Provider::Agenda { urls } => {
+            let urls = if urls.is_empty() { &state.config.calendar_urls } else { &urls };
+            if urls.is_empty() {
+                return Err(JobError::NotConfigured("CALENDAR_URLS"));
+            }
+            let entries = agenda::today(urls).await?;
+            if entries.is_empty() {
+                receipt.line_center("Nothing scheduled");
+            }
+            for entry in entries {
+                receipt.hanging(&format!("{:<13}", entry.when), &entry.what);
+            }
+        }
+        Provider::Todo { url } => {
+            let url = url.or_else(|| state.config.todo_url.clone()).ok_or(JobError::NotConfigured("TODO_URL"))?;
+            let tasks = markdown::open_tasks(&crate::html::fetch_page(&url).await?);
+            if tasks.is_empty() {
+                receipt.line_center("All done");
+            }
+            for task in tasks {
+                receipt.hanging("[ ] ", &task);
+            }
+        }
+        Provider::Headlines { count } => {
+            for title in hn::headlines(state, count).await? {
+                receipt.hanging("* ", &title);
+            }
+        }
+        Provider::Countdowns { within } => {
+            let lines = countdowns::upcoming(&state.config, Local::now().date_naive(), within)?;
+            if lines.is_empty() {
+                receipt.line_center("Nothing coming up");
+            }
+            for line in lines {
+                receipt.wrapped(&line);
+            }
+        }
+        Provider::Feed { url, count } => {
+            for title in feeds::headlines(&url, count).await? {
+                receipt.hanging("* ", &title);
+            }
+        }
+        Provider::Quote { file, url } => {
+            let (quote, author) = quote::today(file.as_deref(), url.as_deref()).await?;
+            receipt.wrapped(&format!("\"{}\"", quote));
+            if let Some(author) = author {
+                receipt.line_left(&format!("{:>width$}", format!("- {}", author), width = receipt.width()));
+            }
+        }
+        Provider::Exec { name } => return exec::render(state, &name).await,
+        Provider::Plugin { name, params } => return plugins::render(state, &name, params).await,
+        Provider::Transit { stop, count, url } => {
+            let departures = transit::departures(&stop, count, url.as_deref()).await?;
+            if departures.is_empty() {
+                receipt.line_center("No departures in the next hour");
+            }
+            for departure in departures {
+                receipt.line_left(&departure);
+            }
+        }
+    }
+    Ok(receipt)
+}
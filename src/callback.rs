@@ -0,0 +1,54 @@
+use crate::history::JobStatus;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use utoipa::ToSchema;
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Header carrying `sha256=<hex HMAC of the body>` when `CALLBACK_SECRET` is set.
+pub const SIGNATURE_HEADER: &str = "x-print-jobber-signature";
+const ATTEMPTS: u32 = 3;
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Completion {
+    pub id: u64,
+    pub status: JobStatus,
+    pub error: Option<String>,
+}
+
+/// Accepts only absolute http(s) URLs, so a typo fails the request instead of every callback.
+pub fn valid_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+fn signature(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(body);
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// POSTs the job's outcome to `url`, retrying with backoff if the receiver is down.
+pub async fn send(url: String, secret: Option<String>, completion: Completion) {
+    let body = serde_json::to_vec(&completion).unwrap_or_default();
+    let client = reqwest::Client::new();
+    for attempt in 1..=ATTEMPTS {
+        let mut request = client
+            .post(&url)
+            .timeout(Duration::from_secs(10))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone());
+        if let Some(secret) = &secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, &body));
+        }
+        match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(_) => {
+                eprintln!("Sent completion callback for job #{}", completion.id);
+                return;
+            }
+            Err(e) => eprintln!("Completion callback for job #{} failed (attempt {}): {}", completion.id, attempt, e),
+        }
+        tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+    }
+}
@@ -0,0 +1,146 @@
+//! Text encoding for the printer. No single character code table covers €, Central European
+//! and Cyrillic letters, so text switches tables (`ESC t`) as it goes, staying on the current
+//! one for as long as it has the characters, and spells out anything no table has.
+//! Printers with a Kanji font (see [`Kanji`]) print CJK text in Kanji mode instead.
+
+use crate::{
+    profile::{CommandSet, Kanji, Profile},
+    star,
+};
+use escpos::utils::PageCode;
+
+/// Code tables in order of preference, each with its characters for bytes 0x80-0xFF.
+/// Bytes below 0x80 are ASCII in all of them.
+const TABLES: [(PageCode, &str); 5] = [
+    // The printer's default, with German letters and box drawing.
+    (PageCode::PC437, "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}"),
+    // Western European, with €.
+    (PageCode::PC858, "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜø£Ø×ƒáíóúñÑªº¿®¬½¼¡«»░▒▓│┤ÁÂÀ©╣║╗╝¢¥┐└┴┬├─┼ãÃ╚╔╩╦╠═╬¤ðÐÊËÈ€ÍÎÏ┘┌█▄¦Ì▀ÓßÔÒõÕµþÞÚÛÙýÝ¯´\u{ad}±‗¾¶§÷¸°¨·¹³²■\u{a0}"),
+    // Central European: Polish, Czech, Hungarian, ...
+    (PageCode::PC852, "ÇüéâäůćçłëŐőîŹÄĆÉĹĺôöĽľŚśÖÜŤťŁ×čáíóúĄąŽžĘę¬źČş«»░▒▓│┤ÁÂĚŞ╣║╗╝Żż┐└┴┬├─┼Ăă╚╔╩╦╠═╬¤đĐĎËďŇÍÎě┘┌█▄ŢŮ▀ÓßÔŃńňŠšŔÚŕŰýÝţ´\u{ad}˝˛ˇ˘§÷¸°¨˙űŘř■\u{a0}"),
+    // Cyrillic.
+    (PageCode::PC866, "АБВГДЕЖЗИЙКЛМНОПРСТУФХЦЧШЩЪЫЬЭЮЯабвгдежзийклмноп░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀рстуфхцчшщъыьэюяЁёЄєЇїЎў°∙·√№¤■\u{a0}"),
+    // Greek.
+    (PageCode::PC737, "ΑΒΓΔΕΖΗΘΙΚΛΜΝΞΟΠΡΣΤΥΦΧΨΩαβγδεζηθικλμνξοπρσςτυφχψ░▒▓│┤╡╢╖╕╣║╗╝╜╛┐└┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀ωάέήϊίόύϋώΆΈΉΊΌΎΏ±≥≤ΪΫ÷≈°∙·√ⁿ²■\u{a0}"),
+];
+
+/// The command that switches the printer to `page`.
+pub fn select(page: PageCode, commands: CommandSet) -> Vec<u8> {
+    match commands {
+        // ESC t n
+        CommandSet::EscPos => vec![0x1b, b't', u8::from(page)],
+        CommandSet::StarLine => star::code_page(page),
+    }
+}
+
+/// Every table this module can encode for.
+pub fn pages() -> Vec<PageCode> {
+    TABLES.iter().map(|(page, _)| *page).collect()
+}
+
+/// A table by name, e.g. `PC858`.
+pub fn parse(name: &str) -> Option<PageCode> {
+    TABLES.iter().map(|(page, _)| *page).find(|page| format!("{:?}", page).eq_ignore_ascii_case(name.trim()))
+}
+
+fn byte_in(table: &str, c: char) -> Option<u8> {
+    table.chars().position(|t| t == c).map(|i| 0x80 + i as u8)
+}
+
+/// ASCII stand-ins for typographic characters no table has, `?` for anything else.
+fn transliterate(c: char) -> &'static str {
+    match c {
+        '‘' | '’' | '‚' | '′' => "'",
+        '“' | '”' | '„' | '″' => "\"",
+        '–' | '—' | '―' | '−' => "-",
+        '…' => "...",
+        '•' => "*",
+        '→' => "->",
+        '←' => "<-",
+        '™' => "TM",
+        '\u{2009}' | '\u{202f}' => " ",
+        _ => "?",
+    }
+}
+
+/// Whether `c` prints as a character rather than a stand-in: it's ASCII, in one of the
+/// profile's tables or its Kanji font, or has an ASCII spelling.
+pub fn printable(c: char, profile: &Profile) -> bool {
+    c.is_ascii()
+        || TABLES.iter().any(|(page, table)| profile.code_pages.contains(page) && table.contains(c))
+        || profile.kanji.is_some_and(|font| profile.commands == CommandSet::EscPos && kanji(c, font).is_some())
+        || transliterate(c) != "?"
+}
+
+/// `FS &` and `FS .`: in and out of Kanji mode, where bytes pair up into double-byte
+/// characters.
+const KANJI_ON: [u8; 2] = [0x1c, b'&'];
+const KANJI_OFF: [u8; 2] = [0x1c, b'.'];
+
+/// `c` in the Kanji font's encoding, if it's a double-byte character there.
+fn kanji(c: char, kanji: Kanji) -> Option<Vec<u8>> {
+    let mut utf8 = [0; 4];
+    let (bytes, _, unmappable) = kanji.encoding().encode(c.encode_utf8(&mut utf8));
+    (!unmappable && bytes.len() == 2).then(|| bytes.into_owned())
+}
+
+/// Encodes `text` for the printer, starting from table `current` and updating it as the
+/// text switches tables. `None` means the printer's table is unknown. Only the profile's
+/// tables are used. Characters none of them has go in Kanji mode if the profile has a
+/// Kanji font, which is left again before anything else and at the end.
+pub fn encode(text: &str, current: &mut Option<PageCode>, profile: &Profile) -> Vec<u8> {
+    let kanji_font = profile.kanji.filter(|_| profile.commands == CommandSet::EscPos);
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut in_kanji = false;
+    for c in text.chars() {
+        let in_tables = || {
+            TABLES
+                .iter()
+                .any(|(page, table)| profile.code_pages.contains(page) && table.contains(c))
+        };
+        let double = match kanji_font {
+            Some(font) if !c.is_ascii() && !in_tables() => kanji(c, font),
+            _ => None,
+        };
+        if let Some(double) = double {
+            if !in_kanji {
+                bytes.extend(KANJI_ON);
+                in_kanji = true;
+            }
+            bytes.extend(double);
+            continue;
+        }
+        if in_kanji {
+            bytes.extend(KANJI_OFF);
+            in_kanji = false;
+        }
+        if c.is_ascii() {
+            bytes.push(c as u8);
+            continue;
+        }
+        let in_current = TABLES
+            .iter()
+            .find(|(page, _)| Some(*page) == *current)
+            .and_then(|(_, table)| byte_in(table, c));
+        if let Some(b) = in_current {
+            bytes.push(b);
+            continue;
+        }
+        match TABLES
+            .iter()
+            .filter(|(page, _)| profile.code_pages.contains(page))
+            .find_map(|(page, table)| Some((*page, byte_in(table, c)?)))
+        {
+            Some((page, b)) => {
+                bytes.extend(select(page, profile.commands));
+                bytes.push(b);
+                *current = Some(page);
+            }
+            None => bytes.extend(transliterate(c).bytes()),
+        }
+    }
+    if in_kanji {
+        bytes.extend(KANJI_OFF);
+    }
+    bytes
+}
@@ -0,0 +1,955 @@
+use crate::{
+    bluetooth::BtAddr,
+    briefing,
+    callback,
+    countdowns,
+    discovery::UsbId,
+    network,
+    notify::Notifier,
+    printer::Device,
+    profile::{self, Profile},
+    receipt::Font,
+    redact::Redaction,
+    relay::Relay,
+    timefmt,
+};
+use chrono::{DateTime, Days, Local, Locale, NaiveDate, NaiveTime, Weekday};
+use std::{collections::HashMap, env, net::ToSocketAddrs, path::PathBuf, time::Duration};
+
+/// Namespace for API keys and schedule entries that don't name one.
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+pub enum StocksProvider {
+    Finnhub,
+    AlphaVantage,
+}
+
+/// When a countdown falls.
+#[derive(Clone, Copy)]
+pub enum CountdownDate {
+    /// A one-off event, dropped once it has passed.
+    Once(NaiveDate),
+    /// Every year, e.g. a birthday; `birth_year` gives the age it turns.
+    Yearly { month: u32, day: u32, birth_year: Option<i32> },
+}
+
+/// An event for `/countdowns`, from `COUNTDOWNS` or `BIRTHDAYS_FILE`.
+#[derive(Clone)]
+pub struct Countdown {
+    pub name: String,
+    pub date: CountdownDate,
+    /// Printed as "<name>'s birthday".
+    pub birthday: bool,
+}
+
+/// The tracking API behind `/packages`, from `TRACKING_PROVIDER`.
+pub enum TrackingProvider {
+    AfterShip,
+    SeventeenTrack,
+}
+
+/// A shipment for `/packages`, from a `TRACKING_NUMBERS` entry.
+#[derive(Clone)]
+pub struct TrackedPackage {
+    pub number: String,
+    /// AfterShip's carrier slug (required there), or 17track's numeric carrier code.
+    pub carrier: Option<String>,
+    /// Printed instead of the bare number, e.g. "New headphones".
+    pub label: Option<String>,
+}
+
+/// Units for the weather report and tides, from `WEATHER_UNITS`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Units {
+    /// Celsius, km/h, millimetres of rain and centimetres of snow.
+    Metric,
+    /// Fahrenheit, mph, and inches of both rain and snow.
+    Imperial,
+}
+
+impl Units {
+    /// Open-Meteo's `temperature_unit`.
+    pub fn temperature_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "celsius",
+            Units::Imperial => "fahrenheit",
+        }
+    }
+
+    /// Open-Meteo's `wind_speed_unit`.
+    pub fn wind_speed_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "kmh",
+            Units::Imperial => "mph",
+        }
+    }
+
+    /// Open-Meteo's `precipitation_unit`.
+    pub fn precipitation_unit(self) -> &'static str {
+        match self {
+            Units::Metric => "mm",
+            Units::Imperial => "inch",
+        }
+    }
+
+    /// What temperatures are printed with, e.g. `21C`.
+    pub fn degrees(self) -> &'static str {
+        match self {
+            Units::Metric => "C",
+            Units::Imperial => "F",
+        }
+    }
+
+    /// What wind speeds are printed with, e.g. `12 km/h`.
+    pub fn speed(self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+
+    /// A temperature in these units as °F, for thresholds such as freezing.
+    pub fn fahrenheit(self, temperature: f64) -> f64 {
+        match self {
+            Units::Metric => temperature * 9.0 / 5.0 + 32.0,
+            Units::Imperial => temperature,
+        }
+    }
+
+    /// A temperature in °C as these units, e.g. from a sensor.
+    pub fn from_celsius(self, temperature: f64) -> f64 {
+        match self {
+            Units::Metric => temperature,
+            Units::Imperial => temperature * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    /// A wind speed in these units as mph.
+    pub fn mph(self, speed: f64) -> f64 {
+        match self {
+            Units::Metric => speed / 1.609_344,
+            Units::Imperial => speed,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScheduledJob {
+    pub namespace: String,
+    pub job: String,
+    pub at: NaiveTime,
+    /// Only runs on this day of the week; every day when `None`.
+    pub weekday: Option<Weekday>,
+}
+
+/// A printer besides the main one, from `PRINTERS`.
+#[derive(Clone)]
+pub struct NamedPrinter {
+    pub name: String,
+    pub device: Device,
+    /// Its own profile from `:profile=<name>`, or else `PRINTER_PROFILE`'s.
+    pub profile: Profile,
+}
+
+/// What a `PRINTER_ROUTES` rule matches a job by.
+#[derive(Clone)]
+pub enum RouteMatch {
+    /// The job's name, e.g. `weather` or `print` for `POST /`.
+    Job(String),
+    /// The name of the API key it was submitted with.
+    Key(String),
+    /// The request's `?tag=`.
+    Tag(String),
+}
+
+/// Sends matching jobs to a printer by name, from `PRINTER_ROUTES`.
+#[derive(Clone)]
+pub struct PrinterRoute {
+    pub when: RouteMatch,
+    pub printer: String,
+}
+
+impl PrinterRoute {
+    pub fn matches(&self, job: &str, key: Option<&str>, tag: Option<&str>) -> bool {
+        match &self.when {
+            RouteMatch::Job(name) => name == job,
+            RouteMatch::Key(name) => key == Some(name.as_str()),
+            RouteMatch::Tag(name) => tag == Some(name.as_str()),
+        }
+    }
+}
+
+/// When jobs are held back instead of printed, from `QUIET_HOURS`, e.g. `22:00-07:00`.
+#[derive(Clone, Copy)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// When the quiet hours `at` falls in end, or `None` outside them.
+    pub fn until(&self, at: DateTime<Local>) -> Option<DateTime<Local>> {
+        let time = at.time();
+        let quiet = if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        };
+        if !quiet {
+            return None;
+        }
+        // Past midnight the quiet hours end today, before it tomorrow.
+        let date = if time < self.end { at.date_naive() } else { at.date_naive() + Days::new(1) };
+        date.and_time(self.end).and_local_timezone(Local).earliest()
+    }
+}
+
+/// A named place for `/weather?locations=...`.
+#[derive(Clone)]
+pub struct Location {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Certificate and private key files, in PEM, for serving HTTPS.
+#[derive(Clone)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Only accept clients with a certificate signed by this CA, from `TLS_CLIENT_CA`.
+    pub client_ca: Option<PathBuf>,
+}
+
+pub struct ApiKey {
+    pub name: String,
+    pub token: String,
+    /// Jobs submitted with this key get their own history, HN dedupe list etc.
+    pub namespace: String,
+}
+
+/// A command `/exec/{name}` may run, from an `EXEC_COMMANDS` entry.
+pub struct ExecCommand {
+    pub name: String,
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+pub struct Config {
+    pub port: String,
+    /// Serve on this Unix socket instead of `port`, from `UNIX_SOCKET`, e.g. behind a
+    /// reverse proxy on the same machine. A socket from systemd comes first.
+    pub unix_socket: Option<PathBuf>,
+    /// Serve HTTPS instead of HTTP, with `TLS=1`, `TLS_CERT` and `TLS_KEY` or
+    /// `TLS_CLIENT_CA`; see [`crate::tls`].
+    pub tls: Option<Tls>,
+    /// Serve the gRPC API (see `proto/print_jobber.proto`) on this port too.
+    pub grpc_port: Option<u16>,
+    /// Origins allowed to call the API from a browser, from `CORS_ORIGINS`; `*` allows
+    /// any. Empty turns CORS off.
+    pub cors_origins: Vec<String>,
+    /// Methods allowed cross-origin, from `CORS_METHODS`.
+    pub cors_methods: Vec<String>,
+    /// When non-empty, every request must present one of these keys.
+    pub api_keys: Vec<ApiKey>,
+    /// Print "via <source>" and the job ID at the bottom of every receipt.
+    pub source_footer: bool,
+    /// Print a line with the time, source and job ID at the top of every receipt, from
+    /// `JOB_METADATA`. `?no_header` leaves it off one job.
+    pub job_metadata: bool,
+    /// Where the server can be reached from a phone, e.g. `http://printer.local:3000`,
+    /// from `JOB_QR_URL`. When set, every receipt ends with a small QR code for its
+    /// `/jobs/{id}/content`.
+    pub job_qr_url: Option<String>,
+    /// Keep every job's receipt in `archive/` in the data directory, from `ARCHIVE_JOBS`,
+    /// so `/jobs/{id}/content` and reprints keep working after a restart.
+    pub archive_jobs: bool,
+    /// Text masked on every job before it's archived or printed: the built-in `cards`,
+    /// `tokens` and `emails` from `REDACT`, and the regular expressions in
+    /// `REDACT_PATTERNS`, one per line (see [`parse_redactions`]).
+    pub redactions: Vec<Redaction>,
+    /// Locale for printed dates, e.g. `de_DE`.
+    pub locale: Locale,
+    /// Pattern for the long date in report headers, from `DATE_FORMAT`; see [`timefmt`].
+    pub date_format: String,
+    /// Pattern for times of day, from `TIME_FORMAT`: `24h` (the default), `12h`, or a
+    /// pattern of its own such as `%H.%M`.
+    pub time_format: String,
+    /// Release the USB handle after this long without a job. `None` keeps it open forever.
+    pub printer_idle_timeout: Option<Duration>,
+    /// How often an idle printer is sent a status query to catch a dead handle, from
+    /// `PRINTER_KEEPALIVE_SECONDS`. `None` (set to 0) turns the check off.
+    pub printer_keepalive: Option<Duration>,
+    /// USB printer to open, from `PRINTER_USB=vvvv:pppp`. `None` picks the first one
+    /// [`crate::discovery`] finds.
+    pub printer_usb: Option<UsbId>,
+    /// Bluetooth printer to connect to instead of USB, from `PRINTER_BLUETOOTH`.
+    pub printer_bluetooth: Option<BtAddr>,
+    /// RFCOMM channel of the printer's serial port service; almost always 1.
+    pub printer_bluetooth_channel: u8,
+    /// The main printer is behind a `print-jobber agent` that pulls its jobs (see
+    /// [`crate::agent`]), from `PRINTER_AGENT`.
+    pub printer_agent: bool,
+    /// The printer model's quirks, by name from `PRINTER_PROFILE`: a built-in profile or
+    /// one defined in `PRINTER_PROFILES` (see [`profile::parse`]).
+    pub printer_profile: Profile,
+    /// Ask the printer for its model on startup (`GS I`) and switch to the profile for it
+    /// (see [`profile::for_model`]), from `PRINTER_AUTODETECT`. `printer_profile` stays
+    /// when the printer doesn't answer or isn't recognised.
+    pub printer_autodetect: bool,
+    /// Font receipts are laid out and printed in, from `PRINTER_FONT` (`a` or `b`). Font
+    /// B fits the profile's `font_b_columns` on a line.
+    pub printer_font: Font,
+    /// Line feed in dots, from `LINE_SPACING`; unset keeps the printer's 1/6 inch.
+    pub line_spacing: Option<u8>,
+    /// More printers, by name, from `PRINTERS` (see [`parse_named_printer`]). The main
+    /// printer is called `default`.
+    pub printers: Vec<NamedPrinter>,
+    /// Printers every job goes to at once, from `MIRROR_PRINTERS`, unless the request
+    /// says `?mirror=`. Empty prints on the main printer only.
+    pub mirror_printers: Vec<String>,
+    /// Which printer jobs go to by their name, API key or `?tag=`, from `PRINTER_ROUTES`
+    /// (see [`parse_printer_route`]). The first matching rule wins.
+    pub printer_routes: Vec<PrinterRoute>,
+    /// A printer from `PRINTERS` that jobs go to instead when the main printer is offline
+    /// or fails mid-job, from `PRINTER_FAILOVER`.
+    pub printer_failover: Option<String>,
+    /// Printers on other print-jobber servers that jobs are forwarded to, by name, from
+    /// `RELAYS` (see [`parse_relay`]). `default` forwards the main printer's jobs, for an
+    /// instance with no printer of its own.
+    pub relays: Vec<Relay>,
+    /// Hours shown in the weather's hourly table instead of the default every-3-hours view.
+    pub weather_key_hours: Vec<NaiveTime>,
+    /// Add an AIR section (AQI, particulates, pollen) to the weather report.
+    pub weather_air_quality: bool,
+    /// A sensor's JSON endpoint, from `WEATHER_INDOOR_URL`, whose reading is printed in an
+    /// INDOOR section beside the outdoor forecast; see [`crate::weather`].
+    pub weather_indoor_url: Option<String>,
+    /// End every weather report with the 7-day outlook grid.
+    pub weather_outlook: bool,
+    /// Per-attempt timeout for forecast requests.
+    pub weather_timeout: Duration,
+    /// Extra forecast attempts, with exponential backoff, before falling back to the cache.
+    pub weather_retries: u32,
+    /// Known places for multi-location reports; other names are geocoded.
+    pub weather_locations: Vec<Location>,
+    /// Switches the weather's temperatures, wind speeds and precipitation amounts, and
+    /// tide heights, between metric and imperial.
+    pub weather_units: Units,
+    /// N2YO key for the ISS passes on `/sky`; without one only the planets are printed.
+    pub n2yo_api_key: Option<String>,
+    /// NOAA station for `/tides` when the request doesn't name one.
+    pub tide_station: Option<String>,
+    /// Personal access token for `/github/{user}`'s GraphQL queries, from `GITHUB_TOKEN`.
+    pub github_token: Option<String>,
+    /// NASA key for `/apod`, from `NASA_API_KEY`.
+    pub nasa_api_key: Option<String>,
+    /// Longest APOD explanation printed, from `APOD_MAX_CHARS`; longer ones are cut at a
+    /// word. `None` (set to 0) prints it whole.
+    pub apod_max_chars: Option<usize>,
+    /// Rows of the `/habits` grid, from `HABITS`.
+    pub habits: Vec<String>,
+    /// iCalendar feeds for the briefing's agenda, from `CALENDAR_URLS`.
+    pub calendar_urls: Vec<String>,
+    /// Events for `/countdowns`, from `COUNTDOWNS` (see [`parse_countdown`]).
+    pub countdowns: Vec<Countdown>,
+    /// A vCard or CSV file of birthdays to count down to as well, from `BIRTHDAYS_FILE`;
+    /// see [`countdowns::parse_birthdays`].
+    pub birthdays_file: Option<PathBuf>,
+    /// A Markdown or plain text list whose open items the briefing prints, from `TODO_URL`.
+    pub todo_url: Option<String>,
+    /// Hacker News headlines in the default briefing, from `BRIEFING_HEADLINES`; 0 leaves
+    /// them out.
+    pub briefing_headlines: usize,
+    /// The briefing's sections in order, from `BRIEFING_SECTIONS` (see
+    /// [`briefing::parse_section`]). Empty uses the default sections.
+    pub briefing_sections: Vec<briefing::Section>,
+    pub stocks_provider: StocksProvider,
+    pub stocks_api_key: Option<String>,
+    pub stock_symbols: Vec<String>,
+    /// Currency `/fx` quotes rates in, from `FX_BASE`.
+    pub fx_base: String,
+    /// Currencies `/fx` prints, from `FX_SYMBOLS`.
+    pub fx_symbols: Vec<String>,
+    pub tracking_provider: TrackingProvider,
+    pub tracking_api_key: Option<String>,
+    /// Shipments `/packages` checks, from `TRACKING_NUMBERS` (see [`parse_tracking_number`]).
+    pub tracking_numbers: Vec<TrackedPackage>,
+    pub schedule: Vec<ScheduledJob>,
+    pub schedule_retry_interval: Duration,
+    pub schedule_max_retries: u32,
+    /// Print a short slip when a scheduled job's upstream fetch fails.
+    pub schedule_error_slips: bool,
+    /// Printing requests get a 429 while this many jobs are waiting, queued or scheduled,
+    /// from `MAX_QUEUED_JOBS`. Unset lets the queue grow.
+    pub max_queued_jobs: Option<usize>,
+    /// Jobs submitted or due during these hours wait for them to end, unless the request
+    /// says `?override_quiet=true`, and idle printers aren't sent keep-alive queries.
+    pub quiet_hours: Option<QuietHours>,
+    /// Printed above every job; see [`crate::template`] for placeholders.
+    pub header_template: Option<String>,
+    /// Printed below every job, before the source footer.
+    pub footer_template: Option<String>,
+    /// Printed at the top of every `/ticket`, e.g. the event name.
+    pub ticket_header: Option<String>,
+    /// Default completion callback per API key name, from `CALLBACK_URLS=name=url,...`.
+    pub callback_urls: HashMap<String, String>,
+    /// Key for the HMAC-SHA256 signature on completion callbacks.
+    pub callback_secret: Option<String>,
+    /// Push alerts for an offline printer, failed jobs and low paper, from `NTFY_URL`
+    /// or `PUSHOVER_TOKEN` and `PUSHOVER_USER`.
+    pub notifier: Option<Notifier>,
+    /// Length of a fresh paper roll, for estimating what's left.
+    pub paper_roll_length_mm: Option<f64>,
+    /// Warn when this much of the roll or less is left.
+    pub paper_low_percent: f64,
+    /// Print files dropped into this directory, from `SPOOL_DIR`; see [`crate::spool`].
+    pub spool_dir: Option<PathBuf>,
+    /// Where reminders and other state that outlives a restart are saved.
+    pub data_dir: PathBuf,
+    /// URL schemes `/fetch` may download from, from `FETCH_SCHEMES`; http and https by default.
+    pub fetch_schemes: Vec<String>,
+    /// Hosts (and their subdomains) `/fetch` may download from, from `FETCH_HOSTS`.
+    /// Empty allows any host.
+    pub fetch_hosts: Vec<String>,
+    /// Largest download `/fetch` accepts, from `FETCH_MAX_BYTES`.
+    pub fetch_max_bytes: usize,
+    /// Directory of Lua plugin scripts, from `PLUGINS_DIR`; see [`crate::plugins`].
+    pub plugins_dir: Option<PathBuf>,
+    /// Hosts plugins may fetch from, from `PLUGIN_HOSTS`. Empty keeps them offline.
+    pub plugin_hosts: Vec<String>,
+    /// How long a plugin may run, fetches included, from `PLUGIN_TIMEOUT_SECONDS`.
+    pub plugin_timeout: Duration,
+    /// Commands `/exec/{name}` may run, from `EXEC_COMMANDS` (see [`parse_exec_command`]).
+    pub exec_commands: Vec<ExecCommand>,
+    /// How long a command may run before it's killed, from `EXEC_TIMEOUT_SECONDS`.
+    pub exec_timeout: Duration,
+    /// Replaces the Open-Meteo hosts, e.g. to point at a local stub in tests.
+    pub open_meteo_url: Option<String>,
+    /// Replaces `https://api.n2yo.com`, likewise for tests.
+    pub n2yo_url: Option<String>,
+    /// Replaces `https://api.tidesandcurrents.noaa.gov`, likewise for tests.
+    pub noaa_tides_url: Option<String>,
+    /// Replaces the Hacker News API base, likewise for tests.
+    pub hn_url: Option<String>,
+    /// Replaces `https://api.github.com`, likewise for tests.
+    pub github_url: Option<String>,
+    /// Replaces `https://xkcd.com`, likewise for tests.
+    pub xkcd_url: Option<String>,
+    /// Replaces `https://api.nasa.gov`, likewise for tests.
+    pub nasa_url: Option<String>,
+    /// Replaces the tracking provider's API base, likewise for tests.
+    pub tracking_url: Option<String>,
+    /// Replaces `https://api.frankfurter.app`, likewise for tests.
+    pub fx_url: Option<String>,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Builds the config from any variable lookup, e.g. a map in tests.
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let vars = Vars(&lookup);
+        let printer_idle_timeout = vars.parsed::<u64>("PRINTER_IDLE_MINUTES")
+            .filter(|&minutes| minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes * 60));
+        let printer_keepalive = Some(vars.parsed::<u64>("PRINTER_KEEPALIVE_SECONDS").unwrap_or(30))
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs);
+
+        let printer_usb = vars.var("PRINTER_USB").filter(|v| !v.is_empty()).and_then(|v| {
+            let id = v.parse().ok();
+            if id.is_none() {
+                eprintln!("Ignoring invalid PRINTER_USB {:?} (expected vendor:product in hex)", v);
+            }
+            id
+        });
+
+        let printer_bluetooth = vars.var("PRINTER_BLUETOOTH").filter(|v| !v.is_empty()).and_then(|v| {
+            let address = v.parse().ok();
+            if address.is_none() {
+                eprintln!("Ignoring invalid PRINTER_BLUETOOTH {:?} (expected an address like 66:22:B3:4C:1A:0F)", v);
+            }
+            address
+        });
+
+        let mut profiles = profile::builtins();
+        for entry in vars.list("PRINTER_PROFILES") {
+            match profile::parse(&entry, &profiles) {
+                Some(profile) => profiles.push(profile),
+                None => eprintln!("Ignoring invalid PRINTER_PROFILES entry {:?}", entry),
+            }
+        }
+        let printer_profile = match vars.var("PRINTER_PROFILE").filter(|v| !v.is_empty()) {
+            // Later definitions override built-ins of the same name.
+            Some(name) => profiles.iter().rev().find(|p| p.name == name).cloned().unwrap_or_else(|| {
+                eprintln!("Unknown PRINTER_PROFILE {:?}, using the default", name);
+                Profile::default()
+            }),
+            None => Profile::default(),
+        };
+
+        let printers = vars.list("PRINTERS").iter().filter_map(|entry| parse_named_printer(entry, &profiles, &printer_profile)).collect();
+
+        let stocks_provider = match vars.var("STOCKS_PROVIDER").as_deref() {
+            Some("alphavantage") => StocksProvider::AlphaVantage,
+            _ => StocksProvider::Finnhub,
+        };
+
+        let tracking_provider = match vars.var("TRACKING_PROVIDER").as_deref() {
+            Some("17track") => TrackingProvider::SeventeenTrack,
+            _ => TrackingProvider::AfterShip,
+        };
+
+        let locale = vars
+            .var("LOCALE")
+            .and_then(|v| {
+                let locale = v.parse().ok();
+                if locale.is_none() {
+                    eprintln!("Ignoring unknown LOCALE {:?}", v);
+                }
+                locale
+            })
+            .unwrap_or(Locale::en_US);
+        let date_format = match vars.var("DATE_FORMAT").filter(|v| !v.is_empty()) {
+            Some(pattern) if timefmt::valid_pattern(&pattern, true) => pattern,
+            Some(pattern) => {
+                eprintln!("Ignoring invalid DATE_FORMAT {:?}", pattern);
+                timefmt::DEFAULT_DATE_FORMAT.to_owned()
+            }
+            None => timefmt::DEFAULT_DATE_FORMAT.to_owned(),
+        };
+        let time_format = match vars.var("TIME_FORMAT").as_deref().map(str::trim) {
+            None | Some("" | "24h") => timefmt::CLOCK_24H.to_owned(),
+            Some("12h") => timefmt::CLOCK_12H.to_owned(),
+            Some(pattern) if timefmt::valid_pattern(pattern, false) => pattern.to_owned(),
+            Some(pattern) => {
+                eprintln!("Ignoring invalid TIME_FORMAT {:?} (expected 24h, 12h or a time pattern)", pattern);
+                timefmt::CLOCK_24H.to_owned()
+            }
+        };
+
+        let notifier = match (vars.var("NTFY_URL"), vars.var("PUSHOVER_TOKEN"), vars.var("PUSHOVER_USER")) {
+            (Some(url), _, _) if !url.is_empty() => Some(Notifier::Ntfy { url }),
+            (_, Some(token), Some(user)) => Some(Notifier::Pushover { token, user }),
+            _ => None,
+        };
+
+        let data_dir: PathBuf = vars.var("DATA_DIR").unwrap_or("data".to_owned()).into();
+        let tls_cert = vars.var("TLS_CERT").filter(|v| !v.is_empty());
+        let tls_key = vars.var("TLS_KEY").filter(|v| !v.is_empty());
+        let client_ca = vars.var("TLS_CLIENT_CA").filter(|v| !v.is_empty()).map(PathBuf::from);
+        let tls = (vars.flag("TLS") || tls_cert.is_some() || tls_key.is_some() || client_ca.is_some()).then(|| Tls {
+            cert: tls_cert.map(PathBuf::from).unwrap_or_else(|| data_dir.join("tls/cert.pem")),
+            key: tls_key.map(PathBuf::from).unwrap_or_else(|| data_dir.join("tls/key.pem")),
+            client_ca,
+        });
+
+        Config {
+            port: vars.var("PORT").unwrap_or("3000".to_owned()),
+            unix_socket: vars.var("UNIX_SOCKET").filter(|path| !path.is_empty()).map(PathBuf::from),
+            tls,
+            grpc_port: vars.parsed("GRPC_PORT"),
+            cors_origins: vars.list("CORS_ORIGINS"),
+            cors_methods: match vars.list("CORS_METHODS") {
+                methods if methods.is_empty() => ["GET", "POST", "PUT", "DELETE"].map(str::to_owned).to_vec(),
+                methods => methods,
+            },
+            api_keys: vars.list("API_KEYS").iter().filter_map(|entry| parse_api_key(entry)).collect(),
+            source_footer: vars.flag("SOURCE_FOOTER"),
+            job_metadata: vars.flag("JOB_METADATA"),
+            archive_jobs: vars.flag("ARCHIVE_JOBS"),
+            redactions: parse_redactions(&vars.list("REDACT"), &vars.var("REDACT_PATTERNS").unwrap_or_default()),
+            job_qr_url: vars.var("JOB_QR_URL").filter(|url| !url.is_empty()).map(|url| url.trim_end_matches('/').to_owned()),
+            locale,
+            date_format,
+            time_format,
+            printer_idle_timeout,
+            printer_keepalive,
+            printer_usb,
+            printer_bluetooth,
+            printer_bluetooth_channel: vars.parsed("PRINTER_BLUETOOTH_CHANNEL").unwrap_or(1),
+            printer_agent: vars.flag("PRINTER_AGENT"),
+            printer_profile,
+            printer_autodetect: vars.flag("PRINTER_AUTODETECT"),
+            printer_font: vars.parsed("PRINTER_FONT").unwrap_or_default(),
+            line_spacing: vars.parsed("LINE_SPACING"),
+            printers,
+            mirror_printers: vars.list("MIRROR_PRINTERS"),
+            printer_routes: vars.list("PRINTER_ROUTES").iter().filter_map(|entry| parse_printer_route(entry)).collect(),
+            printer_failover: vars.var("PRINTER_FAILOVER").filter(|name| !name.is_empty()),
+            relays: vars.list("RELAYS").iter().filter_map(|entry| parse_relay(entry)).collect(),
+            weather_key_hours: vars.list("WEATHER_KEY_HOURS")
+                .iter()
+                .filter_map(|v| {
+                    let time = NaiveTime::parse_from_str(v, "%H:%M").ok();
+                    if time.is_none() {
+                        eprintln!("Ignoring invalid WEATHER_KEY_HOURS entry {:?} (expected HH:MM)", v);
+                    }
+                    time
+                })
+                .collect(),
+            weather_air_quality: vars.flag("WEATHER_AIR_QUALITY"),
+            weather_indoor_url: vars.var("WEATHER_INDOOR_URL").filter(|url| !url.is_empty()),
+            weather_outlook: vars.flag("WEATHER_OUTLOOK"),
+            weather_timeout: Duration::from_secs(vars.parsed("WEATHER_TIMEOUT_SECONDS").unwrap_or(10)),
+            weather_retries: vars.parsed("WEATHER_RETRIES").unwrap_or(2),
+            weather_locations: vars.list("WEATHER_LOCATIONS").iter().filter_map(|entry| parse_location(entry)).collect(),
+            weather_units: match vars.var("WEATHER_UNITS").as_deref() {
+                Some("metric") => Units::Metric,
+                _ => Units::Imperial,
+            },
+            n2yo_api_key: vars.var("N2YO_API_KEY"),
+            tide_station: vars.var("TIDE_STATION"),
+            github_token: vars.var("GITHUB_TOKEN").filter(|token| !token.is_empty()),
+            nasa_api_key: vars.var("NASA_API_KEY").filter(|key| !key.is_empty()),
+            apod_max_chars: Some(vars.parsed("APOD_MAX_CHARS").unwrap_or(600)).filter(|&max| max > 0),
+            habits: vars.list("HABITS"),
+            calendar_urls: vars.list("CALENDAR_URLS"),
+            countdowns: vars.list("COUNTDOWNS").iter().filter_map(|entry| parse_countdown(entry)).collect(),
+            birthdays_file: vars.var("BIRTHDAYS_FILE").filter(|v| !v.is_empty()).map(PathBuf::from),
+            todo_url: vars.var("TODO_URL").filter(|url| !url.is_empty()),
+            briefing_headlines: vars.parsed("BRIEFING_HEADLINES").unwrap_or(5),
+            briefing_sections: vars
+                .list("BRIEFING_SECTIONS")
+                .iter()
+                .filter_map(|entry| {
+                    let section = briefing::parse_section(entry);
+                    if section.is_none() {
+                        eprintln!("Ignoring invalid BRIEFING_SECTIONS entry {:?}", entry);
+                    }
+                    section
+                })
+                .collect(),
+            stocks_provider,
+            stocks_api_key: vars.var("STOCKS_API_KEY"),
+            stock_symbols: vars.list("STOCK_SYMBOLS"),
+            fx_base: vars.var("FX_BASE").map(|base| base.trim().to_uppercase()).filter(|base| base.len() == 3).unwrap_or("EUR".to_owned()),
+            fx_symbols: match vars.list("FX_SYMBOLS") {
+                symbols if symbols.is_empty() => ["USD", "GBP", "JPY", "CHF"].map(str::to_owned).to_vec(),
+                symbols => symbols.iter().map(|symbol| symbol.to_uppercase()).collect(),
+            },
+            tracking_provider,
+            tracking_api_key: vars.var("TRACKING_API_KEY").filter(|key| !key.is_empty()),
+            tracking_numbers: vars.list("TRACKING_NUMBERS").iter().filter_map(|entry| parse_tracking_number(entry)).collect(),
+            schedule: vars.list("SCHEDULE").iter().filter_map(|entry| parse_scheduled_job(entry)).collect(),
+            schedule_retry_interval: Duration::from_secs(vars.parsed("SCHEDULE_RETRY_MINUTES").unwrap_or(15) * 60),
+            schedule_max_retries: vars.parsed("SCHEDULE_MAX_RETRIES").unwrap_or(3),
+            schedule_error_slips: vars.flag("SCHEDULE_ERROR_SLIPS"),
+            max_queued_jobs: vars.parsed("MAX_QUEUED_JOBS").filter(|&max| max > 0),
+            quiet_hours: vars.var("QUIET_HOURS").filter(|v| !v.is_empty()).and_then(|v| parse_quiet_hours(&v)),
+            header_template: vars.var("HEADER_TEMPLATE").filter(|t| !t.is_empty()),
+            footer_template: vars.var("FOOTER_TEMPLATE").filter(|t| !t.is_empty()),
+            ticket_header: vars.var("TICKET_HEADER"),
+            callback_urls: vars.list("CALLBACK_URLS").iter().filter_map(|entry| parse_callback_url(entry)).collect(),
+            callback_secret: vars.var("CALLBACK_SECRET").filter(|s| !s.is_empty()),
+            notifier,
+            paper_roll_length_mm: vars.parsed::<f64>("PAPER_ROLL_METERS").filter(|&m| m > 0.0).map(|m| m * 1000.0),
+            paper_low_percent: vars.parsed("PAPER_LOW_PERCENT").unwrap_or(10.0),
+            spool_dir: vars.var("SPOOL_DIR").filter(|v| !v.is_empty()).map(PathBuf::from),
+            data_dir,
+            fetch_schemes: match vars.list("FETCH_SCHEMES") {
+                schemes if schemes.is_empty() => vec!["http".to_owned(), "https".to_owned()],
+                schemes => schemes.iter().map(|scheme| scheme.to_lowercase()).collect(),
+            },
+            fetch_hosts: vars.list("FETCH_HOSTS").iter().map(|host| host.to_lowercase()).collect(),
+            fetch_max_bytes: vars.parsed("FETCH_MAX_BYTES").unwrap_or(5 * 1024 * 1024),
+            plugins_dir: vars.var("PLUGINS_DIR").filter(|v| !v.is_empty()).map(PathBuf::from),
+            plugin_hosts: vars.list("PLUGIN_HOSTS").iter().map(|host| host.to_lowercase()).collect(),
+            plugin_timeout: Duration::from_secs(vars.parsed("PLUGIN_TIMEOUT_SECONDS").unwrap_or(5)),
+            exec_commands: vars.list("EXEC_COMMANDS").iter().filter_map(|entry| parse_exec_command(entry)).collect(),
+            exec_timeout: Duration::from_secs(vars.parsed("EXEC_TIMEOUT_SECONDS").unwrap_or(10)),
+            open_meteo_url: vars.var("OPEN_METEO_URL"),
+            n2yo_url: vars.var("N2YO_URL"),
+            noaa_tides_url: vars.var("NOAA_TIDES_URL"),
+            hn_url: vars.var("HN_URL"),
+            github_url: vars.var("GITHUB_URL"),
+            xkcd_url: vars.var("XKCD_URL"),
+            nasa_url: vars.var("NASA_URL"),
+            tracking_url: vars.var("TRACKING_URL"),
+            fx_url: vars.var("FX_URL"),
+        }
+    }
+}
+
+struct Vars<'a>(&'a dyn Fn(&str) -> Option<String>);
+
+impl Vars<'_> {
+    fn var(&self, name: &str) -> Option<String> {
+        (self.0)(name)
+    }
+
+    /// Reads a comma-separated variable, skipping empty entries.
+    fn list(&self, name: &str) -> Vec<String> {
+        self.var(name)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    fn parsed<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.var(name).and_then(|v| v.trim().parse().ok())
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        matches!(self.var(name).as_deref(), Some("1" | "true" | "yes"))
+    }
+}
+
+/// Parses a `name:lat:lon` location entry, e.g. `hamburg:53.55:9.99`.
+fn parse_location(entry: &str) -> Option<Location> {
+    let mut parts = entry.split(':');
+    let parsed = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(name), Some(lat), Some(lon), None) if !name.is_empty() => lat
+            .parse()
+            .ok()
+            .zip(lon.parse().ok())
+            .map(|(lat, lon)| Location {
+                name: name.to_lowercase(),
+                lat,
+                lon,
+            }),
+        _ => None,
+    };
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid WEATHER_LOCATIONS entry {:?} (expected name:lat:lon)", entry);
+    }
+    parsed
+}
+
+/// Parses a `name=date` countdown entry, e.g. `Christmas=12-24` (every year) or
+/// `Trip to Lisbon=2025-06-14`.
+fn parse_countdown(entry: &str) -> Option<Countdown> {
+    let parsed = entry.rsplit_once('=').and_then(|(name, date)| {
+        Some(Countdown {
+            name: Some(name.trim()).filter(|name| !name.is_empty())?.to_owned(),
+            date: countdowns::parse_date(date.trim())?,
+            birthday: false,
+        })
+    });
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid COUNTDOWNS entry {:?} (expected name=YYYY-MM-DD or name=MM-DD)", entry);
+    }
+    parsed
+}
+
+/// Parses a `name=device` printer entry, where the device is `usb` (the first one found),
+/// `usb:vvvv:pppp`, `bt:<address>[/channel]`, `tcp:<host>[:port]` or `agent` (one fed by
+/// a [`crate::agent`]), e.g. `office=bt:66:22:B3:4C:1A:0F/2` or `backup=tcp:192.168.1.50`.
+/// A `:profile=<name>` suffix gives the printer a profile of its own from `profiles`, e.g.
+/// `kitchen=usb:0416:5011:profile=star-tsp100`; without one it shares `main`. Host names
+/// are looked up once, at startup.
+fn parse_named_printer(entry: &str, profiles: &[Profile], main: &Profile) -> Option<NamedPrinter> {
+    let parsed = entry.split_once('=').and_then(|(name, device)| {
+        let (device, profile) = match device.rsplit_once(":profile=") {
+            Some((device, profile)) => (device, profiles.iter().rev().find(|p| p.name == profile.trim()).cloned()?),
+            None => (device, main.clone()),
+        };
+        let device = match device.trim().split_once(':') {
+            None if device.trim() == "usb" => Device::Usb(None),
+            None if device.trim() == "agent" => Device::Agent,
+            Some(("usb", id)) => Device::Usb(Some(id.parse().ok()?)),
+            Some(("bt", address)) => {
+                let (address, channel) = address.split_once('/').unwrap_or((address, "1"));
+                Device::Bluetooth(address.parse().ok()?, channel.parse().ok()?)
+            }
+            Some(("tcp", host)) => {
+                let host = if host.contains(':') { host.to_owned() } else { format!("{}:{}", host, network::DEFAULT_PORT) };
+                Device::Network(host.to_socket_addrs().ok()?.next()?)
+            }
+            _ => return None,
+        };
+        let name = name.trim();
+        (!name.is_empty()).then(|| NamedPrinter {
+            name: name.to_owned(),
+            device,
+            profile,
+        })
+    });
+    if parsed.is_none() {
+        eprintln!(
+            "Ignoring invalid PRINTERS entry {:?} (expected name=usb, name=usb:vvvv:pppp, name=bt:<address>, name=tcp:<host> or name=agent, optionally followed by :profile=<name> of a known profile)",
+            entry
+        );
+    }
+    parsed
+}
+
+/// Parses a `name=url [key]` relay entry: the downstream server's base URL and the API
+/// key to send it, if it needs one, e.g. `office=https://office.local:3000 s3cret`.
+fn parse_relay(entry: &str) -> Option<Relay> {
+    let parsed = entry.split_once('=').and_then(|(name, target)| {
+        let mut target = target.split_whitespace();
+        let url = target.next().filter(|url| callback::valid_url(url))?;
+        let key = target.next().map(str::to_owned);
+        let name = name.trim();
+        (!name.is_empty() && target.next().is_none()).then(|| Relay {
+            name: name.to_owned(),
+            url: url.trim_end_matches('/').to_owned(),
+            key,
+        })
+    });
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid RELAYS entry {:?} (expected name=http(s)://host[:port] [key])", entry);
+    }
+    parsed
+}
+
+/// Parses a `job:<name>=printer`, `key:<name>=printer` or `tag:<name>=printer` routing
+/// rule, e.g. `job:weather=kitchen` or `key:alerts=office`.
+fn parse_printer_route(entry: &str) -> Option<PrinterRoute> {
+    let parsed = entry.split_once('=').and_then(|(when, printer)| {
+        let (kind, name) = when.trim().split_once(':')?;
+        let name = name.to_owned();
+        let when = match kind {
+            "job" => RouteMatch::Job(name),
+            "key" => RouteMatch::Key(name),
+            "tag" => RouteMatch::Tag(name),
+            _ => return None,
+        };
+        let printer = printer.trim();
+        (!printer.is_empty()).then(|| PrinterRoute { when, printer: printer.to_owned() })
+    });
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid PRINTER_ROUTES entry {:?} (expected job:<name>=printer, key:<name>=printer or tag:<name>=printer)", entry);
+    }
+    parsed
+}
+
+/// Parses a `[label=]number[:carrier]` tracking entry, e.g. `1Z999AA10123456784:ups` or
+/// `Headphones=1Z999AA10123456784:ups`.
+fn parse_tracking_number(entry: &str) -> Option<TrackedPackage> {
+    let (label, rest) = match entry.split_once('=') {
+        Some((label, rest)) => (Some(label.trim().to_owned()).filter(|l| !l.is_empty()), rest),
+        None => (None, entry),
+    };
+    let (number, carrier) = match rest.split_once(':') {
+        Some((number, carrier)) => (number.trim(), Some(carrier.trim().to_lowercase()).filter(|c| !c.is_empty())),
+        None => (rest.trim(), None),
+    };
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_alphanumeric()) {
+        eprintln!("Ignoring invalid TRACKING_NUMBERS entry {:?} (expected [label=]number[:carrier])", entry);
+        return None;
+    }
+    Some(TrackedPackage {
+        number: number.to_owned(),
+        carrier,
+        label,
+    })
+}
+
+/// Parses a `keyname=url` callback entry.
+fn parse_callback_url(entry: &str) -> Option<(String, String)> {
+    match entry.split_once('=') {
+        Some((name, url)) if !name.is_empty() && crate::callback::valid_url(url) => Some((name.to_owned(), url.to_owned())),
+        _ => {
+            eprintln!("Ignoring invalid CALLBACK_URLS entry {:?} (expected keyname=url)", entry);
+            None
+        }
+    }
+}
+
+/// Parses a `name=program args...` command entry, e.g. `disk=df -h`. Arguments are split
+/// on whitespace; there is no quoting.
+fn parse_exec_command(entry: &str) -> Option<ExecCommand> {
+    let parsed = entry.split_once('=').and_then(|(name, command)| {
+        let name = name.trim();
+        let mut words = command.split_whitespace().map(str::to_owned);
+        let program = words.next()?;
+        (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')).then(|| ExecCommand {
+            name: name.to_owned(),
+            program,
+            args: words.collect(),
+        })
+    });
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid EXEC_COMMANDS entry {:?} (expected name=program args)", entry);
+    }
+    parsed
+}
+
+/// Parses a `HH:MM-HH:MM` range, which may span midnight.
+fn parse_quiet_hours(entry: &str) -> Option<QuietHours> {
+    let parsed = entry.split_once('-').and_then(|(start, end)| {
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+        (start != end).then_some(QuietHours { start, end })
+    });
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid QUIET_HOURS {:?} (expected HH:MM-HH:MM)", entry);
+    }
+    parsed
+}
+
+/// Parses `REDACT` names and `REDACT_PATTERNS` lines. Patterns go one per line, not
+/// comma-separated, as they often contain commas themselves.
+fn parse_redactions(names: &[String], patterns: &str) -> Vec<Redaction> {
+    let builtin = names.iter().filter_map(|name| match name.to_lowercase().as_str() {
+        "cards" => Some(Redaction::cards()),
+        "tokens" => Some(Redaction::tokens()),
+        "emails" => Some(Redaction::emails()),
+        _ => {
+            eprintln!("Ignoring unknown REDACT filter {:?} (expected cards, tokens or emails)", name);
+            None
+        }
+    });
+    let custom = patterns.lines().map(str::trim).filter(|line| !line.is_empty()).filter_map(|pattern| {
+        Redaction::pattern(pattern)
+            .inspect_err(|e| eprintln!("Ignoring invalid REDACT_PATTERNS pattern {:?}: {}", pattern, e))
+            .ok()
+    });
+    builtin.chain(custom).collect()
+}
+
+/// Splits an optional `namespace/` prefix off `name`.
+fn split_namespace(name: &str) -> (String, &str) {
+    match name.split_once('/') {
+        Some((namespace, rest)) if !namespace.is_empty() => (namespace.to_owned(), rest),
+        _ => (DEFAULT_NAMESPACE.to_owned(), name),
+    }
+}
+
+/// Parses a `[namespace/]name:token` API key entry.
+fn parse_api_key(entry: &str) -> Option<ApiKey> {
+    match entry.split_once(':') {
+        Some((name, token)) if !name.is_empty() && !token.is_empty() => {
+            let (namespace, name) = split_namespace(name);
+            Some(ApiKey {
+                name: name.to_owned(),
+                token: token.to_owned(),
+                namespace,
+            })
+        }
+        _ => {
+            eprintln!("Ignoring invalid API_KEYS entry (expected [namespace/]name:token)");
+            None
+        }
+    }
+}
+
+/// Parses a `[namespace/]job@[weekday ]HH:MM` schedule entry, e.g. `weather@07:00`,
+/// `shop/stocks@09:30` or `habits@mon 07:00`.
+fn parse_scheduled_job(entry: &str) -> Option<ScheduledJob> {
+    let parsed = entry.split_once('@').and_then(|(job, when)| {
+        let (namespace, job) = split_namespace(job.trim());
+        let (weekday, at) = match when.trim().split_once(' ') {
+            Some((weekday, at)) => (Some(weekday.parse().ok()?), at),
+            None => (None, when),
+        };
+        Some(ScheduledJob {
+            namespace,
+            job: job.to_owned(),
+            at: NaiveTime::parse_from_str(at.trim(), "%H:%M").ok()?,
+            weekday,
+        })
+    });
+    if parsed.is_none() {
+        eprintln!("Ignoring invalid SCHEDULE entry {:?} (expected [namespace/]job@[weekday ]HH:MM)", entry);
+    }
+    parsed
+}
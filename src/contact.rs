@@ -0,0 +1,86 @@
+use crate::{AppState, receipt::Receipt, source::Source};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Scale of the name above the code; wide enough for most names on one line.
+const NAME_SCALE: u8 = 2;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ContactRequest {
+    name: String,
+    phone: Option<String>,
+    email: Option<String>,
+    url: Option<String>,
+    organization: Option<String>,
+    title: Option<String>,
+}
+
+/// Escapes text for a vCard property value (RFC 6350 section 3.4).
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+/// A vCard 3.0, which phone cameras and contact apps import more reliably than 4.0.
+fn vcard(contact: &ContactRequest) -> String {
+    // N is family;given; split on the last space as a best guess.
+    let name = contact.name.trim();
+    let (given, family) = name.rsplit_once(' ').unwrap_or((name, ""));
+    let mut card = vec![
+        "BEGIN:VCARD".to_owned(),
+        "VERSION:3.0".to_owned(),
+        format!("N:{};{};;;", escape(family), escape(given)),
+        format!("FN:{}", escape(name)),
+    ];
+    let optional = [
+        ("ORG", &contact.organization),
+        ("TITLE", &contact.title),
+        ("TEL;TYPE=CELL", &contact.phone),
+        ("EMAIL", &contact.email),
+        ("URL", &contact.url),
+    ];
+    for (property, value) in optional {
+        if let Some(value) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+            card.push(format!("{}:{}", property, escape(value)));
+        }
+    }
+    card.push("END:VCARD".to_owned());
+    card.join("\r\n")
+}
+
+#[utoipa::path(
+    post,
+    path = "/contact",
+    tag = "printing",
+    summary = "Print a contact card with a vCard QR code",
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 400, description = "No name."),
+    )
+)]
+pub async fn contact(State(state): State<AppState>, source: Source, Json(request): Json<ContactRequest>) -> Result<(), StatusCode> {
+    if request.name.trim().is_empty() {
+        eprintln!("Contact request without a name");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    eprintln!("Contact card for {:?}", request.name);
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let name = request.name.trim();
+    // Names too long for the large font still fit at normal size.
+    if name.chars().count() <= receipt.width() / NAME_SCALE as usize {
+        receipt.line_large(name, NAME_SCALE);
+    } else {
+        receipt.line_center(name);
+    }
+    if let Some(organization) = &request.organization {
+        receipt.line_center(organization);
+    }
+    receipt.qr_code(&vcard(&request), 6);
+    state.print(receipt, "contact", &source);
+    Ok(())
+}
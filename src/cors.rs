@@ -0,0 +1,43 @@
+//! Cross-origin access for browser frontends served from elsewhere, e.g. a dashboard
+//! on another host on the LAN. Off unless `CORS_ORIGINS` is set.
+
+use crate::config::Config;
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// The CORS layer for the configured origins, or `None` when cross-origin requests
+/// aren't allowed.
+pub fn layer(config: &Config) -> Option<CorsLayer> {
+    if config.cors_origins.is_empty() {
+        return None;
+    }
+    let origins = if config.cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(config.cors_origins.iter().filter_map(|origin| {
+            let value = HeaderValue::from_str(origin).ok();
+            if value.is_none() {
+                eprintln!("Ignoring invalid CORS_ORIGINS entry {:?}", origin);
+            }
+            value
+        }))
+    };
+    let methods: Vec<Method> = config
+        .cors_methods
+        .iter()
+        .filter_map(|method| {
+            let parsed = method.to_uppercase().parse().ok();
+            if parsed.is_none() {
+                eprintln!("Ignoring invalid CORS_METHODS entry {:?}", method);
+            }
+            parsed
+        })
+        .collect();
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(methods)
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, HeaderName::from_static("x-api-key")])
+            .expose_headers([header::RETRY_AFTER]),
+    )
+}
@@ -0,0 +1,180 @@
+//! Countdowns to the events in `COUNTDOWNS` and the birthdays in `BIRTHDAYS_FILE`, as
+//! "37 days until ..." lines for `/countdowns` and the briefing's `countdowns` section.
+
+use crate::{
+    AppState,
+    config::{Config, Countdown, CountdownDate},
+    error::JobError,
+    receipt::Receipt,
+    source::Source,
+    timefmt,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{Datelike, Local, NaiveDate};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::path::Path;
+
+#[derive(Deserialize, IntoParams)]
+pub struct CountdownParams {
+    /// Only events this many days away or closer.
+    #[serde(default = "default_within")]
+    #[param(default = default_within)]
+    within: i64,
+}
+
+pub fn default_within() -> i64 {
+    365
+}
+
+/// The next time `date` comes round on or after `today`; `None` for a one-off date that
+/// has passed. Feb 29 birthdays fall on Feb 28 in other years.
+fn next_occurrence(date: &CountdownDate, today: NaiveDate) -> Option<NaiveDate> {
+    match *date {
+        CountdownDate::Once(date) => (date >= today).then_some(date),
+        CountdownDate::Yearly { month, day, .. } => {
+            let in_year = |year: i32| NaiveDate::from_ymd_opt(year, month, day).or_else(|| NaiveDate::from_ymd_opt(year, month, day - 1));
+            in_year(today.year()).filter(|&date| date >= today).or_else(|| in_year(today.year() + 1))
+        }
+    }
+}
+
+fn line(countdown: &Countdown, date: NaiveDate, today: NaiveDate) -> String {
+    let what = match countdown.date {
+        CountdownDate::Yearly { birth_year: Some(year), .. } if countdown.birthday => {
+            format!("{}'s birthday (turns {})", countdown.name, date.year() - year)
+        }
+        _ if countdown.birthday => format!("{}'s birthday", countdown.name),
+        _ => countdown.name.clone(),
+    };
+    match (date - today).num_days() {
+        0 => format!("Today: {}", what),
+        1 => format!("Tomorrow: {}", what),
+        days => format!("{} days until {}", days, what),
+    }
+}
+
+/// Parses a `BIRTHDAYS_FILE`: a vCard file (`.vcf`), using each card's `FN` and `BDAY`,
+/// or CSV lines of `name,date`. Dates are `YYYY-MM-DD` or, without a year, `MM-DD`.
+/// Entries without a readable date are skipped.
+pub fn parse_birthdays(path: &Path, text: &str) -> Vec<Countdown> {
+    let birthday = |name: &str, date: &str| {
+        let date = parse_date(date.trim())?;
+        let date = match date {
+            CountdownDate::Once(date) => CountdownDate::Yearly {
+                month: date.month(),
+                day: date.day(),
+                birth_year: Some(date.year()),
+            },
+            yearly => yearly,
+        };
+        Some(Countdown {
+            name: name.trim().to_owned(),
+            date,
+            birthday: true,
+        })
+    };
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("vcf")) {
+        // Folded lines continue with a leading space.
+        let unfolded = text.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+        let mut birthdays = Vec::new();
+        let (mut name, mut bday) = (None, None);
+        for line in unfolded.lines() {
+            let Some((key, value)) = line.split_once(':') else { continue };
+            // Properties may carry parameters, e.g. `BDAY;VALUE=date:`.
+            match key.split(';').next().unwrap_or(key).to_uppercase().as_str() {
+                "BEGIN" => (name, bday) = (None, None),
+                "FN" => name = Some(value.replace("\\,", ",")),
+                "BDAY" => bday = Some(value.to_owned()),
+                "END" => {
+                    if let (Some(name), Some(bday)) = (name.take(), bday.take()) {
+                        birthdays.extend(birthday(&name, &bday));
+                    }
+                }
+                _ => {}
+            }
+        }
+        birthdays
+    } else {
+        text.lines().filter_map(|line| line.rsplit_once(',')).filter_map(|(name, date)| birthday(name, date)).collect()
+    }
+}
+
+/// `2025-12-24` (also `20251224`) once, or `12-24` (also vCard's `--1224`) every year.
+pub fn parse_date(text: &str) -> Option<CountdownDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d").or_else(|_| NaiveDate::parse_from_str(text, "%Y%m%d")) {
+        return Some(CountdownDate::Once(date));
+    }
+    let digits = text.trim_start_matches('-').replace('-', "");
+    let (month, day) = (digits.get(..2)?.parse().ok()?, digits.get(2..)?.parse().ok()?);
+    // Checked against a leap year so Feb 29 is allowed.
+    NaiveDate::from_ymd_opt(2000, month, day)?;
+    Some(CountdownDate::Yearly { month, day, birth_year: None })
+}
+
+/// Countdown lines for everything within `within` days of `today`, soonest first.
+pub fn upcoming(config: &Config, today: NaiveDate, within: i64) -> Result<Vec<String>, JobError> {
+    let mut countdowns = config.countdowns.clone();
+    if let Some(path) = &config.birthdays_file {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| JobError::Unprocessable(format!("can't read {}: {}", path.display(), e)))?;
+        countdowns.extend(parse_birthdays(path, &text));
+    }
+    if countdowns.is_empty() {
+        return Err(JobError::NotConfigured("COUNTDOWNS"));
+    }
+    let mut dated: Vec<(NaiveDate, &Countdown)> = countdowns
+        .iter()
+        .filter_map(|countdown| Some((next_occurrence(&countdown.date, today)?, countdown)))
+        .filter(|(date, _)| (*date - today).num_days() <= within)
+        .collect();
+    dated.sort_by_key(|(date, _)| *date);
+    Ok(dated.into_iter().map(|(date, countdown)| line(countdown, date, today)).collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/countdowns",
+    tag = "printing",
+    summary = "Print countdowns to upcoming events",
+    description = "\"37 days until ...\" for each `COUNTDOWNS` entry (`name=YYYY-MM-DD` once, or `name=MM-DD` every \
+        year) and each birthday in `BIRTHDAYS_FILE` (a vCard `.vcf` file, or CSV lines of `name,date`), \
+        soonest first. Birthdays with a year say the age. Also available as the `countdowns` scheduled job and \
+        briefing section.",
+    params(CountdownParams),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 422, description = "`BIRTHDAYS_FILE` can't be read."),
+        (status = 503, description = "Neither `COUNTDOWNS` nor `BIRTHDAYS_FILE` is set, or the printer is down for \
+            maintenance; see `Retry-After`."),
+    )
+)]
+pub async fn countdowns(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<CountdownParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_countdowns(&state, &source, params.within)?)
+}
+
+pub fn print_countdowns(state: &AppState, source: &Source, within: i64) -> Result<(), JobError> {
+    let today = Local::now().date_naive();
+    let lines = upcoming(&state.config, today, within).inspect_err(|e| eprintln!("Countdowns unavailable: {}", e))?;
+    eprintln!("Countdowns request, {} upcoming", lines.len());
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("COUNTDOWNS");
+    receipt.line_center(&timefmt::long_date(&state.config, today));
+    receipt.divider();
+    if lines.is_empty() {
+        receipt.line_center("Nothing coming up");
+    }
+    for line in lines {
+        receipt.wrapped(&line);
+    }
+    state.print(receipt, "countdowns", source);
+    Ok(())
+}
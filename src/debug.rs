@@ -0,0 +1,283 @@
+//! `POST /debug/render`: lays out a print request like `POST /` and returns the bytes
+//! the printer would be sent, as an annotated hex dump, without printing anything.
+//!
+//! ```text
+//! 0000  1b 40                                            ESC @ — initialize
+//! 0002  1b 61 01                                         ESC a 1 — center
+//! 0005  48 65 6c 6c 6f 0a                                "Hello" LF
+//! ```
+
+use crate::{
+    AppState, PrintParams,
+    printer::{self, PrinterDriver},
+    profile::CommandSet,
+    source::Source,
+};
+use axum::{
+    extract::{Query, Request, State},
+    http::StatusCode,
+};
+use escpos::{driver::Driver, errors::Result as EscposResult};
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+const LF: u8 = 0x0a;
+const ESC: u8 = 0x1b;
+const FS: u8 = 0x1c;
+const GS: u8 = 0x1d;
+/// Bytes per dump line.
+const ROW: usize = 16;
+/// Longer commands (images, QR data) are cut short after this many bytes.
+const MAX_SHOWN: usize = 2 * ROW;
+
+/// A printer driver that keeps everything written to it.
+#[derive(Clone, Default)]
+pub struct Capture {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Capture {
+    /// The bytes written since the last call.
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.data.lock().unwrap())
+    }
+}
+
+impl Driver for Capture {
+    fn name(&self) -> String {
+        "capture".to_owned()
+    }
+
+    fn write(&self, data: &[u8]) -> EscposResult<()> {
+        self.data.lock().unwrap().extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> EscposResult<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> EscposResult<()> {
+        Ok(())
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/debug/render",
+    tag = "status",
+    summary = "Show the printer commands for a print request",
+    description = "Lays out the body like `POST /`, with the same parameters, and returns the bytes the printer would \
+        be sent for the active `PRINTER_PROFILE` as a hex dump: one command per line with its mnemonic and what \
+        it does, e.g. `ESC a 1 — center`. Templates see job `#0`. Nothing is printed or queued.",
+    request_body(content(
+        (String = "text/plain"),
+        (serde_json::Value = "application/json"),
+        (crate::upload::UploadForm = "multipart/form-data"),
+    )),
+    params(PrintParams),
+    responses(
+        (status = 200, description = "The annotated hex dump, with a `# job N of M` line before each job when there \
+            are several.", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid options."),
+        (status = 415, description = "An uploaded file of an unsupported type."),
+        (status = 422, description = "The text can't be laid out, e.g. a word too long with `strict`."),
+    )
+)]
+pub async fn render(
+    State(state): State<AppState>,
+    source: Source,
+    Query(mut params): Query<PrintParams>,
+    request: Request,
+) -> Result<String, StatusCode> {
+    let receipts = crate::receipts(&state, &source, &mut params, request).await?;
+    let profile = &state.config.printer_profile;
+    let capture = Capture::default();
+    let mut printer = Some(printer::new_printer(PrinterDriver::Capture(capture.clone()), profile).map_err(|e| {
+        eprintln!("Failed to set up the capturing printer: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?);
+
+    let mut dump = String::new();
+    let count = receipts.len();
+    for (i, mut receipt) in receipts.into_iter().enumerate() {
+        if !receipt.is_plain() {
+            // Templates see job #0, since nothing is queued.
+            state.decorate(&mut receipt, 0, "print", &source);
+        }
+        receipt.print(&mut printer, profile).map_err(|e| {
+            eprintln!("Failed to render job: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        if count > 1 {
+            let _ = writeln!(dump, "# job {} of {}", i + 1, count);
+        }
+        dump.push_str(&annotate(&capture.take(), profile.commands));
+    }
+    Ok(dump)
+}
+
+/// A hex dump of `data` with one command per line, each followed by its mnemonic and
+/// what it does.
+pub fn annotate(data: &[u8], commands: CommandSet) -> String {
+    let mut dump = String::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let rest = &data[offset..];
+        let command = match commands {
+            CommandSet::EscPos => escpos_command(rest),
+            CommandSet::StarLine => star_command(rest),
+        };
+        let (len, note) = command.unwrap_or_else(|| other(rest));
+        let len = len.clamp(1, rest.len());
+
+        let shown = &rest[..len.min(MAX_SHOWN)];
+        for (i, row) in shown.chunks(ROW).enumerate() {
+            let hex: Vec<String> = row.iter().map(|b| format!("{:02x}", b)).collect();
+            let note = if i == 0 { note.as_str() } else { "" };
+            let line = format!("{:04x}  {:<width$}  {}", offset + i * ROW, hex.join(" "), note, width = ROW * 3 - 1);
+            let _ = writeln!(dump, "{}", line.trim_end());
+        }
+        if len > shown.len() {
+            let _ = writeln!(dump, "      ... {} more bytes", len - shown.len());
+        }
+        offset += len;
+    }
+    dump
+}
+
+/// Text up to the next control byte, with the line feed after it if there is one; or an
+/// unknown control byte on its own.
+fn other(data: &[u8]) -> (usize, String) {
+    let text = data.iter().take_while(|&&b| b >= 0x20 && b != 0x7f).count();
+    if text == 0 {
+        return (1, format!("0x{:02x} — unknown", data[0]));
+    }
+    let note = format!("\"{}\"", data[..text].escape_ascii());
+    match data.get(text) {
+        Some(&LF) => (text + 1, format!("{} LF", note)),
+        _ => (text, note),
+    }
+}
+
+fn on_off(n: u8) -> &'static str {
+    if n & 1 == 1 { "on" } else { "off" }
+}
+
+/// The length and description of the ESC/POS command at the start of `data`.
+fn escpos_command(data: &[u8]) -> Option<(usize, String)> {
+    let command = match *data {
+        [LF, ..] => (1, "LF — print and feed a line".to_owned()),
+        [ESC, b'@', ..] => (2, "ESC @ — initialize".to_owned()),
+        [ESC, b't', n, ..] => (3, format!("ESC t {} — code page {}", n, n)),
+        [ESC, b'R', n, ..] => (3, format!("ESC R {} — international character set {}", n, n)),
+        [ESC, b'a', n, ..] => {
+            let mode = match n {
+                0 | b'0' => "left",
+                1 | b'1' => "center",
+                2 | b'2' => "right",
+                _ => "invalid justification",
+            };
+            (3, format!("ESC a {} — {}", n, mode))
+        }
+        [ESC, b'E', n, ..] => (3, format!("ESC E {} — bold {}", n, on_off(n))),
+        [ESC, b'-', n, ..] => {
+            let mode = match n {
+                0 | b'0' => "off",
+                1 | b'1' => "single",
+                2 | b'2' => "double",
+                _ => "invalid",
+            };
+            (3, format!("ESC - {} — underline {}", n, mode))
+        }
+        [ESC, b'!', n, ..] => (3, format!("ESC ! {} — print mode 0x{:02x}", n, n)),
+        [ESC, b'M', n, ..] => (3, format!("ESC M {} — font {}", n, if n & 1 == 1 { "B" } else { "A" })),
+        [ESC, b'2', ..] => (2, "ESC 2 — default line spacing".to_owned()),
+        [ESC, b'3', n, ..] => (3, format!("ESC 3 {} — line spacing {} dots", n, n)),
+        [ESC, b'd', n, ..] => (3, format!("ESC d {} — feed {} lines", n, n)),
+        [ESC, b'J', n, ..] => (3, format!("ESC J {} — feed {} dots", n, n)),
+        [ESC, b'B', n, t, ..] => (4, format!("ESC B {} {} — beep {} times for {}ms", n, t, n, t as u32 * 50)),
+        [GS, b'B', n, ..] => (3, format!("GS B {} — inverse {}", n, on_off(n))),
+        [GS, b'!', n, ..] => (3, format!("GS ! {} — size {}x{}", n, (n >> 4) + 1, (n & 0x0f) + 1)),
+        [GS, b'V', m @ (0 | 1 | b'0' | b'1'), ..] => (3, format!("GS V {} — {} cut", m, if m & 1 == 1 { "partial" } else { "full" })),
+        [GS, b'V', m @ (b'A' | b'B'), n, ..] => (
+            4,
+            format!("GS V {} {} — feed {} dots, then {} cut", m, n, n, if m == b'B' { "partial" } else { "full" }),
+        ),
+        [GS, b'(', b'k', pl, ph, cn, f, ..] => {
+            // pL and pH count the bytes after themselves, cn and fn included.
+            let len = (5 + pl as usize + 256 * ph as usize).max(7);
+            let what = match (cn, f) {
+                (49, 65) => "QR code model".to_owned(),
+                (49, 67) => format!("QR code module size {}", data.get(7).copied().unwrap_or_default()),
+                (49, 69) => "QR code error correction".to_owned(),
+                (49, 80) => format!("QR code data \"{}\"", data.get(8..len.min(data.len())).unwrap_or_default().escape_ascii()),
+                (49, 81) => "print QR code".to_owned(),
+                _ => format!("2D code function {} {}", cn, f),
+            };
+            (len, format!("GS ( k — {}", what))
+        }
+        [GS, b'(', b'L', pl, ph, ..] => (5 + pl as usize + 256 * ph as usize, "GS ( L — graphics".to_owned()),
+        [GS, b'v', b'0', _, xl, xh, yl, yh, ..] => {
+            let (bytes, rows) = (xl as usize + 256 * xh as usize, yl as usize + 256 * yh as usize);
+            (8 + bytes * rows, format!("GS v 0 — raster image {}x{} dots", bytes * 8, rows))
+        }
+        [FS, b'p', n, _, ..] => (4, format!("FS p {} — print NV image {}", n, n)),
+        [FS, b'&', ..] => (2, "FS & — Kanji mode on".to_owned()),
+        [FS, b'.', ..] => (2, "FS . — Kanji mode off".to_owned()),
+        [FS, b'C', n, ..] => {
+            let system = match n {
+                0 | b'0' => "JIS",
+                1 | b'1' => "Shift JIS",
+                2 | b'2' => "Shift JIS-2004",
+                _ => "invalid",
+            };
+            (3, format!("FS C {} — Kanji code system {}", n, system))
+        }
+        _ => return None,
+    };
+    Some(command)
+}
+
+/// The length and description of the Star Line Mode command at the start of `data`;
+/// see [`crate::star`].
+fn star_command(data: &[u8]) -> Option<(usize, String)> {
+    let command = match *data {
+        [LF, ..] => (1, "LF — print and feed a line".to_owned()),
+        [ESC, b'@', ..] => (2, "ESC @ — initialize".to_owned()),
+        [ESC, GS, b'a', n, ..] => {
+            let mode = ["left", "center", "right"].get(n as usize).copied().unwrap_or("invalid justification");
+            (4, format!("ESC GS a {} — {}", n, mode))
+        }
+        [ESC, GS, b't', n, ..] => (4, format!("ESC GS t {} — code page {}", n, n)),
+        [ESC, GS, 0x19, 0x11, ..] => (7, "ESC GS EM DC1 — buzzer timing".to_owned()),
+        [ESC, GS, 0x19, 0x12, _, n, ..] => (7, format!("ESC GS EM DC2 — beep {} times", n)),
+        [ESC, GS, b'y', b'S', b'0', n, ..] => (6, format!("ESC GS y S 0 {} — QR code model", n)),
+        [ESC, GS, b'y', b'S', b'1', n, ..] => (6, format!("ESC GS y S 1 {} — QR code error correction", n)),
+        [ESC, GS, b'y', b'S', b'2', n, ..] => (6, format!("ESC GS y S 2 {} — QR code cell size {}", n, n)),
+        [ESC, GS, b'y', b'D', b'1', _, nl, nh, ..] => {
+            let len = 8 + nl as usize + 256 * nh as usize;
+            (len, format!("ESC GS y D 1 — QR code data \"{}\"", data.get(8..len.min(data.len())).unwrap_or_default().escape_ascii()))
+        }
+        [ESC, GS, b'y', b'P', ..] => (4, "ESC GS y P — print QR code".to_owned()),
+        [ESC, GS, b'S', _, xl, xh, yl, yh, _, ..] => {
+            let (bytes, rows) = (xl as usize + 256 * xh as usize, yl as usize + 256 * yh as usize);
+            (9 + bytes * rows, format!("ESC GS S — raster image {}x{} dots", bytes * 8, rows))
+        }
+        [ESC, b'i', h, w, ..] => (4, format!("ESC i {} {} — size {}x{}", h, w, w + 1, h + 1)),
+        [ESC, 0x1e, b'F', n, ..] => (4, format!("ESC RS F {} — font {}", n, if n & 1 == 1 { "B" } else { "A" })),
+        [ESC, b'3', n, ..] => (3, format!("ESC 3 {} — line spacing {}mm", n, n as f64 / 4.0)),
+        [ESC, b'z', n, ..] => (3, format!("ESC z {} — line spacing 1/{} inch", n, if n == 1 { 6 } else { 8 })),
+        [ESC, b'E', ..] => (2, "ESC E — bold on".to_owned()),
+        [ESC, b'F', ..] => (2, "ESC F — bold off".to_owned()),
+        [ESC, b'-', n, ..] => (3, format!("ESC - {} — underline {}", n, on_off(n))),
+        [ESC, b'4', ..] => (2, "ESC 4 — inverse on".to_owned()),
+        [ESC, b'5', ..] => (2, "ESC 5 — inverse off".to_owned()),
+        [ESC, b'a', n, ..] => (3, format!("ESC a {} — feed {} lines", n, n)),
+        [ESC, b'd', n, ..] => (3, format!("ESC d {} — {} cut", n, if n == 3 { "partial" } else { "full" })),
+        _ => return None,
+    };
+    Some(command)
+}
@@ -0,0 +1,128 @@
+use crate::maintenance::Window;
+use escpos::{
+    driver::ConsoleDriver,
+    printer::Printer,
+    utils::{PageCode, Protocol},
+};
+use rusb::UsbContext;
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct SubsystemError {
+    pub message: String,
+    /// Unix timestamp (seconds) of when the error was recorded.
+    pub at: u64,
+}
+
+#[derive(Clone, Default, Serialize, ToSchema)]
+pub struct Report {
+    pub usb_device_found: bool,
+    pub driver_opened: bool,
+    pub init_ok: bool,
+    pub page_code_supported: bool,
+    /// Whether the last keep-alive status query got through; `None` before the first.
+    pub printer_responding: Option<bool>,
+    /// Times the keep-alive gave up on the handle and reopened the printer.
+    pub reconnects: u64,
+    /// The USB printer is on the bus but kept failing keep-alive probes after being
+    /// reopened, until it answers again. Only a restart tends to help.
+    pub wedged: bool,
+    /// The model name the printer reported with `PRINTER_AUTODETECT`.
+    pub printer_model: Option<String>,
+    pub last_errors: BTreeMap<&'static str, SubsystemError>,
+    /// The current maintenance window, if any. Filled in when the report is served.
+    pub maintenance: Option<Window>,
+}
+
+/// Running record of printer bring-up results and the most recent failure per subsystem.
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+    report: Arc<Mutex<Report>>,
+}
+
+impl Diagnostics {
+    pub fn update(&self, f: impl FnOnce(&mut Report)) {
+        f(&mut self.report.lock().unwrap());
+    }
+
+    pub fn record_error(&self, subsystem: &'static str, message: impl Into<String>) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.update(|report| {
+            report.last_errors.insert(
+                subsystem,
+                SubsystemError {
+                    message: message.into(),
+                    at,
+                },
+            );
+        });
+    }
+
+    pub fn snapshot(&self) -> Report {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+pub fn usb_device_present(vendor_id: u16, product_id: u16) -> Result<bool, rusb::Error> {
+    // An explicit context reports libusb init failures instead of panicking like the global one.
+    let context = rusb::Context::new()?;
+    Ok(context.devices()?.iter().any(|device| {
+        device
+            .device_descriptor()
+            .map(|d| d.vendor_id() == vendor_id && d.product_id() == product_id)
+            .unwrap_or(false)
+    }))
+}
+
+/// Whether escpos has an encoding table for `page_code`; text written with a
+/// page code it doesn't know fails before anything reaches the printer.
+pub fn page_code_supported(page_code: PageCode) -> bool {
+    let mut printer = Printer::new(ConsoleDriver::open(false), Protocol::default(), None);
+    printer
+        .page_code(page_code)
+        .and_then(|p| p.write("Diagnostics"))
+        .is_ok()
+}
+
+impl Report {
+    pub fn lines(&self) -> Vec<String> {
+        let yes_no = |b: bool| if b { "yes" } else { "NO" };
+        let mut lines = vec![
+            format!("USB device found:    {}", yes_no(self.usb_device_found)),
+            format!("Driver opened:       {}", yes_no(self.driver_opened)),
+            format!("Init OK:             {}", yes_no(self.init_ok)),
+            format!("Page code supported: {}", yes_no(self.page_code_supported)),
+        ];
+        if let Some(responding) = self.printer_responding {
+            lines.push(format!("Responding:          {}", yes_no(responding)));
+        }
+        if self.reconnects > 0 {
+            lines.push(format!("Reconnects:          {}", self.reconnects));
+        }
+        if let Some(model) = &self.printer_model {
+            lines.push(format!("Model:               {}", model));
+        }
+        if let Some(window) = &self.maintenance {
+            let reason = window.reason.as_deref().map(|r| format!(" ({})", r)).unwrap_or_default();
+            lines.push(format!("Maintenance until:   {}{}", window.until.format("%H:%M"), reason));
+        }
+        if self.last_errors.is_empty() {
+            lines.push("No errors recorded".to_owned());
+        } else {
+            lines.push("Last errors:".to_owned());
+            for (subsystem, error) in &self.last_errors {
+                lines.push(format!("  [{}] {}", subsystem, error.message));
+            }
+        }
+        lines
+    }
+}
@@ -0,0 +1,129 @@
+use crate::AppState;
+use axum::{Json, extract::State, http::StatusCode};
+use rusb::UsbContext;
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+use utoipa::ToSchema;
+
+/// USB vendors that make ESC/POS receipt printers. Several cheap brands share a
+/// controller vendor ID, so those match on the printer interface class as well.
+const KNOWN_VENDORS: &[(u16, &str)] = &[
+    (0x04b8, "Epson"),
+    (0x0519, "Star Micronics"),
+    (0x1504, "Bixolon"),
+    (0x1d90, "Citizen"),
+    (0x154f, "SNBC"),
+    (0x0dd4, "Custom"),
+    (0x0fe6, "Rongta/Xprinter"),
+    (0x0416, "Zjiang/POS-58"),
+];
+/// bInterfaceClass of USB printers.
+const PRINTER_CLASS: u8 = 7;
+
+/// A `vendor:product` pair, written in hex like `lsusb` does, e.g. `04b8:0e28`.
+#[derive(Clone, Copy, PartialEq)]
+pub struct UsbId {
+    pub vendor_id: u16,
+    pub product_id: u16,
+}
+
+impl fmt::Display for UsbId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vendor_id, self.product_id)
+    }
+}
+
+impl FromStr for UsbId {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let (vendor, product) = s.trim().split_once(':').ok_or(())?;
+        Ok(UsbId {
+            vendor_id: u16::from_str_radix(vendor.trim_start_matches("0x"), 16).map_err(|_| ())?,
+            product_id: u16::from_str_radix(product.trim_start_matches("0x"), 16).map_err(|_| ())?,
+        })
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Candidate {
+    pub id: String,
+    /// Vendor name from [`KNOWN_VENDORS`].
+    pub vendor: Option<&'static str>,
+    /// The device's own manufacturer and product strings, when it lets us read them.
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    /// Has a USB printer-class interface.
+    pub printer_class: bool,
+    pub bus: u8,
+    pub address: u8,
+    #[serde(skip)]
+    pub usb_id: UsbId,
+}
+
+fn has_printer_interface<T: UsbContext>(device: &rusb::Device<T>) -> bool {
+    device.active_config_descriptor().or_else(|_| device.config_descriptor(0)).is_ok_and(|config| {
+        config
+            .interfaces()
+            .flat_map(|interface| interface.descriptors())
+            .any(|descriptor| descriptor.class_code() == PRINTER_CLASS)
+    })
+}
+
+/// Connected USB devices that look like receipt printers: a known vendor or a
+/// printer-class interface, known vendors first.
+pub fn discover() -> Result<Vec<Candidate>, rusb::Error> {
+    let context = rusb::Context::new()?;
+    let mut candidates: Vec<Candidate> = context
+        .devices()?
+        .iter()
+        .filter_map(|device| {
+            let descriptor = device.device_descriptor().ok()?;
+            let usb_id = UsbId {
+                vendor_id: descriptor.vendor_id(),
+                product_id: descriptor.product_id(),
+            };
+            let vendor = KNOWN_VENDORS.iter().find(|(id, _)| *id == usb_id.vendor_id).map(|(_, name)| *name);
+            let printer_class = has_printer_interface(&device);
+            if vendor.is_none() && !printer_class {
+                return None;
+            }
+            // Reading strings needs to open the device, which udev permissions may not allow.
+            let handle = device.open().ok();
+            Some(Candidate {
+                id: usb_id.to_string(),
+                vendor,
+                manufacturer: handle.as_ref().and_then(|h| h.read_manufacturer_string_ascii(&descriptor).ok()),
+                product: handle.as_ref().and_then(|h| h.read_product_string_ascii(&descriptor).ok()),
+                printer_class,
+                bus: device.bus_number(),
+                address: device.address(),
+                usb_id,
+            })
+        })
+        .collect();
+    candidates.sort_by_key(|candidate| candidate.vendor.is_none());
+    Ok(candidates)
+}
+
+/// Lists printers that could be used, e.g. to pick one for `PRINTER_USB`.
+#[utoipa::path(
+    get,
+    path = "/printer/discover",
+    tag = "status",
+    summary = "USB devices that look like printers",
+    responses(
+        (status = 200, description = "OK", body = Vec<Candidate>),
+        (status = 503, description = "USB devices can't be enumerated."),
+    )
+)]
+pub async fn list(State(_state): State<AppState>) -> Result<Json<Vec<Candidate>>, StatusCode> {
+    match tokio::task::spawn_blocking(discover).await {
+        Ok(Ok(candidates)) => Ok(Json(candidates)),
+        Ok(Err(e)) => {
+            eprintln!("Failed to enumerate USB devices: {:?}", e);
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
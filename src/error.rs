@@ -0,0 +1,33 @@
+use axum::http::StatusCode;
+use std::fmt;
+
+/// Why a job (from an HTTP request or the scheduler) didn't make it onto paper.
+#[derive(Debug)]
+pub enum JobError {
+    /// A required setting is missing; the message names it.
+    NotConfigured(&'static str),
+    /// An upstream API call failed or returned something unusable.
+    Upstream(String),
+    /// The request's input can't be turned into a receipt, e.g. a page without a recipe.
+    Unprocessable(String),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::NotConfigured(setting) => write!(f, "{} is not configured", setting),
+            JobError::Upstream(reason) => write!(f, "{}", reason),
+            JobError::Unprocessable(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<JobError> for StatusCode {
+    fn from(e: JobError) -> Self {
+        match e {
+            JobError::NotConfigured(_) => StatusCode::SERVICE_UNAVAILABLE,
+            JobError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            JobError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! `/exec/{name}`: runs one of the commands in `EXEC_COMMANDS` and prints what it writes
+//! to stdout, e.g. `disk=df -h`. Commands are run directly, not through a shell, with
+//! no input and within `EXEC_TIMEOUT_SECONDS`.
+
+use crate::{AppState, PrintParams, ansi, error::JobError, job_receipt, receipt::Receipt, source::Source};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use std::process::Stdio;
+use tokio::io::AsyncReadExt;
+
+/// Most output a command may produce; one writing more is killed and fails.
+const MAX_OUTPUT: usize = 256 * 1024;
+
+#[utoipa::path(
+    post,
+    path = "/exec/{name}",
+    tag = "printing",
+    summary = "Print a command's output",
+    description = "Runs the command named `{name}` in `EXEC_COMMANDS` (`name=program args`, run without a shell) and \
+        prints its stdout as terminal output: bold, underline and inverse codes are kept and long lines are \
+        broken at the paper edge. The command is killed after `EXEC_TIMEOUT_SECONDS`. Also usable as a \
+        briefing section, `exec:name=<name>`.",
+    params(
+        ("name" = String, Path, description = "A command's name in `EXEC_COMMANDS`."),
+        PrintParams,
+    ),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 404, description = "No command named `{name}` in `EXEC_COMMANDS`."),
+        (status = 502, description = "The command couldn't be started, exited with an error, timed out or wrote too much."),
+    )
+)]
+pub async fn exec(
+    State(state): State<AppState>,
+    source: Source,
+    Path(name): Path<String>,
+    Query(params): Query<PrintParams>,
+) -> Result<(), StatusCode> {
+    if !state.config.exec_commands.iter().any(|command| command.name == name) {
+        eprintln!("No command named {:?} in EXEC_COMMANDS", name);
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let output = run(&state, &name).await.inspect_err(|e| eprintln!("Command {} failed: {}", name, e))?;
+    let mut receipt = job_receipt(&state.config, &params);
+    receipt.sized(params.size, |receipt| ansi::render(receipt, &output, &params));
+    state.print(receipt, &format!("exec:{}", name), &source);
+    Ok(())
+}
+
+/// Command `name`'s output laid out for the briefing.
+pub async fn render(state: &AppState, name: &str) -> Result<Receipt, JobError> {
+    let output = run(state, name).await?;
+    let mut receipt = Receipt::for_config(&state.config);
+    ansi::render(&mut receipt, &output, &PrintParams::default());
+    Ok(receipt)
+}
+
+/// Runs command `name` and returns its stdout. A non-zero exit fails with the first line
+/// of stderr.
+async fn run(state: &AppState, name: &str) -> Result<String, JobError> {
+    let command = state
+        .config
+        .exec_commands
+        .iter()
+        .find(|command| command.name == name)
+        .ok_or_else(|| JobError::Unprocessable(format!("no command named {} in EXEC_COMMANDS", name)))?;
+    eprintln!("Running {} ({} {})", name, command.program, command.args.join(" "));
+    let failed = |e: std::io::Error| JobError::Upstream(format!("{}: {}", command.program, e));
+    let mut child = tokio::process::Command::new(&command.program)
+        .args(&command.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(failed)?;
+    let (Some(mut stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(JobError::Upstream(format!("{}: no output pipes", command.program)));
+    };
+    // Only the first line of stderr is used; it's read alongside so the command can't
+    // block on a full pipe.
+    let stderr = tokio::spawn(async move {
+        let mut errors = Vec::new();
+        let _ = stderr.take(MAX_OUTPUT as u64).read_to_end(&mut errors).await;
+        errors
+    });
+    let timeout = state.config.exec_timeout;
+    let (status, output) = tokio::time::timeout(timeout, async {
+        let mut output = Vec::new();
+        let mut chunk = [0; 8192];
+        loop {
+            let n = stdout.read(&mut chunk).await.map_err(failed)?;
+            if n == 0 {
+                break;
+            }
+            output.extend_from_slice(&chunk[..n]);
+            if output.len() > MAX_OUTPUT {
+                // Stop a runaway command now rather than at the timeout.
+                let _ = child.kill().await;
+                return Err(JobError::Upstream(format!("{} wrote more than {} bytes", name, MAX_OUTPUT)));
+            }
+        }
+        Ok((child.wait().await.map_err(failed)?, output))
+    })
+    .await
+    .map_err(|_| JobError::Upstream(format!("{} timed out after {}s", name, timeout.as_secs())))??;
+    if !status.success() {
+        let stderr = stderr.await.unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr);
+        let reason = stderr.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+        return Err(JobError::Upstream(format!("{} exited with {}: {}", name, status, reason)));
+    }
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
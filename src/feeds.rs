@@ -0,0 +1,23 @@
+//! Headlines from RSS and Atom feeds, pulled out with the [`html`] helpers rather than
+//! a full XML parser.
+
+use crate::{error::JobError, html};
+
+/// The first `count` item titles of the RSS or Atom feed at `url`.
+pub async fn headlines(url: &str, count: usize) -> Result<Vec<String>, JobError> {
+    let xml = html::fetch_page(url).await?;
+    let xml = xml.replace("<![CDATA[", "").replace("]]>", "");
+    let items = match html::elements(&xml, "item") {
+        items if items.is_empty() => html::elements(&xml, "entry"),
+        items => items,
+    };
+    if items.is_empty() {
+        return Err(JobError::Upstream(format!("no items in feed {}", url)));
+    }
+    Ok(items
+        .iter()
+        .filter_map(|item| html::elements(item, "title").first().map(|title| html::to_text(title)))
+        .filter(|title| !title.is_empty())
+        .take(count)
+        .collect())
+}
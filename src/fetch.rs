@@ -0,0 +1,132 @@
+//! `POST /fetch?url=...`: downloads a document or image and prints it through the same
+//! renderers as an upload, so clients don't each need their own download step.
+
+use crate::{
+    AppState, PrintParams,
+    config::Config,
+    receipt::Receipt,
+    source::Source,
+    upload::{self, Upload},
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize, IntoParams)]
+pub struct FetchParams {
+    /// What to download.
+    url: String,
+}
+
+/// Whether `url` is on the `FETCH_SCHEMES` / `FETCH_HOSTS` allowlists. A host entry
+/// also covers its subdomains; no entries allows every host.
+fn allowed(url: &reqwest::Url, config: &Config) -> bool {
+    let host = url.host_str().unwrap_or_default().to_lowercase();
+    config.fetch_schemes.iter().any(|scheme| scheme == url.scheme())
+        && (config.fetch_hosts.is_empty()
+            || config.fetch_hosts.iter().any(|allowed| host == *allowed || host.ends_with(&format!(".{}", allowed))))
+}
+
+/// The response's MIME type without parameters, sniffing images served without one.
+fn content_type(header: Option<&str>, data: &[u8]) -> Option<String> {
+    let mime = header.and_then(|value| value.split(';').next()).map(|mime| mime.trim().to_lowercase());
+    match mime.as_deref() {
+        None | Some("application/octet-stream") => match image::guess_format(data) {
+            Ok(format) => Some(format.to_mime_type().to_owned()),
+            Err(_) => mime,
+        },
+        _ => mime,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/fetch",
+    tag = "printing",
+    summary = "Download a document or image and print it",
+    description = "Text, Markdown, JSON, CSV and images are printed like uploads to `/`, and take the same query \
+        options. Downloads must be on the `FETCH_SCHEMES` and `FETCH_HOSTS` allowlists, redirects included, \
+        and within `FETCH_MAX_BYTES`.",
+    params(FetchParams, PrintParams),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 400, description = "Not a valid URL."),
+        (status = 403, description = "The URL isn't on the allowlist."),
+        (status = 413, description = "The download is larger than `FETCH_MAX_BYTES`."),
+        (status = 415, description = "Not a type that can be printed; HTML pages go to `/article`."),
+        (status = 502, description = "The download failed."),
+    )
+)]
+pub async fn fetch(
+    State(state): State<AppState>,
+    source: Source,
+    Query(fetch): Query<FetchParams>,
+    Query(params): Query<PrintParams>,
+) -> Result<(), StatusCode> {
+    let url = reqwest::Url::parse(&fetch.url).map_err(|e| {
+        eprintln!("Invalid fetch URL {:?}: {}", fetch.url, e);
+        StatusCode::BAD_REQUEST
+    })?;
+    if !allowed(&url, &state.config) {
+        eprintln!("Fetch URL {} is not on the allowlist", url);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    eprintln!("Fetching {} to print", url);
+
+    // Redirects must stay on the allowlist too.
+    let config = state.config.clone();
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 5 {
+                attempt.error("too many redirects")
+            } else if allowed(attempt.url(), &config) {
+                attempt.follow()
+            } else {
+                attempt.error("redirect off the allowlist")
+            }
+        }))
+        .build()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let mut response = client.get(url.clone()).send().await.and_then(|r| r.error_for_status()).map_err(|e| {
+        eprintln!("Failed to fetch {}: {}", url, e);
+        StatusCode::BAD_GATEWAY
+    })?;
+
+    let limit = state.config.fetch_max_bytes;
+    if response.content_length().is_some_and(|length| length > limit as u64) {
+        eprintln!("{} is larger than FETCH_MAX_BYTES ({} bytes)", url, limit);
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let header = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).map(str::to_owned);
+    let mut data = Vec::new();
+    // The declared length can be missing or wrong, so count while reading.
+    while let Some(chunk) = response.chunk().await.map_err(|_| StatusCode::BAD_GATEWAY)? {
+        if data.len() + chunk.len() > limit {
+            eprintln!("{} is larger than FETCH_MAX_BYTES ({} bytes)", url, limit);
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let content_type = content_type(header.as_deref(), &data);
+    if content_type.as_deref() == Some("text/html") {
+        eprintln!("Not printing HTML from {}; /article extracts the text of web pages", url);
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+    let file_name = url.path_segments().and_then(|mut segments| segments.next_back()).filter(|name| !name.is_empty());
+    let upload = Upload::fetched(content_type, file_name, data);
+    let mut receipt = Receipt::for_config(&state.config);
+    upload::render(&mut receipt, &upload, &params, &state.config)?;
+    if !params.cut {
+        receipt.without_cut();
+    }
+    state.print(receipt, "fetch", &source);
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use figlet_rs::FIGfont;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Font {
+    /// The classic FIGlet font, about 6 columns per letter.
+    #[default]
+    Standard,
+    /// Solid 5x5 capitals, narrower and easier to read from across a room.
+    Block,
+}
+
+impl Font {
+    fn load(self) -> &'static FIGfont {
+        static STANDARD: OnceLock<FIGfont> = OnceLock::new();
+        static BLOCK: OnceLock<FIGfont> = OnceLock::new();
+        // Both fonts are compiled in, so failing to parse them is a bug.
+        match self {
+            Font::Standard => STANDARD.get_or_init(|| FIGfont::standard().expect("bundled standard font")),
+            Font::Block => BLOCK.get_or_init(|| FIGfont::from_content(include_str!("fonts/block.flf")).expect("bundled block font")),
+        }
+    }
+}
+
+/// Columns `text` takes up in `font`.
+fn width(font: &FIGfont, text: &str) -> usize {
+    font.convert(text)
+        .map_or(0, |figure| figure.characters.iter().map(|c| c.width as usize).sum())
+}
+
+/// Greedily packs words into rows no wider than `columns`, splitting words that don't
+/// fit on a row by themselves.
+fn fit_rows(font: &FIGfont, line: &str, columns: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut current = String::new();
+    for word in line.split_whitespace() {
+        let candidate = if current.is_empty() { word.to_owned() } else { format!("{} {}", current, word) };
+        if width(font, &candidate) <= columns {
+            current = candidate;
+            continue;
+        }
+        if !current.is_empty() {
+            rows.push(std::mem::take(&mut current));
+        }
+        for c in word.chars() {
+            current.push(c);
+            if width(font, &current) > columns {
+                current.pop();
+                rows.push(std::mem::replace(&mut current, c.to_string()));
+            }
+        }
+    }
+    if !current.is_empty() {
+        rows.push(current);
+    }
+    rows
+}
+
+/// Renders `text` as ASCII-art lettering, one block of art per row that fits across a
+/// `columns` wide receipt.
+pub fn lines(text: &str, font: Font, columns: usize) -> Vec<String> {
+    let font = font.load();
+    let mut lines = Vec::new();
+    for row in text.lines().flat_map(|line| fit_rows(font, line, columns)) {
+        if let Some(figure) = font.convert(&row) {
+            lines.extend(figure.to_string().lines().map(|line| line.trim_end().to_owned()));
+        }
+    }
+    lines
+}
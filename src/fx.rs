@@ -0,0 +1,159 @@
+//! `/fx`: exchange rates from the free Frankfurter API (ECB reference rates), with the
+//! change since the previous business day.
+
+use crate::{
+    AppState,
+    error::JobError,
+    receipt::{Column, Receipt, render_row},
+    source::Source,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{Local, NaiveDate, TimeDelta};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::collections::{BTreeMap, HashMap};
+
+const API_BASE: &str = "https://api.frankfurter.app";
+
+/// The rate table's columns: 45 wide, or 32 for receipts narrower than that.
+fn columns(width: usize) -> [Column; 5] {
+    if width < 45 {
+        [Column::left(5), Column::right(10), Column::right(8), Column::right(7), Column::right(2)]
+    } else {
+        [Column::left(6), Column::right(14), Column::right(12), Column::right(10), Column::right(3)]
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct FxParams {
+    /// Currency code to quote in, e.g. `EUR`; defaults to `FX_BASE`, else EUR.
+    base: Option<String>,
+    /// Comma-separated currency codes, e.g. `USD,GBP,JPY`; defaults to `FX_SYMBOLS`, else
+    /// USD, GBP, JPY and CHF.
+    symbols: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TimeSeries {
+    /// Rates per business day; weekends and holidays are missing.
+    rates: BTreeMap<NaiveDate, HashMap<String, f64>>,
+}
+
+/// The API base, or `FX_URL` when set.
+fn api_base(state: &AppState) -> &str {
+    state.config.fx_url.as_deref().unwrap_or(API_BASE).trim_end_matches('/')
+}
+
+/// Four decimals for rates around 1, two for ones like JPY in the hundreds.
+fn format_rate(rate: f64) -> String {
+    if rate.abs() >= 100.0 { format!("{:.2}", rate) } else { format!("{:.4}", rate) }
+}
+
+fn arrow(change: f64) -> &'static str {
+    if change > 0.0 {
+        "^"
+    } else if change < 0.0 {
+        "v"
+    } else {
+        "="
+    }
+}
+
+/// Three-letter currency codes, uppercased; anything else is dropped.
+fn parse_symbols(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().to_uppercase())
+        .filter(|s| s.len() == 3 && s.chars().all(|c| c.is_ascii_alphabetic()))
+        .collect()
+}
+
+#[utoipa::path(
+    get,
+    path = "/fx",
+    tag = "printing",
+    summary = "Print exchange rates",
+    description = "The latest ECB reference rates from Frankfurter for one unit of `base`, each with its change since \
+        the previous business day, in right-aligned columns. Also available as the `fx` scheduled job, using \
+        `FX_BASE` and `FX_SYMBOLS`.",
+    params(FxParams),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 400, description = "`base` or `symbols` aren't currency codes."),
+        (status = 502, description = "Frankfurter failed or doesn't know a currency."),
+    )
+)]
+pub async fn fx(State(state): State<AppState>, source: Source, Query(params): Query<FxParams>) -> Result<(), StatusCode> {
+    let base = params.base.map(|base| base.trim().to_uppercase()).unwrap_or_else(|| state.config.fx_base.clone());
+    let symbols = params.symbols.as_deref().map(parse_symbols).unwrap_or_else(|| state.config.fx_symbols.clone());
+    if base.len() != 3 || symbols.is_empty() {
+        eprintln!("Invalid fx request for {:?} in {:?}", symbols, base);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(print_fx(&state, &source, &base, &symbols).await?)
+}
+
+pub async fn print_fx(state: &AppState, source: &Source, base: &str, symbols: &[String]) -> Result<(), JobError> {
+    eprintln!("FX request for {} in {}", symbols.join(","), base);
+
+    // A week back always spans at least two business days.
+    let start = Local::now().date_naive() - TimeDelta::days(7);
+    let url = format!("{}/{}..?from={}&to={}", api_base(state), start, base, symbols.join(","));
+    let series = async {
+        reqwest::get(&url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+            .json::<TimeSeries>()
+            .await
+            .map_err(|e| JobError::Upstream(e.without_url().to_string()))
+    }
+    .await
+    .inspect_err(|e| {
+        eprintln!("Failed to fetch exchange rates: {}", e);
+        state.diagnostics.record_error("fx", e.to_string());
+    })?;
+
+    let mut days = series.rates.iter().rev();
+    let Some((date, latest)) = days.next() else {
+        return Err(JobError::Upstream("no exchange rates returned".to_owned()));
+    };
+    let previous = days.next().map(|(_, rates)| rates);
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let columns = columns(receipt.width());
+    receipt.line_center("EXCHANGE RATES");
+    receipt.line_center(&format!("1 {} on {}", base, date.format_localized("%-d %B %Y", state.config.locale)));
+    receipt.divider();
+    receipt.line_left(&render_row(&columns, &["", "RATE", "CHANGE", "%", ""]));
+    receipt.divider();
+    for symbol in symbols {
+        let Some(&rate) = latest.get(symbol) else {
+            receipt.line_left(&render_row(&columns, &[symbol, "n/a"]));
+            continue;
+        };
+        match previous.and_then(|rates| rates.get(symbol)) {
+            Some(&before) => {
+                let change = rate - before;
+                receipt.line_left(&render_row(
+                    &columns,
+                    &[
+                        symbol,
+                        &format_rate(rate),
+                        &format!("{:+.4}", change),
+                        &format!("{:+.2}%", change / before * 100.0),
+                        arrow(change),
+                    ],
+                ));
+            }
+            None => receipt.line_left(&render_row(&columns, &[symbol, &format_rate(rate)])),
+        }
+    }
+    receipt.divider();
+    receipt.line_center("ECB reference rates");
+
+    state.print(receipt, "fx", source);
+    Ok(())
+}
@@ -0,0 +1,239 @@
+//! `/github/{user}`: a user's contribution calendar for the past year, as the familiar
+//! grid of weeks (columns) by weekdays (rows), with streak stats underneath.
+
+use crate::{AppState, error::JobError, raster, receipt::Receipt, source::Source};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use chrono::{Datelike, Local, NaiveDate, TimeDelta};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+const API_BASE: &str = "https://api.github.com";
+
+const QUERY: &str = "query($login: String!) { user(login: $login) { contributionsCollection { contributionCalendar { \
+    totalContributions weeks { contributionDays { date contributionCount contributionLevel } } } } } }";
+
+/// Characters for the text grid, from no contributions to the busiest quartile.
+const DENSITY: [char; 5] = ['.', '-', '+', '*', '#'];
+
+/// Row labels: GitHub weeks start on Sunday.
+const WEEKDAYS: [&str; 7] = ["", "Mo", "", "We", "", "Fr", ""];
+
+#[derive(Deserialize, IntoParams)]
+pub struct GithubParams {
+    /// Draw the grid as a raster image, which fits all 53 weeks, instead of text.
+    #[serde(default)]
+    #[param(default = false)]
+    image: bool,
+}
+
+#[derive(Deserialize)]
+struct GraphqlResponse {
+    data: Option<Data>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Data {
+    user: Option<User>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct User {
+    contributions_collection: ContributionsCollection,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContributionsCollection {
+    contribution_calendar: Calendar,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Calendar {
+    total_contributions: u32,
+    weeks: Vec<Week>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Week {
+    contribution_days: Vec<Day>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Day {
+    date: NaiveDate,
+    contribution_count: u32,
+    contribution_level: Level,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Level {
+    None,
+    FirstQuartile,
+    SecondQuartile,
+    ThirdQuartile,
+    FourthQuartile,
+}
+
+impl Level {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The API base, or `GITHUB_URL` when set.
+fn api_base(state: &AppState) -> &str {
+    state.config.github_url.as_deref().unwrap_or(API_BASE).trim_end_matches('/')
+}
+
+#[utoipa::path(
+    get,
+    path = "/github/{user}",
+    tag = "printing",
+    summary = "Print a GitHub user's contribution calendar",
+    description = "The past year's contributions as a grid of weeks by weekdays, shaded by GitHub's quartiles \
+        (`. - + * #` as text, which fits the most recent weeks; all 53 as an image), followed by total \
+        contributions, the current and longest streaks and the best day. Needs `GITHUB_TOKEN`.",
+    params(
+        ("user" = String, Path, description = "The GitHub login."),
+        GithubParams,
+    ),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 502, description = "GitHub failed or doesn't know the user."),
+        (status = 503, description = "`GITHUB_TOKEN` isn't set, or the printer is down for maintenance; see `Retry-After`."),
+    )
+)]
+pub async fn github(
+    State(state): State<AppState>,
+    source: Source,
+    Path(user): Path<String>,
+    Query(params): Query<GithubParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_github(&state, &source, &user, params.image).await?)
+}
+
+async fn fetch_calendar(state: &AppState, token: &str, user: &str) -> Result<Calendar, JobError> {
+    let response = reqwest::Client::new()
+        .post(format!("{}/graphql", api_base(state)))
+        .bearer_auth(token)
+        .header(reqwest::header::USER_AGENT, "print-jobber")
+        .json(&serde_json::json!({ "query": QUERY, "variables": { "login": user } }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<GraphqlResponse>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+    match response.data.and_then(|data| data.user) {
+        Some(user) => Ok(user.contributions_collection.contribution_calendar),
+        None => Err(JobError::Upstream(
+            response.errors.into_iter().next().map_or_else(|| format!("unknown GitHub user {}", user), |e| e.message),
+        )),
+    }
+}
+
+pub async fn print_github(state: &AppState, source: &Source, user: &str, image: bool) -> Result<(), JobError> {
+    let token = state.config.github_token.as_deref().ok_or(JobError::NotConfigured("GITHUB_TOKEN"))?;
+    eprintln!("GitHub contributions request for {}", user);
+    let calendar = fetch_calendar(state, token, user).await.inspect_err(|e| {
+        eprintln!("Failed to fetch GitHub contributions for {}: {}", user, e);
+        state.diagnostics.record_error("github", e.to_string());
+    })?;
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let width = receipt.width();
+    receipt.line_center("GITHUB");
+    receipt.line_center(user);
+    receipt.divider();
+    let text = render_grid(&calendar.weeks, width);
+    if image {
+        let levels: Vec<[Option<usize>; 7]> = calendar.weeks.iter().map(week_levels).collect();
+        receipt.image(&raster::contribution_grid(&levels, state.config.printer_profile.raster_width), &text.join("\n"));
+    } else {
+        for line in &text {
+            receipt.line_left(line);
+        }
+    }
+    receipt.divider();
+
+    let days: Vec<&Day> = calendar.weeks.iter().flat_map(|week| &week.contribution_days).collect();
+    let stats = Streaks::of(&days, Local::now().date_naive());
+    receipt.line_left(&format!("Contributions: {}", calendar.total_contributions));
+    receipt.line_left(&format!("Current streak: {} days", stats.current));
+    receipt.line_left(&format!("Longest streak: {} days", stats.longest));
+    if let Some(best) = days.iter().max_by_key(|day| (day.contribution_count, day.date)).filter(|day| day.contribution_count > 0) {
+        receipt.line_left(&format!("Best day: {} ({})", best.date.format("%-d %b %Y"), best.contribution_count));
+    }
+
+    state.print(receipt, "github", source);
+    Ok(())
+}
+
+/// Each weekday's level in `week`, Sunday first. The first and last weeks of the year
+/// are partial.
+fn week_levels(week: &Week) -> [Option<usize>; 7] {
+    let mut levels = [None; 7];
+    for day in &week.contribution_days {
+        levels[day.date.weekday().num_days_from_sunday() as usize] = Some(day.contribution_level.index());
+    }
+    levels
+}
+
+/// One row per weekday, one column per week, keeping the most recent weeks that fit
+/// beside the row labels.
+fn render_grid(weeks: &[Week], width: usize) -> Vec<String> {
+    let shown = &weeks[weeks.len().saturating_sub(width.saturating_sub(3))..];
+    let levels: Vec<[Option<usize>; 7]> = shown.iter().map(week_levels).collect();
+    let mut lines: Vec<String> = WEEKDAYS
+        .iter()
+        .enumerate()
+        .map(|(row, label)| {
+            let cells: String = levels.iter().map(|week| week[row].map_or(' ', |level| DENSITY[level])).collect();
+            format!("{:<3}{}", label, cells).trim_end().to_owned()
+        })
+        .collect();
+    lines.push(format!("{:>width$}", "Less . - + * # More", width = 3 + shown.len()));
+    lines
+}
+
+struct Streaks {
+    /// Consecutive days with contributions up to today, or up to yesterday while today
+    /// has none yet.
+    current: u32,
+    longest: u32,
+}
+
+impl Streaks {
+    fn of(days: &[&Day], today: NaiveDate) -> Self {
+        let mut longest = 0;
+        let mut run = 0;
+        for day in days.iter().filter(|day| day.date <= today) {
+            run = if day.contribution_count > 0 { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        let active = |date: NaiveDate| days.iter().any(|day| day.date == date && day.contribution_count > 0);
+        let mut date = if active(today) { today } else { today - TimeDelta::days(1) };
+        let mut current = 0;
+        while active(date) {
+            current += 1;
+            date -= TimeDelta::days(1);
+        }
+        Streaks { current, longest }
+    }
+}
@@ -0,0 +1,364 @@
+//! The gRPC API from `proto/print_jobber.proto`, on `GRPC_PORT` next to the REST API,
+//! over TLS (with the same client certificate check) when the REST API uses it. Jobs go
+//! through the same queue and history, so both APIs see each other's jobs.
+//! The messages and routing are written out here rather than generated, so the build
+//! doesn't need `protoc`.
+
+// Handlers return tonic's `Status`, which is large but what every gRPC method returns.
+#![allow(clippy::result_large_err)]
+
+use crate::{
+    AppState, PrintParams, audit,
+    history::JobRecord,
+    receipt::Receipt,
+    render_text,
+    source::{Peer, Source},
+    tls::TlsListener,
+};
+use axum::http::StatusCode;
+use std::{convert::Infallible, io, net::SocketAddr, pin::Pin};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::server::TlsStream;
+use tokio_stream::{StreamExt, wrappers::BroadcastStream};
+use tonic::{
+    Code, Request, Response, Status,
+    body::Body,
+    codec::ProstCodec,
+    codegen::{BoxFuture, BoxStream, Context, Poll, Service, http},
+    server::{Grpc, NamedService},
+    transport::server::Connected,
+};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitJobRequest {
+    #[prost(string, tag = "1")]
+    pub text: String,
+    #[prost(bool, tag = "2")]
+    pub raw: bool,
+    #[prost(string, tag = "3")]
+    pub job: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitJobReply {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetJobStatusRequest {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StreamJobEventsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Job {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(string, tag = "2")]
+    pub at: String,
+    #[prost(string, tag = "3")]
+    pub job: String,
+    #[prost(string, tag = "4")]
+    pub source: String,
+    #[prost(string, tag = "5")]
+    pub status: String,
+    #[prost(string, tag = "6")]
+    pub error: String,
+    #[prost(double, tag = "7")]
+    pub paper_mm: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PrinterStatusRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PrinterStatusReply {
+    #[prost(bool, tag = "1")]
+    pub online: bool,
+    #[prost(bool, tag = "2")]
+    pub paused: bool,
+    #[prost(uint32, tag = "3")]
+    pub queued: u32,
+    #[prost(uint32, tag = "4")]
+    pub scheduled: u32,
+    #[prost(double, tag = "5")]
+    pub paper_used_mm: f64,
+    #[prost(double, optional, tag = "6")]
+    pub paper_remaining_mm: Option<f64>,
+    #[prost(bool, tag = "7")]
+    pub paper_low: bool,
+}
+
+impl From<JobRecord> for Job {
+    fn from(record: JobRecord) -> Self {
+        Job {
+            id: record.id,
+            at: record.at,
+            job: record.job,
+            source: record.source,
+            status: record.status.as_str().to_owned(),
+            error: record.error.unwrap_or_default(),
+            paper_mm: record.paper_mm.unwrap_or_default(),
+        }
+    }
+}
+
+/// Who made a call, for the audit log: the client certificate's name over mutual TLS,
+/// else the API key's, else `grpc:<ip>`.
+fn caller<T>(request: &Request<T>, source: Option<&Source>) -> String {
+    let peer = request.extensions().get::<Peer>();
+    if let Some(peer) = peer.filter(|peer| peer.cert_name.is_some()) {
+        return peer.origin();
+    }
+    if let Some(source) = source.filter(|source| source.key_name().is_some()) {
+        return source.to_string();
+    }
+    match peer.map(|peer| peer.addr).or_else(|| request.remote_addr()) {
+        Some(addr) => format!("grpc:{}", addr.ip()),
+        None => "grpc".to_owned(),
+    }
+}
+
+/// What the REST API answers for the same failure, for the audit log.
+fn http_status(status: &Status) -> StatusCode {
+    match status.code() {
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Checks the `authorization: Bearer <token>` (or `x-api-key`) metadata the same way
+/// the REST API checks headers. Refused calls to `method` are audited.
+fn authenticate<T>(state: &AppState, request: &Request<T>, method: &str) -> Result<Source, Status> {
+    if state.config.api_keys.is_empty() {
+        return Ok(Source::grpc(None));
+    }
+    let metadata = request.metadata();
+    let token = metadata
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| metadata.get("x-api-key").and_then(|v| v.to_str().ok()));
+    match state.config.api_keys.iter().find(|key| Some(key.token.as_str()) == token) {
+        Some(key) => Ok(Source::grpc(Some(key))),
+        None => {
+            eprintln!("Rejected gRPC call without a valid API key");
+            audit::record_grpc(state, caller(request, None), &path(method), "", StatusCode::UNAUTHORIZED);
+            Err(Status::unauthenticated("missing or invalid API key"))
+        }
+    }
+}
+
+fn path(method: &str) -> String {
+    format!("/{}/{}", PrintJobberService::NAME, method)
+}
+
+/// Queues a print, and audits it whether it was queued or not.
+fn submit_job(state: &AppState, request: Request<SubmitJobRequest>) -> Result<SubmitJobReply, Status> {
+    let source = authenticate(state, &request, "SubmitJob")?;
+    let who = caller(&request, Some(&source));
+    let request = request.into_inner();
+    let submitted = queue_job(state, &source, &request);
+    let status = submitted.as_ref().map_or_else(http_status, |_| StatusCode::OK);
+    audit::record_grpc(state, who, &path("SubmitJob"), &request.text, status);
+    submitted
+}
+
+fn queue_job(state: &AppState, source: &Source, request: &SubmitJobRequest) -> Result<SubmitJobReply, Status> {
+    if state.maintenance.active().is_some() {
+        return Err(Status::unavailable("printer is down for maintenance"));
+    }
+    if state.queue_full() {
+        return Err(Status::resource_exhausted("print queue is full"));
+    }
+    let params = PrintParams {
+        raw: request.raw,
+        ..Default::default()
+    };
+    let mut receipt = Receipt::for_config(&state.config);
+    render_text(&mut receipt, &request.text, &params, None, state.config.locale)
+        .map_err(|status| Status::invalid_argument(format!("can't lay out text: {}", status)))?;
+    let job = if request.job.is_empty() { "grpc" } else { &request.job };
+    eprintln!("Received gRPC print request: {} bytes (raw={})", request.text.len(), request.raw);
+    Ok(SubmitJobReply {
+        id: state.print(receipt, job, source),
+    })
+}
+
+fn get_job_status(state: &AppState, request: Request<GetJobStatusRequest>) -> Result<Job, Status> {
+    let source = authenticate(state, &request, "GetJobStatus")?;
+    let id = request.into_inner().id;
+    state
+        .history
+        .get(source.namespace(), id)
+        .map(Job::from)
+        .ok_or_else(|| Status::not_found(format!("no job #{}", id)))
+}
+
+fn stream_job_events(state: &AppState, request: Request<StreamJobEventsRequest>) -> Result<BoxStream<Job>, Status> {
+    let source = authenticate(state, &request, "StreamJobEvents")?;
+    let namespace = source.namespace().to_owned();
+    let events = BroadcastStream::new(state.history.subscribe()).filter_map(move |event| match event {
+        Ok(record) if record.namespace == namespace => Some(Ok(Job::from(record))),
+        Ok(_) => None,
+        // Fell behind; carry on with the next event.
+        Err(_) => None,
+    });
+    Ok(Box::pin(events))
+}
+
+fn printer_status(state: &AppState, request: Request<PrinterStatusRequest>) -> Result<PrinterStatusReply, Status> {
+    authenticate(state, &request, "PrinterStatus")?;
+    let queue = state.queue.status();
+    let paper = state.paper.status();
+    Ok(PrinterStatusReply {
+        online: state.diagnostics.snapshot().init_ok,
+        paused: queue.paused,
+        queued: queue.queued as u32,
+        scheduled: queue.scheduled as u32,
+        paper_used_mm: paper.used_mm,
+        paper_remaining_mm: paper.remaining_mm,
+        paper_low: paper.low,
+    })
+}
+
+/// Adapts one of the handlers above to the service shape tonic's `Grpc` expects.
+#[derive(Clone)]
+struct Method<F> {
+    state: AppState,
+    handler: F,
+}
+
+impl<F, Req, Res> Service<Request<Req>> for Method<F>
+where
+    F: Fn(&AppState, Request<Req>) -> Result<Res, Status>,
+{
+    type Response = Response<Res>;
+    type Error = Status;
+    type Future = std::future::Ready<Result<Response<Res>, Status>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Status>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        std::future::ready((self.handler)(&self.state, request).map(Response::new))
+    }
+}
+
+#[derive(Clone)]
+pub struct PrintJobberService {
+    state: AppState,
+}
+
+impl NamedService for PrintJobberService {
+    const NAME: &'static str = "print_jobber.PrintJobber";
+}
+
+impl Service<http::Request<Body>> for PrintJobberService {
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let state = self.state.clone();
+        Box::pin(async move {
+            let response = match request.uri().path() {
+                "/print_jobber.PrintJobber/SubmitJob" => {
+                    let method = Method { state, handler: submit_job };
+                    Grpc::new(ProstCodec::default()).unary(method, request).await
+                }
+                "/print_jobber.PrintJobber/GetJobStatus" => {
+                    let method = Method { state, handler: get_job_status };
+                    Grpc::new(ProstCodec::default()).unary(method, request).await
+                }
+                "/print_jobber.PrintJobber/StreamJobEvents" => {
+                    let method = Method { state, handler: stream_job_events };
+                    Grpc::new(ProstCodec::default()).server_streaming(method, request).await
+                }
+                "/print_jobber.PrintJobber/PrinterStatus" => {
+                    let method = Method { state, handler: printer_status };
+                    Grpc::new(ProstCodec::default()).unary(method, request).await
+                }
+                path => Status::unimplemented(format!("no method {}", path)).into_http(),
+            };
+            Ok(response)
+        })
+    }
+}
+
+/// A TLS connection to the gRPC server, and who's on the other end.
+struct TlsConnection(TlsStream<TcpStream>, Peer);
+
+impl Connected for TlsConnection {
+    type ConnectInfo = Peer;
+
+    fn connect_info(&self) -> Peer {
+        self.1.clone()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// Serves the gRPC API on `port` until the process exits, over TLS when the REST API
+/// has it, so `TLS_CLIENT_CA` and API keys are protected the same way on both.
+pub fn spawn(state: AppState, port: u16) {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let tls = state.config.tls.clone();
+    tokio::spawn(async move {
+        let server = tonic::transport::Server::builder().add_service(PrintJobberService { state });
+        let served = match tls {
+            Some(tls) => {
+                let listener = match TcpListener::bind(addr).await.and_then(|listener| TlsListener::for_grpc(listener, &tls)) {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        eprintln!("Not serving gRPC, failed to set up TLS on {}: {}", addr, e);
+                        return;
+                    }
+                };
+                eprintln!("Serving gRPC over TLS on {}", addr);
+                let connections = listener.into_stream().map(|(stream, peer)| Ok::<_, io::Error>(TlsConnection(stream, peer)));
+                server.serve_with_incoming(connections).await
+            }
+            None => {
+                eprintln!("Serving gRPC on {}", addr);
+                server.serve(addr).await
+            }
+        };
+        if let Err(e) = served {
+            eprintln!("gRPC server failed: {}", e);
+        }
+    });
+}
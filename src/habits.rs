@@ -0,0 +1,84 @@
+//! A weekly habit tracker: `HABITS` down the left, a checkbox per day across.
+
+use crate::{
+    AppState,
+    error::JobError,
+    receipt::{Receipt, wrap},
+    source::Source,
+};
+use axum::{extract::State, http::StatusCode};
+use chrono::{Datelike, Local, Locale, NaiveDate, TimeDelta};
+
+/// Name columns narrower than this make the checkboxes smaller instead.
+const MIN_NAME_WIDTH: usize = 8;
+
+#[utoipa::path(
+    get,
+    path = "/habits",
+    tag = "printing",
+    summary = "Print this week's habit tracker grid",
+    description = "One row of Monday-Sunday checkboxes per `HABITS` entry, sized to the paper. Schedule it weekly with \
+        e.g. `SCHEDULE=habits@mon 07:00`.",
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 503, description = "`HABITS` isn't set, or the printer is down for maintenance; see `Retry-After`."),
+    )
+)]
+pub async fn habits(State(state): State<AppState>, source: Source) -> Result<(), StatusCode> {
+    Ok(print_habits(&state, &source)?)
+}
+
+/// Prints this week's grid, Monday to Sunday.
+pub fn print_habits(state: &AppState, source: &Source) -> Result<(), JobError> {
+    if state.config.habits.is_empty() {
+        return Err(JobError::NotConfigured("HABITS"));
+    }
+    let today = Local::now().date_naive();
+    let monday = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+    eprintln!("Habits request for the week of {}", monday);
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let width = receipt.width();
+    receipt.line_center("HABITS");
+    receipt.line_center(&format!(
+        "{} - {}",
+        monday.format_localized("%-d %b", state.config.locale),
+        (monday + TimeDelta::days(6)).format_localized("%-d %b", state.config.locale)
+    ));
+    receipt.divider();
+    for line in render_grid(&state.config.habits, monday, width, state.config.locale) {
+        receipt.line_left(&line);
+    }
+
+    state.print(receipt, "habits", source);
+    Ok(())
+}
+
+/// Two header rows (weekday, day of the month) over one row per habit. Each day gets a
+/// `[ ]` column; long names wrap within the name column.
+fn render_grid(habits: &[String], monday: NaiveDate, width: usize, locale: Locale) -> Vec<String> {
+    // "[ ]" plus a space between days, unless that leaves too little room for names.
+    let cell = if width.saturating_sub(7 * 4) >= MIN_NAME_WIDTH { 4 } else { 3 };
+    let name_width = width.saturating_sub(7 * cell).max(1);
+    let days: Vec<NaiveDate> = (0..7).map(|n| monday + TimeDelta::days(n)).collect();
+
+    let row = |name: &str, cells: &mut dyn Iterator<Item = String>| {
+        let mut line = format!("{:<name_width$}", name);
+        for text in cells {
+            line.push_str(&format!("{}{:^3}", " ".repeat(cell - 3), text));
+        }
+        line.trim_end().to_owned()
+    };
+    let mut lines = vec![
+        row("", &mut days.iter().map(|day| day.format_localized("%a", locale).to_string().chars().take(2).collect())),
+        row("", &mut days.iter().map(|day| day.day().to_string())),
+    ];
+    for habit in habits {
+        let name = wrap(habit, name_width.saturating_sub(1).max(1));
+        lines.push(row(&name[0], &mut std::iter::repeat_n("[ ]".to_owned(), 7)));
+        for rest in &name[1..] {
+            lines.push(rest.clone());
+        }
+    }
+    lines
+}
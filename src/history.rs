@@ -0,0 +1,244 @@
+use crate::{receipt::Receipt, source::Source};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+
+const CAPACITY: usize = 200;
+/// Recent jobs whose receipts are kept for reprinting; images make them large.
+const RECEIPT_CAPACITY: usize = 20;
+/// Status changes a slow event subscriber can fall behind by before missing some.
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Scheduled,
+    Queued,
+    Printed,
+    Failed,
+    /// Dropped unprinted because its `expires_in` ran out while it waited.
+    Expired,
+    /// Discarded from the queue with `DELETE /queue`.
+    Cancelled,
+    /// Only known from the job archive, from before the last restart.
+    Archived,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Scheduled => "scheduled",
+            JobStatus::Queued => "queued",
+            JobStatus::Printed => "printed",
+            JobStatus::Failed => "failed",
+            JobStatus::Expired => "expired",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Archived => "archived",
+        }
+    }
+}
+
+/// How a routed, mirrored or failed-over job went on one of its printers.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct TargetResult {
+    pub printer: String,
+    /// `printed` or `failed`.
+    pub status: JobStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct JobRecord {
+    pub id: u64,
+    /// RFC 3339 local time the job was submitted.
+    #[schema(format = DateTime)]
+    pub at: String,
+    pub namespace: String,
+    pub job: String,
+    pub source: String,
+    /// Lines of text in the receipt as submitted.
+    pub lines: usize,
+    pub status: JobStatus,
+    /// RFC 3339 time a delayed job is due to print.
+    #[schema(format = DateTime)]
+    pub scheduled_for: Option<String>,
+    pub error: Option<String>,
+    /// Estimated paper used, once printed.
+    pub paper_mm: Option<f64>,
+    /// Each printer's outcome, for a job routed away from the main printer, mirrored to
+    /// several or failed over. It counts as printed when any of them printed it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<TargetResult>,
+    /// The `PRINTER_FAILOVER` printer, when the main printer couldn't take the job.
+    pub failover: Option<String>,
+    /// The job's ID on the instance that relayed it here.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_id: Option<u64>,
+}
+
+#[derive(Default)]
+struct History {
+    next_id: u64,
+    records: VecDeque<JobRecord>,
+    /// As rendered, before the header and footer templates, newest last.
+    receipts: VecDeque<(u64, Receipt)>,
+}
+
+/// The most recent jobs, newest last, kept in memory. Every status change is also
+/// broadcast; see [`JobHistory::subscribe`].
+#[derive(Clone)]
+pub struct JobHistory {
+    inner: Arc<Mutex<History>>,
+    events: broadcast::Sender<JobRecord>,
+}
+
+impl Default for JobHistory {
+    fn default() -> Self {
+        JobHistory {
+            inner: Arc::default(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl JobHistory {
+    /// An empty history whose job IDs carry on after `last_id`, e.g. the last archived job.
+    pub fn starting_after(last_id: u64) -> Self {
+        let history = JobHistory::default();
+        history.inner.lock().unwrap().next_id = last_id;
+        history
+    }
+
+    /// Receives each job record as it's submitted, queued, printed or fails.
+    pub fn subscribe(&self) -> broadcast::Receiver<JobRecord> {
+        self.events.subscribe()
+    }
+
+    fn publish(&self, record: &JobRecord) {
+        // No subscribers is fine.
+        let _ = self.events.send(record.clone());
+    }
+
+    pub fn next_id(&self) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        inner.next_id
+    }
+
+    pub fn record(&self, id: u64, job: &str, source: &Source, lines: usize, scheduled_for: Option<DateTime<Local>>) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.records.len() == CAPACITY {
+            inner.records.pop_front();
+        }
+        let record = JobRecord {
+            id,
+            at: Local::now().to_rfc3339(),
+            namespace: source.namespace().to_owned(),
+            job: job.to_owned(),
+            source: source.to_string(),
+            lines,
+            status: if scheduled_for.is_some() { JobStatus::Scheduled } else { JobStatus::Queued },
+            scheduled_for: scheduled_for.map(|at| at.to_rfc3339()),
+            error: None,
+            paper_mm: None,
+            targets: Vec::new(),
+            failover: None,
+            upstream_id: None,
+        };
+        self.publish(&record);
+        inner.records.push_back(record);
+    }
+
+    /// Updates a streamed job's line count once its body has ended.
+    pub fn set_lines(&self, id: u64, lines: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(record) = inner.records.iter_mut().find(|r| r.id == id) {
+            record.lines = lines;
+        }
+    }
+
+    pub fn set_upstream_id(&self, id: u64, upstream_id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(record) = inner.records.iter_mut().find(|r| r.id == id) {
+            record.upstream_id = Some(upstream_id);
+        }
+    }
+
+    /// Marks scheduled jobs as handed to the print queue.
+    pub fn mark_queued(&self, ids: &[u64]) {
+        let mut inner = self.inner.lock().unwrap();
+        for record in inner.records.iter_mut().filter(|r| ids.contains(&r.id)) {
+            record.status = JobStatus::Queued;
+            self.publish(record);
+        }
+    }
+
+    /// Marks a queued job as printed, with the millimetres of paper it took, or failed,
+    /// with the outcome on each printer if it was mirrored or failed over.
+    pub fn finish(&self, id: u64, result: Result<f64, String>, targets: Vec<TargetResult>, failover: Option<String>) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(record) = inner.records.iter_mut().find(|r| r.id == id) {
+            record.targets = targets;
+            record.failover = failover;
+            match result {
+                Ok(mm) => {
+                    record.status = JobStatus::Printed;
+                    record.paper_mm = Some(mm.round());
+                }
+                Err(e) => {
+                    record.status = JobStatus::Failed;
+                    record.error = Some(e);
+                }
+            }
+            self.publish(record);
+        }
+    }
+
+    /// Marks a queued job as dropped unprinted, as `Expired` or `Cancelled`.
+    pub fn drop_unprinted(&self, id: u64, status: JobStatus) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(record) = inner.records.iter_mut().find(|r| r.id == id) {
+            record.status = status;
+            self.publish(record);
+        }
+    }
+
+    /// Keeps a job's receipt for [`JobHistory::receipt`], forgetting the oldest kept one
+    /// beyond [`RECEIPT_CAPACITY`].
+    pub fn keep_receipt(&self, id: u64, receipt: Receipt) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.receipts.len() == RECEIPT_CAPACITY {
+            inner.receipts.pop_front();
+        }
+        inner.receipts.push_back((id, receipt));
+    }
+
+    /// The receipt of job `id` in `namespace`, or of its latest job when `id` is `None`,
+    /// if it's still kept.
+    pub fn receipt(&self, namespace: &str, id: Option<u64>) -> Option<(u64, Receipt)> {
+        let inner = self.inner.lock().unwrap();
+        let in_namespace = |id: u64| inner.records.iter().any(|r| r.id == id && r.namespace == namespace);
+        inner
+            .receipts
+            .iter()
+            .rev()
+            .find(|(kept, _)| id.is_none_or(|id| id == *kept) && in_namespace(*kept))
+            .cloned()
+    }
+
+    pub fn get(&self, namespace: &str, id: u64) -> Option<JobRecord> {
+        let inner = self.inner.lock().unwrap();
+        inner.records.iter().find(|r| r.id == id && r.namespace == namespace).cloned()
+    }
+
+    /// Jobs in `namespace`, newest first.
+    pub fn recent(&self, namespace: &str) -> Vec<JobRecord> {
+        let inner = self.inner.lock().unwrap();
+        inner.records.iter().rev().filter(|r| r.namespace == namespace).cloned().collect()
+    }
+}
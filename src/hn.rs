@@ -0,0 +1,195 @@
+use crate::{AppState, error::JobError, receipt::Receipt, source::Source};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{Local, NaiveDate};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+const API_BASE: &str = "https://hacker-news.firebaseio.com/v0";
+
+/// The API base, or `HN_URL` when set.
+fn api_base(state: &AppState) -> &str {
+    state.config.hn_url.as_deref().unwrap_or(API_BASE).trim_end_matches('/')
+}
+const MAX_COUNT: usize = 30;
+
+#[derive(Deserialize, IntoParams)]
+pub struct HnParams {
+    /// Stories to print, at most 30.
+    #[serde(default = "default_count")]
+    #[param(default = default_count, maximum = 30)]
+    count: usize,
+    /// Print a QR code per story instead of the shortened URL.
+    #[serde(default)]
+    #[param(default = false)]
+    qr: bool,
+}
+
+fn default_count() -> usize {
+    10
+}
+
+#[derive(Deserialize)]
+struct Story {
+    id: u64,
+    title: String,
+    #[serde(default)]
+    score: u64,
+    #[serde(default)]
+    descendants: u64,
+    url: Option<String>,
+}
+
+impl Story {
+    /// Link posts point at their URL; Ask/Show HN text posts only have the discussion page.
+    fn link(&self) -> String {
+        self.url
+            .clone()
+            .unwrap_or_else(|| format!("https://news.ycombinator.com/item?id={}", self.id))
+    }
+}
+
+/// Story IDs already printed, per namespace.
+type SeenStories = HashMap<String, HashSet<u64>>;
+
+/// IDs of stories already printed today per namespace, so repeated requests only print new stories.
+#[derive(Clone)]
+pub struct PrintedStories {
+    inner: Arc<Mutex<(NaiveDate, SeenStories)>>,
+}
+
+impl Default for PrintedStories {
+    fn default() -> Self {
+        PrintedStories {
+            inner: Arc::new(Mutex::new((Local::now().date_naive(), HashMap::new()))),
+        }
+    }
+}
+
+impl PrintedStories {
+    fn with_today<T>(&self, namespace: &str, f: impl FnOnce(&mut HashSet<u64>) -> T) -> T {
+        let mut inner = self.inner.lock().unwrap();
+        let today = Local::now().date_naive();
+        if inner.0 != today {
+            *inner = (today, HashMap::new());
+        }
+        f(inner.1.entry(namespace.to_owned()).or_default())
+    }
+}
+
+/// "https://www.example.com/some/long/path?x=1" -> "example.com/some/long/path?x=1",
+/// cut to fit on the indented line under the title.
+fn shorten_url(url: &str) -> String {
+    let without_scheme = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    let short = without_scheme.strip_prefix("www.").unwrap_or(without_scheme);
+    if short.chars().count() > 45 {
+        format!("{}...", short.chars().take(42).collect::<String>())
+    } else {
+        short.to_owned()
+    }
+}
+
+async fn fetch<T: for<'de> Deserialize<'de>>(url: &str) -> Result<T, JobError> {
+    reqwest::get(url)
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<T>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/hn",
+    tag = "printing",
+    summary = "Print the Hacker News front page",
+    params(HnParams),
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn hn(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<HnParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_hn(&state, &source, params.count.clamp(1, MAX_COUNT), params.qr).await?)
+}
+
+pub async fn print_hn(state: &AppState, source: &Source, count: usize, qr: bool) -> Result<(), JobError> {
+    eprintln!("HN request for {} stories", count);
+
+    let ids: Vec<u64> = fetch(&format!("{}/topstories.json", api_base(state))).await.inspect_err(|e| {
+        eprintln!("Failed to fetch HN top stories: {}", e);
+        state.diagnostics.record_error("hn", e.to_string());
+    })?;
+
+    let unseen: Vec<u64> = state
+        .printed_stories
+        .with_today(source.namespace(), |seen| ids.into_iter().filter(|id| !seen.contains(id)).collect());
+
+    let mut stories = Vec::new();
+    for id in unseen {
+        if stories.len() == count {
+            break;
+        }
+        match fetch::<Option<Story>>(&format!("{}/item/{}.json", api_base(state), id)).await {
+            Ok(Some(story)) => stories.push(story),
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Failed to fetch HN story {}: {}", id, e);
+                state.diagnostics.record_error("hn", e.to_string());
+            }
+        }
+    }
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("HACKER NEWS");
+    receipt.line_center(&Local::now().format("%Y-%m-%d %H:%M").to_string());
+    receipt.divider();
+
+    if stories.is_empty() {
+        receipt.line_center("No new stories since last print");
+    }
+
+    for (i, story) in stories.iter().enumerate() {
+        receipt.wrapped(&format!("{}. {}", i + 1, story.title));
+        receipt.line_left(&format!("   {} points | {} comments", story.score, story.descendants));
+        if qr {
+            receipt.qr_code(&story.link(), 3);
+        } else {
+            receipt.line_left(&format!("   {}", shorten_url(&story.link())));
+        }
+        receipt.divider();
+    }
+
+    state.print(receipt, "hn", source);
+
+    state.printed_stories.with_today(source.namespace(), |seen| seen.extend(stories.iter().map(|s| s.id)));
+    Ok(())
+}
+
+/// Titles of the current top `count` stories, printed or not, fetched together.
+pub async fn headlines(state: &AppState, count: usize) -> Result<Vec<String>, JobError> {
+    let ids: Vec<u64> = fetch(&format!("{}/topstories.json", api_base(state))).await?;
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, id) in ids.into_iter().take(count).enumerate() {
+        let url = format!("{}/item/{}.json", api_base(state), id);
+        tasks.spawn(async move { (i, fetch::<Option<Story>>(&url).await) });
+    }
+    let mut stories = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((i, Ok(Some(story)))) => stories.push((i, story.title)),
+            Ok(_) => {}
+            // Left out like a story that failed to load; the rest still print.
+            Err(e) => eprintln!("Hacker News story task failed: {}", e),
+        }
+    }
+    stories.sort();
+    Ok(stories.into_iter().map(|(_, title)| title).collect())
+}
@@ -0,0 +1,189 @@
+//! Just enough HTML handling to pull content out of web pages without a full parser.
+
+use crate::error::JobError;
+
+/// Fetches a page's HTML.
+pub async fn fetch_page(url: &str) -> Result<String, JobError> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(JobError::Unprocessable(format!("not an http(s) URL: {}", url)));
+    }
+    reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| JobError::Upstream(e.to_string()))
+}
+
+/// Byte offset of the first case-insensitive match of `needle` (ASCII) in `haystack`.
+fn find_ci(haystack: &str, needle: &str, from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|i| i + from)
+}
+
+/// Contents of every `<script type="application/ld+json">` element.
+pub fn json_ld_blocks(html: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = find_ci(html, "<script", pos) {
+        let Some(tag_end) = html[start..].find('>').map(|i| start + i + 1) else {
+            break;
+        };
+        let Some(end) = find_ci(html, "</script", tag_end) else {
+            break;
+        };
+        if find_ci(&html[start..tag_end], "application/ld+json", 0).is_some() {
+            blocks.push(html[tag_end..end].trim());
+        }
+        pos = end;
+    }
+    blocks
+}
+
+/// Decodes the handful of entities that actually show up in text content.
+pub fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';').filter(|&i| i <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some("&".to_owned()),
+            "lt" => Some("<".to_owned()),
+            "gt" => Some(">".to_owned()),
+            "quot" | "rdquo" | "ldquo" => Some("\"".to_owned()),
+            "apos" | "rsquo" | "lsquo" => Some("'".to_owned()),
+            "nbsp" => Some(" ".to_owned()),
+            "ndash" | "mdash" => Some("-".to_owned()),
+            "hellip" => Some("...".to_owned()),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32)
+                .map(String::from),
+        };
+        match decoded {
+            Some(decoded) => {
+                out.push_str(&decoded);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Drops tags, decodes entities and collapses whitespace.
+pub fn to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(' ');
+            }
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    decode_entities(&out).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Byte offset of the next `<tag ...>` or `<tag>` opening tag, skipping tags that
+/// merely start with the same letters (`<p` vs `<pre`).
+fn find_open_tag(html: &str, tag: &str, from: usize) -> Option<usize> {
+    let mut pos = from;
+    while let Some(start) = find_ci(html, &format!("<{}", tag), pos) {
+        match html.as_bytes().get(start + tag.len() + 1) {
+            Some(b'>' | b'/' | b' ' | b'\t' | b'\n' | b'\r') => return Some(start),
+            _ => pos = start + 1,
+        }
+    }
+    None
+}
+
+/// Inner HTML of each `<tag>` element, in document order. Nested elements of the
+/// same tag end at the first closing tag, which is fine for the tags we look at.
+pub fn elements<'a>(html: &'a str, tag: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = find_open_tag(html, tag, pos) {
+        let Some(inner_start) = html[start..].find('>').map(|i| start + i + 1) else {
+            break;
+        };
+        let Some(end) = find_ci(html, &format!("</{}", tag), inner_start) else {
+            break;
+        };
+        found.push(&html[inner_start..end]);
+        pos = end;
+    }
+    found
+}
+
+/// `html` with every `<tag>...</tag>` element for the given tags cut out.
+pub fn without_elements(html: &str, tags: &[&str]) -> String {
+    let mut html = html.to_owned();
+    for tag in tags {
+        while let Some(start) = find_open_tag(&html, tag, 0) {
+            let close = format!("</{}", tag);
+            let end = find_ci(&html, &close, start)
+                .and_then(|end| html[end..].find('>').map(|i| end + i + 1))
+                .unwrap_or(html.len());
+            html.replace_range(start..end, " ");
+        }
+    }
+    html
+}
+
+/// The `content` of the first `<meta>` whose `name` or `property` is `key`.
+pub fn meta_content(html: &str, key: &str) -> Option<String> {
+    let mut pos = 0;
+    while let Some(start) = find_open_tag(html, "meta", pos) {
+        let end = html[start..].find('>').map(|i| start + i)?;
+        let tag = &html[start..end];
+        let matches = ["name", "property"]
+            .iter()
+            .any(|attr| attribute(tag, attr).is_some_and(|v| v.eq_ignore_ascii_case(key)));
+        if matches && let Some(content) = attribute(tag, "content") {
+            return Some(decode_entities(content).trim().to_owned()).filter(|c| !c.is_empty());
+        }
+        pos = end;
+    }
+    None
+}
+
+/// Value of a quoted attribute inside an opening tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut pos = 0;
+    while let Some(at) = find_ci(tag, name, pos) {
+        pos = at + name.len();
+        let preceded_by_space = tag[..at].ends_with(|c: char| c.is_whitespace());
+        let rest = tag[pos..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+        let rest = rest[1..].trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        return rest[1..].split(quote).next();
+    }
+    None
+}
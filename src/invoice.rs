@@ -0,0 +1,195 @@
+use crate::{
+    AppState,
+    receipt::{Column, Receipt, render_row, wrap},
+    source::Source,
+};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::Local;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Largest quantity, unit price or line total accepted, in currency units, so every
+/// amount fits comfortably in `i64` cents.
+const MAX_AMOUNT: f64 = 1e9;
+/// Largest `tax_rate`, in percent.
+const MAX_TAX_RATE: f64 = 100.0;
+
+/// Item, quantity, price and total columns for `width` characters; amounts get less
+/// room on narrow paper so the description still has some.
+fn item_columns(width: usize) -> [Column; 4] {
+    let (quantity, amount) = if width < 40 { (4, 8) } else { (6, 10) };
+    [
+        Column::left(width.saturating_sub(quantity + 2 * amount).max(1)),
+        Column::right(quantity),
+        Column::right(amount),
+        Column::right(amount),
+    ]
+}
+
+fn total_columns(width: usize) -> [Column; 2] {
+    [Column::left(width.saturating_sub(16).max(1)), Column::right(16)]
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct Party {
+    name: String,
+    #[serde(default)]
+    address: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LineItem {
+    description: String,
+    #[serde(default = "default_quantity")]
+    #[schema(default = 1, minimum = 0, maximum = 1_000_000_000)]
+    quantity: f64,
+    /// Negative for a discount.
+    #[schema(minimum = -1_000_000_000, maximum = 1_000_000_000)]
+    unit_price: f64,
+}
+
+fn default_quantity() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct InvoiceRequest {
+    seller: Party,
+    buyer: Option<Party>,
+    /// Invoice or receipt number, printed under the seller.
+    number: Option<String>,
+    /// Printed after every amount, e.g. `EUR`.
+    #[serde(default)]
+    currency: String,
+    items: Vec<LineItem>,
+    /// Percent added on top of the subtotal, 0 to 100.
+    #[serde(default)]
+    #[schema(default = 0, minimum = 0, maximum = 100)]
+    tax_rate: f64,
+    footer: Option<String>,
+    /// Payment link or EPC/SEPA payload to print as a QR code.
+    payment_qr: Option<String>,
+}
+
+/// Amounts are kept in cents so the totals add up exactly.
+fn cents(amount: f64) -> i64 {
+    (amount * 100.0).round() as i64
+}
+
+fn money(cents: i64, currency: &str) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let amount = format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100);
+    if currency.is_empty() { amount } else { format!("{} {}", amount, currency) }
+}
+
+/// Whether `amount` is finite and no larger than [`MAX_AMOUNT`] either way; negative
+/// prices are discounts.
+fn within_bounds(amount: f64) -> bool {
+    amount.is_finite() && amount.abs() <= MAX_AMOUNT
+}
+
+/// Drops trailing zeros so whole quantities print as `2` and weights as `1.25`.
+fn quantity(quantity: f64) -> String {
+    let formatted = format!("{:.3}", quantity);
+    formatted.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+#[utoipa::path(
+    post,
+    path = "/invoice",
+    tag = "printing",
+    summary = "Print an invoice or receipt",
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 422, description = "No items, or an amount or the total is out of range."),
+    )
+)]
+pub async fn invoice(State(state): State<AppState>, source: Source, Json(request): Json<InvoiceRequest>) -> Result<(), StatusCode> {
+    let valid = !request.items.is_empty()
+        && (0.0..=MAX_TAX_RATE).contains(&request.tax_rate)
+        && request.items.iter().all(|item| {
+            item.quantity >= 0.0
+                && within_bounds(item.quantity)
+                && within_bounds(item.unit_price)
+                && within_bounds(item.quantity * item.unit_price)
+        });
+    if !valid {
+        eprintln!("Invoice request with no items or invalid amounts");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let totals: Vec<i64> = request
+        .items
+        .iter()
+        .map(|item| (item.quantity * cents(item.unit_price) as f64).round() as i64)
+        .collect();
+    let subtotal = totals.iter().try_fold(0i64, |sum, &total| sum.checked_add(total));
+    let Some((subtotal, tax, total)) = subtotal.and_then(|subtotal| {
+        let tax = (subtotal as f64 * request.tax_rate / 100.0).round() as i64;
+        subtotal.checked_add(tax).map(|total| (subtotal, tax, total))
+    }) else {
+        eprintln!("Invoice request whose total overflows");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    };
+    eprintln!("Invoice request with {} item(s)", request.items.len());
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let item_columns = item_columns(receipt.width());
+    let total_columns = total_columns(receipt.width());
+    receipt.line_center(&request.seller.name.to_uppercase());
+    for line in &request.seller.address {
+        receipt.line_center(line);
+    }
+    receipt.divider();
+    if let Some(number) = &request.number {
+        receipt.line_left(&format!("No. {}", number));
+    }
+    receipt.line_left(&Local::now().format_localized("%x %H:%M", state.config.locale).to_string());
+    if let Some(buyer) = &request.buyer {
+        receipt.line_left("");
+        receipt.line_left(&format!("Bill to: {}", buyer.name));
+        for line in &buyer.address {
+            receipt.line_left(&format!("         {}", line));
+        }
+    }
+    receipt.divider();
+
+    receipt.line_left(&render_row(&item_columns, &["ITEM", "QTY", "PRICE", "TOTAL"]));
+    for (item, &total) in request.items.iter().zip(&totals) {
+        let unit = cents(item.unit_price);
+
+        let description = wrap(&item.description, item_columns[0].width - 1);
+        let first = description.first().map(String::as_str).unwrap_or_default();
+        receipt.line_left(&render_row(
+            &item_columns,
+            &[first, &quantity(item.quantity), &money(unit, ""), &money(total, "")],
+        ));
+        for line in description.iter().skip(1) {
+            receipt.line_left(&format!("  {}", line));
+        }
+    }
+    receipt.divider();
+
+    if tax > 0 {
+        receipt.line_left(&render_row(&total_columns, &["Subtotal", &money(subtotal, &request.currency)]));
+        receipt.line_left(&render_row(
+            &total_columns,
+            &[&format!("Tax {}%", quantity(request.tax_rate)), &money(tax, &request.currency)],
+        ));
+    }
+    receipt.line_left(&render_row(&total_columns, &["TOTAL", &money(total, &request.currency)]));
+
+    if let Some(payment) = &request.payment_qr {
+        receipt.line_left("");
+        receipt.qr_code(payment, 5);
+        receipt.line_center("Scan to pay");
+    }
+    if let Some(footer) = &request.footer {
+        receipt.divider();
+        for line in wrap(footer, receipt.width()) {
+            receipt.line_center(&line);
+        }
+    }
+
+    state.print(receipt, "invoice", &source);
+    Ok(())
+}
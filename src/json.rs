@@ -0,0 +1,98 @@
+use serde_json::Value;
+
+const INDENT: usize = 2;
+
+/// Lines being pretty-printed for a receipt `width` characters wide. Keys longer than
+/// half the width push their value onto its own line so it keeps a useful width, and
+/// deeper levels stop indenting past half the width so values always have room.
+struct Pretty {
+    lines: Vec<String>,
+    width: usize,
+}
+
+impl Pretty {
+    fn indentation(&self, depth: usize) -> String {
+        " ".repeat((depth * INDENT).min(self.width / 2))
+    }
+}
+
+/// Pretty-prints `value` with two-space indentation for a receipt `width` characters
+/// wide, wrapping long scalars so that continuation lines stay indented under the value
+/// they belong to.
+pub fn pretty_lines(value: &Value, width: usize) -> Vec<String> {
+    let mut pretty = Pretty { lines: Vec::new(), width };
+    write_value(&mut pretty, 0, "", value, "");
+    pretty.lines
+}
+
+fn scalar(value: &Value) -> String {
+    // Serializing a scalar can't fail; it's the JSON text of a string, number, bool or null.
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// Splits `text` after at most `width` characters, preferring the last space.
+fn split_at_width(text: &str, width: usize) -> (&str, &str) {
+    let Some((cut, _)) = text.char_indices().nth(width) else {
+        return (text, "");
+    };
+    match text[..cut].rfind(' ') {
+        Some(space) if space > 0 => (&text[..=space], &text[space + 1..]),
+        _ => text.split_at(cut),
+    }
+}
+
+/// Adds `prefix` + `text` + `suffix` at `depth`, wrapping `text` if the line would overflow.
+fn push_wrapped(pretty: &mut Pretty, depth: usize, prefix: &str, text: &str, suffix: &str) {
+    let indent = pretty.indentation(depth);
+    let continuation = pretty.indentation(depth + 1);
+    let text = format!("{}{}", text, suffix);
+    let lines = &mut pretty.lines;
+    let mut first_width = pretty.width.saturating_sub(indent.len() + prefix.chars().count());
+    if text.chars().count() <= first_width {
+        lines.push(format!("{}{}{}", indent, prefix, text));
+        return;
+    }
+    if first_width == 0 || prefix.chars().count() > pretty.width / 2 {
+        lines.push(format!("{}{}", indent, prefix.trim_end()));
+        first_width = 0;
+    }
+
+    let rest_width = pretty.width.saturating_sub(continuation.len()).max(1);
+    let mut rest = text.as_str();
+    if first_width > 0 {
+        let (head, tail) = split_at_width(rest, first_width);
+        lines.push(format!("{}{}{}", indent, prefix, head));
+        rest = tail;
+    }
+    while !rest.is_empty() {
+        let (head, tail) = split_at_width(rest, rest_width);
+        lines.push(format!("{}{}", continuation, head));
+        rest = tail;
+    }
+}
+
+fn write_value(pretty: &mut Pretty, depth: usize, prefix: &str, value: &Value, suffix: &str) {
+    let indent = pretty.indentation(depth);
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            pretty.lines.push(format!("{}{}{{", indent, prefix));
+            for (i, (key, value)) in map.iter().enumerate() {
+                let comma = if i + 1 < map.len() { "," } else { "" };
+                let key = format!("{}: ", scalar(&Value::String(key.clone())));
+                write_value(pretty, depth + 1, &key, value, comma);
+            }
+            pretty.lines.push(format!("{}}}{}", indent, suffix));
+        }
+        Value::Array(items) if !items.is_empty() => {
+            pretty.lines.push(format!("{}{}[", indent, prefix));
+            for (i, item) in items.iter().enumerate() {
+                let comma = if i + 1 < items.len() { "," } else { "" };
+                write_value(pretty, depth + 1, "", item, comma);
+            }
+            pretty.lines.push(format!("{}]{}", indent, suffix));
+        }
+        Value::Object(_) => pretty.lines.push(format!("{}{}{{}}{}", indent, prefix, suffix)),
+        Value::Array(_) => pretty.lines.push(format!("{}{}[]{}", indent, prefix, suffix)),
+        scalar_value => push_wrapped(pretty, depth, prefix, &scalar(scalar_value), suffix),
+    }
+}
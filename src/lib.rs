@@ -0,0 +1,1497 @@
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{FromRequest, Multipart, Path, Query, Request, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    middleware,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{mpsc::UnboundedReceiver, oneshot};
+use tower_http::decompression::RequestDecompressionLayer;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+/// Options for `POST /` and the endpoints that print like it, from the query string (or
+/// a multipart upload's fields). The doc comments are the API docs' descriptions.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PrintParams {
+    /// Print the text as is, without wrapping.
+    #[serde(default)]
+    #[param(default = false)]
+    raw: bool,
+    /// Reject words longer than a line instead of breaking them across lines.
+    #[serde(default)]
+    #[param(default = false)]
+    strict: bool,
+    /// Keep each line's leading whitespace and indent its continuation lines to match.
+    #[serde(default)]
+    #[param(default = false)]
+    indent: bool,
+    /// Columns between tab stops; tabs are expanded to spaces, also in raw mode. Values
+    /// outside 1-16 are clamped.
+    #[serde(default = "default_tab_width")]
+    #[param(default = default_tab_width, minimum = 1, maximum = 16)]
+    tab_width: usize,
+    /// Drop whitespace at the end of each line.
+    #[serde(default)]
+    #[param(default = false)]
+    trim: bool,
+    /// Collapse runs of blank lines into one.
+    #[serde(default)]
+    #[param(default = false)]
+    squeeze: bool,
+    /// `json` pretty-prints the body (also implied by a JSON content type), or draws a
+    /// `{"table": [...]}` body as a table; `csv` draws a table with the first row as its
+    /// header; `figlet` renders it as ASCII-art lettering in `font`; `ansi` prints terminal
+    /// output with its bold, underline and inverse codes and without its colours.
+    #[serde(default)]
+    #[param(inline, default = "text")]
+    format: Format,
+    /// Lettering for `format=figlet`.
+    #[serde(default)]
+    #[param(inline, default = "standard")]
+    font: figlet::Font,
+    /// The printer's character font; `b` is narrower and fits the profile's Font B
+    /// columns on a line. Defaults to `PRINTER_FONT`.
+    #[param(inline)]
+    printer_font: Option<receipt::Font>,
+    /// Line feed in dots (ESC 3). Defaults to `LINE_SPACING`, or the printer's 1/6 inch.
+    line_spacing: Option<u8>,
+    /// Character size as width x height, each 1-8 (`3` is 3x3), for headers, door signs
+    /// and ticket numbers. Text wraps to the fewer characters that fit on a line.
+    #[serde(default)]
+    #[param(value_type = String, example = "2x2")]
+    size: receipt::CharSize,
+    /// `raster` draws every line as an image in a bundled font, so any script prints;
+    /// `auto` only the lines with characters the printer's code pages lack. Slower to print.
+    #[serde(default)]
+    #[param(inline, default = "text")]
+    render: Render,
+    /// Cut the paper after the job; `false` leaves it uncut, e.g. to print several jobs
+    /// onto one strip.
+    #[serde(default = "default_true")]
+    #[param(default = true)]
+    cut: bool,
+    /// Copies to print, at most 10.
+    #[serde(default = "default_copies")]
+    #[param(default = default_copies)]
+    copies: u32,
+    /// Break text into pages of this many lines, each with a header.
+    page_lines: Option<usize>,
+    /// Title for page headers; defaults to the uploaded file name or "Document".
+    title: Option<String>,
+    /// Image uploads: target width in dots.
+    width: Option<u32>,
+    /// Image uploads: target height in dots.
+    height: Option<u32>,
+    /// Image uploads: how to fill the target size.
+    #[serde(default)]
+    #[param(inline, default = "contain")]
+    fit: raster::Fit,
+    /// Image uploads: where to place the image.
+    #[serde(default)]
+    #[param(inline, default = "center")]
+    align: upload::ImageAlign,
+    /// Image uploads: clockwise rotation in degrees. Text, CSV and JSON: 90 (or 270,
+    /// turned the other way) lays the lines along the paper in strips, for lines too wide
+    /// to print legibly across it, e.g. a 120-column CSV report; only 0, 90 and 270 are
+    /// accepted.
+    #[serde(default)]
+    #[param(default = 0)]
+    rotate: u16,
+    /// Image uploads: how to reduce them to black and white.
+    #[serde(default)]
+    #[param(inline, default = "threshold")]
+    dither: raster::Dither,
+    /// Image uploads: brightness adjustment.
+    #[serde(default)]
+    #[param(default = 0)]
+    brightness: i32,
+    /// Image uploads: contrast adjustment.
+    #[serde(default)]
+    #[param(default = 0)]
+    contrast: f32,
+    /// Name of a stored logo (see `/assets`) to print at the top.
+    logo: Option<String>,
+    /// Skip the header/footer templates and source footer for this job.
+    #[serde(default)]
+    #[param(default = false)]
+    plain: bool,
+    /// Leave off the `JOB_METADATA` line (time, source and job ID) for this job.
+    #[serde(default)]
+    #[param(default = false)]
+    no_header: bool,
+    /// Partially cut the paper between pages.
+    #[serde(default)]
+    #[param(default = false)]
+    page_cut: bool,
+    /// Treat the body as several documents, each printed and cut separately: a JSON
+    /// array (strings print as text, anything else pretty-printed), or text split on
+    /// `delimiter`.
+    #[serde(default)]
+    #[param(default = false)]
+    batch: bool,
+    /// Separator between batch documents in a text body; a form feed by default.
+    #[serde(default = "default_delimiter")]
+    delimiter: String,
+    /// Print later instead: RFC 3339, or `HH:MM` for the next occurrence of that time.
+    schedule_at: Option<String>,
+    /// Print this many seconds from now.
+    delay_seconds: Option<u64>,
+    /// Drop the job as `expired` if it hasn't printed this many seconds after it was due,
+    /// e.g. because the printer was jammed.
+    expires_in: Option<u64>,
+    /// Gets a POST with the job ID and whether it printed or failed, once it has.
+    callback_url: Option<String>,
+}
+
+impl Default for PrintParams {
+    /// The options of a request without a query string.
+    fn default() -> Self {
+        serde_json::from_value(serde_json::json!({})).expect("every print option has a default")
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Text,
+    Json,
+    Figlet,
+    Ansi,
+    Csv,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+enum Render {
+    #[default]
+    Text,
+    Raster,
+    Auto,
+}
+
+/// Upper bound on `copies`, so a typo doesn't empty the paper roll.
+const MAX_COPIES: u32 = 10;
+
+/// Upper bound on `tab_width`, so a huge one can't expand a tab into gigabytes of spaces.
+const MAX_TAB_WIDTH: usize = 16;
+
+/// Characters per line for text laid along the paper with `?rotate`: a wide terminal's
+/// worth, where a CSV report's columns still fit side by side.
+const LANDSCAPE_COLUMNS: usize = 200;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_copies() -> u32 {
+    1
+}
+
+fn default_tab_width() -> usize {
+    8
+}
+
+fn default_delimiter() -> String {
+    "\x0c".to_owned()
+}
+
+/// Upper bound on documents in one batch request.
+const MAX_BATCH: usize = 100;
+
+mod agenda;
+pub mod agent;
+mod ansi;
+mod apod;
+mod archive;
+mod article;
+mod assets;
+mod audit;
+mod astro;
+mod banner;
+mod bluetooth;
+mod briefing;
+mod callback;
+mod codepage;
+pub mod config;
+mod contact;
+mod cors;
+mod countdowns;
+mod debug;
+mod diagnostics;
+mod discovery;
+mod error;
+mod exec;
+mod feeds;
+mod fetch;
+mod figlet;
+mod fx;
+mod github;
+pub mod grpc;
+mod history;
+mod habits;
+mod hn;
+mod html;
+mod invoice;
+mod json;
+mod maintenance;
+mod markdown;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod network;
+mod onthisday;
+mod openapi;
+mod notes;
+mod notify;
+mod order;
+mod packages;
+mod paper;
+mod plugins;
+mod pomodoro;
+pub mod printer;
+mod profile;
+mod quote;
+mod queue;
+mod raster;
+mod receipt;
+mod recipe;
+mod redact;
+mod relay;
+mod reminders;
+mod scheduler;
+pub mod send;
+mod sky;
+pub mod source;
+mod spool;
+mod star;
+mod stats;
+mod stocks;
+mod table;
+mod storage;
+mod stream;
+pub mod systemd;
+mod template;
+mod ticket;
+mod tides;
+mod timefmt;
+mod transit;
+pub mod tls;
+mod typeset;
+mod upload;
+mod weather;
+mod wifi;
+mod xkcd;
+
+use callback::Completion;
+use config::Config;
+use diagnostics::{Diagnostics, Report};
+use history::{JobHistory, JobRecord, JobStatus};
+use printer::{Device, MAIN_PRINTER, PrinterDriver, PrinterSlot};
+use profile::Profile;
+use queue::{PrintQueue, QueueStatus, QueuedJob};
+use receipt::{Column, Receipt, render_row, text_width, wrap, wrap_indented};
+use source::Source;
+use chrono::{DateTime, Local, TimeDelta};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    config: Arc<Config>,
+    printer: PrinterSlot,
+    /// The printers from `PRINTERS`, by name.
+    printers: Arc<HashMap<String, PrinterSlot>>,
+    /// The ends of the printers fed by agents, by name, for them to poll.
+    agents: Arc<HashMap<String, agent::AgentDriver>>,
+    /// Printers on other servers that jobs are forwarded to, by name; see [`relay`].
+    relays: Arc<HashMap<String, relay::Relay>>,
+    diagnostics: Diagnostics,
+    printed_stories: hn::PrintedStories,
+    history: JobHistory,
+    archive: archive::JobArchive,
+    queue: PrintQueue,
+    maintenance: maintenance::Maintenance,
+    forecast_cache: weather::ForecastCache,
+    reminders: reminders::Reminders,
+    notes: notes::Notes,
+    package_statuses: packages::PackageStatuses,
+    tickets: ticket::TicketCounter,
+    orders: ticket::TicketCounter,
+    assets: assets::Assets,
+    paper: paper::PaperTracker,
+    audit: audit::AuditLog,
+}
+
+impl AppState {
+    /// `driver` stands in for the USB printer, e.g. a mock in tests; `None` opens the real device.
+    pub fn new(config: Config, driver: Option<PrinterDriver>) -> Self {
+        Self::with_printers(config, driver, Vec::new())
+    }
+
+    /// Like [`AppState::new`], with `named` drivers standing in for (or adding to) the
+    /// printers from `PRINTERS`.
+    pub fn with_printers(mut config: Config, driver: Option<PrinterDriver>, named: Vec<(String, PrinterDriver)>) -> Self {
+        let diagnostics = Diagnostics::default();
+        let mut agents = HashMap::new();
+        let relays: HashMap<String, relay::Relay> = config.relays.iter().map(|relay| (relay.name.clone(), relay.clone())).collect();
+        let printer = match driver {
+            Some(driver) => {
+                if config.printer_autodetect {
+                    config.printer_profile = printer::detect_profile(&driver, &config.printer_profile, &diagnostics);
+                }
+                PrinterSlot::with_driver(driver, &config.printer_profile, diagnostics.clone())
+            }
+            None if config.printer_agent => agent_slot(MAIN_PRINTER, &config.printer_profile, diagnostics.clone(), &mut agents),
+            // Nothing to open: its jobs are relayed.
+            None if relays.contains_key(MAIN_PRINTER) => PrinterSlot::connected(None, None, &config.printer_profile, diagnostics.clone()),
+            None => {
+                let device = printer_device(&config);
+                let driver = printer::connect(device, &config.printer_profile, &diagnostics);
+                if config.printer_autodetect
+                    && let Some(driver) = &driver
+                {
+                    config.printer_profile = printer::detect_profile(driver, &config.printer_profile, &diagnostics);
+                }
+                PrinterSlot::connected(Some(device), driver, &config.printer_profile, diagnostics.clone())
+            }
+        };
+        // Named printers keep their own diagnostics, which only report the main one.
+        let mut printers: HashMap<String, PrinterSlot> = config
+            .printers
+            .iter()
+            .filter(|configured| !named.iter().any(|(name, _)| name == &configured.name))
+            .map(|configured| {
+                let slot = match configured.device {
+                    Device::Agent => agent_slot(&configured.name, &configured.profile, Diagnostics::default(), &mut agents),
+                    device => PrinterSlot::new(device, &configured.profile, Diagnostics::default()),
+                };
+                (configured.name.clone(), slot)
+            })
+            .collect();
+        for (name, driver) in named {
+            let configured = config.printers.iter().find(|configured| configured.name == name);
+            let profile = configured.map_or(&config.printer_profile, |configured| &configured.profile);
+            printers.insert(name, PrinterSlot::with_driver(driver, profile, Diagnostics::default()));
+        }
+        for name in relays.keys().filter(|name| *name != MAIN_PRINTER) {
+            let slot = PrinterSlot::connected(None, None, &config.printer_profile, Diagnostics::default());
+            if printers.insert(name.clone(), slot).is_some() {
+                eprintln!("RELAYS names {:?}, which is also in PRINTERS; its jobs are relayed", name);
+            }
+        }
+        for name in config.mirror_printers.iter().filter(|name| *name != MAIN_PRINTER && !printers.contains_key(*name)) {
+            eprintln!("MIRROR_PRINTERS names {:?}, which isn't in PRINTERS", name);
+        }
+        for route in config.printer_routes.iter().filter(|route| route.printer != MAIN_PRINTER && !printers.contains_key(&route.printer)) {
+            eprintln!("PRINTER_ROUTES names {:?}, which isn't in PRINTERS", route.printer);
+        }
+        if let Some(name) = config.printer_failover.as_ref().filter(|name| !printers.contains_key(*name)) {
+            eprintln!("PRINTER_FAILOVER names {:?}, which isn't in PRINTERS", name);
+        }
+        let reminders = reminders::Reminders::load(config.data_dir.join("reminders.json"));
+        let notes = notes::Notes::load(config.data_dir.join("notes.json"));
+        let package_statuses = packages::PackageStatuses::load(config.data_dir.join("packages.json"));
+        let tickets = ticket::TicketCounter::load(config.data_dir.join("tickets.json"));
+        let orders = ticket::TicketCounter::load(config.data_dir.join("orders.json"));
+        let assets = assets::Assets::new(config.data_dir.join("assets"));
+        let audit = audit::AuditLog::load(config.data_dir.join("audit.jsonl"));
+        let archive = archive::JobArchive::load(config.archive_jobs.then(|| config.data_dir.join("archive")));
+        let paper = paper::PaperTracker::load(
+            config.data_dir.join("paper.json"),
+            config.paper_roll_length_mm,
+            config.paper_low_percent,
+        );
+        AppState {
+            config: Arc::new(config),
+            printer,
+            printers: Arc::new(printers),
+            agents: Arc::new(agents),
+            relays: Arc::new(relays),
+            diagnostics,
+            printed_stories: hn::PrintedStories::default(),
+            history: JobHistory::starting_after(archive.last_id()),
+            archive,
+            queue: PrintQueue::default(),
+            maintenance: maintenance::Maintenance::default(),
+            forecast_cache: weather::ForecastCache::default(),
+            reminders,
+            notes,
+            package_statuses,
+            tickets,
+            orders,
+            assets,
+            paper,
+            audit,
+        }
+    }
+
+    /// Queues a rendered receipt for the print worker, records it in the job history and
+    /// returns its job ID.
+    fn print(&self, receipt: Receipt, job: &str, source: &Source) -> u64 {
+        self.print_at(receipt, job, source, None)
+    }
+
+    /// Like [`AppState::print`], but with `at` the job waits for the scheduler to release it.
+    fn print_at(&self, receipt: Receipt, job: &str, source: &Source, at: Option<DateTime<Local>>) -> u64 {
+        self.print_all(vec![receipt], job, source, at, None, None).jobs[0].id
+    }
+
+    /// Whether `MAX_QUEUED_JOBS` jobs are already waiting.
+    fn queue_full(&self) -> bool {
+        self.config.max_queued_jobs.is_some_and(|limit| self.queue.depth() >= limit)
+    }
+
+    /// Adds the configured header and footer templates and the source footer.
+    fn decorate(&self, receipt: &mut Receipt, id: u64, job: &str, source: &Source) {
+        let info = template::JobInfo {
+            id,
+            job,
+            source,
+            at: Local::now(),
+        };
+        if let Some(header) = &self.config.header_template {
+            let mut top = Receipt::with_width(receipt.width());
+            template::render(&mut top, header, &info);
+            top.divider();
+            receipt.prepend(top);
+        }
+        if self.config.job_metadata && receipt.has_metadata() {
+            let mut top = Receipt::with_width(receipt.width());
+            top.line_left(&metadata_line(&info, top.width()));
+            receipt.prepend(top);
+        }
+        if let Some(footer) = &self.config.footer_template {
+            receipt.divider();
+            template::render(receipt, footer, &info);
+        }
+        if self.config.source_footer {
+            receipt.line_left(&format!("via {} (job #{})", source, id));
+        }
+        if let Some(url) = &self.config.job_qr_url {
+            receipt.qr_code(&format!("{}/jobs/{}/content", url, id), 3);
+        }
+    }
+
+    /// The printers a job goes to, by name: the request's `?mirror=` or `?printer=`, else
+    /// the first matching `PRINTER_ROUTES` rule, else `MIRROR_PRINTERS`. Empty means the
+    /// main printer.
+    fn route(&self, job: &str, source: &Source) -> Vec<String> {
+        let printers = if !source.mirror().is_empty() {
+            source.mirror().to_vec()
+        } else if let Some(printer) = source.printer() {
+            vec![printer.to_owned()]
+        } else if let Some(route) = self.config.printer_routes.iter().find(|route| route.matches(job, source.key_name(), source.tag())) {
+            vec![route.printer.clone()]
+        } else {
+            self.config.mirror_printers.clone()
+        };
+        if printers == [MAIN_PRINTER] { Vec::new() } else { printers }
+    }
+
+    /// `callback`, or else the API key's configured callback URL.
+    fn callback_url<'a>(&'a self, callback: Option<&'a str>, source: &Source) -> Option<&'a str> {
+        callback.or_else(|| source.key_name().and_then(|key| self.config.callback_urls.get(key)).map(String::as_str))
+    }
+
+    /// When a job due `at` (or now) prints: at the end of `QUIET_HOURS` if it falls in
+    /// them and the request didn't override them.
+    fn hold_until(&self, job: &str, source: &Source, at: Option<DateTime<Local>>) -> Option<DateTime<Local>> {
+        let quiet_until = (self.config.quiet_hours)
+            .filter(|_| !source.overrides_quiet())
+            .and_then(|quiet| quiet.until(at.unwrap_or_else(Local::now)));
+        match quiet_until {
+            Some(until) => {
+                eprintln!("Quiet hours: holding {} job until {}", job, until.format("%H:%M"));
+                Some(until)
+            }
+            None => at,
+        }
+    }
+
+    /// Queues several receipts as separate jobs that print back to back. Each job reports
+    /// to `callback`, or else the API key's configured callback URL, when it's done. Jobs
+    /// still waiting at `expires_at` are dropped instead of printed. Jobs due during
+    /// `QUIET_HOURS` wait for the scheduler to release them when they end.
+    fn print_all(
+        &self,
+        receipts: Vec<Receipt>,
+        job: &str,
+        source: &Source,
+        at: Option<DateTime<Local>>,
+        callback: Option<&str>,
+        expires_at: Option<DateTime<Local>>,
+    ) -> Submission {
+        let callback = self.callback_url(callback, source);
+        let printers = self.route(job, source);
+        let at = self.hold_until(job, source, at);
+        let mut submitted = Vec::new();
+        let jobs: Vec<QueuedJob> = receipts
+            .into_iter()
+            .map(|mut receipt| {
+                receipt.redact(&self.config.redactions);
+                let id = self.history.next_id();
+                let lines = receipt.to_text().lines().count();
+                self.archive.store(id, job, source, lines, &receipt);
+                self.history.keep_receipt(id, receipt.clone());
+                if !receipt.is_plain() {
+                    self.decorate(&mut receipt, id, job, source);
+                }
+                self.history.record(id, job, source, lines, at);
+                submitted.push(SubmittedJob {
+                    id,
+                    lines,
+                    paper_mm: (receipt.paper_mm() * 10.0).round() / 10.0,
+                });
+                QueuedJob {
+                    id,
+                    receipt,
+                    stream: None,
+                    done: None,
+                    callback: callback.map(str::to_owned),
+                    expires_at,
+                    namespace: source.namespace().to_owned(),
+                    source: source.to_string(),
+                    printers: printers.clone(),
+                }
+            })
+            .collect();
+        self.queue.push(jobs, at);
+        Submission {
+            jobs: submitted,
+            printers: if printers.is_empty() { vec![MAIN_PRINTER.to_owned()] } else { printers },
+            scheduled_for: at,
+        }
+    }
+
+    /// Queues a job that starts with `first` and goes on with the receipts from `rest` as
+    /// they come, until it's closed; see [`stream`]. Streamed jobs have no header, footer
+    /// or expiry, and print on the first printer they're routed to.
+    fn print_stream(&self, first: Receipt, rest: UnboundedReceiver<Receipt>, job: &str, source: &Source, callback: Option<&str>) -> Submission {
+        let callback = self.callback_url(callback, source);
+        let printers = self.route(job, source);
+        let at = self.hold_until(job, source, None);
+        let id = self.history.next_id();
+        let lines = first.to_text().lines().count();
+        self.history.record(id, job, source, lines, at);
+        let queued = QueuedJob {
+            id,
+            receipt: first,
+            stream: Some(rest),
+            done: None,
+            callback: callback.map(str::to_owned),
+            expires_at: None,
+            namespace: source.namespace().to_owned(),
+            source: source.to_string(),
+            printers: printers.clone(),
+        };
+        self.queue.push(vec![queued], at);
+        Submission {
+            jobs: vec![SubmittedJob { id, lines, paper_mm: 0.0 }],
+            printers: if printers.is_empty() { vec![MAIN_PRINTER.to_owned()] } else { printers },
+            scheduled_for: at,
+        }
+    }
+
+    /// Queues a receipt relayed from job `upstream_id` on another instance as it came,
+    /// since it was decorated and held for quiet hours there; see [`relay`]. The receiver
+    /// hears how the job went.
+    fn relay_in(&self, mut receipt: Receipt, source: &Source, upstream_id: u64) -> (u64, oneshot::Receiver<Completion>) {
+        receipt.redact(&self.config.redactions);
+        let printers = self.route("relay", source);
+        let id = self.history.next_id();
+        let lines = receipt.to_text().lines().count();
+        self.archive.store(id, "relay", source, lines, &receipt);
+        self.history.keep_receipt(id, receipt.clone());
+        self.history.record(id, "relay", source, lines, None);
+        self.history.set_upstream_id(id, upstream_id);
+        let (done, outcome) = oneshot::channel();
+        let queued = QueuedJob {
+            id,
+            receipt,
+            stream: None,
+            done: Some(done),
+            callback: self.callback_url(None, source).map(str::to_owned),
+            expires_at: None,
+            namespace: source.namespace().to_owned(),
+            source: source.to_string(),
+            printers,
+        };
+        self.queue.push(vec![queued], None);
+        (id, outcome)
+    }
+}
+
+/// A job [`AppState::print_all`] queued.
+#[derive(Serialize, ToSchema)]
+struct SubmittedJob {
+    id: u64,
+    /// Lines of text, as in the job history.
+    lines: usize,
+    /// Estimated paper it takes, header and footer included.
+    paper_mm: f64,
+}
+
+/// The jobs [`AppState::print_all`] queued, and where and when they print.
+#[derive(Serialize, ToSchema)]
+struct Submission {
+    /// One job per document and copy, in print order.
+    jobs: Vec<SubmittedJob>,
+    /// Printers the jobs go to, by name, before any failover; `default` is the main printer.
+    printers: Vec<String>,
+    /// When the jobs are held until, by `schedule_at`, `delay_seconds` or `QUIET_HOURS`.
+    scheduled_for: Option<DateTime<Local>>,
+}
+
+/// `2026-10-16 08:14 kitchen` on the left and `#42 3fa9c1` on the right: the job ID with
+/// a short hash of it and the time, which stays unique across restarts. The source is
+/// the API key's name when there is one, and is cut short to fit.
+fn metadata_line(info: &template::JobInfo, width: usize) -> String {
+    let hash = Sha256::digest(format!("{}@{}", info.id, info.at.to_rfc3339()));
+    let id = format!("#{} {:02x}{:02x}{:02x}", info.id, hash[0], hash[1], hash[2]);
+    let source = info.source.key_name().map_or_else(|| info.source.to_string(), str::to_owned);
+    let left = format!("{} {}", info.at.format("%Y-%m-%d %H:%M"), source);
+    let left: String = left.chars().take(width.saturating_sub(id.len() + 1)).collect();
+    format!("{:<width$} {}", left, id, width = width.saturating_sub(id.len() + 1))
+}
+
+#[derive(Deserialize, IntoParams)]
+struct DiagnosticsParams {
+    /// Also print the report.
+    #[serde(default)]
+    #[param(default = false)]
+    print: bool,
+}
+
+/// The configured agent or Bluetooth printer, or else USB.
+fn printer_device(config: &Config) -> Device {
+    if config.printer_agent {
+        return Device::Agent;
+    }
+    match config.printer_bluetooth {
+        Some(address) => Device::Bluetooth(address, config.printer_bluetooth_channel),
+        None => Device::Usb(config.printer_usb),
+    }
+}
+
+/// A slot for the printer `name`, printing through the agent that polls for it.
+fn agent_slot(name: &str, profile: &Profile, diagnostics: Diagnostics, agents: &mut HashMap<String, agent::AgentDriver>) -> PrinterSlot {
+    let driver = agent::AgentDriver::new(name);
+    agents.insert(name.to_owned(), driver.clone());
+    PrinterSlot::with_driver(PrinterDriver::Agent(driver), profile, diagnostics)
+}
+
+/// Prints text straight to the printer, without a server or queue, for `send --local`.
+pub fn print_local(config: Config, text: &str, raw: bool) -> Result<(), String> {
+    let params = PrintParams { raw, ..Default::default() };
+    let mut receipt = job_receipt(&config, &params);
+    render_text(&mut receipt, text, &params, None, config.locale).map_err(|status| status.to_string())?;
+    let slot = PrinterSlot::new(printer_device(&config), &config.printer_profile, Diagnostics::default());
+    receipt.print(&mut slot.acquire(), &config.printer_profile).map_err(|e| e.to_string())
+}
+
+/// Starts the print worker, the scheduler and, if configured, the idle printer reapers,
+/// the printer keep-alives, the spool directory watcher, the gRPC server and the systemd
+/// watchdog pings.
+pub fn spawn_background(state: &AppState) {
+    for slot in std::iter::once(&state.printer).chain(state.printers.values()) {
+        if let Some(timeout) = state.config.printer_idle_timeout {
+            slot.spawn_idle_reaper(timeout);
+        }
+        if let Some(period) = state.config.printer_keepalive {
+            slot.spawn_keepalive(period, state.config.quiet_hours);
+        }
+    }
+    queue::spawn_worker(state.clone());
+    scheduler::spawn(state.clone());
+    if let Some(dir) = &state.config.spool_dir {
+        spool::spawn(state.clone(), dir.clone());
+    }
+    if let Some(port) = state.config.grpc_port {
+        grpc::spawn(state.clone(), port);
+    }
+    systemd::spawn_watchdog(state);
+}
+
+pub fn router(state: AppState) -> Router {
+    let mut printing = OpenApiRouter::new()
+        .routes(routes!(print))
+        .routes(routes!(weather::weather))
+        .routes(routes!(stocks::stocks))
+        .routes(routes!(hn::hn))
+        .routes(routes!(recipe::recipe))
+        .routes(routes!(article::article))
+        .routes(routes!(fetch::fetch))
+        .routes(routes!(onthisday::onthisday))
+        .routes(routes!(sky::sky))
+        .routes(routes!(tides::tides))
+        .routes(routes!(habits::habits))
+        .routes(routes!(github::github))
+        .routes(routes!(xkcd::xkcd))
+        .routes(routes!(apod::apod))
+        .routes(routes!(packages::packages))
+        .routes(routes!(fx::fx))
+        .routes(routes!(countdowns::countdowns))
+        .routes(routes!(briefing::briefing))
+        .routes(routes!(ticket::ticket))
+        .routes(routes!(wifi::wifi))
+        .routes(routes!(contact::contact))
+        .routes(routes!(invoice::invoice))
+        .routes(routes!(order::order))
+        .routes(routes!(banner::banner))
+        .routes(routes!(pomodoro::pomodoro))
+        .routes(routes!(plugins::plugin))
+        .routes(routes!(exec::exec))
+        .routes(routes!(notes::print))
+        .routes(routes!(reprint))
+        .routes(routes!(reprint_last))
+        .routes(routes!(relay::accept))
+        // `Content-Encoding: gzip` or `deflate` bodies, e.g. from log shippers. The body
+        // size limit applies after decompression.
+        .route_layer(RequestDecompressionLayer::new())
+        .route_layer(middleware::from_fn_with_state(state.clone(), maintenance::reject_during_maintenance))
+        .route_layer(middleware::from_fn_with_state(state.clone(), queue::reject_when_full))
+        .route_layer(middleware::from_fn(audit::mark_print_route));
+    openapi::add_printing_responses(printing.get_openapi_mut());
+
+    let cors = cors::layer(&state.config);
+    let mut api = OpenApiRouter::with_openapi(openapi::ApiDoc::openapi())
+        .merge(printing)
+        .routes(routes!(diagnostics_report))
+        .routes(routes!(jobs))
+        .routes(routes!(job_content))
+        .routes(routes!(stats::stats))
+        .routes(routes!(ticket::reset))
+        .routes(routes!(assets::list))
+        .routes(routes!(assets::upload, assets::delete))
+        .routes(routes!(assets::store_nv, assets::delete_nv))
+        .routes(routes!(reminders::list, reminders::create))
+        .routes(routes!(reminders::get_one, reminders::update, reminders::delete))
+        .routes(routes!(notes::create))
+        .routes(routes!(audit::list))
+        .routes(routes!(audit::verify))
+        .routes(routes!(paper::status))
+        .routes(routes!(discovery::list))
+        .routes(routes!(paper::roll_reset))
+        .routes(routes!(agent::next))
+        .routes(routes!(agent::report))
+        .routes(routes!(debug::render))
+        .routes(routes!(clear_queue))
+        .routes(routes!(pause))
+        .routes(routes!(resume))
+        // The original names, kept for existing clients.
+        .routes(routes!(pause_by_old_name))
+        .routes(routes!(resume_by_old_name))
+        .routes(routes!(maintenance::start, maintenance::end))
+        .layer(middleware::from_fn_with_state(state.clone(), source::require_api_key))
+        .layer(middleware::from_fn_with_state(state.clone(), audit::record));
+    openapi::add_key_responses(api.get_openapi_mut());
+    // Readiness probes don't carry API keys.
+    let (router, spec) = api.routes(routes!(readyz)).split_for_parts();
+    let router = router
+        // The API description is public, so clients can be written before they have a key.
+        .merge(openapi::docs(spec))
+        .with_state(state);
+    // Outermost, so preflight requests are answered before the API key check.
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+}
+
+/// What `POST /` answers with, for clients to log and correlate with `/jobs`.
+#[derive(Serialize, ToSchema)]
+struct Printed {
+    #[serde(flatten)]
+    submission: Submission,
+    /// Whether each job is cut at the end.
+    cut: bool,
+    /// Times each document is printed, after clamping to the maximum.
+    copies: u32,
+    /// Whether templates and footers were left off.
+    plain: bool,
+}
+
+/// Prints a plain-text or JSON body, or the files of a `multipart/form-data` upload. A
+/// chunked text body prints as it comes in; see [`stream`].
+#[utoipa::path(
+    method(post, put),
+    path = "/",
+    tag = "printing",
+    summary = "Print text, JSON or uploaded files",
+    description = "PUT is the same, for clients that upload with it, e.g. `curl -T -`. A `Transfer-Encoding: \
+        chunked` plain-text body prints line by line as it streams in, holding the printer until it ends and \
+        then cutting, e.g. `tail -f build.log | curl -T - http://printer:3000/`. That needs the default \
+        `format`, one copy, and no `batch`, `page_lines`, `logo`, `schedule_at`, `delay_seconds` or \
+        `expires_in`; otherwise the body is read whole first. Streamed jobs have no header or footer.",
+    request_body(content(
+        (String = "text/plain"),
+        (serde_json::Value = "application/json"),
+        (upload::UploadForm = "multipart/form-data"),
+    )),
+    params(PrintParams),
+    responses(
+        (status = 200, description = "Queued to print.", body = Printed),
+        (status = 400, description = "Invalid options."),
+        (status = 415, description = "An uploaded file of an unsupported type."),
+        (status = 422, description = "The text can't be laid out, e.g. a word too long with `strict`."),
+    )
+)]
+async fn print(
+    State(state): State<AppState>,
+    source: Source,
+    Query(mut params): Query<PrintParams>,
+    request: Request,
+) -> Result<Json<Printed>, StatusCode> {
+    let at = print_time(&params)?;
+    let expires_at = expiry(&params, at)?;
+    if params.callback_url.as_deref().is_some_and(|url| !callback::valid_url(url)) {
+        eprintln!("Invalid callback_url {:?}", params.callback_url);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if stream::streamable(&params, request.headers()) {
+        return stream::print(&state, &source, &params, request).await;
+    }
+    let receipts = receipts(&state, &source, &mut params, request).await?;
+    if let Some(at) = at {
+        eprintln!("Holding print until {}", at.format("%Y-%m-%d %H:%M:%S"));
+    }
+    let submission = state.print_all(receipts, "print", &source, at, params.callback_url.as_deref(), expires_at);
+
+    Ok(Json(Printed {
+        submission,
+        cut: params.cut,
+        copies: params.copies.clamp(1, MAX_COPIES),
+        plain: params.plain,
+    }))
+}
+
+/// A receipt in the request's `printer_font` and `line_spacing`, or the configured ones.
+fn job_receipt(config: &Config, params: &PrintParams) -> Receipt {
+    Receipt::with_font(
+        &config.printer_profile,
+        params.printer_font.unwrap_or(config.printer_font),
+        params.line_spacing.or(config.line_spacing),
+    )
+}
+
+/// Lays out a print request's body as receipts, one per document and copy, with its
+/// logo and cut options applied.
+async fn receipts(state: &AppState, source: &Source, params: &mut PrintParams, request: Request) -> Result<Vec<Receipt>, StatusCode> {
+    let mut receipts = Vec::new();
+
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+    let is_upload = content_type.starts_with("multipart/form-data");
+    if is_upload {
+        let multipart = Multipart::from_request(request, state).await.map_err(|e| e.status())?;
+        let uploads = upload::read_parts(multipart, params).await?;
+        if uploads.is_empty() {
+            eprintln!("Multipart print request without any files");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        eprintln!("Received upload: {} part(s) (raw={})", uploads.len(), params.raw);
+        let mut receipt = job_receipt(&state.config, params);
+        receipt.sized(params.size, |receipt| uploads.iter().try_for_each(|upload| upload::render(receipt, upload, params, &state.config)))?;
+        receipts.push(receipt);
+    } else {
+        let body = Bytes::from_request(request, state).await.map_err(|e| e.status())?;
+        let str = std::str::from_utf8(&body).or(Err(StatusCode::UNPROCESSABLE_ENTITY))?;
+        eprintln!("Received print request: {} bytes (raw={})", body.len(), params.raw);
+        eprintln!("Content: {:?}", str);
+        if !matches!(params.rotate, 0 | 90 | 270) {
+            eprintln!("Invalid rotation {} for text (expected 0, 90 or 270)", params.rotate);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let json = params.format == Format::Json || content_type.starts_with("application/json");
+        for document in documents(str, json, params)? {
+            let mut receipt = job_receipt(&state.config, params);
+            if params.rotate != 0 {
+                receipt.widen(LANDSCAPE_COLUMNS);
+            }
+            receipt.sized(params.size, |receipt| {
+                match document {
+                    Document::Text(text) if params.format == Format::Figlet => {
+                        render_lines(receipt, figlet::lines(&text, params.font, receipt.width()), params, None, state.config.locale)
+                    }
+                    Document::Text(text) if params.format == Format::Ansi => ansi::render(receipt, &text, params),
+                    Document::Text(text) if params.format == Format::Csv => {
+                        let lines = csv_lines(&text, receipt.width())?;
+                        render_lines(receipt, lines, params, None, state.config.locale)
+                    }
+                    Document::Text(text) => render_text(receipt, &text, params, None, state.config.locale)?,
+                    Document::Json(value) => {
+                        let lines = value_lines(&value, receipt.width())?;
+                        render_lines(receipt, lines, params, None, state.config.locale)
+                    }
+                }
+                Ok::<_, StatusCode>(())
+            })?;
+            receipts.push(receipt);
+        }
+    }
+
+    for receipt in &mut receipts {
+        // Here as well as when queued, so `/debug/render` shows it too and it's masked
+        // before text is drawn as images.
+        receipt.redact(&state.config.redactions);
+        // Uploads use the rotation for their images.
+        if params.rotate != 0 && !is_upload {
+            receipt.landscape(&state.config.printer_profile, params.rotate);
+        } else if params.render != Render::Text {
+            receipt.rasterize(&state.config.printer_profile, params.render == Render::Raster);
+        }
+    }
+    if let Some(name) = &params.logo {
+        let mut top = Receipt::for_config(&state.config);
+        let alt = format!("[logo: {}]", name);
+        if let Some(key) = state.assets.nv_key(source.namespace(), name) {
+            top.nv_image(key, &alt);
+        } else if let Some(logo) = state.assets.load(source.namespace(), name) {
+            top.image(&logo, &alt);
+        } else {
+            eprintln!("Unknown logo {:?}", name);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        for receipt in &mut receipts {
+            receipt.prepend(top.clone());
+        }
+    }
+    if !params.cut {
+        receipts.iter_mut().for_each(Receipt::without_cut);
+    }
+    if params.plain {
+        receipts.iter_mut().for_each(Receipt::set_plain);
+    }
+    if params.no_header {
+        receipts.iter_mut().for_each(Receipt::without_metadata);
+    }
+    let copies = params.copies.clamp(1, MAX_COPIES) as usize;
+    Ok(receipts.iter().cycle().take(receipts.len() * copies).cloned().collect())
+}
+
+/// Wraps text at word boundaries to the paper width, or with `raw` keeps lines as they are.
+/// Words too long for a line (URLs, German compounds) are broken across lines, unless
+/// `strict` asks for them to be rejected.
+fn text_lines(str: &str, params: &PrintParams, width: usize) -> Result<Vec<String>, StatusCode> {
+    let lines = normalize_whitespace(str, params);
+    if params.raw {
+        return Ok(lines);
+    }
+    if params.strict
+        && let Some(chunk) = lines
+            .iter()
+            .flat_map(|line| line.split_whitespace())
+            .find(|chunk| text_width(chunk) > width)
+    {
+        eprintln!("Chunk too long ({} columns): {:?}", text_width(chunk), chunk);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    if params.indent {
+        return Ok(lines.iter().flat_map(|line| wrap_indented(line, width)).collect());
+    }
+    Ok(lines.iter().flat_map(|line| wrap(line, width)).collect())
+}
+
+/// Expands tabs to the next tab stop, then applies the `trim` and `squeeze` options.
+fn normalize_whitespace(str: &str, params: &PrintParams) -> Vec<String> {
+    let tab_width = params.tab_width.clamp(1, MAX_TAB_WIDTH);
+    let mut lines: Vec<String> = Vec::new();
+    for line in str.lines() {
+        let mut expanded = String::with_capacity(line.len());
+        let mut column = 0;
+        for c in line.chars() {
+            if c == '\t' {
+                let spaces = tab_width - column % tab_width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            } else {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+        if params.trim {
+            expanded.truncate(expanded.trim_end().len());
+        }
+        let blank = expanded.trim().is_empty();
+        if params.squeeze && blank && lines.last().is_some_and(|last| last.trim().is_empty()) {
+            continue;
+        }
+        lines.push(expanded);
+    }
+    lines
+}
+
+/// Parses and pretty-prints a JSON document; malformed JSON can't be printed.
+fn json_lines(str: &str, width: usize) -> Result<Vec<String>, StatusCode> {
+    value_lines(&parse_json(str)?, width)
+}
+
+/// A `{"table": [...]}` document as a table, anything else pretty-printed.
+fn value_lines(value: &serde_json::Value, width: usize) -> Result<Vec<String>, StatusCode> {
+    match table::from_json(value) {
+        Some(rows) => table_lines(&rows, width),
+        None => Ok(json::pretty_lines(value, width)),
+    }
+}
+
+/// Draws CSV as a table with the first row as the header.
+fn csv_lines(str: &str, width: usize) -> Result<Vec<String>, StatusCode> {
+    table_lines(&table::parse_csv(str), width)
+}
+
+fn table_lines(rows: &[Vec<String>], width: usize) -> Result<Vec<String>, StatusCode> {
+    if rows.is_empty() {
+        eprintln!("Table without any rows");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    table::lines(rows, true, width).ok_or_else(|| {
+        eprintln!("Table with {} columns is too wide for the paper", rows.iter().map(Vec::len).max().unwrap_or(0));
+        StatusCode::UNPROCESSABLE_ENTITY
+    })
+}
+
+fn parse_json(str: &str) -> Result<serde_json::Value, StatusCode> {
+    serde_json::from_str(str).map_err(|e| {
+        eprintln!("Invalid JSON body: {}", e);
+        StatusCode::UNPROCESSABLE_ENTITY
+    })
+}
+
+enum Document<'a> {
+    Text(Cow<'a, str>),
+    Json(serde_json::Value),
+}
+
+/// Splits a request body into the documents to print, one per receipt.
+fn documents<'a>(str: &'a str, json: bool, params: &PrintParams) -> Result<Vec<Document<'a>>, StatusCode> {
+    if !params.batch {
+        return Ok(vec![if json { Document::Json(parse_json(str)?) } else { Document::Text(str.into()) }]);
+    }
+
+    let documents: Vec<Document> = if json {
+        let serde_json::Value::Array(items) = parse_json(str)? else {
+            eprintln!("Batch JSON body is not an array");
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        };
+        items
+            .into_iter()
+            .map(|item| match item {
+                serde_json::Value::String(text) => Document::Text(text.into()),
+                other => Document::Json(other),
+            })
+            .collect()
+    } else if params.delimiter.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    } else {
+        str.split(params.delimiter.as_str())
+            .map(|document| document.trim_matches('\n'))
+            .filter(|document| !document.trim().is_empty())
+            .map(|document| Document::Text(document.into()))
+            .collect()
+    };
+    if documents.is_empty() || documents.len() > MAX_BATCH {
+        eprintln!("Batch of {} documents (expected 1 to {})", documents.len(), MAX_BATCH);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    eprintln!("Batch of {} documents", documents.len());
+    Ok(documents)
+}
+
+fn render_text(
+    receipt: &mut Receipt,
+    str: &str,
+    params: &PrintParams,
+    file_name: Option<&str>,
+    locale: chrono::Locale,
+) -> Result<(), StatusCode> {
+    let lines = text_lines(str, params, receipt.width())?;
+    render_lines(receipt, lines, params, file_name, locale);
+    Ok(())
+}
+
+/// Prints laid-out lines, paginated when `page_lines` is set. `file_name` is the fallback page title.
+fn render_lines(receipt: &mut Receipt, lines: Vec<String>, params: &PrintParams, file_name: Option<&str>, locale: chrono::Locale) {
+    let Some(page_lines) = params.page_lines.filter(|&n| n > 0) else {
+        for line in &lines {
+            receipt.text(line);
+            receipt.text("\n");
+        }
+        return;
+    };
+
+    let title = params.title.as_deref().or(file_name).unwrap_or("Document");
+    let date = Local::now().format_localized("%-d %B %Y", locale).to_string();
+    let pages = lines.len().div_ceil(page_lines).max(1);
+    let header = [Column::left(receipt.width().saturating_sub(12)), Column::right(12)];
+    for (i, page) in lines.chunks(page_lines).enumerate() {
+        if i > 0 && params.page_cut {
+            receipt.partial_cut();
+        }
+        receipt.line_left(&render_row(&header, &[title, &format!("page {}/{}", i + 1, pages)]));
+        receipt.line_left(&date);
+        receipt.divider();
+        for line in page {
+            receipt.text(line);
+            receipt.text("\n");
+        }
+        receipt.line_left("");
+    }
+}
+
+/// When a print request should come out: `None` for now, or the `schedule_at` / `delay_seconds` time.
+fn print_time(params: &PrintParams) -> Result<Option<DateTime<Local>>, StatusCode> {
+    let now = Local::now();
+    let at = match (params.schedule_at.as_deref(), params.delay_seconds) {
+        (None, None) => return Ok(None),
+        (Some(when), None) => scheduler::parse_when(when).filter(|at| *at > now),
+        (None, Some(seconds)) => i64::try_from(seconds)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|delay| now.checked_add_signed(delay)),
+        (Some(_), Some(_)) => None,
+    };
+    if at.is_none() {
+        eprintln!("Invalid print time (schedule_at={:?}, delay_seconds={:?})", params.schedule_at, params.delay_seconds);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(at)
+}
+
+/// The `expires_in` deadline, counted from when the job is due to print.
+fn expiry(params: &PrintParams, at: Option<DateTime<Local>>) -> Result<Option<DateTime<Local>>, StatusCode> {
+    let Some(seconds) = params.expires_in else {
+        return Ok(None);
+    };
+    let expires_at = i64::try_from(seconds)
+        .ok()
+        .and_then(TimeDelta::try_seconds)
+        .and_then(|ttl| at.unwrap_or_else(Local::now).checked_add_signed(ttl));
+    if expires_at.is_none() {
+        eprintln!("Invalid expires_in {}", seconds);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(expires_at)
+}
+
+#[utoipa::path(
+    get,
+    path = "/diagnostics",
+    tag = "status",
+    summary = "Printer and job diagnostics",
+    params(DiagnosticsParams),
+    responses((status = 200, description = "OK", body = Report))
+)]
+async fn diagnostics_report(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<DiagnosticsParams>,
+) -> Json<Report> {
+    let mut report = state.diagnostics.snapshot();
+    report.maintenance = state.maintenance.active();
+
+    if params.print {
+        let mut receipt = Receipt::for_config(&state.config);
+        receipt.line_left("DIAGNOSTICS");
+        receipt.divider();
+        for line in report.lines() {
+            receipt.line_left(&line);
+        }
+
+        state.print(receipt, "diagnostics", &source);
+    }
+
+    Json(report)
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ClearQueueParams {
+    /// Only drop jobs from this submitter, e.g. `key:feeds` (see `/jobs`).
+    source: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ClearedQueue {
+    dropped: usize,
+}
+
+/// Discards the caller's waiting jobs, queued or scheduled. They're recorded as
+/// cancelled and their callbacks told so.
+#[utoipa::path(
+    delete,
+    path = "/queue",
+    tag = "admin",
+    summary = "Discard waiting jobs",
+    description = "Drops the caller's queued and scheduled jobs; they're recorded as `cancelled`.",
+    params(ClearQueueParams),
+    responses((status = 200, description = "OK", body = ClearedQueue))
+)]
+async fn clear_queue(State(state): State<AppState>, source: Source, Query(params): Query<ClearQueueParams>) -> Json<ClearedQueue> {
+    let dropped = state.queue.remove(|job| {
+        job.namespace == source.namespace() && params.source.as_ref().is_none_or(|wanted| &job.source == wanted)
+    });
+    eprintln!("Cleared {} job(s) from the queue", dropped.len());
+    for job in &dropped {
+        state.history.drop_unprinted(job.id, JobStatus::Cancelled);
+        if let Some(url) = &job.callback {
+            let completion = callback::Completion {
+                id: job.id,
+                status: JobStatus::Cancelled,
+                error: None,
+            };
+            tokio::spawn(callback::send(url.clone(), state.config.callback_secret.clone(), completion));
+        }
+    }
+    Json(ClearedQueue { dropped: dropped.len() })
+}
+
+#[derive(Serialize, ToSchema)]
+struct Readiness {
+    /// Jobs submitted now will print now.
+    ready: bool,
+    paused: bool,
+    maintenance: bool,
+    queued: usize,
+}
+
+/// 200 while jobs are printing, 503 while the queue is paused or the printer is down
+/// for maintenance.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "admin",
+    summary = "Whether jobs submitted now will print now",
+    security(()),
+    responses(
+        (status = 200, description = "Ready", body = Readiness),
+        (status = 503, description = "Paused or down for maintenance", body = Readiness),
+    )
+)]
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let queue = state.queue.status();
+    let maintenance = state.maintenance.active().is_some();
+    let ready = !queue.paused && !maintenance;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(Readiness {
+            ready,
+            paused: queue.paused,
+            maintenance,
+            queued: queue.queued,
+        }),
+    )
+}
+
+#[derive(Serialize, ToSchema)]
+struct Reprinted {
+    /// The new job.
+    id: u64,
+    reprint_of: u64,
+}
+
+/// Job `id`'s receipt as first rendered, or the caller's latest when `id` is `None`: from
+/// the archive when it's on, otherwise while the history still keeps it.
+fn kept_receipt(state: &AppState, source: &Source, id: Option<u64>) -> Option<(u64, Receipt)> {
+    state
+        .archive
+        .receipt(source.namespace(), id)
+        .or_else(|| state.history.receipt(source.namespace(), id))
+}
+
+/// Queues one of the caller's recent jobs again, as it was first rendered.
+fn queue_reprint(state: &AppState, source: &Source, id: Option<u64>) -> Result<Json<Reprinted>, StatusCode> {
+    let Some((original, receipt)) = kept_receipt(state, source, id) else {
+        eprintln!("No kept receipt to reprint (job {:?})", id);
+        return Err(StatusCode::NOT_FOUND);
+    };
+    eprintln!("Reprinting job #{}", original);
+    let id = state.print(receipt, "reprint", source);
+    Ok(Json(Reprinted { id, reprint_of: original }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/jobs/{id}/reprint",
+    tag = "printing",
+    summary = "Print a recent job again",
+    description = "Queues the job's receipt as it was first rendered. With `ARCHIVE_JOBS` that's read from the \
+        archive, so any earlier job reprints byte for byte, even after a restart.",
+    responses(
+        (status = 200, description = "Queued again", body = Reprinted),
+        (status = 404, description = "No such job, or its receipt is no longer kept (only the last 20 are, \
+            without `ARCHIVE_JOBS`)."),
+    )
+)]
+async fn reprint(State(state): State<AppState>, source: Source, Path(id): Path<u64>) -> Result<Json<Reprinted>, StatusCode> {
+    queue_reprint(&state, &source, Some(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/reprint-last",
+    tag = "printing",
+    summary = "Print the caller's latest job again",
+    responses(
+        (status = 200, description = "Queued again", body = Reprinted),
+        (status = 404, description = "No such job, or its receipt is no longer kept (only the last 20 are, \
+            without `ARCHIVE_JOBS`)."),
+    )
+)]
+async fn reprint_last(State(state): State<AppState>, source: Source) -> Result<Json<Reprinted>, StatusCode> {
+    queue_reprint(&state, &source, None)
+}
+
+/// The text of a recent (or archived) job as it was submitted, before templates and footers.
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}/content",
+    tag = "status",
+    summary = "Get a recent job's text",
+    description = "The job as it was submitted, laid out as plain text the way it printed, without templates or \
+        footers. With `JOB_QR_URL` set, every receipt ends with a QR code linking here. Only the last 20 jobs' \
+        receipts are kept, unless `ARCHIVE_JOBS` keeps every one on disk, across restarts.",
+    responses(
+        (status = 200, description = "The job's text.", body = String, content_type = "text/plain"),
+        (status = 404, description = "No such job, or its receipt is no longer kept."),
+    )
+)]
+async fn job_content(State(state): State<AppState>, source: Source, Path(id): Path<u64>) -> Result<String, StatusCode> {
+    let (_, receipt) = kept_receipt(&state, &source, Some(id)).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(receipt.to_text())
+}
+
+/// Jobs `GET /jobs` returns unless `limit` says otherwise.
+const DEFAULT_JOBS_LIMIT: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+struct JobsParams {
+    /// Text the job has to contain, ignoring case.
+    q: Option<String>,
+    /// Submitter: an API key name (or `key:<name>`), `cert:<name>` or `http:<ip>`.
+    source: Option<String>,
+    /// RFC 3339 lower bound on when the job was submitted.
+    from: Option<DateTime<chrono::FixedOffset>>,
+    /// RFC 3339 upper bound on when the job was submitted.
+    to: Option<DateTime<chrono::FixedOffset>>,
+    /// Jobs to return at most; 100 by default.
+    limit: Option<usize>,
+    /// Matching jobs to skip, for the next page.
+    #[serde(default)]
+    #[param(default = 0)]
+    offset: usize,
+}
+
+/// Jobs in `namespace`, newest first: the ones in memory, and with `ARCHIVE_JOBS` every
+/// archived one too. Also returns the archived receipts' hashes by job ID.
+fn all_jobs(state: &AppState, namespace: &str) -> (Vec<JobRecord>, HashMap<u64, String>) {
+    let mut records = state.history.recent(namespace);
+    let archived_jobs = state.archive.jobs(namespace);
+    let live: HashSet<u64> = records.iter().map(|record| record.id).collect();
+    records.extend(archived_jobs.iter().filter(|job| !live.contains(&job.id)).map(|job| job.to_record()));
+    records.sort_by_key(|record| std::cmp::Reverse(record.id));
+    (records, archived_jobs.into_iter().map(|job| (job.id, job.hash)).collect())
+}
+
+/// The caller's matching jobs, newest first; see [`all_jobs`]. `q` searches the text of
+/// kept and archived receipts.
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    tag = "status",
+    summary = "Search jobs, newest first",
+    description = "The caller's jobs still in memory (the last 200), plus with `ARCHIVE_JOBS` every archived one, \
+        including from before a restart (as `archived`). Filters combine; `q` searches the jobs' text, which \
+        needs the receipt kept (the last 20) or archived.",
+    params(JobsParams),
+    responses((status = 200, description = "OK", body = Vec<JobRecord>))
+)]
+async fn jobs(State(state): State<AppState>, source: Source, Query(params): Query<JobsParams>) -> Json<Vec<JobRecord>> {
+    let namespace = source.namespace();
+    let (records, archived) = all_jobs(&state, namespace);
+
+    // Bare names are API keys; `http` alone is a request with no known peer.
+    let source_filter = params
+        .source
+        .map(|source| if source.contains(':') || source == "http" { source } else { format!("key:{}", source) });
+    let query = params.q.map(|q| q.to_lowercase());
+    // Identical receipts share an archived file, so each is only read once.
+    let mut matched: HashMap<String, bool> = HashMap::new();
+    let mut contains = |id: u64, query: &str| match archived.get(&id) {
+        Some(hash) => *matched.entry(hash.clone()).or_insert_with(|| {
+            state.archive.content(hash).is_some_and(|receipt| receipt.to_text().to_lowercase().contains(query))
+        }),
+        None => state.history.receipt(namespace, Some(id)).is_some_and(|(_, receipt)| receipt.to_text().to_lowercase().contains(query)),
+    };
+    let found = records
+        .into_iter()
+        .filter(|record| {
+            let at = DateTime::parse_from_rfc3339(&record.at).ok();
+            source_filter.as_ref().is_none_or(|source| &record.source == source)
+                && params.from.is_none_or(|from| at.is_some_and(|at| at >= from))
+                && params.to.is_none_or(|to| at.is_some_and(|at| at <= to))
+        })
+        .filter(|record| query.as_ref().is_none_or(|query| contains(record.id, query)))
+        .skip(params.offset)
+        .take(params.limit.unwrap_or(DEFAULT_JOBS_LIMIT))
+        .collect();
+    Json(found)
+}
+
+#[utoipa::path(
+    post,
+    path = "/queue/pause",
+    tag = "admin",
+    summary = "Hold queued jobs, e.g. while changing the paper roll",
+    responses((status = 200, description = "OK", body = QueueStatus))
+)]
+async fn pause(State(state): State<AppState>) -> Json<QueueStatus> {
+    eprintln!("Print queue paused");
+    Json(state.queue.set_paused(true))
+}
+
+#[utoipa::path(
+    post,
+    path = "/queue/resume",
+    tag = "admin",
+    summary = "Print held jobs",
+    responses((status = 200, description = "OK", body = QueueStatus))
+)]
+async fn resume(State(state): State<AppState>) -> Json<QueueStatus> {
+    eprintln!("Print queue resumed");
+    Json(state.queue.set_paused(false))
+}
+
+/// `/admin/pause`, the original name of `/queue/pause`, kept for existing clients.
+#[utoipa::path(
+    post,
+    path = "/admin/pause",
+    tag = "admin",
+    summary = "Hold queued jobs; the old name of `/queue/pause`",
+    responses((status = 200, description = "OK", body = QueueStatus))
+)]
+async fn pause_by_old_name(state: State<AppState>) -> Json<QueueStatus> {
+    pause(state).await
+}
+
+/// `/admin/resume`, the original name of `/queue/resume`, kept for existing clients.
+#[utoipa::path(
+    post,
+    path = "/admin/resume",
+    tag = "admin",
+    summary = "Print held jobs; the old name of `/queue/resume`",
+    responses((status = 200, description = "OK", body = QueueStatus))
+)]
+async fn resume_by_old_name(state: State<AppState>) -> Json<QueueStatus> {
+    resume(state).await
+}
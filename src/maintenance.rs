@@ -0,0 +1,115 @@
+use crate::{AppState, scheduler::parse_when};
+use axum::{
+    Json,
+    extract::{Query, Request, State},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Window {
+    pub until: DateTime<Local>,
+    pub reason: Option<String>,
+}
+
+/// A time-boxed maintenance window during which printing endpoints are refused
+/// and scheduled jobs wait. It ends on its own once `until` passes.
+#[derive(Clone, Default)]
+pub struct Maintenance {
+    window: Arc<Mutex<Option<Window>>>,
+}
+
+impl Maintenance {
+    pub fn active(&self) -> Option<Window> {
+        let mut window = self.window.lock().unwrap();
+        if window.as_ref().is_some_and(|w| w.until <= Local::now()) {
+            eprintln!("Maintenance window ended");
+            *window = None;
+        }
+        window.clone()
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct MaintenanceParams {
+    /// RFC 3339 timestamp, or `HH:MM` for the next occurrence of that local time.
+    until: String,
+    reason: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance",
+    tag = "admin",
+    summary = "Start a maintenance window",
+    params(MaintenanceParams),
+    responses(
+        (status = 200, description = "OK", body = Window),
+        (status = 400, description = "`until` is invalid or in the past."),
+    )
+)]
+pub async fn start(
+    State(state): State<AppState>,
+    Query(params): Query<MaintenanceParams>,
+) -> Result<Json<Window>, StatusCode> {
+    let Some(until) = parse_when(&params.until).filter(|until| *until > Local::now()) else {
+        eprintln!("Invalid maintenance end time {:?}", params.until);
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let window = Window {
+        until,
+        reason: params.reason,
+    };
+    eprintln!("Maintenance until {}", until.format("%Y-%m-%d %H:%M"));
+    *state.maintenance.window.lock().unwrap() = Some(window.clone());
+    Ok(Json(window))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/maintenance",
+    tag = "admin",
+    summary = "End the maintenance window early",
+    responses((status = 204, description = "Ended."))
+)]
+pub async fn end(State(state): State<AppState>) -> StatusCode {
+    if state.maintenance.window.lock().unwrap().take().is_some() {
+        eprintln!("Maintenance ended early");
+    }
+    StatusCode::NO_CONTENT
+}
+
+#[derive(Serialize)]
+struct Rejection {
+    error: &'static str,
+    reason: Option<String>,
+    until: DateTime<Local>,
+}
+
+/// Answers printing endpoints with a 503 and the window's reason and ETA while in maintenance.
+pub async fn reject_during_maintenance(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(window) = state.maintenance.active() else {
+        return next.run(req).await;
+    };
+    eprintln!("Rejected request to {} during maintenance", req.uri().path());
+
+    let retry_after = (window.until - Local::now()).num_seconds().max(1);
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(Rejection {
+            error: "maintenance",
+            reason: window.reason,
+            until: window.until,
+        }),
+    )
+        .into_response();
+    if let Ok(value) = HeaderValue::from_str(&retry_after.to_string()) {
+        response.headers_mut().insert(RETRY_AFTER, value);
+    }
+    response
+}
@@ -0,0 +1,190 @@
+use crate::{
+    receipt::{Receipt, wrap},
+    table,
+};
+
+/// Drops inline emphasis and code markers, which the printer can't show.
+fn plain(text: &str) -> String {
+    text.replace("**", "").replace("__", "").replace('`', "")
+}
+
+/// Splits an ordered list marker like `12. ` off the start of a line.
+fn ordered_item(line: &str) -> Option<(&str, &str)> {
+    let (number, rest) = line.split_once(". ")?;
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then_some((number, rest))
+}
+
+/// Splits a task list checkbox like `[ ] ` or `[x] ` off a list item, as the box to print.
+fn task_item(item: &str) -> Option<(&'static str, &str)> {
+    let (mark, rest) = item.strip_prefix('[')?.split_once(']')?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    match mark {
+        " " => Some(("[ ] ", rest.trim_start())),
+        "x" | "X" => Some(("[X] ", rest.trim_start())),
+        _ => None,
+    }
+}
+
+/// The indentation to print a nested list item with: its leading spaces, with tabs
+/// counted as two, capped at half the paper.
+fn nesting(line: &str, width: usize) -> String {
+    let columns: usize = line.chars().take_while(|c| c.is_whitespace()).map(|c| if c == '\t' { 2 } else { 1 }).sum();
+    " ".repeat(columns.min(width / 2))
+}
+
+/// The cells of a GFM table row, with the outer pipes optional and `\\|` for a literal pipe.
+fn table_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').filter(|rest| !rest.ends_with('\\')).unwrap_or(line);
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => cells.last_mut().unwrap().push(chars.next().unwrap()),
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|cell| plain(cell.trim())).collect()
+}
+
+/// Whether `line` is the `|---|:--:|` row that turns the line above it into a table header.
+fn delimiter_row(line: &str) -> bool {
+    line.contains('-')
+        && line.contains(['|', ':'])
+        && table_cells(line).iter().all(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+        })
+}
+
+/// Draws a table sized to the paper, or lists each row as `header: value` lines when
+/// it has too many columns to fit.
+fn render_table(receipt: &mut Receipt, rows: &[Vec<String>]) {
+    if let Some(lines) = table::lines(rows, true, receipt.width()) {
+        for line in lines {
+            receipt.line_left(&line);
+        }
+        return;
+    }
+    let Some((header, body)) = rows.split_first() else {
+        return;
+    };
+    for (i, row) in body.iter().enumerate() {
+        if i > 0 {
+            receipt.line_left("");
+        }
+        for (name, value) in header.iter().zip(row) {
+            receipt.hanging(&format!("{}: ", name), value);
+        }
+    }
+}
+
+/// The unchecked task list items in `text`, or every list item and plain line when it
+/// has no checkboxes at all, e.g. a to-do list kept in a note.
+pub fn open_tasks(text: &str) -> Vec<String> {
+    let items: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            ["- ", "* ", "+ "]
+                .iter()
+                .find_map(|marker| line.strip_prefix(marker))
+                .or_else(|| ordered_item(line).map(|(_, item)| item))
+                .unwrap_or(line)
+        })
+        .collect();
+    if !items.iter().any(|item| task_item(item).is_some()) {
+        return items.iter().map(|item| plain(item)).collect();
+    }
+    items
+        .iter()
+        .filter_map(|item| task_item(item))
+        .filter(|(mark, _)| *mark == "[ ] ")
+        .map(|(_, task)| plain(task))
+        .collect()
+}
+
+fn flush_paragraph(receipt: &mut Receipt, paragraph: &mut Vec<&str>) {
+    if !paragraph.is_empty() {
+        receipt.wrapped(&plain(&paragraph.join(" ")));
+        paragraph.clear();
+    }
+}
+
+/// Renders the common subset of Markdown: headings, nested and task lists, tables, quotes, rules,
+/// code blocks and paragraphs, with soft-wrapped lines joined back together before wrapping
+/// to the paper.
+pub fn render(receipt: &mut Receipt, text: &str) {
+    let mut paragraph = Vec::new();
+    let mut in_code = false;
+
+    let mut lines = text.lines().peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            flush_paragraph(receipt, &mut paragraph);
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            for chunk in wrap(line, receipt.width()) {
+                receipt.line_left(&chunk);
+            }
+            continue;
+        }
+        if trimmed.is_empty() {
+            flush_paragraph(receipt, &mut paragraph);
+            receipt.line_left("");
+            continue;
+        }
+
+        let heading_level = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+            flush_paragraph(receipt, &mut paragraph);
+            let heading = plain(trimmed[heading_level..].trim()).to_uppercase();
+            if heading_level == 1 {
+                for chunk in wrap(&heading, receipt.width()) {
+                    receipt.line_center(&chunk);
+                }
+                receipt.divider();
+            } else {
+                receipt.wrapped(&heading);
+            }
+        } else if matches!(trimmed, "---" | "***" | "___") {
+            flush_paragraph(receipt, &mut paragraph);
+            receipt.divider();
+        } else if trimmed.contains('|') && lines.peek().is_some_and(|next| delimiter_row(next)) {
+            flush_paragraph(receipt, &mut paragraph);
+            lines.next();
+            let header = table_cells(trimmed);
+            let mut rows = vec![header.clone()];
+            while let Some(row) = lines.next_if(|next| next.contains('|') && !next.trim().is_empty()) {
+                // Rows are padded or cut to the header's columns, as GFM does.
+                let mut cells = table_cells(row);
+                cells.resize(header.len(), String::new());
+                rows.push(cells);
+            }
+            render_table(receipt, &rows);
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|marker| trimmed.strip_prefix(marker)) {
+            flush_paragraph(receipt, &mut paragraph);
+            let indent = nesting(line, receipt.width());
+            let (marker, item) = task_item(item).unwrap_or(("- ", item));
+            receipt.hanging(&format!("{}{}", indent, marker), &plain(item));
+        } else if let Some((number, item)) = ordered_item(trimmed) {
+            flush_paragraph(receipt, &mut paragraph);
+            let indent = nesting(line, receipt.width());
+            receipt.hanging(&format!("{}{}. ", indent, number), &plain(item));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            flush_paragraph(receipt, &mut paragraph);
+            receipt.hanging("| ", &plain(quote.trim()));
+        } else {
+            paragraph.push(trimmed);
+        }
+    }
+    flush_paragraph(receipt, &mut paragraph);
+}
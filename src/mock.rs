@@ -0,0 +1,164 @@
+//! An in-memory printer and helpers for driving the whole server from integration
+//! tests, e.g.
+//!
+//! ```ignore
+//! let (url, driver) = mock::spawn_server(&[("PRINTER_PROFILE", "generic-58mm")]).await;
+//! reqwest::Client::new().post(&url).body("hello").send().await?;
+//! assert!(driver.wait_for_jobs(1).await[0].contains("hello"));
+//! ```
+
+use crate::{AppState, config::Config, printer::PrinterDriver};
+use axum::Router;
+use escpos::{driver::Driver, errors::Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[derive(Default)]
+struct Recording {
+    pending: Vec<u8>,
+    jobs: Vec<Vec<u8>>,
+    dir: Option<PathBuf>,
+    /// Writes fail as if the cable had been pulled.
+    failing: bool,
+    /// What reads return, e.g. the answer to a `GS I` query.
+    reply: Vec<u8>,
+}
+
+/// A printer driver that keeps the exact bytes it's sent instead of talking to
+/// hardware. Each flush (one per printed receipt) closes off a job.
+#[derive(Clone, Default)]
+pub struct MockDriver {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl MockDriver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also writes every job to `dir/job-<n>.bin`.
+    pub fn recording_to(dir: PathBuf) -> Self {
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Failed to create {}: {}", dir.display(), e);
+        }
+        MockDriver {
+            recording: Arc::new(Mutex::new(Recording {
+                dir: Some(dir),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// A printer that answers reads with `reply`, once.
+    pub fn answering(reply: &[u8]) -> Self {
+        MockDriver {
+            recording: Arc::new(Mutex::new(Recording {
+                reply: reply.to_vec(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Makes every write fail with an I/O error from now on, or work again.
+    pub fn set_failing(&self, failing: bool) {
+        self.recording.lock().unwrap().failing = failing;
+    }
+
+    /// The byte stream of every job flushed so far, oldest first.
+    pub fn jobs(&self) -> Vec<Vec<u8>> {
+        self.recording.lock().unwrap().jobs.clone()
+    }
+
+    /// Waits up to two seconds for the print worker to flush `count` jobs, then returns
+    /// them all as (lossy) text. Panics if they don't arrive.
+    pub async fn wait_for_jobs(&self, count: usize) -> Vec<String> {
+        for _ in 0..100 {
+            let jobs = self.jobs();
+            if jobs.len() >= count {
+                return jobs.iter().map(|job| String::from_utf8_lossy(job).into_owned()).collect();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("expected {} jobs, got {}", count, self.jobs().len());
+    }
+}
+
+/// Serves `app` on a free local port, e.g. a stub upstream API, and returns its base URL.
+pub async fn serve(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+/// Starts the server, with its background tasks, on a mock printer and with `vars` as
+/// its environment. Returns its base URL and the printer.
+pub async fn spawn_server(vars: &[(&str, &str)]) -> (String, MockDriver) {
+    let (url, driver, _) = spawn_server_with_printers(vars, &[]).await;
+    (url, driver)
+}
+
+/// Like [`spawn_server`], with a mock printer for each of `names` as well, as if they
+/// were in `PRINTERS`. Returns them in the same order.
+pub async fn spawn_server_with_printers(vars: &[(&str, &str)], names: &[&str]) -> (String, MockDriver, Vec<MockDriver>) {
+    let driver = MockDriver::new();
+    let (url, named) = spawn_server_on(driver.clone(), vars, names).await;
+    (url, driver, named)
+}
+
+/// Like [`spawn_server_with_printers`] with `driver` as the main printer, e.g. one made
+/// with [`MockDriver::answering`]. Returns the base URL and the named printers.
+pub async fn spawn_server_on(driver: MockDriver, vars: &[(&str, &str)], names: &[&str]) -> (String, Vec<MockDriver>) {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    let config = Config::from_vars(|name| vars.get(name).cloned());
+    let named: Vec<MockDriver> = names.iter().map(|_| MockDriver::new()).collect();
+    let state = AppState::with_printers(
+        config,
+        Some(PrinterDriver::Mock(driver.clone())),
+        names.iter().zip(&named).map(|(name, driver)| (name.to_string(), PrinterDriver::Mock(driver.clone()))).collect(),
+    );
+    crate::spawn_background(&state);
+    (serve(crate::router(state)).await, named)
+}
+
+impl Driver for MockDriver {
+    fn name(&self) -> String {
+        "mock".to_owned()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<()> {
+        let mut recording = self.recording.lock().unwrap();
+        if recording.failing {
+            return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "mock printer unplugged").into());
+        }
+        recording.pending.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut recording = self.recording.lock().unwrap();
+        let n = buf.len().min(recording.reply.len());
+        buf[..n].copy_from_slice(&recording.reply[..n]);
+        recording.reply.drain(..n);
+        Ok(n)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let mut recording = self.recording.lock().unwrap();
+        let job = std::mem::take(&mut recording.pending);
+        if let Some(dir) = &recording.dir {
+            let path = dir.join(format!("job-{}.bin", recording.jobs.len() + 1));
+            match fs::write(&path, &job) {
+                Ok(()) => eprintln!("Recorded {} bytes to {}", job.len(), path.display()),
+                Err(e) => eprintln!("Failed to write {}: {}", path.display(), e),
+            }
+        }
+        recording.jobs.push(job);
+        Ok(())
+    }
+}
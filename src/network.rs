@@ -0,0 +1,56 @@
+//! Network printers, which take raw ESC/POS on TCP port 9100 (often called "JetDirect"
+//! or "RAW" printing).
+
+use escpos::{driver::Driver, errors::Result};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The port network receipt printers listen on when none is given.
+pub const DEFAULT_PORT: u16 = 9100;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// A printer that stops reading, e.g. with its cover open, fails the job instead of
+/// holding up the queue.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a query, e.g. for the model name, waits for an answer.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An open TCP connection to a printer.
+#[derive(Clone)]
+pub struct NetworkDriver {
+    address: SocketAddr,
+    stream: Arc<Mutex<TcpStream>>,
+}
+
+impl NetworkDriver {
+    pub fn open(address: SocketAddr) -> io::Result<Self> {
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        Ok(NetworkDriver {
+            address,
+            stream: Arc::new(Mutex::new(stream)),
+        })
+    }
+}
+
+impl Driver for NetworkDriver {
+    fn name(&self) -> String {
+        format!("Network ({})", self.address)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<()> {
+        Ok(self.stream.lock().unwrap().write_all(data)?)
+    }
+
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.stream.lock().unwrap().read(buf)?)
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(self.stream.lock().unwrap().flush()?)
+    }
+}
@@ -0,0 +1,161 @@
+//! Sticky notes: short notes stashed through the day with `POST /notes`, printed together
+//! as one digest by `POST /notes/print` or the `notes` scheduled job.
+
+use crate::{AppState, receipt::Receipt, source::Source, storage};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{DateTime, Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// How long printed notes are kept before they're forgotten.
+const KEEP_PRINTED_DAYS: i64 = 7;
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct Note {
+    pub id: u64,
+    pub namespace: String,
+    pub text: String,
+    #[schema(format = DateTime)]
+    pub at: DateTime<Local>,
+    /// When the note went out in a digest; null until then.
+    #[schema(format = DateTime)]
+    pub printed_at: Option<DateTime<Local>>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NoteRequest {
+    text: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PrintedNotes {
+    printed: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Store {
+    next_id: u64,
+    notes: Vec<Note>,
+}
+
+/// Notes for all namespaces, saved to `notes.json` in the data directory after every change.
+#[derive(Clone)]
+pub struct Notes {
+    inner: Arc<Mutex<Store>>,
+    path: PathBuf,
+}
+
+impl Notes {
+    /// Loads saved notes, starting empty if the file is missing or unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        Notes {
+            inner: Arc::new(Mutex::new(storage::load_json(&path))),
+            path,
+        }
+    }
+
+    fn save_or_log(&self, store: &Store) -> Result<(), StatusCode> {
+        storage::save_json(&self.path, store).map_err(|e| {
+            eprintln!("Failed to save notes to {}: {}", self.path.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+
+    /// Marks `namespace`'s unprinted notes as printed at `now` and returns them, oldest
+    /// first, forgetting notes printed more than [`KEEP_PRINTED_DAYS`] ago.
+    fn take_unprinted(&self, namespace: &str, now: DateTime<Local>) -> Vec<Note> {
+        let mut store = self.inner.lock().unwrap();
+        let cutoff = now - TimeDelta::days(KEEP_PRINTED_DAYS);
+        store.notes.retain(|note| note.printed_at.is_none_or(|printed| printed > cutoff));
+        let mut taken = Vec::new();
+        for note in store.notes.iter_mut().filter(|n| n.namespace == namespace && n.printed_at.is_none()) {
+            note.printed_at = Some(now);
+            taken.push(note.clone());
+        }
+        let _ = self.save_or_log(&store);
+        taken.sort_by_key(|note| note.at);
+        taken
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/notes",
+    tag = "notes",
+    summary = "Stash a note for the next digest",
+    responses(
+        (status = 201, description = "Created.", body = Note),
+        (status = 400, description = "The note is empty."),
+    )
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    source: Source,
+    Json(request): Json<NoteRequest>,
+) -> Result<(StatusCode, Json<Note>), StatusCode> {
+    let text = request.text.trim();
+    if text.is_empty() {
+        eprintln!("Empty note");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mut store = state.notes.inner.lock().unwrap();
+    store.next_id += 1;
+    let note = Note {
+        id: store.next_id,
+        namespace: source.namespace().to_owned(),
+        text: text.to_owned(),
+        at: Local::now(),
+        printed_at: None,
+    };
+    eprintln!("Note #{} stashed", note.id);
+    store.notes.push(note.clone());
+    state.notes.save_or_log(&store)?;
+    Ok((StatusCode::CREATED, Json(note)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/notes/print",
+    tag = "notes",
+    summary = "Print unprinted notes as one digest",
+    description = "Prints the caller's notes that haven't been printed yet, with the time each was stashed, and \
+        marks them printed. Nothing prints when there are none. Also available as the `notes` scheduled job.",
+    responses((status = 200, description = "Printed, or queued to print.", body = PrintedNotes))
+)]
+pub async fn print(State(state): State<AppState>, source: Source) -> Json<PrintedNotes> {
+    Json(PrintedNotes {
+        printed: print_digest(&state, &source),
+    })
+}
+
+/// Prints the caller's unprinted notes as one receipt and returns how many there were;
+/// nothing prints when there are none.
+pub fn print_digest(state: &AppState, source: &Source) -> usize {
+    let now = Local::now();
+    let notes = state.notes.take_unprinted(source.namespace(), now);
+    if notes.is_empty() {
+        eprintln!("No notes to print");
+        return 0;
+    }
+    eprintln!("Printing {} note(s)", notes.len());
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("NOTES");
+    receipt.line_center(&now.format_localized("%A, %-d %B", state.config.locale).to_string());
+    receipt.divider();
+    for note in &notes {
+        // Notes left over from earlier days say which day.
+        let stamp = if note.at.date_naive() == now.date_naive() {
+            note.at.format("%H:%M ").to_string()
+        } else {
+            note.at.format_localized("%a %H:%M ", state.config.locale).to_string()
+        };
+        receipt.hanging(&stamp, &note.text);
+    }
+    state.print(receipt, "notes", source);
+    notes.len()
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+/// Where to push alerts about the printer, so failures don't go unnoticed in the logs.
+#[derive(Clone)]
+pub enum Notifier {
+    /// An ntfy topic URL, e.g. `https://ntfy.sh/my-printer`.
+    Ntfy { url: String },
+    Pushover { token: String, user: String },
+}
+
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+
+/// Sends one alert; failures are only logged, there's nowhere else to report them.
+pub async fn send(notifier: Notifier, title: String, message: String) {
+    eprintln!("Alert: {}: {}", title, message);
+    let client = reqwest::Client::new();
+    let request = match &notifier {
+        Notifier::Ntfy { url } => client.post(url).header("Title", &title).body(message),
+        Notifier::Pushover { token, user } => client.post(PUSHOVER_URL).form(&[
+            ("token", token.as_str()),
+            ("user", user.as_str()),
+            ("title", &title),
+            ("message", &message),
+        ]),
+    };
+    let result = request
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(e) = result {
+        eprintln!("Failed to send alert {:?}: {}", title, e);
+    }
+}
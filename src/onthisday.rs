@@ -0,0 +1,112 @@
+use crate::{AppState, error::JobError, receipt::Receipt, source::Source};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::Local;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+pub struct OnThisDayParams {
+    /// Events and births to print, 3 to 5 of each.
+    #[serde(default = "default_count")]
+    #[param(default = default_count, minimum = 3, maximum = 5)]
+    count: usize,
+}
+
+fn default_count() -> usize {
+    4
+}
+
+#[derive(Deserialize)]
+struct Feed {
+    #[serde(default)]
+    selected: Vec<Entry>,
+    #[serde(default)]
+    events: Vec<Entry>,
+    #[serde(default)]
+    births: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    text: String,
+    year: Option<i32>,
+}
+
+impl Entry {
+    fn line(&self) -> String {
+        match self.year {
+            Some(year) => format!("{}: {}", year, self.text),
+            None => self.text.clone(),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/onthisday",
+    tag = "printing",
+    summary = "Print events and births on this day",
+    params(OnThisDayParams),
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn onthisday(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<OnThisDayParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_onthisday(&state, &source, params.count.clamp(3, 5)).await?)
+}
+
+pub async fn print_onthisday(state: &AppState, source: &Source, count: usize) -> Result<(), JobError> {
+    let today = Local::now().date_naive();
+    eprintln!("On this day request for {}", today.format("%m-%d"));
+
+    let url = format!("https://api.wikimedia.org/feed/v1/wikipedia/en/onthisday/all/{}", today.format("%m/%d"));
+    let client = reqwest::Client::new();
+    // Wikimedia rejects requests without a descriptive User-Agent.
+    let feed = client
+        .get(&url)
+        .header(reqwest::header::USER_AGENT, concat!("print-jobber/", env!("CARGO_PKG_VERSION")))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.to_string()))?
+        .json::<Feed>()
+        .await
+        .map_err(|e| JobError::Upstream(e.to_string()))
+        .inspect_err(|e| {
+            eprintln!("Failed to fetch on this day feed: {}", e);
+            state.diagnostics.record_error("onthisday", e.to_string());
+        })?;
+
+    // The editors' picks read best; fall back to the full event list.
+    let events = if feed.selected.is_empty() { &feed.events } else { &feed.selected };
+    if events.is_empty() && feed.births.is_empty() {
+        return Err(JobError::Upstream("empty on this day feed".to_owned()));
+    }
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("ON THIS DAY");
+    receipt.line_center(&today.format_localized("%-d %B", state.config.locale).to_string());
+    receipt.divider();
+
+    for event in events.iter().take(count) {
+        receipt.wrapped(&event.line());
+        receipt.divider();
+    }
+
+    if !feed.births.is_empty() {
+        receipt.line_center("BORN TODAY");
+        receipt.divider();
+        for birth in feed.births.iter().take(count) {
+            receipt.wrapped(&birth.line());
+            receipt.divider();
+        }
+    }
+
+    state.print(receipt, "onthisday", source);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! The OpenAPI description of the REST API and a Swagger UI page for browsing it. The
+//! spec is generated from the `#[utoipa::path]` annotations on the handlers as
+//! [`crate::router`] registers them, so each operation's parameters and bodies come
+//! from its actual `Query`, `Path` and `Json` extractors. Swagger UI is built into the
+//! binary, so `/docs` works on a LAN without internet access.
+
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        self, ResponseBuilder,
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "Prints receipts on a thermal printer. When `API_KEYS` is set, every endpoint needs an \
+        `Authorization: Bearer <token>` or `X-Api-Key` header. Every printing endpoint also takes \
+        `?printer=<name>` to pick a printer (`default` for the main printer, or a name from `PRINTERS`), \
+        `?mirror=<name>,...` to print on several at once, and `?tag=<label>` for `PRINTER_ROUTES` rules to \
+        match. Without `printer` or `mirror`, the first matching `PRINTER_ROUTES` rule (by job name, API key \
+        or tag) picks the printer, else `MIRROR_PRINTERS`, else the main printer. During `QUIET_HOURS` jobs \
+        are held as `scheduled` until the quiet hours end; `?override_quiet=true` prints one now anyway."),
+    security(("bearer" = []), ("apiKey" = [])),
+    modifiers(&Security, &NoLicense)
+)]
+pub struct ApiDoc;
+
+/// The two ways of passing an `API_KEYS` token.
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, api: &mut openapi::OpenApi) {
+        let components = api.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+        components.add_security_scheme("apiKey", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))));
+    }
+}
+
+/// The package has no `license`, which utoipa would otherwise list as one with an empty name.
+struct NoLicense;
+
+impl Modify for NoLicense {
+    fn modify(&self, api: &mut openapi::OpenApi) {
+        api.info.license = None;
+    }
+}
+
+/// Adds the answers the printing routes' layers give: 429 while the queue is full and
+/// 503 during maintenance. An operation's own 503 is kept, since it says more.
+pub fn add_printing_responses(api: &mut openapi::OpenApi) {
+    add_response(
+        api,
+        "429",
+        "`MAX_QUEUED_JOBS` jobs are already waiting; `X-Queue-Depth` and `X-Queue-Limit` say how many, \
+         `Retry-After` when to try again.",
+    );
+    add_response(api, "503", "The printer is down for maintenance; see `Retry-After`.");
+}
+
+/// Adds the 401 that every route behind the API key check can answer with.
+pub fn add_key_responses(api: &mut openapi::OpenApi) {
+    add_response(api, "401", "`API_KEYS` is set and the request has no valid key.");
+}
+
+fn add_response(api: &mut openapi::OpenApi, status: &str, description: &str) {
+    for item in api.paths.paths.values_mut() {
+        let operations = [&mut item.get, &mut item.put, &mut item.post, &mut item.delete];
+        for operation in operations.into_iter().flatten() {
+            let response = ResponseBuilder::new().description(description).build();
+            operation.responses.responses.entry(status.to_owned()).or_insert(response.into());
+        }
+    }
+}
+
+/// `/openapi.json`, and Swagger UI at `/docs`.
+pub fn docs(api: openapi::OpenApi) -> SwaggerUi {
+    SwaggerUi::new("/docs").url("/openapi.json", api)
+}
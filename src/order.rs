@@ -0,0 +1,95 @@
+use crate::{
+    AppState,
+    receipt::{Receipt, wrap},
+    source::Source,
+    ticket::NUMBER_SCALE,
+};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct OrderItem {
+    name: String,
+    #[serde(default = "default_quantity")]
+    #[schema(default = 1)]
+    quantity: u32,
+    /// "no onions", "extra hot" and the like, printed indented under the item.
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+fn default_quantity() -> u32 {
+    1
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct OrderRequest {
+    /// Order number from a till; without one the next number from the order counter is used.
+    number: Option<u64>,
+    table: Option<String>,
+    customer: Option<String>,
+    items: Vec<OrderItem>,
+    notes: Option<String>,
+    /// Sound the printer's buzzer so the kitchen notices the ticket.
+    #[serde(default)]
+    beep: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Order {
+    number: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/order",
+    tag = "printing",
+    summary = "Print a kitchen order ticket",
+    responses(
+        (status = 200, description = "Printed, or queued to print.", body = Order),
+        (status = 422, description = "No items."),
+    )
+)]
+pub async fn order(State(state): State<AppState>, source: Source, Json(request): Json<OrderRequest>) -> Result<Json<Order>, StatusCode> {
+    if request.items.is_empty() {
+        eprintln!("Order request without items");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let number = match request.number {
+        Some(number) => number,
+        None => state.orders.next(source.namespace())?,
+    };
+    eprintln!("Kitchen order #{} with {} item(s)", number, request.items.len());
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_large(&format!("#{}", number), NUMBER_SCALE);
+    if let Some(table) = &request.table {
+        receipt.line_large(&format!("TABLE {}", table), 2);
+    }
+    if let Some(customer) = &request.customer {
+        receipt.line_center(customer);
+    }
+    receipt.line_center(&Local::now().format("%H:%M:%S").to_string());
+    receipt.divider();
+
+    for item in &request.items {
+        for line in wrap(&format!("{} x {}", item.quantity, item.name), receipt.width()) {
+            receipt.line_tall(&line);
+        }
+        for modifier in &item.modifiers {
+            receipt.hanging("    - ", modifier);
+        }
+    }
+    if let Some(notes) = &request.notes {
+        receipt.divider();
+        receipt.wrapped(notes);
+    }
+    if request.beep {
+        receipt.beep(3);
+    }
+
+    state.print(receipt, "order", &source);
+    Ok(Json(Order { number }))
+}
@@ -0,0 +1,379 @@
+//! Package tracking: `/packages` looks up every `TRACKING_NUMBERS` entry with the
+//! `TRACKING_PROVIDER` API and prints the shipments whose status changed since the
+//! last check. What was last seen is saved to `packages.json` in the data directory.
+
+use crate::{
+    AppState,
+    config::{TrackedPackage, TrackingProvider},
+    error::JobError,
+    receipt::Receipt,
+    source::Source,
+    storage,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+const AFTERSHIP_BASE: &str = "https://api.aftership.com";
+const SEVENTEEN_TRACK_BASE: &str = "https://api.17track.net";
+
+#[derive(Deserialize, IntoParams)]
+pub struct PackagesParams {
+    /// Print every shipment, changed or not.
+    #[serde(default)]
+    #[param(default = false)]
+    all: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CheckedPackages {
+    checked: usize,
+    changed: usize,
+}
+
+/// A shipment as either provider reports it.
+struct Shipment {
+    carrier: String,
+    /// E.g. "In transit", from the provider's `InTransit`.
+    status: String,
+    /// The latest checkpoint's description.
+    message: Option<String>,
+    location: Option<String>,
+    eta: Option<String>,
+}
+
+impl Shipment {
+    /// What has to differ for the shipment to count as changed.
+    fn signature(&self) -> String {
+        format!("{}|{}|{}", self.status, self.message.as_deref().unwrap_or(""), self.location.as_deref().unwrap_or(""))
+    }
+}
+
+#[derive(Deserialize)]
+struct AfterShipResponse {
+    data: AfterShipData,
+}
+
+#[derive(Deserialize)]
+struct AfterShipData {
+    tracking: AfterShipTracking,
+}
+
+#[derive(Deserialize)]
+struct AfterShipTracking {
+    slug: String,
+    tag: String,
+    expected_delivery: Option<String>,
+    #[serde(default)]
+    checkpoints: Vec<AfterShipCheckpoint>,
+}
+
+#[derive(Deserialize)]
+struct AfterShipCheckpoint {
+    message: Option<String>,
+    location: Option<String>,
+    city: Option<String>,
+    country_name: Option<String>,
+}
+
+impl AfterShipCheckpoint {
+    fn location(&self) -> Option<String> {
+        self.location.clone().filter(|l| !l.is_empty()).or_else(|| {
+            let parts: Vec<&str> = [&self.city, &self.country_name].into_iter().flatten().map(String::as_str).collect();
+            (!parts.is_empty()).then(|| parts.join(", "))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackResponse {
+    data: SeventeenTrackData,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackData {
+    #[serde(default)]
+    accepted: Vec<SeventeenTrackAccepted>,
+    #[serde(default)]
+    rejected: Vec<SeventeenTrackRejected>,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackAccepted {
+    carrier: Option<u32>,
+    track_info: SeventeenTrackInfo,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackRejected {
+    error: SeventeenTrackError,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackInfo {
+    latest_status: SeventeenTrackStatus,
+    latest_event: Option<SeventeenTrackEvent>,
+    time_metrics: Option<SeventeenTrackMetrics>,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackStatus {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackEvent {
+    description: Option<String>,
+    location: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackMetrics {
+    estimated_delivery_date: Option<SeventeenTrackEta>,
+}
+
+#[derive(Deserialize)]
+struct SeventeenTrackEta {
+    to: Option<String>,
+}
+
+/// The last status signature seen per tracking number, per namespace.
+type Seen = HashMap<String, HashMap<String, String>>;
+
+/// Shipment statuses as of the last check, saved after every check.
+#[derive(Clone)]
+pub struct PackageStatuses {
+    inner: Arc<Mutex<Seen>>,
+    path: PathBuf,
+}
+
+impl PackageStatuses {
+    /// Loads saved statuses, starting empty if the file is missing or unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        PackageStatuses {
+            inner: Arc::new(Mutex::new(storage::load_json(&path))),
+            path,
+        }
+    }
+
+    /// Records `signature` for `number` and returns whether it differs from the last one.
+    fn update(&self, namespace: &str, number: &str, signature: String) -> bool {
+        let mut seen = self.inner.lock().unwrap();
+        let previous = seen.entry(namespace.to_owned()).or_default().insert(number.to_owned(), signature.clone());
+        if let Err(e) = storage::save_json(&self.path, &*seen) {
+            eprintln!("Failed to save package statuses to {}: {}", self.path.display(), e);
+        }
+        previous.as_ref() != Some(&signature)
+    }
+}
+
+/// "InTransit" -> "In transit".
+fn humanize(status: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in status.chars().enumerate() {
+        if i > 0 && c.is_uppercase() {
+            out.push(' ');
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The API base for `provider`, or `TRACKING_URL` when set.
+fn api_base<'a>(state: &'a AppState, provider: &TrackingProvider) -> &'a str {
+    let default = match provider {
+        TrackingProvider::AfterShip => AFTERSHIP_BASE,
+        TrackingProvider::SeventeenTrack => SEVENTEEN_TRACK_BASE,
+    };
+    state.config.tracking_url.as_deref().unwrap_or(default).trim_end_matches('/')
+}
+
+/// Formats a request error without its URL.
+fn describe(e: reqwest::Error) -> String {
+    e.without_url().to_string()
+}
+
+async fn fetch_shipment(state: &AppState, api_key: &str, package: &TrackedPackage) -> Result<Shipment, String> {
+    let provider = &state.config.tracking_provider;
+    let client = reqwest::Client::new();
+    match provider {
+        TrackingProvider::AfterShip => {
+            let Some(carrier) = &package.carrier else {
+                return Err("AfterShip needs a carrier, e.g. number:ups".to_owned());
+            };
+            let tracking = client
+                .get(format!("{}/v4/trackings/{}/{}", api_base(state, provider), carrier, package.number))
+                .header("as-api-key", api_key)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(describe)?
+                .json::<AfterShipResponse>()
+                .await
+                .map_err(describe)?
+                .data
+                .tracking;
+            let latest = tracking.checkpoints.last();
+            Ok(Shipment {
+                carrier: tracking.slug,
+                status: humanize(&tracking.tag),
+                message: latest.and_then(|c| c.message.clone()),
+                location: latest.and_then(AfterShipCheckpoint::location),
+                eta: tracking.expected_delivery,
+            })
+        }
+        TrackingProvider::SeventeenTrack => {
+            let mut body = serde_json::json!([{ "number": package.number }]);
+            if let Some(carrier) = package.carrier.as_ref().and_then(|c| c.parse::<u32>().ok()) {
+                body[0]["carrier"] = carrier.into();
+            }
+            let mut data = client
+                .post(format!("{}/track/v2.2/gettrackinfo", api_base(state, provider)))
+                .header("17token", api_key)
+                .json(&body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(describe)?
+                .json::<SeventeenTrackResponse>()
+                .await
+                .map_err(describe)?
+                .data;
+            let Some(accepted) = data.accepted.pop() else {
+                return Err(data.rejected.pop().map_or("not tracked; register it with 17track first".to_owned(), |r| r.error.message));
+            };
+            let info = accepted.track_info;
+            Ok(Shipment {
+                carrier: accepted.carrier.map_or("unknown".to_owned(), |c| c.to_string()),
+                status: humanize(&info.latest_status.status),
+                message: info.latest_event.as_ref().and_then(|e| e.description.clone()),
+                location: info.latest_event.and_then(|e| e.location).filter(|l| !l.is_empty()),
+                eta: info.time_metrics.and_then(|m| m.estimated_delivery_date).and_then(|eta| eta.to),
+            })
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/packages",
+    tag = "printing",
+    summary = "Print package tracking updates",
+    description = "Looks up every `TRACKING_NUMBERS` entry (`[label=]number[:carrier]`) with AfterShip, or 17track with \
+        `TRACKING_PROVIDER=17track`, and prints the shipments whose status, latest checkpoint or location \
+        changed since the last check: carrier, status, location, ETA and the latest message. Nothing prints \
+        when none changed. Lookups that fail are skipped. Needs `TRACKING_API_KEY`; AfterShip also needs each \
+        entry's carrier slug. Also available as the `packages` scheduled job, e.g. hourly.",
+    params(PackagesParams),
+    responses(
+        (status = 200, description = "Checked; `changed` shipments were printed.", body = CheckedPackages),
+        (status = 502, description = "Every lookup failed."),
+        (status = 503, description = "`TRACKING_API_KEY` or `TRACKING_NUMBERS` isn't set, or the printer is down for \
+            maintenance; see `Retry-After`."),
+    )
+)]
+pub async fn packages(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<PackagesParams>,
+) -> Result<Json<CheckedPackages>, StatusCode> {
+    Ok(Json(check_packages(&state, &source, params.all).await?))
+}
+
+/// Looks every package up and prints the changed ones (or, with `all`, every one) on one
+/// receipt. Nothing prints when none changed; lookups that fail are skipped, and the
+/// check only fails if all of them did.
+pub async fn check_packages(state: &AppState, source: &Source, all: bool) -> Result<CheckedPackages, JobError> {
+    let Some(api_key) = &state.config.tracking_api_key else {
+        return Err(JobError::NotConfigured("TRACKING_API_KEY"));
+    };
+    let packages = &state.config.tracking_numbers;
+    if packages.is_empty() {
+        return Err(JobError::NotConfigured("TRACKING_NUMBERS"));
+    }
+    eprintln!("Packages request for {} shipment(s)", packages.len());
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, package) in packages.iter().enumerate() {
+        let (state, api_key, package) = (state.clone(), api_key.clone(), package.clone());
+        tasks.spawn(async move { (i, fetch_shipment(&state, &api_key, &package).await) });
+    }
+    let mut results: Vec<Option<Result<Shipment, String>>> = packages.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((i, result)) => results[i] = Some(result),
+            // Skipped like a failed lookup; the other shipments are still checked.
+            Err(e) => eprintln!("Tracking task failed: {}", e),
+        }
+    }
+
+    let mut changed = Vec::new();
+    let mut fetched = 0;
+    let mut last_error = String::from("no response");
+    for (package, result) in packages.iter().zip(results) {
+        match result {
+            Some(Ok(shipment)) => {
+                fetched += 1;
+                if state.package_statuses.update(source.namespace(), &package.number, shipment.signature()) || all {
+                    changed.push((package, shipment));
+                }
+            }
+            Some(Err(e)) => {
+                eprintln!("Failed to track {}: {}", package.number, e);
+                state.diagnostics.record_error("packages", e.clone());
+                last_error = e;
+            }
+            None => {}
+        }
+    }
+    if fetched == 0 {
+        return Err(JobError::Upstream(last_error));
+    }
+    let checked = packages.len();
+    if changed.is_empty() {
+        eprintln!("No package status changes");
+        return Ok(CheckedPackages { checked, changed: 0 });
+    }
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("PACKAGES");
+    receipt.line_center(&Local::now().format_localized("%A, %-d %B %H:%M", state.config.locale).to_string());
+    receipt.divider();
+    for (package, shipment) in &changed {
+        receipt.line_left(package.label.as_deref().unwrap_or(&package.number));
+        if package.label.is_some() {
+            receipt.line_left(&format!("  {}", package.number));
+        }
+        receipt.line_left(&format!("  Carrier: {}", shipment.carrier));
+        receipt.line_left(&format!("  Status: {}", shipment.status));
+        if let Some(location) = &shipment.location {
+            receipt.hanging("  Location: ", location);
+        }
+        if let Some(eta) = &shipment.eta {
+            receipt.line_left(&format!("  ETA: {}", eta.split('T').next().unwrap_or(eta)));
+        }
+        if let Some(message) = &shipment.message {
+            receipt.hanging("  ", message);
+        }
+        receipt.divider();
+    }
+    state.print(receipt, "packages", source);
+    Ok(CheckedPackages { checked, changed: changed.len() })
+}
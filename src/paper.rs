@@ -0,0 +1,144 @@
+use crate::{AppState, queue::QueueStatus, storage};
+use axum::{Json, extract::State};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Paper printed since the roll was last changed, saved as JSON in the data directory.
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Usage {
+    pub used_mm: f64,
+    jobs: u64,
+    /// RFC 3339 time of the last `/printer/roll-reset`.
+    since: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PaperStatus {
+    pub used_mm: f64,
+    jobs: u64,
+    /// When the roll was last changed.
+    #[schema(format = DateTime)]
+    since: Option<String>,
+    /// Only known when `PAPER_ROLL_METERS` is set.
+    roll_length_mm: Option<f64>,
+    pub remaining_mm: Option<f64>,
+    remaining_percent: Option<f64>,
+    /// At or below `PAPER_LOW_PERCENT` of the roll left.
+    pub low: bool,
+}
+
+impl PaperStatus {
+    pub fn summary(&self) -> String {
+        format!(
+            "about {}mm ({}%) of the roll left",
+            self.remaining_mm.unwrap_or_default(),
+            self.remaining_percent.unwrap_or_default()
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct PaperTracker {
+    inner: Arc<Mutex<Usage>>,
+    path: PathBuf,
+    roll_length_mm: Option<f64>,
+    low_percent: f64,
+}
+
+impl PaperTracker {
+    pub fn load(path: PathBuf, roll_length_mm: Option<f64>, low_percent: f64) -> Self {
+        PaperTracker {
+            inner: Arc::new(Mutex::new(storage::load_json(&path))),
+            path,
+            roll_length_mm,
+            low_percent,
+        }
+    }
+
+    fn status_of(&self, usage: &Usage) -> PaperStatus {
+        let remaining_mm = self.roll_length_mm.map(|roll| (roll - usage.used_mm).max(0.0));
+        let remaining_percent = remaining_mm.zip(self.roll_length_mm).map(|(remaining, roll)| remaining / roll * 100.0);
+        PaperStatus {
+            used_mm: usage.used_mm.round(),
+            jobs: usage.jobs,
+            since: usage.since.clone(),
+            roll_length_mm: self.roll_length_mm,
+            remaining_mm: remaining_mm.map(f64::round),
+            remaining_percent: remaining_percent.map(|percent| (percent * 10.0).round() / 10.0),
+            low: remaining_percent.is_some_and(|percent| percent <= self.low_percent),
+        }
+    }
+
+    pub fn status(&self) -> PaperStatus {
+        self.status_of(&self.inner.lock().unwrap())
+    }
+
+    fn save(&self, usage: &Usage) {
+        if let Err(e) = storage::save_json(&self.path, usage) {
+            eprintln!("Failed to save paper usage to {}: {}", self.path.display(), e);
+        }
+    }
+
+    /// Adds a printed job's paper. Returns the status when that took the roll below the
+    /// low mark, so the caller can raise the alarm once.
+    pub fn record(&self, mm: f64) -> Option<PaperStatus> {
+        let mut usage = self.inner.lock().unwrap();
+        let was_low = self.status_of(&usage).low;
+        usage.used_mm += mm;
+        usage.jobs += 1;
+        let status = self.status_of(&usage);
+        if status.low && !was_low {
+            eprintln!("Paper roll running low: {}", status.summary());
+        }
+        self.save(&usage);
+        (status.low && !was_low).then_some(status)
+    }
+
+    fn reset(&self) -> PaperStatus {
+        let mut usage = self.inner.lock().unwrap();
+        *usage = Usage {
+            since: Some(Local::now().to_rfc3339()),
+            ..Default::default()
+        };
+        self.save(&usage);
+        self.status_of(&usage)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PrinterStatus {
+    queue: QueueStatus,
+    paper: PaperStatus,
+}
+
+#[utoipa::path(
+    get,
+    path = "/printer/status",
+    tag = "status",
+    summary = "Queue and paper status",
+    responses((status = 200, description = "OK", body = PrinterStatus))
+)]
+pub async fn status(State(state): State<AppState>) -> Json<PrinterStatus> {
+    Json(PrinterStatus {
+        queue: state.queue.status(),
+        paper: state.paper.status(),
+    })
+}
+
+/// Starts counting from zero after a new roll goes in.
+#[utoipa::path(
+    post,
+    path = "/printer/roll-reset",
+    tag = "admin",
+    summary = "Record a fresh paper roll",
+    responses((status = 200, description = "OK", body = PaperStatus))
+)]
+pub async fn roll_reset(State(state): State<AppState>) -> Json<PaperStatus> {
+    eprintln!("Paper roll replaced, usage reset");
+    Json(state.paper.reset())
+}
@@ -0,0 +1,213 @@
+//! Lua plugins: each `PLUGINS_DIR/<name>.lua` script is served as `/plugins/<name>` and
+//! can be used as a briefing section (`plugin:name=<name>`).
+//!
+//! A script defines `render(params)`, which gets the request's query parameters (and
+//! `body`, when there is one) and returns a list of blocks, e.g.
+//! `{ {type = "center", text = "HELLO"}, {type = "divider"}, {type = "text", text = "..."} }`.
+//! Block types are `text` (wrapped), `center`, `large` (with `scale`), `divider`, `blank`
+//! and `qr` (with `data`); `text` and `center` also take a character `size` like `"2x1"`.
+//! Scripts run without `io`, `os` or `require`, within a memory and time limit; the host
+//! provides `fetch(url)`, limited to `PLUGIN_HOSTS`, and `json(text)` to decode a response.
+
+use crate::{
+    AppState,
+    error::JobError,
+    receipt::{CharSize, Receipt},
+    source::Source,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use mlua::{HookTriggers, Lua, LuaOptions, LuaSerdeExt, StdLib, VmState};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+/// Fetches one run of a script may make.
+const MAX_FETCHES: usize = 10;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Block {
+    Text {
+        text: String,
+        #[serde(default)]
+        size: CharSize,
+    },
+    Center {
+        text: String,
+        #[serde(default)]
+        size: CharSize,
+    },
+    Large {
+        text: String,
+        #[serde(default = "default_scale")]
+        scale: u8,
+    },
+    Divider,
+    Blank,
+    Qr { data: String },
+}
+
+fn default_scale() -> u8 {
+    2
+}
+
+/// The script for plugin `name`, if there is one. Names are limited to letters, digits,
+/// `-` and `_` so they can't reach outside the directory.
+fn script(state: &AppState, name: &str) -> Result<Option<PathBuf>, JobError> {
+    let dir = state.config.plugins_dir.as_ref().ok_or(JobError::NotConfigured("PLUGINS_DIR"))?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Ok(None);
+    }
+    let path = dir.join(format!("{}.lua", name));
+    Ok(path.is_file().then_some(path))
+}
+
+#[utoipa::path(
+    method(get, post),
+    path = "/plugins/{name}",
+    tag = "printing",
+    summary = "Print a Lua plugin's output",
+    description = "Runs `PLUGINS_DIR/{name}.lua`, whose `render(params)` returns a list of blocks (`text`, `center`, \
+        `large`, `divider`, `blank`, `qr`) to print. Query parameters are passed in `params`, and a `POST` body as \
+        `params.body`. Scripts have no `io`, `os` or `require`, run within `PLUGIN_TIMEOUT_SECONDS`, and can \
+        call `fetch(url)` for hosts in `PLUGIN_HOSTS` and `json(text)`. Also usable as a briefing section, \
+        `plugin:name=<name>`.",
+    params(("name" = String, Path, description = "The script's file name without `.lua`: letters, digits, `-` and `_`.")),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 404, description = "No script named `{name}.lua` in `PLUGINS_DIR`."),
+        (status = 502, description = "The script failed, ran out of time or memory, returned blocks that don't parse, \
+            or fetched a host not in `PLUGIN_HOSTS`."),
+        (status = 503, description = "`PLUGINS_DIR` is not set, or the printer is down for maintenance; see `Retry-After`."),
+    )
+)]
+pub async fn plugin(
+    State(state): State<AppState>,
+    source: Source,
+    Path(name): Path<String>,
+    Query(mut params): Query<HashMap<String, String>>,
+    body: String,
+) -> Result<(), StatusCode> {
+    if script(&state, &name)?.is_none() {
+        eprintln!("No plugin named {:?}", name);
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !body.is_empty() {
+        params.insert("body".to_owned(), body);
+    }
+    let receipt = render(&state, &name, params).await.inspect_err(|e| eprintln!("Plugin {} failed: {}", name, e))?;
+    state.print(receipt, &format!("plugin:{}", name), &source);
+    Ok(())
+}
+
+/// Runs plugin `name` with `params` and lays out the blocks it returns.
+pub async fn render(state: &AppState, name: &str, params: HashMap<String, String>) -> Result<Receipt, JobError> {
+    let path = script(state, name)?.ok_or_else(|| JobError::Unprocessable(format!("no plugin named {}", name)))?;
+    let host = Host {
+        hosts: state.config.plugin_hosts.clone(),
+        max_bytes: state.config.fetch_max_bytes,
+        deadline: Instant::now() + state.config.plugin_timeout,
+        runtime: tokio::runtime::Handle::current(),
+    };
+    let name = name.to_owned();
+    let blocks = tokio::task::spawn_blocking(move || run(&path, params, host))
+        .await
+        .map_err(|e| JobError::Upstream(e.to_string()))?
+        .map_err(|e| JobError::Upstream(format!("plugin {}: {}", name, e)))?;
+
+    let mut receipt = Receipt::for_config(&state.config);
+    for block in blocks {
+        match block {
+            Block::Text { text, size } => receipt.sized(size, |receipt| receipt.wrapped(&text)),
+            Block::Center { text, size } => receipt.sized(size, |receipt| receipt.line_center(&text)),
+            Block::Large { text, scale } => receipt.line_large(&text, scale.clamp(1, 8)),
+            Block::Divider => receipt.divider(),
+            Block::Blank => receipt.line_left(""),
+            Block::Qr { data } => receipt.qr_code(&data, 6),
+        }
+    }
+    Ok(receipt)
+}
+
+/// What a script's host functions are allowed to do.
+struct Host {
+    /// Hosts `fetch` may reach, subdomains included; none when empty.
+    hosts: Vec<String>,
+    max_bytes: usize,
+    deadline: Instant,
+    runtime: tokio::runtime::Handle,
+}
+
+impl Host {
+    fn allows(&self, url: &reqwest::Url) -> bool {
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        matches!(url.scheme(), "http" | "https")
+            && self.hosts.iter().any(|allowed| host == *allowed || host.ends_with(&format!(".{}", allowed)))
+    }
+
+    /// Downloads `url` as text, within the size limit and what's left of the time limit.
+    fn fetch(&self, url: &str) -> Result<String, String> {
+        let url = reqwest::Url::parse(url).map_err(|e| format!("invalid URL {:?}: {}", url, e))?;
+        if !self.allows(&url) {
+            return Err(format!("{} is not in PLUGIN_HOSTS", url));
+        }
+        let timeout = self.deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1));
+        self.runtime.block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(timeout)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .map_err(|e| e.to_string())?;
+            let mut response = client.get(url).send().await.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?;
+            let mut data = Vec::new();
+            while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+                if data.len() + chunk.len() > self.max_bytes {
+                    return Err(format!("response is larger than {} bytes", self.max_bytes));
+                }
+                data.extend_from_slice(&chunk);
+            }
+            Ok(String::from_utf8_lossy(&data).into_owned())
+        })
+    }
+}
+
+fn run(path: &std::path::Path, params: HashMap<String, String>, host: Host) -> mlua::Result<Vec<Block>> {
+    let code = std::fs::read_to_string(path).map_err(mlua::Error::external)?;
+    let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8, LuaOptions::default())?;
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    let deadline = host.deadline;
+    lua.set_hook(HookTriggers::new().every_nth_instruction(1000), move |_, _| {
+        if Instant::now() > deadline {
+            Err(mlua::Error::runtime("time limit exceeded"))
+        } else {
+            Ok(VmState::Continue)
+        }
+    })?;
+
+    let fetches = AtomicUsize::new(0);
+    let fetch = lua.create_function(move |_, url: String| {
+        if fetches.fetch_add(1, Ordering::SeqCst) >= MAX_FETCHES {
+            return Err(mlua::Error::runtime(format!("more than {} fetches", MAX_FETCHES)));
+        }
+        host.fetch(&url).map_err(mlua::Error::runtime)
+    })?;
+    let json = lua.create_function(|lua, text: String| {
+        let value: serde_json::Value = serde_json::from_str(&text).map_err(mlua::Error::external)?;
+        lua.to_value(&value)
+    })?;
+    lua.globals().set("fetch", fetch)?;
+    lua.globals().set("json", json)?;
+
+    lua.load(&code).set_name(path.display().to_string()).exec()?;
+    let render: mlua::Function = lua.globals().get("render")?;
+    let blocks = render.call::<mlua::Value>(lua.to_value(&params)?)?;
+    lua.from_value(blocks)
+}
@@ -0,0 +1,102 @@
+//! `POST /pomodoro`: a session ticket with a box to tick per interval, optionally
+//! followed by a break reminder when the first interval is up.
+
+use crate::{
+    AppState,
+    receipt::{Receipt, wrap},
+    source::Source,
+};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::{Local, TimeDelta};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Scale of the task name; long names wrap over several large lines.
+const TASK_SCALE: u8 = 2;
+const MAX_INTERVALS: u32 = 16;
+
+fn default_intervals() -> u32 {
+    4
+}
+
+fn default_minutes() -> u32 {
+    25
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PomodoroRequest {
+    task: String,
+    /// Boxes to print, one per interval.
+    #[serde(default = "default_intervals")]
+    #[schema(default = default_intervals, minimum = 1, maximum = 16)]
+    intervals: u32,
+    /// Length of an interval.
+    #[serde(default = "default_minutes")]
+    #[schema(default = default_minutes, minimum = 1)]
+    minutes: u32,
+    /// Print a break reminder once the first interval is over.
+    #[serde(default, rename = "break")]
+    #[schema(default = false)]
+    break_reminder: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Session {
+    /// The break reminder, which can be cancelled with `DELETE /reminders/{id}`.
+    break_reminder: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/pomodoro",
+    tag = "printing",
+    summary = "Print a pomodoro session ticket",
+    description = "The task in large text, the start time and a box to tick per interval. With `break`, a reminder \
+        prints when the first interval is up.",
+    responses(
+        (status = 200, description = "Printed, or queued to print.", body = Session),
+        (status = 400, description = "No task, or intervals outside 1-16, or zero minutes."),
+    )
+)]
+pub async fn pomodoro(State(state): State<AppState>, source: Source, Json(request): Json<PomodoroRequest>) -> Result<Json<Session>, StatusCode> {
+    let task = request.task.trim();
+    if task.is_empty() || !(1..=MAX_INTERVALS).contains(&request.intervals) || request.minutes == 0 {
+        eprintln!(
+            "Invalid pomodoro (task={:?}, intervals={}, minutes={})",
+            request.task, request.intervals, request.minutes
+        );
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let start = Local::now();
+    eprintln!("Pomodoro for {:?}, {} x {} minutes", task, request.intervals, request.minutes);
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("POMODORO");
+    receipt.divider();
+    for line in wrap(task, receipt.width() / TASK_SCALE as usize) {
+        receipt.line_large(&line, TASK_SCALE);
+    }
+    receipt.divider();
+    receipt.line_center(&format!(
+        "Started {}, {} x {} min",
+        start.format_localized("%H:%M", state.config.locale),
+        request.intervals,
+        request.minutes
+    ));
+    receipt.line_left("");
+    // Rows of whole boxes, never breaking one across lines.
+    let boxes = vec!["[ ]"; request.intervals as usize];
+    for row in boxes.chunks((receipt.width() + 1) / 4) {
+        receipt.line_center(&row.join(" "));
+    }
+    state.print(receipt, "pomodoro", &source);
+
+    let break_reminder = if request.break_reminder {
+        let at = start + TimeDelta::minutes(request.minutes as i64);
+        let reminder = state.reminders.add(source.namespace(), format!("Break time - {}", task), at, None)?;
+        Some(reminder.id)
+    } else {
+        None
+    };
+    Ok(Json(Session { break_reminder }))
+}
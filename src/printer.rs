@@ -0,0 +1,548 @@
+use crate::{
+    agent::AgentDriver,
+    bluetooth::{BluetoothDriver, BtAddr},
+    codepage,
+    config::QuietHours,
+    diagnostics::{self, Diagnostics},
+    discovery::{self, UsbId},
+    network::NetworkDriver,
+    profile::{self, CommandSet, Kanji, Profile},
+};
+use chrono::Local;
+use escpos::{
+    driver::{self, Driver},
+    errors::Result as EscposResult,
+    printer::Printer,
+    printer_options::PrinterOptions,
+    utils::{PageCode, Protocol, RealTimeStatusRequest},
+};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The transport behind the printer: the USB device, a Bluetooth or network connection,
+/// an agent polling from elsewhere, or with the `mock` feature an in-memory recorder.
+#[derive(Clone)]
+pub enum PrinterDriver {
+    Usb(driver::UsbDriver),
+    Bluetooth(BluetoothDriver),
+    Network(NetworkDriver),
+    Agent(AgentDriver),
+    #[cfg(feature = "mock")]
+    Mock(crate::mock::MockDriver),
+    /// Keeps the bytes for `/debug/render`.
+    Capture(crate::debug::Capture),
+}
+
+impl Driver for PrinterDriver {
+    fn name(&self) -> String {
+        match self {
+            PrinterDriver::Usb(d) => d.name(),
+            PrinterDriver::Bluetooth(d) => d.name(),
+            PrinterDriver::Network(d) => d.name(),
+            PrinterDriver::Agent(d) => d.name(),
+            #[cfg(feature = "mock")]
+            PrinterDriver::Mock(d) => d.name(),
+            PrinterDriver::Capture(d) => d.name(),
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> EscposResult<()> {
+        match self {
+            PrinterDriver::Usb(d) => d.write(data),
+            PrinterDriver::Bluetooth(d) => d.write(data),
+            PrinterDriver::Network(d) => d.write(data),
+            PrinterDriver::Agent(d) => d.write(data),
+            #[cfg(feature = "mock")]
+            PrinterDriver::Mock(d) => d.write(data),
+            PrinterDriver::Capture(d) => d.write(data),
+        }
+    }
+
+    fn read(&self, buf: &mut [u8]) -> EscposResult<usize> {
+        match self {
+            PrinterDriver::Usb(d) => d.read(buf),
+            PrinterDriver::Bluetooth(d) => d.read(buf),
+            PrinterDriver::Network(d) => d.read(buf),
+            PrinterDriver::Agent(d) => d.read(buf),
+            #[cfg(feature = "mock")]
+            PrinterDriver::Mock(d) => d.read(buf),
+            PrinterDriver::Capture(d) => d.read(buf),
+        }
+    }
+
+    fn flush(&self) -> EscposResult<()> {
+        match self {
+            PrinterDriver::Usb(d) => d.flush(),
+            PrinterDriver::Bluetooth(d) => d.flush(),
+            PrinterDriver::Network(d) => d.flush(),
+            PrinterDriver::Agent(d) => d.flush(),
+            #[cfg(feature = "mock")]
+            PrinterDriver::Mock(d) => d.flush(),
+            PrinterDriver::Capture(d) => d.flush(),
+        }
+    }
+}
+
+pub type DevicePrinter = Printer<PrinterDriver>;
+
+/// Where the printer is attached.
+#[derive(Clone, Copy)]
+pub enum Device {
+    /// A USB printer by ID, or `None` for the first one discovered.
+    Usb(Option<UsbId>),
+    /// A Bluetooth printer's address and RFCOMM channel.
+    Bluetooth(BtAddr, u8),
+    /// A network printer's address, usually on port 9100.
+    Network(SocketAddr),
+    /// A printer whose [`crate::agent`] pulls its jobs from this server. Its slot is made
+    /// around the agent's driver, as there's nothing here to open.
+    Agent,
+}
+
+/// What the main printer is called among the named ones from `PRINTERS`.
+pub const MAIN_PRINTER: &str = "default";
+
+/// Font A columns on 80mm paper, which the fixed-layout reports are designed for. The
+/// active [`Profile`] has the actual width.
+pub const CHARS_PER_LINE: usize = 48;
+/// Printable width in dots (80mm paper, 12-dot font A columns).
+pub const DOTS_PER_LINE: usize = 576;
+/// 203 dpi print head.
+pub const DOTS_PER_MM: f64 = 8.0;
+
+/// The Epson TM-T20 this was written for, used when discovery finds nothing.
+const DEFAULT_USB_ID: UsbId = UsbId {
+    vendor_id: 0x04b8,
+    product_id: 0x0e28,
+};
+/// How often a missing printer is looked for again, so it can be plugged in or
+/// switched on after startup.
+const REDISCOVER_INTERVAL: Duration = Duration::from_secs(10);
+/// Failed keep-alive probes in a row before the handle is given up and reopened.
+const KEEPALIVE_MAX_FAILURES: u32 = 3;
+/// Longest model name reply read, in case a printer answers with something else.
+const MODEL_REPLY_MAX: usize = 256;
+/// The table the printer starts on: the profile's first choice.
+fn page_code(profile: &Profile) -> PageCode {
+    profile.code_pages.first().copied().unwrap_or_default()
+}
+
+/// A printer on `driver`, reset and set to the profile's first code page. The commands
+/// are buffered until the first job is printed.
+pub fn new_printer(driver: PrinterDriver, profile: &Profile) -> EscposResult<DevicePrinter> {
+    // ESC @ resets Star printers too, but they select tables with their own command.
+    let escpos_page = (profile.commands == CommandSet::EscPos).then(|| page_code(profile));
+    let mut printer = Printer::new(
+        driver,
+        Protocol::default(),
+        Some(PrinterOptions::new(escpos_page, None, profile.font_a_columns as u8)),
+    );
+
+    printer.init()?;
+    // FS C 1: Shift JIS rather than JIS in Kanji mode.
+    if profile.kanji == Some(Kanji::ShiftJis) && profile.commands == CommandSet::EscPos {
+        printer.custom(&[0x1c, b'C', 1])?;
+    }
+    if profile.commands == CommandSet::StarLine {
+        printer.custom(&codepage::select(page_code(profile), profile.commands))?;
+    }
+    Ok(printer)
+}
+
+fn init_printer(driver: PrinterDriver, profile: &Profile, diagnostics: &Diagnostics) -> Option<DevicePrinter> {
+    let printer = match new_printer(driver, profile) {
+        Ok(printer) => printer,
+        Err(e) => {
+            eprintln!("Failed to initialize printer: {:?}", e);
+            diagnostics.record_error("printer", format!("init failed: {}", e));
+            return None;
+        }
+    };
+    eprintln!("Printer initialized successfully");
+    diagnostics.update(|r| r.init_ok = true);
+
+    Some(printer)
+}
+
+/// The configured printer, or else the first connected device that looks like one.
+fn resolve_usb_id(configured: Option<UsbId>) -> UsbId {
+    if let Some(id) = configured {
+        return id;
+    }
+    match discovery::discover() {
+        Ok(candidates) => match candidates.first() {
+            Some(candidate) => {
+                eprintln!("Discovered USB printer {} ({})", candidate.id, candidate.vendor.unwrap_or("printer class"));
+                candidate.usb_id
+            }
+            None => DEFAULT_USB_ID,
+        },
+        Err(e) => {
+            eprintln!("USB printer discovery failed: {:?}", e);
+            DEFAULT_USB_ID
+        }
+    }
+}
+
+pub fn create_printer(device: Device, profile: &Profile, diagnostics: &Diagnostics) -> Option<DevicePrinter> {
+    init_printer(connect(device, profile, diagnostics)?, profile, diagnostics)
+}
+
+/// Opens the connection to `device` without initializing the printer.
+pub fn connect(device: Device, profile: &Profile, diagnostics: &Diagnostics) -> Option<PrinterDriver> {
+    match device {
+        Device::Usb(configured) => open_usb(configured, profile, diagnostics),
+        Device::Bluetooth(address, channel) => open_bluetooth(address, channel, profile, diagnostics),
+        Device::Network(address) => open_network(address, profile, diagnostics),
+        Device::Agent => None,
+    }
+}
+
+/// Asks the printer for its model name (GS I 67). The answer is `_`, the name and a
+/// NUL; `None` if nothing comes back before the driver's read timeout.
+fn identify(driver: &PrinterDriver) -> Option<String> {
+    driver.write(&[0x1d, b'I', 0x43]).and_then(|()| driver.flush()).ok()?;
+    let mut reply = Vec::new();
+    let mut buf = [0; 64];
+    while !reply.contains(&0) && reply.len() < MODEL_REPLY_MAX {
+        match driver.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => reply.extend_from_slice(&buf[..n]),
+        }
+    }
+    let name = reply.strip_prefix(b"_")?.split(|&b| b == 0).next()?;
+    let name = String::from_utf8_lossy(name).trim().to_owned();
+    (!name.is_empty()).then_some(name)
+}
+
+/// The profile for the printer on `driver` by the model it reports, or `configured`
+/// when it doesn't answer or isn't recognised.
+pub fn detect_profile(driver: &PrinterDriver, configured: &Profile, diagnostics: &Diagnostics) -> Profile {
+    // Star Line Mode has no GS I.
+    if configured.commands == CommandSet::StarLine {
+        return configured.clone();
+    }
+    let Some(model) = identify(driver) else {
+        eprintln!("Printer didn't report its model, using the {} profile", configured.name);
+        return configured.clone();
+    };
+    diagnostics.update(|r| r.printer_model = Some(model.clone()));
+    match profile::for_model(&model, configured) {
+        Some(profile) => {
+            eprintln!(
+                "Printer reports model {:?}, using the {} profile at {} columns",
+                model, profile.name, profile.font_a_columns
+            );
+            profile
+        }
+        None => {
+            eprintln!("Printer reports unknown model {:?}, using the {} profile", model, configured.name);
+            configured.clone()
+        }
+    }
+}
+
+fn open_network(address: SocketAddr, profile: &Profile, diagnostics: &Diagnostics) -> Option<PrinterDriver> {
+    diagnostics.update(|r| {
+        r.driver_opened = false;
+        r.init_ok = false;
+        r.page_code_supported = diagnostics::page_code_supported(page_code(profile));
+    });
+    eprintln!("Connecting to network printer {}...", address);
+    let driver = match NetworkDriver::open(address) {
+        Ok(d) => {
+            eprintln!("Network printer connected");
+            d
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to network printer: {:?}", e);
+            diagnostics.record_error("network", e.to_string());
+            return None;
+        }
+    };
+    diagnostics.update(|r| r.driver_opened = true);
+
+    Some(PrinterDriver::Network(driver))
+}
+
+fn open_bluetooth(address: BtAddr, channel: u8, profile: &Profile, diagnostics: &Diagnostics) -> Option<PrinterDriver> {
+    diagnostics.update(|r| {
+        r.driver_opened = false;
+        r.init_ok = false;
+        r.page_code_supported = diagnostics::page_code_supported(page_code(profile));
+    });
+    eprintln!("Connecting to Bluetooth printer {} (channel {})...", address, channel);
+    let driver = match BluetoothDriver::open(address, channel) {
+        Ok(d) => {
+            eprintln!("Bluetooth printer connected");
+            d
+        }
+        Err(e) => {
+            eprintln!("Failed to connect to Bluetooth printer: {:?}", e);
+            diagnostics.record_error("bluetooth", e.to_string());
+            return None;
+        }
+    };
+    diagnostics.update(|r| r.driver_opened = true);
+
+    Some(PrinterDriver::Bluetooth(driver))
+}
+
+fn open_usb(configured: Option<UsbId>, profile: &Profile, diagnostics: &Diagnostics) -> Option<PrinterDriver> {
+    let UsbId { vendor_id, product_id } = resolve_usb_id(configured);
+    let device_found = match diagnostics::usb_device_present(vendor_id, product_id) {
+        Ok(found) => found,
+        Err(e) => {
+            eprintln!("Failed to enumerate USB devices: {:?}", e);
+            diagnostics.record_error("usb", format!("enumeration failed: {}", e));
+            false
+        }
+    };
+    diagnostics.update(|r| {
+        r.usb_device_found = device_found;
+        r.driver_opened = false;
+        r.init_ok = false;
+        r.page_code_supported = diagnostics::page_code_supported(page_code(profile));
+    });
+
+    eprintln!(
+        "Attempting to open USB printer (vendor={:#06x}, product={:#06x})...",
+        vendor_id, product_id
+    );
+    let driver = match driver::UsbDriver::open(vendor_id, product_id, Some(Duration::from_secs(2)), None) {
+        Ok(d) => {
+            eprintln!("USB driver opened successfully");
+            d
+        }
+        Err(e) => {
+            eprintln!("Failed to open USB driver: {:?}", e);
+            diagnostics.record_error("usb", e.to_string());
+            return None;
+        }
+    };
+    diagnostics.update(|r| r.driver_opened = true);
+
+    Some(PrinterDriver::Usb(driver))
+}
+
+struct SlotState {
+    printer: Option<DevicePrinter>,
+    /// Set when the idle policy closed the handle, so the next job knows to reopen it.
+    released: bool,
+    last_used: Instant,
+    /// When the device was last opened or looked for.
+    last_attempt: Instant,
+}
+
+/// Shared handle to the printer that can be released while idle and reopened on demand.
+#[derive(Clone)]
+pub struct PrinterSlot {
+    state: Arc<Mutex<SlotState>>,
+    diagnostics: Diagnostics,
+    profile: Profile,
+    /// Set for slots backed by a real device rather than a given driver.
+    device: Option<Device>,
+    /// The driver a slot was given instead of a device, initialized again to reconnect.
+    driver: Option<PrinterDriver>,
+}
+
+impl PrinterSlot {
+    pub fn new(device: Device, profile: &Profile, diagnostics: Diagnostics) -> Self {
+        let driver = connect(device, profile, &diagnostics);
+        Self::connected(Some(device), driver, profile, diagnostics)
+    }
+
+    /// A slot around an already-open driver instead of a device.
+    pub fn with_driver(driver: PrinterDriver, profile: &Profile, diagnostics: Diagnostics) -> Self {
+        diagnostics.update(|r| r.driver_opened = true);
+        PrinterSlot {
+            driver: Some(driver.clone()),
+            ..Self::connected(None, Some(driver), profile, diagnostics)
+        }
+    }
+
+    /// A slot around `driver`, opened with [`connect`] (`None` if that failed), that is
+    /// reopened from `device` when it's lost.
+    pub fn connected(device: Option<Device>, driver: Option<PrinterDriver>, profile: &Profile, diagnostics: Diagnostics) -> Self {
+        PrinterSlot {
+            state: Arc::new(Mutex::new(SlotState {
+                printer: driver.and_then(|driver| init_printer(driver, profile, &diagnostics)),
+                released: false,
+                last_used: Instant::now(),
+                last_attempt: Instant::now(),
+            })),
+            diagnostics,
+            profile: profile.clone(),
+            device,
+            driver: None,
+        }
+    }
+
+    /// The profile the printer was opened with, which its jobs are laid out and sent for.
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Returns the printer for a new job, reopening it first if the idle policy released
+    /// it, or looking for it again if it wasn't there, e.g. plugged in after startup.
+    pub fn acquire(&self) -> Option<DevicePrinter> {
+        let mut state = self.state.lock().unwrap();
+        if state.released {
+            eprintln!("Reopening printer released while idle");
+            state.printer = create_printer(self.device.unwrap_or(Device::Usb(None)), &self.profile, &self.diagnostics);
+            state.released = false;
+            state.last_attempt = Instant::now();
+        } else if let Some(device) = self.device
+            && state.printer.is_none()
+            && state.last_attempt.elapsed() >= REDISCOVER_INTERVAL
+        {
+            eprintln!("No printer open, looking for it again");
+            state.printer = create_printer(device, &self.profile, &self.diagnostics);
+            state.last_attempt = Instant::now();
+        }
+        state.last_used = Instant::now();
+        state.printer.clone()
+    }
+
+    /// Forgets a printer that stopped taking data, e.g. a Bluetooth printer that went out
+    /// of range or a USB cable that was pulled, so a later job connects again.
+    pub fn disconnect(&self) {
+        if self.device.is_some() {
+            self.state.lock().unwrap().printer = None;
+        }
+    }
+
+    /// Drops the device handle if no job has run for `timeout`. Jobs still holding a
+    /// clone keep the device open until they finish.
+    fn release_if_idle(&self, timeout: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if state.printer.is_some() && state.last_used.elapsed() >= timeout {
+            eprintln!("Printer idle for {:?}, releasing device handle", timeout);
+            state.printer = None;
+            state.released = true;
+        }
+    }
+
+    /// Sends an idle printer a real-time status request (DLE EOT 1). A handle broken by a
+    /// USB glitch fails the write; one that's gone or was never found is looked for again.
+    /// Returns whether the printer took the request, or `None` if it's busy or released.
+    fn probe(&self, period: Duration) -> Option<bool> {
+        let printer = {
+            let mut state = self.state.lock().unwrap();
+            if state.released || state.last_used.elapsed() < period {
+                return None;
+            }
+            if state.printer.is_none() && state.last_attempt.elapsed() >= REDISCOVER_INTERVAL {
+                eprintln!("Keep-alive: no printer open, looking for it again");
+                state.printer = self.reopen();
+                state.last_attempt = Instant::now();
+            }
+            state.printer.clone()
+        };
+        let Some(mut printer) = printer else {
+            return Some(false);
+        };
+        // Star printers don't speak DLE EOT; being open is all that can be checked.
+        if self.profile.commands == CommandSet::StarLine {
+            return Some(true);
+        }
+        match printer.real_time_status(RealTimeStatusRequest::Printer).and_then(|p| p.send_status()) {
+            Ok(_) => Some(true),
+            Err(e) => {
+                eprintln!("Keep-alive status query failed: {:?}", e);
+                self.diagnostics.record_error("keepalive", e.to_string());
+                Some(false)
+            }
+        }
+    }
+
+    /// Whether the keep-alive has anything to reopen: a device, or a given driver other
+    /// than an agent's, which isn't talked to directly.
+    fn reopenable(&self) -> bool {
+        self.device.is_some() || self.driver.as_ref().is_some_and(|driver| !matches!(driver, PrinterDriver::Agent(_)))
+    }
+
+    /// Opens the device again, or initializes the given driver again.
+    fn reopen(&self) -> Option<DevicePrinter> {
+        match (self.device, &self.driver) {
+            (Some(device), _) => create_printer(device, &self.profile, &self.diagnostics),
+            (None, Some(driver)) => init_printer(driver.clone(), &self.profile, &self.diagnostics),
+            (None, None) => None,
+        }
+    }
+
+    /// Drops the handle and opens the device again.
+    fn reconnect(&self) {
+        eprintln!("Printer stopped responding, reconnecting");
+        let mut state = self.state.lock().unwrap();
+        state.printer = None;
+        state.printer = self.reopen();
+        state.last_attempt = Instant::now();
+        self.diagnostics.update(|r| r.reconnects += 1);
+    }
+
+    /// Probes the printer every `period` while it's idle, reconnecting after
+    /// [`KEEPALIVE_MAX_FAILURES`] failures in a row. Not for agents, which report on
+    /// their own printers. No probes are sent during `quiet_hours`, so a
+    /// printer in standby is left to sleep. A USB printer that's still on the bus but keeps
+    /// failing after being reopened is reported as wedged, which stops the systemd
+    /// watchdog pings (see [`crate::systemd`]) so the service is restarted.
+    pub fn spawn_keepalive(&self, period: Duration, quiet_hours: Option<QuietHours>) {
+        if !self.reopenable() {
+            return;
+        }
+        let slot = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            let mut failures = 0;
+            let mut reopened = false;
+            loop {
+                interval.tick().await;
+                if quiet_hours.is_some_and(|quiet| quiet.until(Local::now()).is_some()) {
+                    continue;
+                }
+                // Opening and writing to the device block.
+                let probing = slot.clone();
+                let Ok(Some(responding)) = tokio::task::spawn_blocking(move || probing.probe(period)).await else {
+                    continue;
+                };
+                slot.diagnostics.update(|r| {
+                    r.printer_responding = Some(responding);
+                    r.wedged &= !responding;
+                });
+                failures = if responding { 0 } else { failures + 1 };
+                reopened &= !responding;
+                if failures >= KEEPALIVE_MAX_FAILURES {
+                    failures = 0;
+                    if reopened && matches!(slot.device, Some(Device::Usb(_))) && slot.diagnostics.snapshot().usb_device_found {
+                        eprintln!("Printer is on the bus but still not responding after reopening it; the USB stack looks wedged");
+                        slot.diagnostics.update(|r| r.wedged = true);
+                    }
+                    reopened = true;
+                    let reconnecting = slot.clone();
+                    let _ = tokio::task::spawn_blocking(move || reconnecting.reconnect()).await;
+                }
+            }
+        });
+    }
+
+    /// Releases the handle after `timeout` without a job. Only for real devices, as with
+    /// the keep-alive: a given driver can't be reopened.
+    pub fn spawn_idle_reaper(&self, timeout: Duration) {
+        if self.device.is_none() {
+            return;
+        }
+        let slot = self.clone();
+        let period = (timeout / 4).clamp(Duration::from_secs(1), Duration::from_secs(60));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                slot.release_if_idle(timeout);
+            }
+        });
+    }
+}
@@ -0,0 +1,195 @@
+//! Per-model printer quirks. Receipt printers differ in paper width, which character
+//! tables they have, whether they can cut, and whether there's a buzzer; the profile
+//! says which of those to rely on.
+
+use crate::{codepage, receipt::Font};
+use escpos::utils::PageCode;
+
+/// The printer's command language.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommandSet {
+    /// Epson ESC/POS, which most receipt printers speak.
+    EscPos,
+    /// Star Line Mode; see [`crate::star`].
+    StarLine,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cutter {
+    /// Full and partial cuts.
+    Full,
+    /// Only partial cuts, leaving a tab the receipt tears off at.
+    Partial,
+    /// No cutter; jobs are fed out to tear off by hand.
+    None,
+}
+
+/// The double-byte encoding of a printer's Kanji (CJK) font, which ESC/POS printers sold
+/// in Asia have alongside the code tables.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kanji {
+    /// Japanese models, switched to Shift JIS with `FS C 1`.
+    ShiftJis,
+    /// Simplified Chinese models (GB2312/GB18030).
+    Gbk,
+    /// Traditional Chinese models.
+    Big5,
+}
+
+impl Kanji {
+    pub fn encoding(self) -> &'static encoding_rs::Encoding {
+        match self {
+            Kanji::ShiftJis => encoding_rs::SHIFT_JIS,
+            Kanji::Gbk => encoding_rs::GBK,
+            Kanji::Big5 => encoding_rs::BIG5,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub name: String,
+    pub commands: CommandSet,
+    /// Characters per line in the default font A.
+    pub font_a_columns: usize,
+    /// Characters per line in the condensed font B.
+    pub font_b_columns: usize,
+    /// Character tables the printer has, in order of preference.
+    pub code_pages: Vec<PageCode>,
+    pub cutter: Cutter,
+    /// Printable width in dots.
+    pub raster_width: usize,
+    pub buzzer: bool,
+    /// The Kanji font's encoding, for printing CJK text (ESC/POS only); `None` if the
+    /// printer has none.
+    pub kanji: Option<Kanji>,
+}
+
+fn builtin(name: &str, columns: (usize, usize), code_pages: &[PageCode], cutter: Cutter, raster_width: usize, buzzer: bool) -> Profile {
+    Profile {
+        name: name.to_owned(),
+        commands: CommandSet::EscPos,
+        font_a_columns: columns.0,
+        font_b_columns: columns.1,
+        code_pages: code_pages.to_vec(),
+        cutter,
+        raster_width,
+        buzzer,
+        kanji: None,
+    }
+}
+
+/// The profiles that ship with the server; the first is the default.
+pub fn builtins() -> Vec<Profile> {
+    let all = codepage::pages();
+    vec![
+        builtin("tm-t20", (48, 64), &all, Cutter::Full, 576, true),
+        builtin("tm-t88", (48, 64), &all, Cutter::Full, 576, false),
+        builtin("tm-m30", (48, 64), &all, Cutter::Full, 576, false),
+        Profile {
+            commands: CommandSet::StarLine,
+            ..builtin("star-tsp100", (48, 64), &all, Cutter::Full, 576, false)
+        },
+        Profile {
+            commands: CommandSet::StarLine,
+            ..builtin("star-tsp650", (48, 64), &all, Cutter::Full, 576, false)
+        },
+        builtin("generic-80mm", (48, 64), &[PageCode::PC437], Cutter::Partial, 576, true),
+        builtin("generic-58mm", (32, 42), &[PageCode::PC437], Cutter::None, 384, false),
+    ]
+}
+
+/// Paper widths unknown models often give in their name, e.g. `POS-58`, and the generic
+/// profile for each.
+const PAPER_WIDTHS: [(&str, &str); 2] = [("58", "generic-58mm"), ("80", "generic-80mm")];
+
+/// The profile for a model name the printer reports: the built-in one for a known model,
+/// e.g. `tm-t20` for `TM-T20II`, or else `configured` at the paper width in the name.
+pub fn for_model(model: &str, configured: &Profile) -> Option<Profile> {
+    let normalize = |name: &str| -> String {
+        name.chars().filter(char::is_ascii_alphanumeric).map(|c| c.to_ascii_lowercase()).collect()
+    };
+    let model = normalize(model);
+    let builtins = builtins();
+    if let Some(known) = builtins.iter().find(|p| !p.name.starts_with("generic") && model.starts_with(&normalize(&p.name))) {
+        return Some(known.clone());
+    }
+    let (_, generic) = PAPER_WIDTHS.iter().find(|(width, _)| model.contains(width))?;
+    let generic = builtins.iter().find(|p| p.name == *generic)?;
+    Some(Profile {
+        font_a_columns: generic.font_a_columns,
+        font_b_columns: generic.font_b_columns,
+        raster_width: generic.raster_width,
+        ..configured.clone()
+    })
+}
+
+impl Profile {
+    /// Characters per line in `font`.
+    pub fn columns(&self, font: Font) -> usize {
+        match font {
+            Font::A => self.font_a_columns,
+            Font::B => self.font_b_columns,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        builtins().remove(0)
+    }
+}
+
+/// Parses a user-defined profile, `name:key=value:...`, starting from the built-in
+/// `base` (the default if not given). Keys: `base`, `commands` (`escpos` or `star`),
+/// `columns`, `font_b_columns`, `code_pages` (e.g. `PC437/PC858`), `cutter` (`full`,
+/// `partial` or `none`), `raster_width`, `buzzer` (`yes` or `no`) and `kanji`
+/// (`shift_jis`, `gbk`, `big5` or `none`).
+pub fn parse(entry: &str, known: &[Profile]) -> Option<Profile> {
+    let mut parts = entry.split(':').map(str::trim);
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let settings: Vec<(&str, &str)> = parts.map(|part| part.split_once('=')).collect::<Option<_>>()?;
+
+    let base = settings.iter().find(|(key, _)| *key == "base").map(|(_, value)| *value);
+    let mut profile = match base {
+        Some(base) => known.iter().find(|p| p.name == base)?.clone(),
+        None => Profile::default(),
+    };
+    profile.name = name.to_owned();
+    for (key, value) in settings {
+        match key {
+            "base" => {}
+            "commands" => {
+                profile.commands = match value {
+                    "escpos" => CommandSet::EscPos,
+                    "star" => CommandSet::StarLine,
+                    _ => return None,
+                }
+            }
+            "columns" => profile.font_a_columns = value.parse().ok().filter(|&n| n > 0)?,
+            "font_b_columns" => profile.font_b_columns = value.parse().ok().filter(|&n| n > 0)?,
+            "code_pages" => profile.code_pages = value.split('/').map(codepage::parse).collect::<Option<_>>()?,
+            "cutter" => {
+                profile.cutter = match value {
+                    "full" => Cutter::Full,
+                    "partial" => Cutter::Partial,
+                    "none" => Cutter::None,
+                    _ => return None,
+                }
+            }
+            "raster_width" => profile.raster_width = value.parse().ok().filter(|&n| n >= 8)?,
+            "buzzer" => profile.buzzer = matches!(value, "1" | "true" | "yes"),
+            "kanji" => {
+                profile.kanji = match value {
+                    "shift_jis" => Some(Kanji::ShiftJis),
+                    "gbk" => Some(Kanji::Gbk),
+                    "big5" => Some(Kanji::Big5),
+                    "none" => None,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(profile)
+}
@@ -0,0 +1,397 @@
+use crate::{
+    AppState,
+    callback::{self, Completion},
+    history::{JobStatus, TargetResult},
+    notify,
+    printer::MAIN_PRINTER,
+    receipt::Receipt,
+};
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Local};
+use escpos::errors::PrinterError;
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc::UnboundedReceiver, oneshot},
+};
+
+/// How long a client turned away by a full queue is told to wait.
+const FULL_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// A rendered receipt waiting for the printer.
+pub struct QueuedJob {
+    pub id: u64,
+    pub receipt: Receipt,
+    /// The rest of a streamed job, printed as it arrives after `receipt`; see [`crate::stream`].
+    pub stream: Option<UnboundedReceiver<Receipt>>,
+    /// Told how a job relayed from another instance went, to answer it with; see
+    /// [`crate::relay`].
+    pub done: Option<oneshot::Sender<Completion>>,
+    /// URL to tell once the job has printed, failed or expired.
+    pub callback: Option<String>,
+    /// Past this the job is no longer worth printing and is dropped as expired.
+    pub expires_at: Option<DateTime<Local>>,
+    pub namespace: String,
+    /// Who submitted it, as in the job history, e.g. `key:feeds`.
+    pub source: String,
+    /// Printers to print it on at once, by name; empty for just the main printer.
+    pub printers: Vec<String>,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<QueuedJob>,
+    /// One-off jobs held back until their time, released by the scheduler.
+    delayed: Vec<(DateTime<Local>, QueuedJob)>,
+    paused: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QueueStatus {
+    pub paused: bool,
+    pub queued: usize,
+    pub scheduled: usize,
+}
+
+impl Queue {
+    fn status(&self) -> QueueStatus {
+        QueueStatus {
+            paused: self.paused,
+            queued: self.jobs.len(),
+            scheduled: self.delayed.len(),
+        }
+    }
+}
+
+/// Jobs waiting to be printed. Submissions are always accepted; while paused the
+/// worker leaves them queued so nothing is lost during a paper change.
+#[derive(Clone, Default)]
+pub struct PrintQueue {
+    inner: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+impl PrintQueue {
+    /// Adds jobs in order under one lock, so nothing else can be queued between them.
+    /// With `at` they wait for [`PrintQueue::release_due`] instead.
+    pub fn push(&self, jobs: Vec<QueuedJob>, at: Option<DateTime<Local>>) {
+        let (queue, ready) = &*self.inner;
+        let mut queue = queue.lock().unwrap();
+        match at {
+            Some(at) => queue.delayed.extend(jobs.into_iter().map(|job| (at, job))),
+            None => {
+                queue.jobs.extend(jobs);
+                ready.notify_one();
+            }
+        }
+    }
+
+    /// Moves delayed jobs that are due onto the queue and returns their IDs.
+    pub fn release_due(&self, now: DateTime<Local>) -> Vec<u64> {
+        let (queue, ready) = &*self.inner;
+        let mut queue = queue.lock().unwrap();
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut queue.delayed).into_iter().partition(|(at, _)| *at <= now);
+        queue.delayed = waiting;
+        let ids = due.iter().map(|(_, job)| job.id).collect();
+        queue.jobs.extend(due.into_iter().map(|(_, job)| job));
+        ready.notify_one();
+        ids
+    }
+
+    pub fn set_paused(&self, paused: bool) -> QueueStatus {
+        let (queue, ready) = &*self.inner;
+        let mut queue = queue.lock().unwrap();
+        queue.paused = paused;
+        ready.notify_one();
+        queue.status()
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        self.inner.0.lock().unwrap().status()
+    }
+
+    /// Jobs waiting, queued or scheduled.
+    pub fn depth(&self) -> usize {
+        let queue = self.inner.0.lock().unwrap();
+        queue.jobs.len() + queue.delayed.len()
+    }
+
+    /// Takes every waiting job, queued or delayed, that `matches`, e.g. after a feed
+    /// flooded the queue.
+    pub fn remove(&self, matches: impl Fn(&QueuedJob) -> bool) -> Vec<QueuedJob> {
+        let mut queue = self.inner.0.lock().unwrap();
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut queue.jobs).into_iter().partition(|job| matches(job));
+        queue.jobs = kept.into();
+        let (removed_delayed, kept_delayed): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut queue.delayed).into_iter().partition(|(_, job)| matches(job));
+        queue.delayed = kept_delayed;
+        removed.into_iter().chain(removed_delayed.into_iter().map(|(_, job)| job)).collect()
+    }
+
+    /// Blocks until there's a job and the queue isn't paused.
+    fn next(&self) -> QueuedJob {
+        let (queue, ready) = &*self.inner;
+        let mut queue = ready
+            .wait_while(queue.lock().unwrap(), |q| q.paused || q.jobs.is_empty())
+            .unwrap();
+        queue.jobs.pop_front().unwrap()
+    }
+}
+
+#[derive(Serialize)]
+struct Full {
+    error: &'static str,
+    depth: usize,
+    limit: usize,
+}
+
+/// Answers printing endpoints with a 429 while `MAX_QUEUED_JOBS` jobs are waiting, with
+/// the queue's depth and limit in `X-Queue-Depth` and `X-Queue-Limit`. A request let in
+/// just under the limit may still add several jobs, e.g. with `?copies=`.
+pub async fn reject_when_full(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let depth = state.queue.depth();
+    let Some(limit) = state.config.max_queued_jobs.filter(|&limit| depth >= limit) else {
+        return next.run(req).await;
+    };
+    eprintln!("Rejected request to {}: {} jobs waiting", req.uri().path(), depth);
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(Full {
+            error: "queue_full",
+            depth,
+            limit,
+        }),
+    )
+        .into_response();
+    let headers = response.headers_mut();
+    headers.insert(RETRY_AFTER, HeaderValue::from(FULL_RETRY_AFTER.as_secs()));
+    headers.insert("x-queue-depth", HeaderValue::from(depth));
+    headers.insert("x-queue-limit", HeaderValue::from(limit));
+    response
+}
+
+/// Prints queued jobs one at a time on a dedicated thread, since USB writes block.
+/// Completion callbacks and alerts go out on the async runtime it was started from.
+pub fn spawn_worker(state: AppState) {
+    let runtime = tokio::runtime::Handle::current();
+    std::thread::spawn(move || {
+        let alert = |title: &str, message: String| {
+            if let Some(notifier) = &state.config.notifier {
+                runtime.spawn(notify::send(notifier.clone(), title.to_owned(), message));
+            }
+        };
+        // Only alert when the printer goes away, not for every job while it's gone.
+        let mut was_online = true;
+        loop {
+            let mut job = state.queue.next();
+            if let Some(expires_at) = job.expires_at
+                && expires_at < Local::now()
+            {
+                eprintln!("Job #{} expired at {} before it could print, dropping it", job.id, expires_at.format("%H:%M:%S"));
+                if let Some(url) = job.callback {
+                    let completion = Completion {
+                        id: job.id,
+                        status: JobStatus::Expired,
+                        error: None,
+                    };
+                    runtime.spawn(callback::send(url, state.config.callback_secret.clone(), completion));
+                }
+                state.history.drop_unprinted(job.id, JobStatus::Expired);
+                continue;
+            }
+            if let Some(rest) = job.stream.take() {
+                let result = print_stream(&state, &runtime, &job, rest);
+                if let Err(error) = &result {
+                    alert("Print job failed", format!("Job #{} failed: {}", job.id, error));
+                }
+                finish(&state, &runtime, job, result, Vec::new(), None);
+                continue;
+            }
+            // Routed or mirrored away from just the main printer.
+            let routed = !job.printers.is_empty();
+            let targets = if routed { job.printers.clone() } else { vec![MAIN_PRINTER.to_owned()] };
+            // A routed job already goes to every printer it's meant for.
+            let failover = state.config.printer_failover.as_ref().filter(|_| !routed);
+            // Named printers print on threads of their own, at the same time as the main one.
+            let mut outcomes: Vec<(String, Result<f64, String>)> = std::thread::scope(|scope| {
+                let named: Vec<_> = targets
+                    .iter()
+                    .filter(|name| *name != MAIN_PRINTER)
+                    .map(|name| (name, scope.spawn(|| print_named(&state, &runtime, name, &job))))
+                    .collect();
+                let main = targets.iter().find(|name| *name == MAIN_PRINTER).map(|name| {
+                    if let Some(relay) = state.relays.get(MAIN_PRINTER) {
+                        return (name.clone(), relay.forward(&runtime, job.id, &job.receipt));
+                    }
+                    let mut printer = state.printer.acquire();
+                    if printer.is_none() && was_online {
+                        alert("Printer offline", format!("No printer connected for job #{}", job.id));
+                    }
+                    was_online = printer.is_some();
+                    if printer.is_none() && failover.is_some() {
+                        // Stdout is the last resort, after the failover printer.
+                        return (name.clone(), Err("not connected".to_owned()));
+                    }
+                    if printer.is_none() {
+                        eprintln!("No printer connected, outputting to stdout");
+                    }
+                    let result = job
+                        .receipt
+                        .print(&mut printer, &state.config.printer_profile)
+                        .map(|()| job.receipt.paper_mm())
+                        .map_err(|e| {
+                            eprintln!("Failed to print job #{}: {:?}", job.id, e);
+                            state.diagnostics.record_error("printer", e.to_string());
+                            if matches!(e, PrinterError::Io(_)) {
+                                state.printer.disconnect();
+                            }
+                            e.to_string()
+                        });
+                    if let Ok(mm) = result
+                        && was_online
+                        && let Some(paper) = state.paper.record(mm)
+                    {
+                        alert("Paper running low", paper.summary());
+                    }
+                    (name.clone(), result)
+                });
+                let named = named.into_iter().map(|(name, handle)| {
+                    (name.clone(), handle.join().unwrap_or_else(|_| Err("print thread panicked".to_owned())))
+                });
+                main.into_iter().chain(named).collect()
+            });
+            outcomes.sort_by_key(|(name, _)| targets.iter().position(|target| target == name));
+            let failed_over = match (failover, &outcomes[..]) {
+                (Some(backup), [(_, Err(reason))]) => {
+                    eprintln!("Main printer couldn't print job #{} ({}), failing over to {}", job.id, reason, backup);
+                    alert("Printer failover", format!("Job #{} went to {}: {}", job.id, backup, reason));
+                    let result = print_named(&state, &runtime, backup, &job);
+                    let nowhere = result.is_err() && !was_online;
+                    outcomes.push((backup.clone(), result));
+                    if nowhere {
+                        eprintln!("No printer connected, outputting to stdout");
+                        let result = job.receipt.print(&mut None, &state.config.printer_profile).map(|()| job.receipt.paper_mm());
+                        outcomes.push(("stdout".to_owned(), result.map_err(|e| e.to_string())));
+                    }
+                    Some(backup.clone())
+                }
+                _ => None,
+            };
+            let labelled = routed || failed_over.is_some();
+            for (name, error) in outcomes.iter().filter_map(|(name, result)| Some((name, result.as_ref().err()?))) {
+                let on = if labelled { format!(" on {}", name) } else { String::new() };
+                alert("Print job failed", format!("Job #{} failed{}: {}", job.id, on, error));
+            }
+            // A mirrored or failed-over job has printed if any of its printers printed it.
+            let result = match outcomes.iter().find_map(|(_, result)| result.as_ref().ok()) {
+                Some(&mm) => Ok(mm),
+                None => Err(outcomes
+                    .iter()
+                    .filter_map(|(name, result)| result.as_ref().err().map(|e| if labelled { format!("{}: {}", name, e) } else { e.clone() }))
+                    .collect::<Vec<_>>()
+                    .join("; ")),
+            };
+            let targets = if labelled {
+                outcomes
+                    .iter()
+                    .map(|(name, result)| TargetResult {
+                        printer: name.clone(),
+                        status: if result.is_ok() { JobStatus::Printed } else { JobStatus::Failed },
+                        error: result.as_ref().err().cloned(),
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            finish(&state, &runtime, job, result, targets, failed_over);
+        }
+    });
+}
+
+/// Records how `job` went and tells its callback URL, and the instance it was relayed
+/// from.
+fn finish(state: &AppState, runtime: &Handle, job: QueuedJob, result: Result<f64, String>, targets: Vec<TargetResult>, failed_over: Option<String>) {
+    let completion = Completion {
+        id: job.id,
+        status: if result.is_ok() { JobStatus::Printed } else { JobStatus::Failed },
+        error: result.as_ref().err().cloned(),
+    };
+    if let Some(done) = job.done {
+        // The relaying request may have given up waiting.
+        let _ = done.send(completion.clone());
+    }
+    if let Some(url) = job.callback {
+        runtime.spawn(callback::send(url, state.config.callback_secret.clone(), completion));
+    }
+    state.history.finish(job.id, result, targets, failed_over);
+}
+
+/// Prints a streamed job part by part as the request body comes in, on the first printer
+/// it's routed to, holding up the queue until the body ends. After a failed part the
+/// rest is dropped, which ends the request. A relayed printer gets each part as a job.
+fn print_stream(state: &AppState, runtime: &Handle, job: &QueuedJob, mut rest: UnboundedReceiver<Receipt>) -> Result<f64, String> {
+    let name = job.printers.first().map_or(MAIN_PRINTER, String::as_str);
+    if let Some(relay) = state.relays.get(name) {
+        let parts = std::iter::once(job.receipt.clone()).chain(std::iter::from_fn(|| rest.blocking_recv()));
+        return parts.map(|part| relay.forward(runtime, job.id, &part)).sum();
+    }
+    let slot = if name == MAIN_PRINTER { Some(&state.printer) } else { state.printers.get(name) };
+    let Some(slot) = slot else {
+        return Err(format!("no such printer {}", name));
+    };
+    let profile = slot.profile();
+    let mut mm = 0.0;
+    for part in std::iter::once(job.receipt.clone()).chain(std::iter::from_fn(|| rest.blocking_recv())) {
+        let mut printer = slot.acquire();
+        if printer.is_none() && name != MAIN_PRINTER {
+            return Err("not connected".to_owned());
+        }
+        let part = part.laid_out_for(profile);
+        part.print(&mut printer, profile).map_err(|e| {
+            eprintln!("Failed to print streamed job #{}: {:?}", job.id, e);
+            if matches!(e, PrinterError::Io(_)) {
+                slot.disconnect();
+            }
+            e.to_string()
+        })?;
+        mm += part.paper_mm();
+    }
+    if name == MAIN_PRINTER {
+        state.paper.record(mm);
+    }
+    Ok(mm)
+}
+
+/// Prints `job` on the printer called `name` from `PRINTERS` or `RELAYS`, laid out for its
+/// profile, failing rather than falling back to stdout when it isn't connected.
+fn print_named(state: &AppState, runtime: &Handle, name: &str, job: &QueuedJob) -> Result<f64, String> {
+    if let Some(relay) = state.relays.get(name) {
+        return relay.forward(runtime, job.id, &job.receipt);
+    }
+    let Some(slot) = state.printers.get(name) else {
+        return Err("no such printer".to_owned());
+    };
+    let mut printer = slot.acquire();
+    if printer.is_none() {
+        return Err("not connected".to_owned());
+    }
+    let receipt = job.receipt.laid_out_for(slot.profile());
+    receipt.print(&mut printer, slot.profile()).map(|()| receipt.paper_mm()).map_err(|e| {
+        eprintln!("Failed to print job #{} on {}: {:?}", job.id, name, e);
+        if matches!(e, PrinterError::Io(_)) {
+            slot.disconnect();
+        }
+        e.to_string()
+    })
+}
@@ -0,0 +1,44 @@
+//! A quote of the day, from a local file of favourites or ZenQuotes.
+
+use crate::error::JobError;
+use chrono::{Datelike, Local};
+use serde::Deserialize;
+use std::path::Path;
+
+const API_URL: &str = "https://zenquotes.io/api/today";
+
+#[derive(Deserialize)]
+struct ZenQuote {
+    /// The quote.
+    q: String,
+    /// Its author.
+    a: String,
+}
+
+/// Today's quote and its author, if known. `file` has one quote per line, optionally
+/// followed by ` -- Author`, and is cycled through by day of the year; without one,
+/// the quote comes from ZenQuotes (or `url`).
+pub async fn today(file: Option<&Path>, url: Option<&str>) -> Result<(String, Option<String>), JobError> {
+    if let Some(file) = file {
+        let text = std::fs::read_to_string(file).map_err(|e| JobError::Upstream(format!("{}: {}", file.display(), e)))?;
+        let quotes: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).collect();
+        if quotes.is_empty() {
+            return Err(JobError::Upstream(format!("no quotes in {}", file.display())));
+        }
+        let quote = quotes[Local::now().ordinal0() as usize % quotes.len()];
+        return Ok(match quote.rsplit_once(" -- ") {
+            Some((quote, author)) => (quote.to_owned(), Some(author.to_owned())),
+            None => (quote.to_owned(), None),
+        });
+    }
+
+    let quotes = reqwest::get(url.unwrap_or(API_URL))
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<Vec<ZenQuote>>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+    let quote = quotes.into_iter().next().ok_or_else(|| JobError::Upstream("no quote today".to_owned()))?;
+    Ok((quote.q, Some(quote.a).filter(|author| !author.is_empty())))
+}
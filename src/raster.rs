@@ -0,0 +1,418 @@
+use crate::printer::DOTS_PER_LINE;
+use image::{DynamicImage, GrayImage, Luma, imageops::FilterType};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// A 1-bit image where `true` is a printed (black) dot.
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Bitmap {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = on;
+        }
+    }
+
+    /// Black and white pixels, for saving as a PNG.
+    pub fn to_gray(&self) -> GrayImage {
+        GrayImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            Luma([if self.get(x as usize, y as usize) { 0 } else { 255 }])
+        })
+    }
+
+    /// Reads back an image saved by [`Bitmap::to_gray`]; anything darker than mid-grey is black.
+    pub fn from_gray(gray: &GrayImage) -> Self {
+        let mut bitmap = Bitmap::new(gray.width() as usize, gray.height() as usize);
+        for (x, y, pixel) in gray.enumerate_pixels() {
+            bitmap.set(x as usize, y as usize, pixel.0[0] < 128);
+        }
+        bitmap
+    }
+
+    /// `parts` one below the other, as wide as the widest.
+    pub fn stacked(parts: &[Bitmap]) -> Self {
+        let width = parts.iter().map(|part| part.width).max().unwrap_or(0);
+        let mut bitmap = Bitmap::new(width, parts.iter().map(|part| part.height).sum());
+        let mut top = 0;
+        for part in parts {
+            for y in 0..part.height {
+                let row = (top + y) * width;
+                bitmap.pixels[row..row + part.width].copy_from_slice(&part.pixels[y * part.width..(y + 1) * part.width]);
+            }
+            top += part.height;
+        }
+        bitmap
+    }
+
+    /// The image turned a quarter turn clockwise, or anticlockwise without `clockwise`.
+    pub fn quarter_turn(&self, clockwise: bool) -> Self {
+        let mut turned = Bitmap::new(self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (tx, ty) = if clockwise { (self.height - 1 - y, x) } else { (y, self.width - 1 - x) };
+                turned.set(tx, ty, self.get(x, y));
+            }
+        }
+        turned
+    }
+
+    /// Rows top to bottom, 8 dots per byte with the leftmost dot in the high bit.
+    fn raster_data(&self) -> Vec<u8> {
+        let bytes_per_row = self.width.div_ceil(8);
+        let mut data = Vec::with_capacity(bytes_per_row * self.height);
+        for y in 0..self.height {
+            for byte in 0..bytes_per_row {
+                let mut b = 0u8;
+                for bit in 0..8 {
+                    if self.get(byte * 8 + bit, y) {
+                        b |= 0x80 >> bit;
+                    }
+                }
+                data.push(b);
+            }
+        }
+        data
+    }
+
+    /// Encodes the image as a `GS v 0` raster bit image command.
+    pub fn to_escpos(&self) -> Vec<u8> {
+        let bytes_per_row = self.width.div_ceil(8);
+        let mut cmd = vec![
+            0x1d,
+            b'v',
+            b'0',
+            0,
+            (bytes_per_row & 0xff) as u8,
+            (bytes_per_row >> 8) as u8,
+            (self.height & 0xff) as u8,
+            (self.height >> 8) as u8,
+        ];
+        cmd.extend(self.raster_data());
+        cmd
+    }
+
+    /// Encodes a `GS 8 L` function 67 command that stores the image in the printer's
+    /// NV graphics memory under `key`. The long form is needed since a full-width
+    /// logo easily passes the 64K limit of `GS ( L`.
+    pub fn to_nv_definition(&self, key: [u8; 2]) -> Vec<u8> {
+        let data = self.raster_data();
+        // m fn a kc1 kc2 b xL xH yL yH c, then the data.
+        let len = (11 + data.len()) as u32;
+        let mut cmd = vec![0x1d, b'8', b'L'];
+        cmd.extend(len.to_le_bytes());
+        cmd.extend([0x30, 67, 0x30, key[0], key[1], 1]);
+        cmd.extend((self.width as u16).to_le_bytes());
+        cmd.extend((self.height as u16).to_le_bytes());
+        cmd.push(0x31);
+        cmd.extend(data);
+        cmd
+    }
+}
+
+/// `GS ( L` function 69: prints the NV graphic stored under `key` at normal size.
+pub fn nv_print_command(key: [u8; 2]) -> Vec<u8> {
+    vec![0x1d, b'(', b'L', 6, 0, 0x30, 69, key[0], key[1], 1, 1]
+}
+
+/// `GS ( L` function 66: deletes the NV graphic stored under `key`.
+pub fn nv_delete_command(key: [u8; 2]) -> Vec<u8> {
+    vec![0x1d, b'(', b'L', 4, 0, 0x30, 66, key[0], key[1]]
+}
+
+/// A moon icon: outlined disc with the unlit part filled in, lit side on the
+/// right while waxing as seen from the northern hemisphere.
+pub fn moon_icon(phase: f64, size: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(size, size);
+    let r = size as f64 / 2.0 - 1.0;
+    let c = size as f64 / 2.0;
+    let terminator = (2.0 * std::f64::consts::PI * phase).cos();
+
+    for y in 0..size {
+        for x in 0..size {
+            let nx = (x as f64 + 0.5 - c) / r;
+            let ny = (y as f64 + 0.5 - c) / r;
+            let d = nx * nx + ny * ny;
+            if d > 1.0 {
+                continue;
+            }
+            let outline = d > (1.0 - 2.5 / r).powi(2);
+            let w = (1.0 - ny * ny).sqrt();
+            let lit = if phase < 0.5 { nx >= w * terminator } else { nx <= -w * terminator };
+            bitmap.set(x, y, outline || !lit);
+        }
+    }
+    bitmap
+}
+
+/// 3x5 glyphs for chart labels, one row per string, `#` for a dot.
+fn glyph(c: char) -> Option<[&'static str; 5]> {
+    Some(match c {
+        '0' => ["###", "#.#", "#.#", "#.#", "###"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["###", "..#", "###", "#..", "###"],
+        '3' => ["###", "..#", "###", "..#", "###"],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' => ["###", "#..", "###", "..#", "###"],
+        '6' => ["###", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", "..#", "..#", "..#"],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "###"],
+        '-' => ["...", "...", "###", "...", "..."],
+        'C' => ["###", "#..", "#..", "#..", "###"],
+        'F' => ["###", "#..", "##.", "#..", "#.."],
+        _ => return None,
+    })
+}
+
+/// Dots per glyph dot in chart labels.
+const LABEL_SCALE: usize = 2;
+/// Label character width, including the gap after it.
+const LABEL_ADVANCE: usize = 4 * LABEL_SCALE;
+const LABEL_HEIGHT: usize = 5 * LABEL_SCALE;
+
+fn draw_label(bitmap: &mut Bitmap, text: &str, x: usize, y: usize) {
+    for (i, c) in text.chars().enumerate() {
+        let Some(rows) = glyph(c) else { continue };
+        for (gy, row) in rows.iter().enumerate() {
+            for (gx, dot) in row.chars().enumerate() {
+                if dot != '#' {
+                    continue;
+                }
+                for dy in 0..LABEL_SCALE {
+                    for dx in 0..LABEL_SCALE {
+                        bitmap.set(x + i * LABEL_ADVANCE + gx * LABEL_SCALE + dx, y + gy * LABEL_SCALE + dy, true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws a line `thickness` dots thick from `(x0, y0)` to `(x1, y1)`.
+fn draw_line(bitmap: &mut Bitmap, (x0, y0): (f64, f64), (x1, y1): (f64, f64), thickness: usize) {
+    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        let t = step as f64 / steps as f64;
+        let x = (x0 + (x1 - x0) * t).round() as usize;
+        let y = (y0 + (y1 - y0) * t).round() as usize;
+        for dy in 0..thickness {
+            for dx in 0..thickness {
+                bitmap.set(x + dx, y + dy, true);
+            }
+        }
+    }
+}
+
+/// A contribution calendar: one column of seven squares per week, Sunday at the top.
+/// Level 0 is an outlined square and levels 1-4 are filled ever more densely; days
+/// outside the calendar (`None`) are left blank.
+pub fn contribution_grid(weeks: &[[Option<usize>; 7]], width: usize) -> Bitmap {
+    let pitch = (width / weeks.len().max(1)).clamp(4, 16);
+    let size = pitch - 2;
+    let mut bitmap = Bitmap::new(pitch * weeks.len(), pitch * 7);
+    for (column, week) in weeks.iter().enumerate() {
+        for (row, level) in week.iter().enumerate() {
+            let Some(level) = *level else { continue };
+            let (left, top) = (column * pitch, row * pitch);
+            for y in 0..size {
+                for x in 0..size {
+                    let edge = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+                    let fill = match level {
+                        0 => false,
+                        1 => x % 2 == 0 && y % 2 == 0,
+                        2 => (x + y) % 2 == 0,
+                        3 => x % 2 == 0 || y % 2 == 0,
+                        _ => true,
+                    };
+                    bitmap.set(left + x, top + y, edge || fill);
+                }
+            }
+        }
+    }
+    bitmap
+}
+
+/// A line chart of hourly `temps` in `degrees` (`C` or `F`), `width` by `height` dots: the
+/// curve over a dotted grid, with the low and high on the left and every sixth hour along
+/// the bottom.
+pub fn temperature_chart(temps: &[f64], degrees: &str, width: usize, height: usize) -> Bitmap {
+    let mut bitmap = Bitmap::new(width, height);
+    let low = temps.iter().copied().fold(f64::INFINITY, f64::min).floor();
+    let high = temps.iter().copied().fold(f64::NEG_INFINITY, f64::max).ceil();
+    let (low_label, high_label) = (format!("{:.0}{}", low, degrees), format!("{:.0}{}", high, degrees));
+    let left = low_label.len().max(high_label.len()) * LABEL_ADVANCE + 4;
+    let top = LABEL_HEIGHT / 2;
+    let bottom = height - LABEL_HEIGHT - 6;
+    let right = width - 2;
+    let hours = temps.len().max(2) - 1;
+    let x_at = |hour: f64| left as f64 + hour * (right - left) as f64 / hours as f64;
+    let range = (high - low).max(1.0);
+    let y_at = |temp: f64| bottom as f64 - (temp - low) / range * (bottom - top) as f64;
+
+    draw_label(&mut bitmap, &high_label, 0, top - LABEL_HEIGHT / 2);
+    draw_label(&mut bitmap, &low_label, 0, bottom - LABEL_HEIGHT / 2);
+    for y in top..=bottom {
+        bitmap.set(left, y, true);
+    }
+    for x in left..=right {
+        bitmap.set(x, bottom, true);
+        // Dotted rule at the high.
+        if x % 4 == 0 {
+            bitmap.set(x, top, true);
+        }
+    }
+    for hour in (0..=hours).step_by(6) {
+        let x = x_at(hour as f64) as usize;
+        for y in top..bottom {
+            if y % 4 == 0 {
+                bitmap.set(x, y, true);
+            }
+        }
+        let label = hour.to_string();
+        let label_width = label.len() * LABEL_ADVANCE;
+        let label_x = x.saturating_sub(label_width / 2).min(width - label_width);
+        draw_label(&mut bitmap, &label, label_x, bottom + 4);
+    }
+    for (hour, pair) in temps.windows(2).enumerate() {
+        let from = (x_at(hour as f64), y_at(pair[0]));
+        let to = (x_at(hour as f64 + 1.0), y_at(pair[1]));
+        draw_line(&mut bitmap, (from.0, from.1 - 1.0), (to.0, to.1 - 1.0), 3);
+    }
+    bitmap
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    /// Black below mid-grey; best for logos and line art.
+    #[default]
+    Threshold,
+    FloydSteinberg,
+    /// Diffuses only part of the error, keeping more contrast than Floyd-Steinberg.
+    Atkinson,
+    /// A 4x4 Bayer pattern; regular texture, no error artifacts.
+    Ordered,
+}
+
+/// How an image fills a `width` x `height` box.
+#[derive(Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale to fit inside the box, keeping the whole image.
+    #[default]
+    Contain,
+    /// Scale to fill the box and crop what sticks out, keeping the centre.
+    Cover,
+}
+
+/// How to size a photo or logo and turn it into black and white dots.
+#[derive(Clone, Copy, Default)]
+pub struct ImageOptions {
+    /// Target width in dots, at most the paper width. Defaults to the image's own width.
+    pub width: Option<u32>,
+    /// Printable width in dots, if not 80mm paper.
+    pub paper_width: Option<u32>,
+    /// Target height in dots; only used together with `fit`.
+    pub height: Option<u32>,
+    pub fit: Fit,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    pub rotate: u16,
+    pub dither: Dither,
+    /// Added to every pixel's brightness, -255 to 255.
+    pub brightness: i32,
+    /// Percent contrast change; positive values push greys towards black and white.
+    pub contrast: f32,
+}
+
+const BAYER_4X4: [[f32; 4]; 4] = [[0.0, 8.0, 2.0, 10.0], [12.0, 4.0, 14.0, 6.0], [3.0, 11.0, 1.0, 9.0], [15.0, 7.0, 13.0, 5.0]];
+
+/// Spreads each pixel's rounding error onto the neighbours given as (dx, dy, weight).
+fn diffuse(levels: &mut [f32], width: usize, height: usize, kernel: &[(isize, usize, f32)], bitmap: &mut Bitmap) {
+    for y in 0..height {
+        for x in 0..width {
+            let old = levels[y * width + x];
+            let black = old < 128.0;
+            bitmap.set(x, y, black);
+            let error = old - if black { 0.0 } else { 255.0 };
+            for &(dx, dy, weight) in kernel {
+                let (nx, ny) = (x as isize + dx, y + dy);
+                if nx >= 0 && (nx as usize) < width && ny < height {
+                    levels[ny * width + nx as usize] += error * weight;
+                }
+            }
+        }
+    }
+}
+
+/// Rotates, then scales (and for `cover`, crops) to the requested size, never wider than the paper.
+fn transform(image: DynamicImage, options: &ImageOptions) -> DynamicImage {
+    let image = match options.rotate % 360 {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image,
+    };
+    let max_width = options.paper_width.unwrap_or(DOTS_PER_LINE as u32);
+    let width = options.width.unwrap_or(image.width()).clamp(1, max_width);
+    match (options.height, options.fit) {
+        (Some(height), Fit::Cover) => image.resize_to_fill(width, height.max(1), FilterType::Triangle),
+        (Some(height), Fit::Contain) => image.resize(width, height.max(1), FilterType::Triangle),
+        (None, _) if width != image.width() => image.resize(width, u32::MAX, FilterType::Triangle),
+        (None, _) => image,
+    }
+}
+
+/// Decodes a PNG, JPEG or GIF, sizes it for the paper and dithers it to black and white.
+pub fn decode_image(bytes: &[u8], options: &ImageOptions) -> Result<Bitmap, image::ImageError> {
+    let image = transform(image::load_from_memory(bytes)?, options);
+    let mut gray = image.to_luma8();
+    if options.brightness != 0 {
+        gray = image::imageops::brighten(&gray, options.brightness);
+    }
+    if options.contrast != 0.0 {
+        gray = image::imageops::contrast(&gray, options.contrast);
+    }
+
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let mut bitmap = Bitmap::new(width, height);
+    let mut levels: Vec<f32> = gray.pixels().map(|p| p.0[0] as f32).collect();
+    match options.dither {
+        Dither::Threshold => {
+            for (i, level) in levels.iter().enumerate() {
+                bitmap.set(i % width, i / width, *level < 128.0);
+            }
+        }
+        Dither::FloydSteinberg => {
+            let kernel = [(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)];
+            diffuse(&mut levels, width, height, &kernel, &mut bitmap);
+        }
+        Dither::Atkinson => {
+            let kernel = [(1, 0, 0.125), (2, 0, 0.125), (-1, 1, 0.125), (0, 1, 0.125), (1, 1, 0.125), (0, 2, 0.125)];
+            diffuse(&mut levels, width, height, &kernel, &mut bitmap);
+        }
+        Dither::Ordered => {
+            for (i, level) in levels.iter().enumerate() {
+                let (x, y) = (i % width, i / width);
+                let threshold = (BAYER_4X4[y % 4][x % 4] + 0.5) * 16.0;
+                bitmap.set(x, y, *level < threshold);
+            }
+        }
+    }
+    Ok(bitmap)
+}
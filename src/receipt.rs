@@ -0,0 +1,1011 @@
+use crate::{
+    codepage,
+    config::Config,
+    printer::{CHARS_PER_LINE, DevicePrinter, DOTS_PER_MM},
+    profile::{CommandSet, Cutter, Profile},
+    raster::{self, Bitmap},
+    redact::{self, Redaction},
+    star,
+    typeset::{self, Span},
+};
+use std::{borrow::Cow, io::IsTerminal};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use escpos::{
+    errors::PrinterError,
+    utils::{JustifyMode, QRCodeCorrectionLevel, QRCodeModel, QRCodeOption, UnderlineMode},
+};
+
+/// Default line spacing of 1/6 inch.
+const LINE_MM: f64 = 25.4 / 6.0;
+/// Paper fed past the print head to reach the cutter.
+const CUT_FEED_MM: f64 = 15.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+enum Op {
+    Text(String),
+    Justify(#[serde(with = "JustifyModeDef")] JustifyMode),
+    PartialCut,
+    /// Sounds the buzzer this many times, on printers that have one.
+    Beep(u8),
+    /// Character scale, 1-8 times the normal width and height.
+    Size(u8, u8),
+    Style(Style),
+    QrCode { data: String, size: u8 },
+    /// Raster image (or NV graphics) command bytes, with text to show instead when printing to stdout.
+    Image { data: Vec<u8>, alt: String },
+}
+
+/// escpos's `JustifyMode`, for serializing archived receipts.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "JustifyMode")]
+#[allow(clippy::upper_case_acronyms)]
+enum JustifyModeDef {
+    LEFT,
+    CENTER,
+    RIGHT,
+}
+
+/// The printer's character font. Font B is narrower, fitting about a third more on a line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Font {
+    #[default]
+    A,
+    B,
+}
+
+impl std::str::FromStr for Font {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim().to_lowercase().as_str() {
+            "a" => Ok(Font::A),
+            "b" => Ok(Font::B),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Character size as `?size=` gives it, e.g. `2x2` or `1x2`, or `3` for 3x3: 1-8 times the
+/// normal width and height.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct CharSize {
+    pub width: u8,
+    pub height: u8,
+}
+
+impl Default for CharSize {
+    fn default() -> Self {
+        CharSize { width: 1, height: 1 }
+    }
+}
+
+impl std::str::FromStr for CharSize {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim().to_lowercase();
+        let (width, height) = s.split_once(['x', '*']).unwrap_or((s.as_str(), s.as_str()));
+        let scale = |n: &str| n.trim().parse().ok().filter(|n| (1..=8).contains(n)).ok_or(());
+        Ok(CharSize {
+            width: scale(width)?,
+            height: scale(height)?,
+        })
+    }
+}
+
+impl TryFrom<String> for CharSize {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse().map_err(|()| format!("invalid size {:?}, expected e.g. 2x2 (each 1-8)", s))
+    }
+}
+
+/// Text emphasis, e.g. from ANSI escape codes.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Style {
+    pub bold: bool,
+    pub underline: bool,
+    /// White on black.
+    pub inverse: bool,
+}
+
+/// Character cells for [`Receipt::rasterize`] and [`Receipt::landscape`], in dots.
+struct LineLayout {
+    cell: usize,
+    height: usize,
+    paper: usize,
+}
+
+/// A receipt's ops taken a printed line at a time, for drawing lines as images.
+enum Piece {
+    /// A line's ops, its text, and the justification it started with.
+    Line { ops: Vec<Op>, spans: Vec<Span>, justify: JustifyMode },
+    /// Anything but text and the state it's printed in, which starts a new line.
+    Op(Op),
+}
+
+fn split_lines(ops: Vec<Op>) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut line = Vec::new();
+    let mut spans: Vec<Span> = Vec::new();
+    let mut line_justify = JustifyMode::LEFT;
+    let (mut justify, mut style, mut scale) = (JustifyMode::LEFT, Style::default(), (1, 1));
+    let end_line = |pieces: &mut Vec<Piece>, line: &mut Vec<Op>, spans: &mut Vec<Span>, justify: JustifyMode| {
+        if !line.is_empty() || !spans.is_empty() {
+            pieces.push(Piece::Line {
+                ops: std::mem::take(line),
+                spans: std::mem::take(spans),
+                justify,
+            });
+        }
+    };
+    for op in ops {
+        match op {
+            Op::Text(text) => {
+                let mut parts = text.split('\n').peekable();
+                while let Some(part) = parts.next() {
+                    let ends_line = parts.peek().is_some();
+                    if spans.is_empty() {
+                        line_justify = justify;
+                    }
+                    if !part.is_empty() {
+                        spans.push(Span { text: part.to_owned(), style, scale });
+                    }
+                    if ends_line {
+                        line.push(Op::Text(format!("{}\n", part)));
+                        end_line(&mut pieces, &mut line, &mut spans, line_justify);
+                    } else if !part.is_empty() {
+                        line.push(Op::Text(part.to_owned()));
+                    }
+                }
+            }
+            Op::Justify(mode) => {
+                justify = mode;
+                line.push(op);
+            }
+            Op::Size(width, height) => {
+                scale = (width, height);
+                line.push(op);
+            }
+            Op::Style(new) => {
+                style = new;
+                line.push(op);
+            }
+            op => {
+                end_line(&mut pieces, &mut line, &mut spans, line_justify);
+                pieces.push(Piece::Op(op));
+            }
+        }
+    }
+    end_line(&mut pieces, &mut line, &mut spans, line_justify);
+    pieces
+}
+
+/// Lines fed past the print head to tear off at, on printers without a cutter.
+const TEAR_FEED_LINES: u8 = 4;
+
+/// A job rendered ahead of time so it can be sent to the printer (or stdout) in one go.
+/// Serializes for the job archive.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Receipt {
+    ops: Vec<Op>,
+    /// Characters per line that the wrapping helpers lay text out for.
+    width: usize,
+    #[serde(default)]
+    font: Font,
+    /// Line feed in dots (ESC 3); `None` leaves the printer's default of 1/6 inch.
+    #[serde(default)]
+    line_spacing: Option<u8>,
+    /// Feed the paper out without cutting at the end.
+    skip_cut: bool,
+    /// Leave off the configured header/footer templates and source footer.
+    plain: bool,
+    /// Leave off the `JOB_METADATA` line, from `?no_header`.
+    no_metadata: bool,
+}
+
+impl Default for Receipt {
+    fn default() -> Self {
+        Receipt {
+            ops: Vec::new(),
+            width: CHARS_PER_LINE,
+            font: Font::A,
+            line_spacing: None,
+            skip_cut: false,
+            plain: false,
+            no_metadata: false,
+        }
+    }
+}
+
+impl Receipt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A receipt laid out for `width` characters per line instead of 80mm paper.
+    pub fn with_width(width: usize) -> Self {
+        Receipt { width, ..Self::default() }
+    }
+
+    /// A receipt in `font`, as wide as its lines are on the profile's paper.
+    pub fn with_font(profile: &Profile, font: Font, line_spacing: Option<u8>) -> Self {
+        Receipt {
+            width: profile.columns(font),
+            font,
+            line_spacing,
+            ..Self::default()
+        }
+    }
+
+    /// A receipt in the configured `PRINTER_FONT` and `LINE_SPACING`.
+    pub fn for_config(config: &Config) -> Self {
+        Self::with_font(&config.printer_profile, config.printer_font, config.line_spacing)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Writes text as-is at the current justification.
+    pub fn text(&mut self, text: &str) {
+        self.ops.push(Op::Text(text.to_owned()));
+    }
+
+    pub fn line_left(&mut self, text: &str) {
+        self.ops.push(Op::Justify(JustifyMode::LEFT));
+        self.ops.push(Op::Text(format!("{}\n", text)));
+    }
+
+    pub fn line_center(&mut self, text: &str) {
+        self.ops.push(Op::Justify(JustifyMode::CENTER));
+        self.ops.push(Op::Text(format!("{}\n", text)));
+    }
+
+    /// A centered line at `scale` times the normal character width and height.
+    pub fn line_large(&mut self, text: &str, scale: u8) {
+        self.ops.push(Op::Justify(JustifyMode::CENTER));
+        self.ops.push(Op::Size(scale, scale));
+        self.ops.push(Op::Text(format!("{}\n", text)));
+        self.ops.push(Op::Size(1, 1));
+    }
+
+    /// A left-aligned line in double-height characters, still the normal width.
+    pub fn line_tall(&mut self, text: &str) {
+        self.ops.push(Op::Justify(JustifyMode::LEFT));
+        self.ops.push(Op::Size(1, 2));
+        self.ops.push(Op::Text(format!("{}\n", text)));
+        self.ops.push(Op::Size(1, 1));
+    }
+
+    /// Lays out what `body` adds at `size` times the normal character width and height,
+    /// wrapped to the fewer characters that then fit on a line.
+    pub fn sized<R>(&mut self, size: CharSize, body: impl FnOnce(&mut Receipt) -> R) -> R {
+        if size == CharSize::default() {
+            return body(self);
+        }
+        let width = self.width;
+        self.width = (width / size.width as usize).max(1);
+        self.ops.push(Op::Size(size.width, size.height));
+        let laid_out = body(self);
+        self.ops.push(Op::Size(1, 1));
+        self.width = width;
+        laid_out
+    }
+
+    /// Word-wraps `text` to the paper width, hard-breaking words that don't fit on a line.
+    pub fn wrapped(&mut self, text: &str) {
+        for line in wrap(text, self.width) {
+            self.line_left(&line);
+        }
+    }
+
+    /// A left-aligned line kept as it is when it fits across the paper, word-wrapped
+    /// otherwise, so lines laid out with spacing for a wide printer still fit a narrow one.
+    pub fn fitted(&mut self, text: &str) {
+        if text_width(text) <= self.width {
+            self.line_left(text);
+        } else {
+            self.wrapped(text);
+        }
+    }
+
+    /// Like [`Receipt::wrapped`], but the first line starts with `prefix` (e.g. "1. " or
+    /// "[ ] ") and continuation lines are indented to line up after it.
+    pub fn hanging(&mut self, prefix: &str, text: &str) {
+        let indent = text_width(prefix);
+        for (i, line) in wrap(text, self.width.saturating_sub(indent).max(1)).iter().enumerate() {
+            if i == 0 {
+                self.line_left(&format!("{}{}", prefix, line));
+            } else {
+                self.line_left(&format!("{}{}", " ".repeat(indent), line));
+            }
+        }
+    }
+
+    /// A centered QR code; `size` is the module size in dots (1-16).
+    pub fn qr_code(&mut self, data: &str, size: u8) {
+        self.ops.push(Op::Justify(JustifyMode::CENTER));
+        self.ops.push(Op::QrCode {
+            data: data.to_owned(),
+            size,
+        });
+    }
+
+    /// A centered bit image; stdout gets `alt` instead.
+    pub fn image(&mut self, bitmap: &Bitmap, alt: &str) {
+        self.image_aligned(bitmap, alt, JustifyMode::CENTER);
+    }
+
+    pub fn image_aligned(&mut self, bitmap: &Bitmap, alt: &str, justify: JustifyMode) {
+        self.ops.push(Op::Justify(justify));
+        self.ops.push(Op::Image {
+            data: bitmap.to_escpos(),
+            alt: alt.to_owned(),
+        });
+    }
+
+    /// A bit image already stored in the printer's NV graphics memory under `key`.
+    pub fn nv_image(&mut self, key: [u8; 2], alt: &str) {
+        self.ops.push(Op::Justify(JustifyMode::CENTER));
+        self.ops.push(Op::Image {
+            data: raster::nv_print_command(key),
+            alt: alt.to_owned(),
+        });
+    }
+
+    /// Sends a command that changes the printer's NV graphics memory; nothing is printed.
+    pub fn nv_command(&mut self, data: Vec<u8>, alt: &str) {
+        self.ops.push(Op::Image { data, alt: alt.to_owned() });
+    }
+
+    /// Sets the emphasis for the text that follows; skipped if it's already in effect.
+    pub fn style(&mut self, style: Style) {
+        let current = self.ops.iter().rev().find_map(|op| match op {
+            Op::Style(style) => Some(*style),
+            _ => None,
+        });
+        if current.unwrap_or_default() != style {
+            self.ops.push(Op::Style(style));
+        }
+    }
+
+    /// Cuts part way through, leaving a tab that keeps the pages together.
+    pub fn partial_cut(&mut self) {
+        self.ops.push(Op::PartialCut);
+    }
+
+    /// Sounds the buzzer `times` times (1-9), on printers that have one.
+    pub fn beep(&mut self, times: u8) {
+        self.ops.push(Op::Beep(times.clamp(1, 9)));
+    }
+
+    pub fn without_cut(&mut self) {
+        self.skip_cut = true;
+    }
+
+    pub fn set_plain(&mut self) {
+        self.plain = true;
+    }
+
+    pub fn is_plain(&self) -> bool {
+        self.plain
+    }
+
+    pub fn without_metadata(&mut self) {
+        self.no_metadata = true;
+    }
+
+    pub fn has_metadata(&self) -> bool {
+        !self.no_metadata
+    }
+
+    /// Puts everything in `header` before what's already on the receipt.
+    pub fn prepend(&mut self, header: Receipt) {
+        self.ops.splice(0..0, header.ops);
+    }
+
+    /// Puts everything in `other` after what's already on the receipt.
+    pub fn append(&mut self, other: Receipt) {
+        self.ops.extend(other.ops);
+    }
+
+    /// Masks what `redactions` match in the receipt's text, and in images' alt text.
+    /// Images and QR codes are left as they are.
+    pub fn redact(&mut self, redactions: &[Redaction]) {
+        if redactions.is_empty() {
+            return;
+        }
+        for op in &mut self.ops {
+            if let Op::Text(text) | Op::Image { alt: text, .. } = op
+                && let Cow::Owned(masked) = redact::redact(text, redactions)
+            {
+                *text = masked;
+            }
+        }
+    }
+
+    /// A copy for a printer with `profile` rather than the one it was laid out for, e.g. a
+    /// narrower one from `PRINTERS`: whole lines too wide for it are word-wrapped, and rules
+    /// such as dividers cut to its width. Images are left as they are.
+    pub fn laid_out_for(&self, profile: &Profile) -> Receipt {
+        let width = profile.columns(self.font);
+        let mut receipt = self.clone();
+        if width >= self.width {
+            return receipt;
+        }
+        // Text is often pushed a piece at a time, e.g. a line and then its line feed.
+        let mut ops: Vec<Op> = Vec::with_capacity(receipt.ops.len());
+        for op in receipt.ops {
+            match (ops.last_mut(), op) {
+                (Some(Op::Text(text)), Op::Text(more)) => text.push_str(&more),
+                (_, op) => ops.push(op),
+            }
+        }
+        receipt.ops = ops;
+        let mut scale = 1;
+        let mut line_start = true;
+        for op in &mut receipt.ops {
+            match op {
+                Op::Size(w, _) => scale = (*w).max(1) as usize,
+                Op::Text(text) => {
+                    let columns = (width / scale).max(1);
+                    let mut refitted = String::new();
+                    let mut parts = text.split('\n').peekable();
+                    while let Some(part) = parts.next() {
+                        let ends_line = parts.peek().is_some();
+                        if line_start && ends_line && text_width(part) > columns {
+                            refitted.push_str(&refit(part, columns).join("\n"));
+                        } else {
+                            refitted.push_str(part);
+                        }
+                        if ends_line {
+                            refitted.push('\n');
+                            line_start = true;
+                        } else if !part.is_empty() {
+                            line_start = false;
+                        }
+                    }
+                    *text = refitted;
+                }
+                _ => {}
+            }
+        }
+        receipt.width = width;
+        receipt
+    }
+
+    /// Redraws lines of text as raster images in the bundled font (see [`typeset`]): every
+    /// line with `all`, otherwise only lines with characters the printer can't print.
+    pub fn rasterize(&mut self, profile: &Profile, all: bool) {
+        let layout = LineLayout {
+            cell: (profile.raster_width / self.width.max(1)).max(1),
+            height: self.line_spacing.map_or(typeset::LINE_DOTS, usize::from),
+            paper: profile.raster_width,
+        };
+        let mut ops = Vec::new();
+        for piece in split_lines(std::mem::take(&mut self.ops)) {
+            match piece {
+                Piece::Line { ops: mut line, spans, justify } => {
+                    let missing = spans.iter().flat_map(|span| span.text.chars()).any(|c| !codepage::printable(c, profile));
+                    if spans.is_empty() || !(all || missing) {
+                        ops.append(&mut line);
+                    } else {
+                        let bitmap = typeset::line(&spans, layout.cell, layout.height, layout.paper, justify);
+                        let alt: String = spans.iter().map(|span| span.text.as_str()).collect();
+                        // Style and size changes still apply to the lines after.
+                        ops.extend(line.into_iter().filter(|op| !matches!(op, Op::Text(_))));
+                        ops.push(Op::Image { data: bitmap.to_escpos(), alt });
+                    }
+                }
+                Piece::Op(op) => ops.push(op),
+            }
+        }
+        self.ops = ops;
+    }
+
+    /// Lays out lines `width` characters wide, for [`Receipt::landscape`] to turn.
+    pub fn widen(&mut self, width: usize) {
+        self.width = width;
+    }
+
+    /// Turns the receipt's text a quarter turn to run along the paper, for lines too wide
+    /// to print across it (`?rotate=90`, or `270` the other way round). Lines are drawn in
+    /// the bundled font at their usual size and grouped into strips as wide as the paper,
+    /// which read left to right once the paper is turned to match. Lines go back to the
+    /// font's usual width afterwards, for the header and footer.
+    pub fn landscape(&mut self, profile: &Profile, degrees: u16) {
+        let columns = profile.columns(self.font).max(1);
+        let layout = LineLayout {
+            cell: (profile.raster_width / columns).max(1),
+            height: self.line_spacing.map_or(typeset::LINE_DOTS, usize::from),
+            paper: profile.raster_width,
+        };
+        let clockwise = degrees != 270;
+        let pieces = split_lines(std::mem::take(&mut self.ops));
+        // As long as the widest line, rather than the widest it could have been.
+        let widest = pieces
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Line { spans, .. } => Some(spans.iter().flat_map(|span| span.text.chars().map(|c| char_width(c) * span.scale.0.max(1) as usize)).sum::<usize>()),
+                Piece::Op(_) => None,
+            })
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut strips: Vec<Vec<Op>> = Vec::new();
+        // The strip being built up: its lines and their text, and the ops to go after it.
+        let mut lines: Vec<Bitmap> = Vec::new();
+        let mut alt = Vec::new();
+        let mut after = Vec::new();
+        let end_strip = |lines: &mut Vec<Bitmap>, alt: &mut Vec<String>, after: &mut Vec<Op>| {
+            let mut strip = Vec::new();
+            if !lines.is_empty() {
+                let turned = Bitmap::stacked(lines).quarter_turn(clockwise);
+                // The first line against the edge the text's top faces.
+                strip.push(Op::Justify(if clockwise { JustifyMode::RIGHT } else { JustifyMode::LEFT }));
+                strip.push(Op::Image {
+                    data: turned.to_escpos(),
+                    alt: alt.iter().map(|line| format!("{}\n", line)).collect(),
+                });
+            }
+            strip.append(after);
+            lines.clear();
+            alt.clear();
+            Some(strip).filter(|strip| !strip.is_empty())
+        };
+        for piece in pieces {
+            match piece {
+                Piece::Line { ops, spans, justify } => {
+                    // State changes with no text of their own, e.g. at the very end.
+                    if !ops.iter().any(|op| matches!(op, Op::Text(_))) {
+                        after.extend(ops);
+                        continue;
+                    }
+                    let bitmap = typeset::line(&spans, layout.cell, layout.height, widest * layout.cell, justify);
+                    if lines.iter().map(|line| line.height).sum::<usize>() + bitmap.height > layout.paper && !lines.is_empty() {
+                        strips.extend(end_strip(&mut lines, &mut alt, &mut after));
+                    }
+                    lines.push(bitmap);
+                    alt.push(spans.iter().map(|span| span.text.as_str()).collect());
+                    after.extend(ops.into_iter().filter(|op| !matches!(op, Op::Text(_))));
+                }
+                Piece::Op(op) => {
+                    strips.extend(end_strip(&mut lines, &mut alt, &mut after));
+                    strips.push(vec![op]);
+                }
+            }
+        }
+        strips.extend(end_strip(&mut lines, &mut alt, &mut after));
+        // Turned the other way, the first strip reads leftmost once it's printed last.
+        if !clockwise {
+            strips.reverse();
+        }
+        self.ops = strips.concat();
+        self.ops.push(Op::Justify(JustifyMode::LEFT));
+        self.width = columns;
+    }
+
+    /// Estimated paper the receipt takes: text lines at their scaled height, images by
+    /// their dot height, and the feed to the cutter. Images stored in NV memory aren't counted.
+    pub fn paper_mm(&self) -> f64 {
+        let line_mm = self.line_spacing.map_or(LINE_MM, |dots| dots as f64 / DOTS_PER_MM);
+        let mut line_height = 1.0;
+        let mut mm = 0.0;
+        for op in &self.ops {
+            match op {
+                Op::Size(_, height) => line_height = *height as f64,
+                Op::Text(text) => mm += text.matches('\n').count() as f64 * line_mm * line_height,
+                // GS v 0 m xL xH yL yH
+                Op::Image { data, .. } if data.starts_with(b"\x1dv0") && data.len() >= 8 => {
+                    mm += u16::from_le_bytes([data[6], data[7]]) as f64 / DOTS_PER_MM;
+                }
+                Op::QrCode { data, size } => {
+                    // Roughly one QR version (4 modules) per 20 bytes, plus the quiet zone.
+                    let modules = 21 + 4 * (data.len() / 20) + 8;
+                    mm += (modules * *size as usize) as f64 / DOTS_PER_MM + LINE_MM;
+                }
+                _ => {}
+            }
+        }
+        if !self.skip_cut {
+            mm += CUT_FEED_MM;
+        }
+        mm
+    }
+
+    pub fn divider(&mut self) {
+        self.line_left(&"-".repeat(self.width));
+    }
+
+    /// Sends the receipt to the printer and cuts, or writes it to stdout when no printer is
+    /// attached. `profile` says which tables, cuts and buzzer the printer has.
+    pub fn print(&self, printer: &mut Option<DevicePrinter>, profile: &Profile) -> Result<(), PrinterError> {
+        let Some(printer) = printer else {
+            let ansi = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            print!("{}", self.simulate(ansi));
+            return Ok(());
+        };
+
+        match profile.commands {
+            CommandSet::EscPos => self.send_escpos(printer, profile)?,
+            CommandSet::StarLine => self.send_star(printer, profile)?,
+        }
+        eprintln!("Flushing print buffer...");
+        printer.print()?;
+        eprintln!("Print successful");
+        Ok(())
+    }
+
+    /// How the receipt comes out on paper, for the stdout fallback: every line justified and
+    /// padded to the paper width between its edges, with bold, underline and inverse as
+    /// ANSI escapes when `ansi` is set. Double-width text is spaced out; cuts are dashed.
+    fn simulate(&self, ansi: bool) -> String {
+        let edge = format!("+{}+\n", "-".repeat(self.width));
+        let mut out = edge.clone();
+        let mut line = SimulatedLine::default();
+        let mut justify = JustifyMode::LEFT;
+        let mut style = Style::default();
+        let mut scale = 1;
+        for op in &self.ops {
+            match op {
+                Op::Text(text) => {
+                    let mut parts = text.split('\n');
+                    line.push(parts.next().unwrap_or_default(), style, scale, justify);
+                    for part in parts {
+                        out.push_str(&line.finish(self.width, ansi));
+                        line.push(part, style, scale, justify);
+                    }
+                }
+                Op::Justify(mode) => justify = *mode,
+                Op::Size(width, _) => scale = (*width).max(1) as usize,
+                Op::Style(new) => style = *new,
+                Op::QrCode { data, .. } => {
+                    out.push_str(&line.finish_started(self.width, ansi));
+                    line.push(&format!("[QR: {}]", data), Style::default(), 1, justify);
+                    out.push_str(&line.finish(self.width, ansi));
+                }
+                Op::Image { alt, .. } => {
+                    out.push_str(&line.finish_started(self.width, ansi));
+                    line.push(alt, Style::default(), 1, justify);
+                    out.push_str(&line.finish(self.width, ansi));
+                }
+                Op::PartialCut => {
+                    out.push_str(&line.finish_started(self.width, ansi));
+                    out.push_str(&format!("+{:<width$}+\n", "- ".repeat(self.width / 2), width = self.width));
+                }
+                Op::Beep(_) => {}
+            }
+        }
+        out.push_str(&line.finish_started(self.width, ansi));
+        out.push_str(&edge);
+        out
+    }
+
+    /// The receipt as plain text, laid out as on paper; cuts are dashed lines.
+    pub fn to_text(&self) -> String {
+        let paper = self.simulate(false);
+        let rows: Vec<&str> = paper.lines().collect();
+        // Every row is framed by one ASCII edge character on each side; a row too short to
+        // be framed (e.g. a bare edge) is blank.
+        let mut text: String = rows[1..rows.len() - 1]
+            .iter()
+            .map(|row| format!("{}\n", row.get(1..row.len().saturating_sub(1)).unwrap_or_default().trim_end()))
+            .collect();
+        text.truncate(text.trim_end().len());
+        text.push('\n');
+        text
+    }
+
+    fn send_escpos(&self, printer: &mut DevicePrinter, profile: &Profile) -> Result<(), PrinterError> {
+        if self.font == Font::B {
+            printer.font(escpos::utils::Font::B)?;
+        }
+        if let Some(dots) = self.line_spacing {
+            printer.line_spacing(dots)?;
+        }
+        // Another job may have left the printer on any table.
+        let mut page = None;
+        for op in &self.ops {
+            match op {
+                Op::Text(text) => printer.custom(&codepage::encode(text, &mut page, profile))?,
+                Op::Justify(mode) => printer.justify(*mode)?,
+                Op::Size(width, height) => printer.size(*width, *height)?,
+                Op::Style(style) => printer
+                    .bold(style.bold)?
+                    .underline(if style.underline { UnderlineMode::Single } else { UnderlineMode::None })?
+                    .reverse(style.inverse)?,
+                Op::PartialCut if profile.cutter == Cutter::None => printer.feeds(TEAR_FEED_LINES)?,
+                Op::PartialCut => printer.partial_cut()?,
+                // ESC B n t: n beeps of t x 50ms.
+                Op::Beep(times) if profile.buzzer => printer.custom(&[0x1b, b'B', *times, 2])?,
+                Op::Beep(_) => printer,
+                Op::QrCode { data, size } => printer.qrcode_option(
+                    data,
+                    QRCodeOption::new(QRCodeModel::Model2, *size, QRCodeCorrectionLevel::M),
+                )?,
+                Op::Image { data, .. } => printer.custom(data)?,
+            };
+        }
+        // Back to the defaults, which the next job assumes.
+        if self.font == Font::B {
+            printer.font(escpos::utils::Font::A)?;
+        }
+        if self.line_spacing.is_some() {
+            printer.reset_line_spacing()?;
+        }
+        match profile.cutter {
+            _ if self.skip_cut => printer,
+            Cutter::Full => printer.cut()?,
+            Cutter::Partial => printer.partial_cut()?,
+            Cutter::None => printer.feeds(TEAR_FEED_LINES)?,
+        };
+        Ok(())
+    }
+
+    /// The same ops as [`Receipt::send_escpos`] in Star Line Mode commands.
+    fn send_star(&self, printer: &mut DevicePrinter, profile: &Profile) -> Result<(), PrinterError> {
+        if self.font == Font::B {
+            printer.custom(&star::font(Font::B))?;
+        }
+        if let Some(dots) = self.line_spacing {
+            printer.custom(&star::line_spacing(Some(dots)))?;
+        }
+        let mut page = None;
+        for op in &self.ops {
+            let command = match op {
+                Op::Text(text) => codepage::encode(text, &mut page, profile),
+                Op::Justify(mode) => star::justify(*mode),
+                Op::Size(width, height) => star::size(*width, *height),
+                Op::Style(style) => star::style(*style),
+                Op::PartialCut if profile.cutter == Cutter::None => star::feed(TEAR_FEED_LINES),
+                Op::PartialCut => star::cut(true),
+                Op::Beep(times) if profile.buzzer => star::beep(*times),
+                Op::Beep(_) => continue,
+                Op::QrCode { data, size } => star::qr_code(data, *size),
+                Op::Image { data, alt } => match star::raster(data) {
+                    Some(command) => command,
+                    None => {
+                        eprintln!("Skipping {}: NV graphics are ESC/POS only", alt);
+                        continue;
+                    }
+                },
+            };
+            printer.custom(&command)?;
+        }
+        if self.font == Font::B {
+            printer.custom(&star::font(Font::A))?;
+        }
+        if self.line_spacing.is_some() {
+            printer.custom(&star::line_spacing(None))?;
+        }
+        match profile.cutter {
+            _ if self.skip_cut => printer,
+            Cutter::Full => printer.custom(&star::cut(false))?,
+            Cutter::Partial => printer.custom(&star::cut(true))?,
+            Cutter::None => printer.custom(&star::feed(TEAR_FEED_LINES))?,
+        };
+        Ok(())
+    }
+}
+
+/// Stands in for the second column of a double-width character.
+const WIDE_FILLER: char = '\0';
+
+/// A line of [`Receipt::simulate`] output being built up, one styled cell per column.
+#[derive(Default)]
+struct SimulatedLine {
+    cells: Vec<(char, Style)>,
+    /// As set when the line was started, which is when printers apply it.
+    justify: JustifyMode,
+}
+
+impl SimulatedLine {
+    fn push(&mut self, text: &str, style: Style, scale: usize, justify: JustifyMode) {
+        if self.cells.is_empty() {
+            self.justify = justify;
+        }
+        for c in text.chars() {
+            let width = char_width(c);
+            self.cells.push((c, style));
+            // The second column of a double-width character, which it covers itself.
+            self.cells.extend(std::iter::repeat_n((WIDE_FILLER, style), width - 1));
+            self.cells.extend(std::iter::repeat_n((' ', style), width * (scale - 1)));
+        }
+    }
+
+    /// The line as one or more rows of the paper (text past the edge wraps, as it does on
+    /// the printer), leaving it empty for the next.
+    fn finish(&mut self, width: usize, ansi: bool) -> String {
+        let cells = std::mem::take(&mut self.cells);
+        let mut rows = String::new();
+        for row in cells.chunks(width.max(1)).chain(cells.is_empty().then_some(&[][..])) {
+            let pad = width.saturating_sub(row.len());
+            let left = match self.justify {
+                JustifyMode::LEFT => 0,
+                JustifyMode::CENTER => pad / 2,
+                JustifyMode::RIGHT => pad,
+            };
+            rows.push('|');
+            rows.push_str(&" ".repeat(left));
+            for run in row.chunk_by(|a, b| a.1 == b.1) {
+                let text: String = run.iter().map(|(c, _)| *c).filter(|&c| c != WIDE_FILLER).collect();
+                let style = run[0].1;
+                if !ansi || style == Style::default() {
+                    rows.push_str(&text);
+                    continue;
+                }
+                let codes: Vec<&str> = [(style.bold, "1"), (style.underline, "4"), (style.inverse, "7")]
+                    .iter()
+                    .filter(|(on, _)| *on)
+                    .map(|(_, code)| *code)
+                    .collect();
+                rows.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text));
+            }
+            rows.push_str(&" ".repeat(pad - left));
+            rows.push_str("|\n");
+        }
+        rows
+    }
+
+    /// Like [`SimulatedLine::finish`], but nothing for a line with nothing on it yet.
+    fn finish_started(&mut self, width: usize, ansi: bool) -> String {
+        if self.cells.is_empty() { String::new() } else { self.finish(width, ansi) }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+pub struct Column {
+    pub width: usize,
+    pub align: Align,
+}
+
+impl Column {
+    pub const fn left(width: usize) -> Self {
+        Column { width, align: Align::Left }
+    }
+
+    pub const fn right(width: usize) -> Self {
+        Column { width, align: Align::Right }
+    }
+}
+
+/// Lays out one table row, padding each cell to its column width and truncating
+/// anything that would spill into the next column.
+pub fn render_row(columns: &[Column], cells: &[&str]) -> String {
+    let mut row = String::new();
+    for (column, cell) in columns.iter().zip(cells) {
+        let cell = truncate(cell, column.width);
+        let pad = " ".repeat(column.width - text_width(&cell));
+        match column.align {
+            Align::Left => row.push_str(&format!("{}{}", cell, pad)),
+            Align::Right => row.push_str(&format!("{}{}", pad, cell)),
+        }
+    }
+    row.trim_end().to_owned()
+}
+
+/// Every sixth hour of the day, for an [`hour_axis`] under a chart of the whole day.
+pub const HOUR_LABELS: [(u32, &str); 5] = [(0, "0"), (6, "6"), (12, "12"), (18, "18"), (24, "24")];
+
+/// A line `width` columns wide marking the hours of a day that runs across it, each
+/// label starting at its hour's column, or ending at the last one for the day's end.
+pub fn hour_axis(width: usize, labels: &[(u32, &str)]) -> String {
+    let mut axis = vec![' '; width];
+    for &(hour, label) in labels {
+        let label: Vec<char> = label.chars().collect();
+        let start = (hour as usize * width / 24).min(width.saturating_sub(label.len()));
+        for (slot, c) in axis.iter_mut().skip(start).zip(label) {
+            *slot = c;
+        }
+    }
+    axis.into_iter().collect::<String>().trim_end().to_owned()
+}
+
+/// Columns `c` takes up: two for CJK ideographs, kana, Hangul and fullwidth forms,
+/// which print from the Kanji font at double width, one for everything else.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0x303e
+        | 0x3041..=0x33ff
+        | 0x3400..=0x4dbf
+        | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// Columns `text` takes up on paper.
+pub fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// As much of the start of `text` as fits in `width` columns.
+fn truncate(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.chars().take_while(|&c| {
+        used += char_width(c);
+        used <= width
+    }).collect()
+}
+
+/// `line` fitted into `width` columns: a rule of one repeated character such as a divider
+/// is shortened, anything else word-wrapped.
+fn refit(line: &str, width: usize) -> Vec<String> {
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(rule) if rule != ' ' && chars.all(|c| c == rule) => vec![rule.to_string().repeat(width / char_width(rule))],
+        _ => wrap(line, width),
+    }
+}
+
+/// Like [`wrap`], but keeps the line's leading whitespace and indents continuation lines
+/// to match, so nested lists and code keep their shape. Indents past half the width are
+/// capped to leave room for the text.
+pub fn wrap_indented(line: &str, width: usize) -> Vec<String> {
+    let text = line.trim_start();
+    let indent: String = line[..line.len() - text.len()].chars().take(width / 2).collect();
+    let indent_len = indent.chars().count();
+    wrap(text, width - indent_len)
+        .into_iter()
+        .map(|wrapped| format!("{}{}", indent, wrapped))
+        .collect()
+}
+
+/// Word-wraps `text` to `width` columns, counting double-width characters as two and
+/// hard-breaking words that don't fit on a line.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        let mut word_len: usize = word.iter().copied().map(char_width).sum();
+        if current_len > 0 && current_len + 1 + word_len > width {
+            lines.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        while word_len > width {
+            // At least one character per line, even one wider than the line.
+            let mut fits = 0;
+            let mut used = 0;
+            while fits < word.len() && (fits == 0 || used + char_width(word[fits]) <= width) {
+                used += char_width(word[fits]);
+                fits += 1;
+            }
+            let rest = word.split_off(fits);
+            lines.push(word.into_iter().collect());
+            word = rest;
+            word_len -= used;
+        }
+        if current_len > 0 {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.extend(&word);
+        current_len += word_len;
+    }
+
+    if current_len > 0 || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
@@ -0,0 +1,150 @@
+use crate::{
+    AppState,
+    error::JobError,
+    html,
+    receipt::{Receipt, wrap},
+    source::Source,
+};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use serde_json::Value;
+
+#[derive(Deserialize, ToSchema)]
+pub struct RecipeRequest {
+    url: String,
+}
+
+struct Recipe {
+    name: String,
+    recipe_yield: Option<String>,
+    ingredients: Vec<String>,
+    steps: Vec<String>,
+}
+
+/// True if the JSON-LD node's `@type` is (or includes) `Recipe`.
+fn is_recipe(node: &Value) -> bool {
+    match &node["@type"] {
+        Value::String(t) => t == "Recipe",
+        Value::Array(types) => types.iter().any(|t| t == "Recipe"),
+        _ => false,
+    }
+}
+
+/// Finds the Recipe node in a JSON-LD document, which may be a bare object, an
+/// array of nodes, or a `@graph`.
+fn find_recipe(node: &Value) -> Option<&Value> {
+    match node {
+        Value::Array(nodes) => nodes.iter().find_map(find_recipe),
+        Value::Object(_) if is_recipe(node) => Some(node),
+        Value::Object(_) => node.get("@graph").and_then(find_recipe),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    value.as_str().map(html::to_text).filter(|s| !s.is_empty())
+}
+
+/// Flattens `recipeInstructions`: a string, a list of strings, HowToSteps, or
+/// HowToSections wrapping HowToSteps.
+fn collect_steps(value: &Value, steps: &mut Vec<String>) {
+    match value {
+        Value::String(s) => steps.extend(s.split('\n').map(html::to_text).filter(|step| !step.is_empty())),
+        Value::Array(items) => items.iter().for_each(|item| collect_steps(item, steps)),
+        Value::Object(_) => {
+            if let Some(items) = value.get("itemListElement") {
+                collect_steps(items, steps);
+            } else if let Some(step) = text(&value["text"]).or_else(|| text(&value["name"])) {
+                steps.push(step);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_recipe(node: &Value) -> Recipe {
+    let recipe_yield = match &node["recipeYield"] {
+        Value::Array(values) => values.iter().find_map(text),
+        Value::Number(n) => Some(n.to_string()),
+        value => text(value),
+    };
+    let ingredients = node["recipeIngredient"]
+        .as_array()
+        .map(|items| items.iter().filter_map(text).collect())
+        .unwrap_or_default();
+    let mut steps = Vec::new();
+    collect_steps(&node["recipeInstructions"], &mut steps);
+
+    Recipe {
+        name: text(&node["name"]).unwrap_or("Recipe".to_owned()),
+        recipe_yield,
+        ingredients,
+        steps,
+    }
+}
+
+fn extract_recipe(page: &str) -> Option<Recipe> {
+    html::json_ld_blocks(page)
+        .into_iter()
+        .filter_map(|block| serde_json::from_str::<Value>(block).ok())
+        .find_map(|doc| find_recipe(&doc).map(parse_recipe))
+}
+
+#[utoipa::path(
+    post,
+    path = "/recipe",
+    tag = "printing",
+    summary = "Print a recipe from its web page",
+    responses(
+        (status = 200, description = "Printed."),
+        (status = 422, description = "No schema.org Recipe on the page."),
+    )
+)]
+pub async fn recipe(
+    State(state): State<AppState>,
+    source: Source,
+    Json(request): Json<RecipeRequest>,
+) -> Result<(), StatusCode> {
+    Ok(print_recipe(&state, &source, &request.url).await?)
+}
+
+pub async fn print_recipe(state: &AppState, source: &Source, url: &str) -> Result<(), JobError> {
+    eprintln!("Recipe request for {}", url);
+    let page = html::fetch_page(url).await.inspect_err(|e| {
+        eprintln!("Failed to fetch recipe page: {}", e);
+    })?;
+    let Some(recipe) = extract_recipe(&page) else {
+        eprintln!("No schema.org Recipe found at {}", url);
+        return Err(JobError::Unprocessable(format!("no recipe found at {}", url)));
+    };
+
+    let mut receipt = Receipt::for_config(&state.config);
+    for line in wrap(&recipe.name.to_uppercase(), receipt.width()) {
+        receipt.line_center(&line);
+    }
+    if let Some(recipe_yield) = &recipe.recipe_yield {
+        receipt.line_center(&format!("Serves: {}", recipe_yield));
+    }
+    receipt.divider();
+
+    if !recipe.ingredients.is_empty() {
+        receipt.line_left("INGREDIENTS");
+        for ingredient in &recipe.ingredients {
+            receipt.hanging("[ ] ", ingredient);
+        }
+        receipt.divider();
+    }
+
+    if !recipe.steps.is_empty() {
+        receipt.line_left("STEPS");
+        for (i, step) in recipe.steps.iter().enumerate() {
+            receipt.hanging(&format!("{}. ", i + 1), step);
+            receipt.line_left("");
+        }
+        receipt.divider();
+    }
+
+    state.print(receipt, "recipe", source);
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Forwarding jobs between instances, e.g. from a house-wide ingress node to a server in
+//! each room: jobs for a printer in `RELAYS` go to another print-jobber server's
+//! `POST /relay` as their rendered receipt, with their ID here. That server queues the
+//! receipt as it is and answers once it has printed or failed, so the job's status here
+//! follows it, and its own history records the ID it came with.
+//!
+//! A job the other server hasn't printed within [`RELAY_TIMEOUT`] counts as failed here,
+//! though it may still print there.
+
+use crate::{AppState, callback::Completion, history::JobStatus, receipt::Receipt, source::Source};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use utoipa::ToSchema;
+
+/// How long the server a job is relayed to waits for it to print before answering.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A printer on another print-jobber server, from `RELAYS`.
+#[derive(Clone)]
+pub struct Relay {
+    pub name: String,
+    /// The other server's base URL.
+    pub url: String,
+    /// API key to send it, if it needs one.
+    pub key: Option<String>,
+}
+
+/// A job as it's relayed.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Relayed {
+    /// The job's ID on the server it was submitted to.
+    id: u64,
+    /// The rendered receipt, as the job archive stores it.
+    #[schema(value_type = Object)]
+    receipt: Receipt,
+}
+
+/// The other server's [`Completion`] for a relayed job.
+#[derive(Deserialize)]
+struct Outcome {
+    id: u64,
+    status: String,
+    error: Option<String>,
+}
+
+impl Relay {
+    /// Sends job `id` to the other server and waits until it has printed there. Returns
+    /// the paper it took, as a printer would. Blocks, for the print worker.
+    pub fn forward(&self, runtime: &Handle, id: u64, receipt: &Receipt) -> Result<f64, String> {
+        runtime.block_on(self.send(id, receipt)).map(|()| receipt.paper_mm())
+    }
+
+    async fn send(&self, id: u64, receipt: &Receipt) -> Result<(), String> {
+        let relayed = Relayed {
+            id,
+            receipt: receipt.clone(),
+        };
+        // A little longer than the other server waits, to hear its answer either way.
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/relay", self.url))
+            .timeout(RELAY_TIMEOUT + Duration::from_secs(10))
+            .json(&relayed);
+        if let Some(key) = &self.key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await.map_err(|e| format!("can't reach {}: {}", self.url, e))?;
+        if !response.status().is_success() {
+            return Err(format!("{} answered {}", self.url, response.status()));
+        }
+        let outcome: Outcome = response.json().await.map_err(|e| format!("unreadable answer from {}: {}", self.url, e))?;
+        eprintln!("Job #{} went to {} as job #{}: {}", id, self.url, outcome.id, outcome.status);
+        match (outcome.status.as_str(), outcome.error) {
+            ("printed", _) => Ok(()),
+            (status, Some(error)) => Err(format!("{} on {} (job #{}): {}", status, self.name, outcome.id, error)),
+            (status, None) => Err(format!("{} on {} (job #{})", status, self.name, outcome.id)),
+        }
+    }
+}
+
+/// `POST /relay`: queues a job relayed from another instance and answers with how it went.
+#[utoipa::path(
+    post,
+    path = "/relay",
+    tag = "printing",
+    summary = "Print a job relayed from another instance",
+    description = "Sent by an instance with this server in `RELAYS`. The receipt prints as rendered there, without \
+        this server's templates, and the request is answered once it has printed or failed.",
+    responses(
+        (status = 200, description = "How the job went here.", body = Completion),
+        (status = 504, description = "The job didn't print within two minutes; it may still print."),
+    )
+)]
+pub async fn accept(State(state): State<AppState>, source: Source, Json(relayed): Json<Relayed>) -> Result<Json<Completion>, StatusCode> {
+    eprintln!("Job #{} relayed by {}", relayed.id, source);
+    let (id, done) = state.relay_in(relayed.receipt, &source, relayed.id);
+    match tokio::time::timeout(RELAY_TIMEOUT, done).await {
+        Ok(Ok(completion)) => Ok(Json(completion)),
+        // Dropped without being printed, e.g. by `DELETE /queue`.
+        Ok(Err(_)) => Ok(Json(Completion {
+            id,
+            status: JobStatus::Cancelled,
+            error: None,
+        })),
+        Err(_) => {
+            eprintln!("Relayed job #{} didn't print within {:?}", id, RELAY_TIMEOUT);
+            Err(StatusCode::GATEWAY_TIMEOUT)
+        }
+    }
+}
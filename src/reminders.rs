@@ -0,0 +1,275 @@
+use crate::{
+    AppState,
+    receipt::Receipt,
+    scheduler::{next_occurrence, parse_when},
+    source::Source,
+    storage,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Datelike, Local, Weekday};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Recurrence {
+    Daily,
+    Weekdays,
+    Weekly,
+}
+
+impl Recurrence {
+    /// The first occurrence after `now` that keeps `at`'s time of day (and weekday, for weekly).
+    fn next(self, at: DateTime<Local>, now: DateTime<Local>) -> DateTime<Local> {
+        let mut next = next_occurrence(at.time(), now.max(at));
+        loop {
+            let fits = match self {
+                Recurrence::Daily => true,
+                Recurrence::Weekdays => !matches!(next.weekday(), Weekday::Sat | Weekday::Sun),
+                Recurrence::Weekly => next.weekday() == at.weekday(),
+            };
+            if fits {
+                return next;
+            }
+            next = next_occurrence(at.time(), next);
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct Reminder {
+    pub id: u64,
+    pub namespace: String,
+    pub message: String,
+    /// When the reminder next prints.
+    #[schema(format = DateTime)]
+    pub at: DateTime<Local>,
+    pub recurrence: Option<Recurrence>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReminderRequest {
+    message: String,
+    /// RFC 3339, or `HH:MM` for the next occurrence of that time.
+    at: String,
+    recurrence: Option<Recurrence>,
+}
+
+impl ReminderRequest {
+    fn validate(&self) -> Result<DateTime<Local>, StatusCode> {
+        let at = parse_when(&self.at).filter(|at| *at > Local::now());
+        match at {
+            Some(at) if !self.message.trim().is_empty() => Ok(at),
+            _ => {
+                eprintln!("Invalid reminder (at={:?}, message={:?})", self.at, self.message);
+                Err(StatusCode::BAD_REQUEST)
+            }
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Store {
+    next_id: u64,
+    reminders: Vec<Reminder>,
+}
+
+/// Reminders for all namespaces, saved to `reminders.json` in the data directory after every change.
+#[derive(Clone)]
+pub struct Reminders {
+    inner: Arc<Mutex<Store>>,
+    path: PathBuf,
+}
+
+impl Reminders {
+    /// Loads saved reminders, starting empty if the file is missing or unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        Reminders {
+            inner: Arc::new(Mutex::new(storage::load_json(&path))),
+            path,
+        }
+    }
+
+    fn save_or_log(&self, store: &Store) -> Result<(), StatusCode> {
+        storage::save_json(&self.path, store).map_err(|e| {
+            eprintln!("Failed to save reminders to {}: {}", self.path.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+
+    /// Saves a new reminder for `namespace` to print at `at`.
+    pub fn add(&self, namespace: &str, message: String, at: DateTime<Local>, recurrence: Option<Recurrence>) -> Result<Reminder, StatusCode> {
+        let mut store = self.inner.lock().unwrap();
+        store.next_id += 1;
+        let reminder = Reminder {
+            id: store.next_id,
+            namespace: namespace.to_owned(),
+            message,
+            at,
+            recurrence,
+        };
+        eprintln!("Reminder #{} set for {}", reminder.id, at.format("%Y-%m-%d %H:%M"));
+        store.reminders.push(reminder.clone());
+        self.save_or_log(&store)?;
+        Ok(reminder)
+    }
+
+    /// Removes due one-off reminders, moves recurring ones to their next time and returns what to print.
+    fn take_due(&self, now: DateTime<Local>) -> Vec<Reminder> {
+        let mut store = self.inner.lock().unwrap();
+        let due: Vec<Reminder> = store.reminders.iter().filter(|r| r.at <= now).cloned().collect();
+        if due.is_empty() {
+            return due;
+        }
+        store.reminders.retain(|r| r.at > now || r.recurrence.is_some());
+        for reminder in store.reminders.iter_mut().filter(|r| r.at <= now) {
+            if let Some(recurrence) = reminder.recurrence {
+                reminder.at = recurrence.next(reminder.at, now);
+            }
+        }
+        let _ = self.save_or_log(&store);
+        due
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/reminders",
+    tag = "reminders",
+    summary = "Pending reminders",
+    responses((status = 200, description = "OK", body = Vec<Reminder>))
+)]
+pub async fn list(State(state): State<AppState>, source: Source) -> Json<Vec<Reminder>> {
+    let store = state.reminders.inner.lock().unwrap();
+    let mut reminders: Vec<Reminder> = store
+        .reminders
+        .iter()
+        .filter(|r| r.namespace == source.namespace())
+        .cloned()
+        .collect();
+    reminders.sort_by_key(|r| r.at);
+    Json(reminders)
+}
+
+#[utoipa::path(
+    post,
+    path = "/reminders",
+    tag = "reminders",
+    summary = "Create a reminder",
+    responses(
+        (status = 201, description = "Created.", body = Reminder),
+        (status = 400, description = "No message, or `at` is invalid or in the past."),
+    )
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    source: Source,
+    Json(request): Json<ReminderRequest>,
+) -> Result<(StatusCode, Json<Reminder>), StatusCode> {
+    let at = request.validate()?;
+    let reminder = state.reminders.add(source.namespace(), request.message, at, request.recurrence)?;
+    Ok((StatusCode::CREATED, Json(reminder)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/reminders/{id}",
+    tag = "reminders",
+    summary = "One reminder",
+    params(("id" = u64, Path, description = "The reminder's ID.")),
+    responses(
+        (status = 200, description = "OK", body = Reminder),
+        (status = 404, description = "No such reminder."),
+    )
+)]
+pub async fn get_one(State(state): State<AppState>, source: Source, Path(id): Path<u64>) -> Result<Json<Reminder>, StatusCode> {
+    let store = state.reminders.inner.lock().unwrap();
+    store
+        .reminders
+        .iter()
+        .find(|r| r.id == id && r.namespace == source.namespace())
+        .cloned()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[utoipa::path(
+    put,
+    path = "/reminders/{id}",
+    tag = "reminders",
+    summary = "Replace a reminder",
+    params(("id" = u64, Path, description = "The reminder's ID.")),
+    responses(
+        (status = 200, description = "OK", body = Reminder),
+        (status = 400, description = "No message, or `at` is invalid or in the past."),
+        (status = 404, description = "No such reminder."),
+    )
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    source: Source,
+    Path(id): Path<u64>,
+    Json(request): Json<ReminderRequest>,
+) -> Result<Json<Reminder>, StatusCode> {
+    let at = request.validate()?;
+    let mut store = state.reminders.inner.lock().unwrap();
+    let reminder = store
+        .reminders
+        .iter_mut()
+        .find(|r| r.id == id && r.namespace == source.namespace())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    reminder.message = request.message;
+    reminder.at = at;
+    reminder.recurrence = request.recurrence;
+    let reminder = reminder.clone();
+    state.reminders.save_or_log(&store)?;
+    Ok(Json(reminder))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/reminders/{id}",
+    tag = "reminders",
+    summary = "Delete a reminder",
+    params(("id" = u64, Path, description = "The reminder's ID.")),
+    responses(
+        (status = 204, description = "Deleted."),
+        (status = 404, description = "No such reminder."),
+    )
+)]
+pub async fn delete(State(state): State<AppState>, source: Source, Path(id): Path<u64>) -> StatusCode {
+    let mut store = state.reminders.inner.lock().unwrap();
+    let before = store.reminders.len();
+    store.reminders.retain(|r| r.id != id || r.namespace != source.namespace());
+    if store.reminders.len() == before {
+        return StatusCode::NOT_FOUND;
+    }
+    match state.reminders.save_or_log(&store) {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(status) => status,
+    }
+}
+
+/// Prints a small ticket for every reminder that has come due. Called on each scheduler tick.
+pub fn print_due(state: &AppState, now: DateTime<Local>) {
+    for reminder in state.reminders.take_due(now) {
+        eprintln!("Printing reminder #{}", reminder.id);
+        let source = Source::scheduler("reminder", &reminder.at.format("%H:%M").to_string(), &reminder.namespace);
+
+        let mut receipt = Receipt::for_config(&state.config);
+        receipt.line_center("REMINDER");
+        receipt.divider();
+        receipt.wrapped(&reminder.message);
+        receipt.divider();
+        receipt.line_center(&reminder.at.format_localized("%a %-d %b %H:%M", state.config.locale).to_string());
+        state.print(receipt, "reminder", &source);
+    }
+}
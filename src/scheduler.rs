@@ -0,0 +1,168 @@
+use crate::{
+    AppState, apod, briefing, config::ScheduledJob, countdowns, error::JobError, fx, habits, hn, notes, onthisday, packages, raster, receipt::Receipt, reminders,
+    source::Source, stats, stocks, weather, xkcd,
+};
+use chrono::{DateTime, Datelike, Local, NaiveTime};
+use std::time::Duration;
+
+const JOBS: &[&str] = &["weather", "stocks", "hn", "onthisday", "notes", "habits", "briefing", "xkcd", "apod", "packages", "fx", "countdowns", "stats"];
+
+struct Pending {
+    entry: ScheduledJob,
+    next_run: DateTime<Local>,
+    retries: u32,
+}
+
+/// Starts the background scheduler, which runs `SCHEDULE` jobs, releases delayed one-off prints and prints due reminders.
+pub fn spawn(state: AppState) {
+    let now = Local::now();
+    let pending: Vec<Pending> = state
+        .config
+        .schedule
+        .iter()
+        .filter(|entry| {
+            let known = JOBS.contains(&entry.job.as_str());
+            if !known {
+                eprintln!("Ignoring unknown scheduled job {:?}", entry.job);
+            }
+            known
+        })
+        .map(|entry| Pending {
+            entry: entry.clone(),
+            next_run: next_run(entry, now),
+            retries: 0,
+        })
+        .collect();
+
+    for p in &pending {
+        eprintln!("Scheduled {} for {}", p.entry.job, p.next_run.format("%Y-%m-%d %H:%M"));
+    }
+    tokio::spawn(run(state, pending));
+}
+
+async fn run(state: AppState, mut pending: Vec<Pending>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        interval.tick().await;
+        let now = Local::now();
+        // Due jobs stay due and run on the first tick after maintenance ends.
+        if state.maintenance.active().is_some() {
+            continue;
+        }
+
+        let released = state.queue.release_due(now);
+        if !released.is_empty() {
+            eprintln!("Releasing delayed jobs {:?}", released);
+            state.history.mark_queued(&released);
+        }
+        reminders::print_due(&state, now);
+
+        for p in pending.iter_mut().filter(|p| p.next_run <= now) {
+            eprintln!("Running scheduled job {}", p.entry.job);
+            let source = Source::scheduler(&p.entry.job, &p.entry.at.format("%H:%M").to_string(), &p.entry.namespace);
+            match run_job(&state, &p.entry.job, &source).await {
+                Ok(()) => {
+                    p.retries = 0;
+                    p.next_run = next_run(&p.entry, now);
+                }
+                Err(JobError::Upstream(reason)) if p.retries < state.config.schedule_max_retries => {
+                    p.retries += 1;
+                    p.next_run = now + state.config.schedule_retry_interval;
+                    eprintln!(
+                        "Scheduled job {} failed ({}), retry {} of {} at {}",
+                        p.entry.job,
+                        reason,
+                        p.retries,
+                        state.config.schedule_max_retries,
+                        p.next_run.format("%H:%M")
+                    );
+                    print_error_slip(&state, &source, &p.entry.job, &reason, &format!("will retry at {}", p.next_run.format("%H:%M")));
+                }
+                Err(e) => {
+                    p.retries = 0;
+                    p.next_run = next_run(&p.entry, now);
+                    eprintln!(
+                        "Scheduled job {} failed ({}), next run {}",
+                        p.entry.job,
+                        e,
+                        p.next_run.format("%Y-%m-%d %H:%M")
+                    );
+                    if let JobError::Upstream(reason) = e {
+                        let next = match p.entry.weekday {
+                            Some(_) => p.next_run.format("next try %A at %H:%M"),
+                            None => p.next_run.format("next try tomorrow at %H:%M"),
+                        };
+                        print_error_slip(&state, &source, &p.entry.job, &reason, &next.to_string());
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn run_job(state: &AppState, job: &str, source: &Source) -> Result<(), JobError> {
+    match job {
+        "weather" => weather::print_weather(state, source, &Default::default()).await,
+        "stocks" => stocks::print_stocks(state, source).await,
+        "hn" => hn::print_hn(state, source, 10, false).await,
+        "onthisday" => onthisday::print_onthisday(state, source, 4).await,
+        "habits" => habits::print_habits(state, source),
+        "briefing" => briefing::print_briefing(state, source).await,
+        "xkcd" => xkcd::print_xkcd(state, source, None, raster::Dither::Atkinson).await,
+        "apod" => apod::print_apod(state, source, None, raster::Dither::FloydSteinberg).await,
+        "packages" => packages::check_packages(state, source, false).await.map(|_| ()),
+        "fx" => fx::print_fx(state, source, &state.config.fx_base, &state.config.fx_symbols).await,
+        "countdowns" => countdowns::print_countdowns(state, source, countdowns::default_within()),
+        "notes" => {
+            notes::print_digest(state, source);
+            Ok(())
+        }
+        "stats" => {
+            stats::print_report(state, source);
+            Ok(())
+        }
+        _ => unreachable!("unknown jobs are filtered out in spawn"),
+    }
+}
+
+fn print_error_slip(state: &AppState, source: &Source, job: &str, reason: &str, next: &str) {
+    if !state.config.schedule_error_slips {
+        return;
+    }
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.divider();
+    receipt.fitted(&format!("{} unavailable: {}, {}", job, reason, next));
+    receipt.divider();
+    state.print(receipt, "error_slip", source);
+}
+
+/// Parses an RFC 3339 timestamp, or `HH:MM` as the next occurrence of that local time.
+pub fn parse_when(when: &str) -> Option<DateTime<Local>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(when) {
+        return Some(at.with_timezone(&Local));
+    }
+    let at = NaiveTime::parse_from_str(when, "%H:%M").ok()?;
+    Some(next_occurrence(at, Local::now()))
+}
+
+/// When a schedule entry next runs after `after`, on its weekday if it has one.
+fn next_run(entry: &ScheduledJob, after: DateTime<Local>) -> DateTime<Local> {
+    let mut next = next_occurrence(entry.at, after);
+    while entry.weekday.is_some_and(|weekday| next.weekday() != weekday) {
+        next = next_occurrence(entry.at, next);
+    }
+    next
+}
+
+/// The first time `at` occurs strictly after `after`, skipping times that don't exist locally (DST gaps).
+pub fn next_occurrence(at: NaiveTime, after: DateTime<Local>) -> DateTime<Local> {
+    let mut date = after.date_naive();
+    loop {
+        if let Some(next) = date.and_time(at).and_local_timezone(Local).earliest()
+            && next > after
+        {
+            return next;
+        }
+        date = date.succ_opt().expect("date out of range");
+    }
+}
@@ -0,0 +1,102 @@
+//! `print-jobber send [--raw] [--local] [--server URL] [--key KEY] [FILE]`: prints a
+//! file, or stdin, through a running server so `dmesg | print-jobber send` just works.
+//! `--local` skips the server and prints straight to the printer.
+
+use crate::config::Config;
+use std::io::Read;
+
+const USAGE: &str = "usage: print-jobber send [--raw] [--local] [--server URL] [--key KEY] [FILE]";
+
+struct SendArgs {
+    raw: bool,
+    local: bool,
+    server: Option<String>,
+    key: Option<String>,
+    file: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<SendArgs, String> {
+    let mut parsed = SendArgs {
+        raw: false,
+        local: false,
+        server: std::env::var("PRINT_JOBBER_SERVER").ok(),
+        key: std::env::var("PRINT_JOBBER_KEY").ok(),
+        file: None,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--raw" => parsed.raw = true,
+            "--local" => parsed.local = true,
+            "--server" => parsed.server = Some(args.next().ok_or("--server needs a URL")?),
+            "--key" => parsed.key = Some(args.next().ok_or("--key needs an API key")?),
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            "-" => parsed.file = None,
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}\n{}", flag, USAGE)),
+            _ if parsed.file.is_some() => return Err(format!("only one file at a time\n{}", USAGE)),
+            _ => parsed.file = Some(arg),
+        }
+    }
+    Ok(parsed)
+}
+
+fn read_input(file: Option<&str>) -> Result<String, String> {
+    match file {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e)),
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map_err(|e| format!("can't read stdin: {}", e))?;
+            Ok(text)
+        }
+    }
+}
+
+async fn post(server: &str, key: Option<&str>, raw: bool, text: String) -> Result<(), String> {
+    let url = format!("{}/?raw={}", server.trim_end_matches('/'), raw);
+    let mut request = reqwest::Client::new().post(&url).header("content-type", "text/plain").body(text);
+    if let Some(key) = key {
+        request = request.bearer_auth(key);
+    }
+    let response = request.send().await.map_err(|e| format!("can't reach {}: {}", server, e))?;
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        status => Err(format!("server refused the job: {}", status)),
+    }
+}
+
+/// Runs the `send` subcommand with the arguments after `send`, returning the exit code.
+pub async fn run(args: impl IntoIterator<Item = String>) -> i32 {
+    let args = match parse_args(args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 2;
+        }
+    };
+    let text = match read_input(args.file.as_deref()) {
+        Ok(text) => text,
+        Err(message) => {
+            eprintln!("{}", message);
+            return 1;
+        }
+    };
+
+    let result = if args.local {
+        let config = Config::from_env();
+        tokio::task::spawn_blocking(move || crate::print_local(config, &text, args.raw))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()))
+    } else {
+        let server = args.server.unwrap_or_else(|| {
+            format!("http://localhost:{}", std::env::var("PORT").unwrap_or("3000".to_owned()))
+        });
+        post(&server, args.key.as_deref(), args.raw, text).await
+    };
+    match result {
+        Ok(()) => 0,
+        Err(message) => {
+            eprintln!("{}", message);
+            1
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Tonight's sky: visible ISS passes from N2YO and the naked-eye planets, computed
+//! locally with [`astro`].
+
+use crate::{
+    AppState,
+    astro::{self, Planet},
+    config::Location,
+    error::JobError,
+    receipt::Receipt,
+    source::Source,
+    weather,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::f64::consts::PI;
+
+/// NORAD catalogue number of the ISS.
+const ISS: u32 = 25544;
+/// A planet counts as visible once it's this many degrees up in a dark sky.
+const MIN_ALTITUDE: f64 = 10.0;
+
+#[derive(Deserialize, Default, IntoParams)]
+pub struct SkyParams {
+    /// A `WEATHER_LOCATIONS` name or a place to geocode; Berlin when missing.
+    location: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PassesResponse {
+    #[serde(default)]
+    passes: Vec<Pass>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pass {
+    start_az_compass: String,
+    #[serde(rename = "startUTC")]
+    start_utc: i64,
+    max_el: f64,
+    end_az_compass: String,
+    #[serde(rename = "endUTC")]
+    end_utc: i64,
+    mag: Option<f64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/sky",
+    tag = "printing",
+    summary = "Print tonight's ISS passes and visible planets",
+    params(SkyParams),
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn sky(State(state): State<AppState>, source: Source, Query(params): Query<SkyParams>) -> Result<(), StatusCode> {
+    Ok(print_sky(&state, &source, &params).await?)
+}
+
+pub async fn print_sky(state: &AppState, source: &Source, params: &SkyParams) -> Result<(), JobError> {
+    let location = match &params.location {
+        Some(name) => weather::resolve_location(state, name).await?,
+        None => Location {
+            name: "berlin".to_owned(),
+            lat: weather::BERLIN_LAT,
+            lon: weather::BERLIN_LON,
+        },
+    };
+    eprintln!("Sky request for {}", location.name);
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("TONIGHT'S SKY");
+    receipt.line_center(&format!(
+        "{}, {}",
+        location.name.to_uppercase(),
+        Local::now().date_naive().format_localized("%-d %B", state.config.locale)
+    ));
+    receipt.divider();
+
+    receipt.line_center("ISS PASSES");
+    match &state.config.n2yo_api_key {
+        None => receipt.line_center("Set N2YO_API_KEY to list passes"),
+        Some(api_key) => match fetch_passes(state, api_key, &location).await {
+            Ok(passes) if passes.is_empty() => receipt.line_center("No visible passes tonight"),
+            Ok(passes) => {
+                for pass in &passes {
+                    receipt.line_left(&render_pass(pass));
+                }
+            }
+            Err(e) => {
+                // The planets are still worth printing without the ISS.
+                eprintln!("Failed to fetch ISS passes: {}", e);
+                state.diagnostics.record_error("sky", e.to_string());
+                receipt.line_center("ISS passes unavailable");
+            }
+        },
+    }
+    receipt.divider();
+
+    receipt.line_center("PLANETS");
+    let night = night(&location);
+    if night.is_none() {
+        receipt.line_center("No true night here tonight");
+    }
+    // Rise and set around tonight rather than the calendar day.
+    let noon = local_today_at(12).unwrap_or_else(Utc::now);
+    for planet in Planet::ALL {
+        let times = astro::planet_times(planet, noon, location.lat, location.lon);
+        receipt.line_left(&format!("{:<9} rise {}  set {}", planet.name(), local_time(times.rise), local_time(times.set)));
+        let visibility = night.and_then(|(dusk, dawn)| visible(planet, dusk, dawn, &location));
+        match visibility {
+            Some(window) => receipt.line_left(&format!(
+                "  visible {}-{}, highest {} ({:.0}°)",
+                local_time(Some(window.from)),
+                local_time(Some(window.until)),
+                local_time(Some(window.highest)),
+                window.altitude
+            )),
+            None => receipt.line_left("  not visible tonight"),
+        }
+    }
+
+    state.print(receipt, "sky", source);
+    Ok(())
+}
+
+async fn fetch_passes(state: &AppState, api_key: &str, location: &Location) -> Result<Vec<Pass>, JobError> {
+    let base = state.config.n2yo_url.as_deref().unwrap_or("https://api.n2yo.com").trim_end_matches('/');
+    // Passes visible to the eye over the next day, at least a minute long.
+    let url = format!(
+        "{}/rest/v1/satellite/visualpasses/{}/{}/{}/0/1/60/&apiKey={}",
+        base, ISS, location.lat, location.lon, api_key
+    );
+    let response = reqwest::get(&url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<PassesResponse>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+    Ok(response.passes)
+}
+
+/// e.g. `19:42-19:48  NW -> SE  max 67°  mag -3.1`
+fn render_pass(pass: &Pass) -> String {
+    let at = |timestamp: i64| local_time(DateTime::from_timestamp(timestamp, 0));
+    let mut line = format!(
+        "{}-{}  {} -> {}  max {:.0}°",
+        at(pass.start_utc),
+        at(pass.end_utc),
+        pass.start_az_compass,
+        pass.end_az_compass,
+        pass.max_el
+    );
+    if let Some(mag) = pass.mag.filter(|&mag| mag < 100_000.0) {
+        // N2YO reports 100000 when the magnitude is unknown.
+        line.push_str(&format!("  mag {:.1}", mag));
+    }
+    line
+}
+
+/// When today's local clock reads `hour`:00, in UTC.
+fn local_today_at(hour: u32) -> Option<DateTime<Utc>> {
+    let time = NaiveTime::from_hms_opt(hour, 0, 0)?;
+    Local::now().date_naive().and_time(time).and_local_timezone(Local).earliest().map(|t| t.to_utc())
+}
+
+fn local_time(time: Option<DateTime<Utc>>) -> String {
+    time.map_or_else(|| "--:--".to_owned(), |t| t.with_timezone(&Local).format("%H:%M").to_string())
+}
+
+/// Tonight from the end of civil twilight to the start of tomorrow's, or `None`
+/// when the sun doesn't get far enough below the horizon.
+fn night(location: &Location) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = Local::now().date_naive();
+    let dusk = astro::sun_times(today, location.lat, location.lon).dusk?;
+    let dawn = astro::sun_times(today.succ_opt()?, location.lat, location.lon).dawn?;
+    Some((dusk, dawn))
+}
+
+struct Window {
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+    highest: DateTime<Utc>,
+    /// Degrees above the horizon at `highest`.
+    altitude: f64,
+}
+
+/// When `planet` is above [`MIN_ALTITUDE`] between `dusk` and `dawn`, to the nearest
+/// ten minutes.
+fn visible(planet: Planet, dusk: DateTime<Utc>, dawn: DateTime<Utc>, location: &Location) -> Option<Window> {
+    let mut window: Option<Window> = None;
+    let mut t = dusk;
+    while t <= dawn {
+        let altitude = astro::planet_altitude(planet, t, location.lat, location.lon) * 180.0 / PI;
+        if altitude >= MIN_ALTITUDE {
+            let window = window.get_or_insert(Window {
+                from: t,
+                until: t,
+                highest: t,
+                altitude,
+            });
+            window.until = t;
+            if altitude > window.altitude {
+                window.highest = t;
+                window.altitude = altitude;
+            }
+        }
+        t += TimeDelta::minutes(10);
+    }
+    window
+}
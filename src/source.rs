@@ -0,0 +1,224 @@
+use crate::{
+    AppState,
+    config::{ApiKey, Config, DEFAULT_NAMESPACE},
+};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Query, Request, State, connect_info::Connected},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::Response,
+    serve::IncomingStream,
+};
+use serde::Deserialize;
+use std::{
+    convert::Infallible,
+    fmt,
+    net::{Ipv4Addr, SocketAddr},
+};
+use tokio::net::{TcpListener, UnixListener};
+
+/// The other end of a connection, as axum's `ConnectInfo`.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    /// Common name of the client certificate, with mutual TLS; see [`crate::tls`].
+    pub cert_name: Option<String>,
+}
+
+impl Peer {
+    /// `cert:<common name>` for a client certificate, else `http:<ip>`.
+    pub fn origin(&self) -> String {
+        match &self.cert_name {
+            Some(name) => format!("cert:{}", name),
+            None => format!("http:{}", self.addr.ip()),
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, TcpListener>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Peer {
+            addr: *stream.remote_addr(),
+            cert_name: None,
+        }
+    }
+}
+
+impl Connected<IncomingStream<'_, UnixListener>> for Peer {
+    /// Clients on a Unix socket are on this machine.
+    fn connect_info(_: IncomingStream<'_, UnixListener>) -> Self {
+        Peer {
+            addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            cert_name: None,
+        }
+    }
+}
+
+/// The API key a request authenticated with, stashed by [`require_api_key`].
+#[derive(Clone)]
+struct ApiKeyName {
+    name: String,
+    namespace: String,
+}
+
+/// Where a job came from, e.g. `key:kitchen-tablet`, `cert:kitchen`, `scheduler:weather@07:00`,
+/// `http:192.168.1.20` or `spool:notes.txt`, and the namespace it belongs to.
+#[derive(Clone)]
+pub struct Source {
+    origin: String,
+    namespace: String,
+    /// Printers the request asked for its jobs to go to at once, from `?mirror=`.
+    mirror: Vec<String>,
+    /// The printer the request asked for, from `?printer=`.
+    printer: Option<String>,
+    /// A label for `PRINTER_ROUTES` to match, from `?tag=`.
+    tag: Option<String>,
+    /// Print now even during `QUIET_HOURS`, from `?override_quiet=true`.
+    override_quiet: bool,
+}
+
+/// The query parameters every printing request takes, whatever the endpoint.
+#[derive(Default, Deserialize)]
+struct RoutingParams {
+    /// Comma-separated printer names.
+    mirror: Option<String>,
+    printer: Option<String>,
+    tag: Option<String>,
+    #[serde(default)]
+    override_quiet: bool,
+}
+
+impl Source {
+    /// A gRPC call, made with `key` when API keys are configured.
+    pub fn grpc(key: Option<&ApiKey>) -> Self {
+        match key {
+            Some(key) => Source {
+                origin: format!("key:{}", key.name),
+                namespace: key.namespace.clone(),
+                mirror: Vec::new(),
+                printer: None,
+                tag: None,
+                override_quiet: false,
+            },
+            None => Source {
+                origin: "grpc".to_owned(),
+                namespace: DEFAULT_NAMESPACE.to_owned(),
+                mirror: Vec::new(),
+                printer: None,
+                tag: None,
+                override_quiet: false,
+            },
+        }
+    }
+
+    /// A file dropped into the spool directory.
+    pub fn spool(file_name: &str) -> Self {
+        Source {
+            origin: format!("spool:{}", file_name),
+            namespace: DEFAULT_NAMESPACE.to_owned(),
+            mirror: Vec::new(),
+            printer: None,
+            tag: None,
+            override_quiet: false,
+        }
+    }
+
+    pub fn scheduler(job: &str, at: &str, namespace: &str) -> Self {
+        Source {
+            origin: format!("scheduler:{}@{}", job, at),
+            namespace: namespace.to_owned(),
+            mirror: Vec::new(),
+            printer: None,
+            tag: None,
+            override_quiet: false,
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Printers the request asked to mirror its jobs to; empty if it didn't say.
+    pub fn mirror(&self) -> &[String] {
+        &self.mirror
+    }
+
+    /// The printer the request asked for with `?printer=`.
+    pub fn printer(&self) -> Option<&str> {
+        self.printer.as_deref()
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn overrides_quiet(&self) -> bool {
+        self.override_quiet
+    }
+
+    /// Name of the API key the job was submitted with, if any.
+    pub fn key_name(&self) -> Option<&str> {
+        self.origin.strip_prefix("key:")
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.origin)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Source {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts.extensions.get::<ConnectInfo<Peer>>().map(|ConnectInfo(peer)| peer);
+        let key = parts.extensions.get::<ApiKeyName>();
+        // A client certificate is the stronger identity; an API key still picks the namespace.
+        let origin = match (peer, key) {
+            (Some(peer), _) if peer.cert_name.is_some() => peer.origin(),
+            (_, Some(key)) => format!("key:{}", key.name),
+            (Some(peer), None) => peer.origin(),
+            (None, None) => "http".to_owned(),
+        };
+        let Query(routing) = Query::<RoutingParams>::try_from_uri(&parts.uri).unwrap_or_default();
+        let mirror = routing.mirror.unwrap_or_default();
+        Ok(Source {
+            origin,
+            namespace: key.map_or(DEFAULT_NAMESPACE, |key| &key.namespace).to_owned(),
+            mirror: mirror.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_owned).collect(),
+            printer: routing.printer.filter(|name| !name.is_empty()),
+            tag: routing.tag.filter(|tag| !tag.is_empty()),
+            override_quiet: routing.override_quiet,
+        })
+    }
+}
+
+/// The configured key whose token is in the `Authorization: Bearer <token>` or `X-Api-Key` header.
+pub fn presented_key<'a>(config: &'a Config, headers: &HeaderMap) -> Option<&'a ApiKey> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))?;
+    config.api_keys.iter().find(|key| key.token == token)
+}
+
+/// When `API_KEYS` is configured, rejects requests without a matching
+/// `Authorization: Bearer <token>` (or `X-Api-Key`) header.
+pub async fn require_api_key(State(state): State<AppState>, mut req: Request, next: Next) -> Result<Response, StatusCode> {
+    if state.config.api_keys.is_empty() {
+        return Ok(next.run(req).await);
+    }
+
+    let Some(key) = presented_key(&state.config, req.headers()) else {
+        eprintln!("Rejected request to {} without a valid API key", req.uri().path());
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    req.extensions_mut().insert(ApiKeyName {
+        name: key.name.clone(),
+        namespace: key.namespace.clone(),
+    });
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,114 @@
+//! Prints files dropped into a directory, for software that can only "save to folder".
+//! Text, Markdown and JSON go through the same layout as uploads, images through the
+//! raster path. Printed files move to `done/`, ones that can't be printed to `failed/`.
+
+use crate::{
+    AppState, PrintParams,
+    receipt::Receipt,
+    source::Source,
+    upload::{self, Upload},
+};
+use chrono::Local;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub fn spawn(state: AppState, dir: PathBuf) {
+    for sub in ["done", "failed"] {
+        if let Err(e) = fs::create_dir_all(dir.join(sub)) {
+            eprintln!("Failed to create {}: {}", dir.join(sub).display(), e);
+        }
+    }
+    eprintln!("Watching {} for files to print", dir.display());
+    tokio::spawn(async move {
+        // File sizes at the last poll; a file is picked up once its size stops
+        // changing, so one that's still being written isn't printed half done.
+        let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            // Files wait in the directory while the queue is full.
+            if state.maintenance.active().is_some() || state.queue_full() {
+                continue;
+            }
+            let mut current = HashMap::new();
+            for (path, size) in candidates(&dir) {
+                if sizes.get(&path) == Some(&size) {
+                    process(&state, &dir, &path);
+                } else {
+                    current.insert(path, size);
+                }
+            }
+            sizes = current;
+        }
+    });
+}
+
+/// Regular files in `dir` with their sizes, skipping hidden and temporary files.
+fn candidates(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Failed to read spool directory {}: {}", dir.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut files: Vec<(PathBuf, u64)> = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            !name.starts_with('.') && !name.ends_with('~') && !name.ends_with(".tmp")
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| (entry.path(), metadata.len()))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn process(state: &AppState, dir: &Path, path: &Path) {
+    let Some(name) = path.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+        return;
+    };
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Failed to read spooled file {}: {}", path.display(), e);
+            return;
+        }
+    };
+    eprintln!("Printing spooled file {} ({} bytes)", name, data.len());
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let rendered = upload::render(&mut receipt, &Upload::from_file(&name, data), &PrintParams::default(), &state.config);
+    let sub = match rendered {
+        Ok(()) => {
+            state.print(receipt, "spool", &Source::spool(&name));
+            "done"
+        }
+        Err(status) => {
+            eprintln!("Can't print spooled file {} ({})", name, status);
+            "failed"
+        }
+    };
+    let mut target = dir.join(sub).join(&name);
+    if target.exists() {
+        target = dir.join(sub).join(format!("{}-{}", Local::now().format("%Y%m%d-%H%M%S"), name));
+    }
+    if let Err(e) = fs::rename(path, &target) {
+        eprintln!("Failed to move {} to {}: {}", path.display(), target.display(), e);
+        // Don't print it again on the next poll.
+        if sub == "done"
+            && let Err(e) = fs::remove_file(path)
+        {
+            eprintln!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Star Line Mode commands, for Star Micronics printers (TSP100, TSP650) that don't
+//! speak ESC/POS. Receipts are rendered the same way and translated here when sent.
+
+use crate::receipt::{Font, Style};
+use escpos::utils::{JustifyMode, PageCode};
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+
+/// `ESC GS a n`: 0 left, 1 center, 2 right.
+pub fn justify(mode: JustifyMode) -> Vec<u8> {
+    let n = match mode {
+        JustifyMode::LEFT => 0,
+        JustifyMode::CENTER => 1,
+        JustifyMode::RIGHT => 2,
+    };
+    vec![ESC, GS, b'a', n]
+}
+
+/// `ESC i n1 n2`: height and width expansion, 1-6 times.
+pub fn size(width: u8, height: u8) -> Vec<u8> {
+    vec![ESC, b'i', height.clamp(1, 6) - 1, width.clamp(1, 6) - 1]
+}
+
+/// `ESC E`/`ESC F` emphasis, `ESC - n` underline and `ESC 4`/`ESC 5` white on black.
+pub fn style(style: Style) -> Vec<u8> {
+    vec![
+        ESC,
+        if style.bold { b'E' } else { b'F' },
+        ESC,
+        b'-',
+        style.underline as u8,
+        ESC,
+        if style.inverse { b'4' } else { b'5' },
+    ]
+}
+
+/// `ESC RS F n`: font A (12x24) or the narrower B (9x24).
+pub fn font(font: Font) -> Vec<u8> {
+    vec![ESC, 0x1e, b'F', (font == Font::B) as u8]
+}
+
+/// `ESC 3 n`: line feed of n/4 mm, from `dots` at 8 dots/mm, or `ESC z 1` for the
+/// default 1/6 inch.
+pub fn line_spacing(dots: Option<u8>) -> Vec<u8> {
+    match dots {
+        Some(dots) => vec![ESC, b'3', dots / 2],
+        None => vec![ESC, b'z', 1],
+    }
+}
+
+/// `ESC a n`: feeds `lines` lines.
+pub fn feed(lines: u8) -> Vec<u8> {
+    vec![ESC, b'a', lines]
+}
+
+/// `ESC d n`: feeds to the cutter, then cuts fully (2) or partially (3).
+pub fn cut(partial: bool) -> Vec<u8> {
+    vec![ESC, b'd', if partial { 3 } else { 2 }]
+}
+
+/// `ESC GS EM DC1` sets the buzzer to 100ms on and off, `ESC GS EM DC2` sounds it `times` times.
+pub fn beep(times: u8) -> Vec<u8> {
+    vec![ESC, GS, 0x19, 0x11, 1, 5, 5, ESC, GS, 0x19, 0x12, 1, times, 0]
+}
+
+/// `ESC GS y S` model 2, correction level M and cell size, `ESC GS y D 1` the data,
+/// then `ESC GS y P` to print it.
+pub fn qr_code(data: &str, size: u8) -> Vec<u8> {
+    let len = data.len().min(u16::MAX as usize) as u16;
+    let mut cmd = vec![
+        ESC, GS, b'y', b'S', b'0', 2,
+        ESC, GS, b'y', b'S', b'1', 1,
+        ESC, GS, b'y', b'S', b'2', size.clamp(1, 8),
+        ESC, GS, b'y', b'D', b'1', 0,
+    ];
+    cmd.extend(len.to_le_bytes());
+    cmd.extend(&data.as_bytes()[..len as usize]);
+    cmd.extend([ESC, GS, b'y', b'P']);
+    cmd
+}
+
+/// Translates an ESC/POS `GS v 0` raster image to `ESC GS S`, which takes the same
+/// row-major, high-bit-first data. NV graphics commands have no equivalent: `None`.
+pub fn raster(escpos: &[u8]) -> Option<Vec<u8>> {
+    if !escpos.starts_with(b"\x1dv0") || escpos.len() < 8 {
+        return None;
+    }
+    // GS v 0 m xL xH yL yH -> ESC GS S m xL xH yL yH n
+    let mut cmd = vec![ESC, GS, b'S', 1];
+    cmd.extend(&escpos[4..8]);
+    cmd.push(0);
+    cmd.extend(&escpos[8..]);
+    Some(cmd)
+}
+
+/// `ESC GS t n`, with Star's own numbering of the code tables.
+pub fn code_page(page: PageCode) -> Vec<u8> {
+    let n = match page {
+        PageCode::PC858 => 4,
+        PageCode::PC852 => 5,
+        PageCode::PC866 => 10,
+        PageCode::PC737 => 15,
+        _ => 1,
+    };
+    vec![ESC, GS, b't', n]
+}
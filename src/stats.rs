@@ -0,0 +1,214 @@
+//! `/stats`: totals over the caller's job history (see [`crate::all_jobs`]), as JSON or
+//! printed as a printer report. The `stats` scheduled job prints the last week's.
+
+use crate::{
+    AppState,
+    history::{JobRecord, JobStatus},
+    receipt::{Column, Receipt, render_row},
+    source::Source,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::{IntoParams, ToSchema};
+
+/// Sources listed in `top_sources`.
+const TOP_SOURCES: usize = 5;
+/// Hours printed on the report; the JSON lists every hour with jobs.
+const REPORT_HOURS: usize = 3;
+/// Upper bound on `days`: a year, leap day included.
+const MAX_DAYS: i64 = 366;
+
+const DAY_COLUMNS: [Column; 3] = [Column::left(16), Column::right(8), Column::right(8)];
+
+#[derive(Deserialize, IntoParams)]
+pub struct StatsParams {
+    /// Days to count back over, today included; 1 to 366.
+    #[serde(default = "default_days")]
+    #[param(default = default_days, minimum = 1, maximum = 366)]
+    days: i64,
+    /// Print the report too.
+    #[serde(default)]
+    #[param(default = false)]
+    print: bool,
+}
+
+pub fn default_days() -> i64 {
+    7
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DayStats {
+    date: NaiveDate,
+    jobs: usize,
+    lines: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HourStats {
+    /// Local hour of the day, 0-23.
+    hour: u32,
+    jobs: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SourceStats {
+    source: String,
+    jobs: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Stats {
+    from: NaiveDate,
+    to: NaiveDate,
+    jobs: usize,
+    lines: usize,
+    printed: usize,
+    failed: usize,
+    /// Failed jobs out of the printed and failed ones, 0 to 1.
+    failure_rate: f64,
+    /// Estimated paper the printed jobs took.
+    paper_mm: f64,
+    /// Every day in the range, oldest first.
+    per_day: Vec<DayStats>,
+    /// Hours of the day that had jobs, busiest first.
+    busiest_hours: Vec<HourStats>,
+    /// Submitters with the most jobs, most first.
+    top_sources: Vec<SourceStats>,
+}
+
+/// Totals over the jobs submitted from `days - 1` days before `today` through `today`.
+fn aggregate(records: &[JobRecord], today: NaiveDate, days: i64) -> Stats {
+    let from = today - TimeDelta::days(days - 1);
+    let mut per_day: BTreeMap<NaiveDate, DayStats> = (0..days)
+        .map(|offset| from + TimeDelta::days(offset))
+        .map(|date| (date, DayStats { date, jobs: 0, lines: 0 }))
+        .collect();
+    let mut hours: HashMap<u32, usize> = HashMap::new();
+    let mut sources: HashMap<&str, usize> = HashMap::new();
+    let (mut printed, mut failed, mut paper_mm) = (0, 0, 0.0);
+    for record in records {
+        let Some(at) = DateTime::parse_from_rfc3339(&record.at).ok().map(|at| at.with_timezone(&Local)) else {
+            continue;
+        };
+        let Some(day) = per_day.get_mut(&at.date_naive()) else { continue };
+        day.jobs += 1;
+        day.lines += record.lines;
+        *hours.entry(at.hour()).or_default() += 1;
+        *sources.entry(&record.source).or_default() += 1;
+        match record.status {
+            JobStatus::Printed => printed += 1,
+            JobStatus::Failed => failed += 1,
+            _ => {}
+        }
+        paper_mm += record.paper_mm.unwrap_or_default();
+    }
+
+    let mut busiest_hours: Vec<HourStats> = hours.into_iter().map(|(hour, jobs)| HourStats { hour, jobs }).collect();
+    busiest_hours.sort_by_key(|h| (std::cmp::Reverse(h.jobs), h.hour));
+    let mut top_sources: Vec<SourceStats> = sources
+        .into_iter()
+        .map(|(source, jobs)| SourceStats { source: source.to_owned(), jobs })
+        .collect();
+    top_sources.sort_by(|a, b| b.jobs.cmp(&a.jobs).then_with(|| a.source.cmp(&b.source)));
+    top_sources.truncate(TOP_SOURCES);
+    let per_day: Vec<DayStats> = per_day.into_values().collect();
+    Stats {
+        from,
+        to: today,
+        jobs: per_day.iter().map(|day| day.jobs).sum(),
+        lines: per_day.iter().map(|day| day.lines).sum(),
+        printed,
+        failed,
+        failure_rate: if printed + failed == 0 { 0.0 } else { failed as f64 / (printed + failed) as f64 },
+        paper_mm: paper_mm.round(),
+        per_day,
+        busiest_hours,
+        top_sources,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "status",
+    summary = "Job statistics",
+    description = "Totals over the caller's jobs (the ones `/jobs` lists): jobs and lines per day, the busiest hours \
+        of the day, the top sources, the failure rate and estimated paper used. Jobs only known from the archive \
+        count toward jobs and lines but not printed, failed or paper. `?print=true` also prints them as a \
+        printer report; the `stats` scheduled job prints the last week's, e.g. `SCHEDULE=stats@mon 08:00`.",
+    params(StatsParams),
+    responses(
+        (status = 200, description = "OK", body = Stats),
+        (status = 400, description = "`days` is outside 1-366."),
+    )
+)]
+pub async fn stats(State(state): State<AppState>, source: Source, Query(params): Query<StatsParams>) -> Result<Json<Stats>, StatusCode> {
+    if !(1..=MAX_DAYS).contains(&params.days) {
+        eprintln!("Invalid stats request for {} days", params.days);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let stats = job_stats(&state, &source, params.days);
+    if params.print {
+        print_stats(&state, &source, &stats);
+    }
+    Ok(Json(stats))
+}
+
+fn job_stats(state: &AppState, source: &Source, days: i64) -> Stats {
+    let (records, _) = crate::all_jobs(state, source.namespace());
+    aggregate(&records, Local::now().date_naive(), days)
+}
+
+/// The scheduled `stats` job: the last week's report.
+pub fn print_report(state: &AppState, source: &Source) {
+    let stats = job_stats(state, source, default_days());
+    print_stats(state, source, &stats);
+}
+
+fn print_stats(state: &AppState, source: &Source, stats: &Stats) {
+    eprintln!("Stats report from {} to {}", stats.from, stats.to);
+    let locale = state.config.locale;
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("PRINTER REPORT");
+    receipt.line_center(&format!(
+        "{} - {}",
+        stats.from.format_localized("%-d %b", locale),
+        stats.to.format_localized("%-d %b %Y", locale)
+    ));
+    receipt.divider();
+    receipt.line_left(&format!("Jobs: {}    Lines: {}", stats.jobs, stats.lines));
+    receipt.line_left(&format!(
+        "Printed: {}    Failed: {} ({:.1}%)",
+        stats.printed,
+        stats.failed,
+        stats.failure_rate * 100.0
+    ));
+    receipt.line_left(&format!("Paper: about {:.1}m", stats.paper_mm / 1000.0));
+    receipt.divider();
+    receipt.line_left(&render_row(&DAY_COLUMNS, &["", "JOBS", "LINES"]));
+    for day in &stats.per_day {
+        let date = day.date.format_localized("%a %-d %b", locale).to_string();
+        receipt.line_left(&render_row(&DAY_COLUMNS, &[&date, &day.jobs.to_string(), &day.lines.to_string()]));
+    }
+    if !stats.busiest_hours.is_empty() {
+        receipt.divider();
+        receipt.line_left("Busiest hours:");
+        for hour in stats.busiest_hours.iter().take(REPORT_HOURS) {
+            receipt.line_left(&format!("  {:02}:00  {} jobs", hour.hour, hour.jobs));
+        }
+    }
+    if !stats.top_sources.is_empty() {
+        receipt.divider();
+        receipt.line_left("Top sources:");
+        for top in &stats.top_sources {
+            receipt.hanging("  ", &format!("{} ({})", top.source, top.jobs));
+        }
+    }
+    state.print(receipt, "stats", source);
+}
@@ -0,0 +1,170 @@
+use crate::{
+    AppState,
+    config::StocksProvider,
+    error::JobError,
+    receipt::{Column, Receipt, render_row},
+    source::Source,
+};
+use axum::{extract::State, http::StatusCode};
+use serde::Deserialize;
+
+struct Quote {
+    price: f64,
+    change: f64,
+    change_percent: f64,
+}
+
+#[derive(Deserialize)]
+struct FinnhubQuote {
+    #[serde(rename = "c")]
+    current: f64,
+    #[serde(rename = "d")]
+    change: Option<f64>,
+    #[serde(rename = "dp")]
+    change_percent: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct AlphaVantageResponse {
+    #[serde(rename = "Global Quote")]
+    quote: AlphaVantageQuote,
+}
+
+#[derive(Deserialize)]
+struct AlphaVantageQuote {
+    #[serde(rename = "05. price")]
+    price: String,
+    #[serde(rename = "09. change")]
+    change: String,
+    #[serde(rename = "10. change percent")]
+    change_percent: String,
+}
+
+/// The quote table's columns: 44 wide, or 32 for receipts narrower than that.
+fn columns(width: usize) -> [Column; 5] {
+    if width < 44 {
+        [Column::left(6), Column::right(9), Column::right(8), Column::right(7), Column::right(2)]
+    } else {
+        [Column::left(8), Column::right(12), Column::right(11), Column::right(10), Column::right(3)]
+    }
+}
+
+/// Formats a request error without its URL, which carries the API key.
+fn describe(e: reqwest::Error) -> String {
+    e.without_url().to_string()
+}
+
+async fn fetch_quote(provider: &StocksProvider, api_key: &str, symbol: &str) -> Result<Quote, String> {
+    match provider {
+        StocksProvider::Finnhub => {
+            let url = format!("https://finnhub.io/api/v1/quote?symbol={}&token={}", symbol, api_key);
+            let quote = reqwest::get(&url)
+                .await
+                .map_err(describe)?
+                .json::<FinnhubQuote>()
+                .await
+                .map_err(describe)?;
+            // Finnhub answers unknown symbols with an all-zero quote rather than an error.
+            if quote.current == 0.0 {
+                return Err(format!("no quote for {}", symbol));
+            }
+            Ok(Quote {
+                price: quote.current,
+                change: quote.change.unwrap_or(0.0),
+                change_percent: quote.change_percent.unwrap_or(0.0),
+            })
+        }
+        StocksProvider::AlphaVantage => {
+            let url = format!(
+                "https://www.alphavantage.co/query?function=GLOBAL_QUOTE&symbol={}&apikey={}",
+                symbol, api_key
+            );
+            let response = reqwest::get(&url)
+                .await
+                .map_err(describe)?
+                .json::<AlphaVantageResponse>()
+                .await
+                .map_err(describe)?;
+            let parse = |s: &str| s.trim_end_matches('%').parse::<f64>().map_err(|e| e.to_string());
+            Ok(Quote {
+                price: parse(&response.quote.price)?,
+                change: parse(&response.quote.change)?,
+                change_percent: parse(&response.quote.change_percent)?,
+            })
+        }
+    }
+}
+
+fn arrow(change: f64) -> &'static str {
+    if change > 0.0 {
+        "^"
+    } else if change < 0.0 {
+        "v"
+    } else {
+        "="
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/stocks",
+    tag = "printing",
+    summary = "Print stock quotes",
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn stocks(State(state): State<AppState>, source: Source) -> Result<(), StatusCode> {
+    Ok(print_stocks(&state, &source).await?)
+}
+
+pub async fn print_stocks(state: &AppState, source: &Source) -> Result<(), JobError> {
+    let Some(api_key) = &state.config.stocks_api_key else {
+        eprintln!("Stocks request but STOCKS_API_KEY is not set");
+        return Err(JobError::NotConfigured("STOCKS_API_KEY"));
+    };
+    if state.config.stock_symbols.is_empty() {
+        eprintln!("Stocks request but STOCK_SYMBOLS is empty");
+        return Err(JobError::NotConfigured("STOCK_SYMBOLS"));
+    }
+    eprintln!("Stocks request for {}", state.config.stock_symbols.join(","));
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let columns = columns(receipt.width());
+    receipt.line_center("STOCKS");
+    receipt.divider();
+    receipt.line_left(&render_row(&columns, &["SYMBOL", "PRICE", "CHANGE", "%", ""]));
+    receipt.divider();
+
+    let mut fetched = 0;
+    let mut last_error = String::new();
+    for symbol in &state.config.stock_symbols {
+        match fetch_quote(&state.config.stocks_provider, api_key, symbol).await {
+            Ok(quote) => {
+                fetched += 1;
+                receipt.line_left(&render_row(
+                    &columns,
+                    &[
+                        symbol,
+                        &format!("{:.2}", quote.price),
+                        &format!("{:+.2}", quote.change),
+                        &format!("{:+.2}%", quote.change_percent),
+                        arrow(quote.change),
+                    ],
+                ));
+            }
+            Err(e) => {
+                eprintln!("Failed to fetch quote for {}: {}", symbol, e);
+                state.diagnostics.record_error("stocks", format!("{}: {}", symbol, e));
+                receipt.line_left(&render_row(&columns, &[symbol, "n/a"]));
+                last_error = e;
+            }
+        }
+    }
+
+    if fetched == 0 {
+        return Err(JobError::Upstream(last_error));
+    }
+    receipt.divider();
+
+    state.print(receipt, "stocks", source);
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use serde::{Serialize, de::DeserializeOwned};
+use std::{io, path::Path};
+
+/// Reads JSON state saved by [`save_json`], starting from the default if the file is missing or unreadable.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            eprintln!("Ignoring unreadable {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Writes `value` to a temporary file and renames it over `path`, so a crash never leaves half a file.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&tmp, path)
+}
@@ -0,0 +1,107 @@
+//! `POST /` (or `PUT /`, as `curl -T -` sends) with a `Transfer-Encoding: chunked` text
+//! body prints line by line as the body streams in, and cuts when it ends, so e.g.
+//! `tail -f build.log | curl -T - http://printer:3000/` prints live. The job holds the
+//! printer until then; see [`AppState::print_stream`].
+
+use crate::{AppState, Format, PrintParams, Printed, Render, Submission, job_receipt, receipt::Receipt, render_text, source::Source};
+use axum::{
+    Json,
+    extract::Request,
+    http::{
+        HeaderMap, StatusCode,
+        header::{CONTENT_TYPE, TRANSFER_ENCODING},
+    },
+};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_stream::StreamExt;
+
+/// Whether a print request can print as its body comes in: a chunked plain-text body,
+/// without options that need all of it first.
+pub fn streamable(params: &PrintParams, headers: &HeaderMap) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    header(TRANSFER_ENCODING).contains("chunked")
+        && !header(CONTENT_TYPE).starts_with("multipart/")
+        && !header(CONTENT_TYPE).starts_with("application/json")
+        && params.format == Format::Text
+        && !params.batch
+        && params.copies <= 1
+        && params.page_lines.is_none()
+        && params.logo.is_none()
+        && params.schedule_at.is_none()
+        && params.delay_seconds.is_none()
+        && params.expires_in.is_none()
+        && params.rotate == 0
+}
+
+/// Lays out and queues each run of whole lines as it arrives, and the rest with the cut
+/// once the body ends. Answers when it has.
+pub async fn print(state: &AppState, source: &Source, params: &PrintParams, request: Request) -> Result<Json<Printed>, StatusCode> {
+    eprintln!("Receiving streamed print request (raw={})", params.raw);
+    let mut body = request.into_body().into_data_stream();
+    // Bytes after the last newline, waiting for the rest of their line.
+    let mut pending = Vec::new();
+    // The whole job, for the history and archive.
+    let mut whole = job_receipt(&state.config, params);
+    let mut sent: Option<(UnboundedSender<Receipt>, Submission)> = None;
+    let error = loop {
+        let (text, last) = match body.next().await {
+            Some(Ok(chunk)) => {
+                pending.extend_from_slice(&chunk);
+                let Some(end) = pending.iter().rposition(|&b| b == b'\n') else { continue };
+                (pending.drain(..=end).collect(), false)
+            }
+            Some(Err(e)) => {
+                eprintln!("Streamed print request broke off: {}", e);
+                (std::mem::take(&mut pending), true)
+            }
+            None => (std::mem::take(&mut pending), true),
+        };
+        let mut part = job_receipt(&state.config, params);
+        let laid_out = part.sized(params.size, |part| render_text(part, &String::from_utf8_lossy(&text), params, None, state.config.locale));
+        let last = last || laid_out.is_err();
+        part.redact(&state.config.redactions);
+        if params.render != Render::Text {
+            part.rasterize(&state.config.printer_profile, params.render == Render::Raster);
+        }
+        if !last || !params.cut {
+            part.without_cut();
+        }
+        whole.append(part.clone());
+        let delivered = match &sent {
+            Some((rest, _)) => rest.send(part).is_ok(),
+            None => {
+                let (rest, parts) = unbounded_channel();
+                let submission = state.print_stream(part, parts, "print", source, params.callback_url.as_deref());
+                sent = Some((rest, submission));
+                true
+            }
+        };
+        // Undelivered means the printer failed, and nothing more will print.
+        if last || !delivered {
+            break laid_out.err();
+        }
+    };
+
+    // The loop only ends after sending a part.
+    let (rest, mut submission) = sent.expect("a part was sent");
+    if !params.cut {
+        whole.without_cut();
+    }
+    let job = &mut submission.jobs[0];
+    job.lines = whole.to_text().lines().count();
+    job.paper_mm = (whole.paper_mm() * 10.0).round() / 10.0;
+    state.history.set_lines(job.id, job.lines);
+    state.archive.store(job.id, "print", source, job.lines, &whole);
+    state.history.keep_receipt(job.id, whole);
+    // Ends the job once the printer has the last part.
+    drop(rest);
+    if let Some(status) = error {
+        return Err(status);
+    }
+    Ok(Json(Printed {
+        submission,
+        cut: params.cut,
+        copies: 1,
+        plain: true,
+    }))
+}
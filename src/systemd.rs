@@ -0,0 +1,120 @@
+//! Running as a systemd service: serving on a socket systemd opened for us (socket
+//! activation, from a `.socket` unit), `sd_notify` readiness for `Type=notify`, and
+//! watchdog pings for `WatchdogSec=`. The pings stop while the keep-alive reports the
+//! main printer wedged (see [`crate::printer::PrinterSlot::spawn_keepalive`]), so
+//! systemd restarts the service, which is what gets a stuck USB stack going again.
+
+use crate::AppState;
+use std::{
+    io,
+    net::TcpListener,
+    os::{
+        fd::{FromRawFd, RawFd},
+        linux::net::SocketAddrExt,
+        unix::{
+            ffi::OsStrExt,
+            net::{SocketAddr, UnixDatagram, UnixListener},
+        },
+    },
+    path::Path,
+    time::Duration,
+};
+
+/// The first socket systemd passes; see `sd_listen_fds(3)`.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// A listening socket, TCP or Unix.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// The socket systemd passed us, if it started us through a `.socket` unit. Only the
+/// first is used.
+pub fn activated() -> Option<Listener> {
+    let pid: u32 = std::env::var("LISTEN_PID").ok()?.parse().ok()?;
+    let count: u32 = std::env::var("LISTEN_FDS").ok()?.parse().ok()?;
+    // Meant for another process, e.g. the one that exec'd us.
+    if pid != std::process::id() || count == 0 {
+        return None;
+    }
+    if count > 1 {
+        eprintln!("systemd passed {} sockets, serving on the first", count);
+    }
+    // SAFETY: an all-zero sockaddr_storage is valid, and it's the size given.
+    let mut address: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut length = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    // SAFETY: getsockname writes at most `length` bytes to `address`.
+    if unsafe { libc::getsockname(LISTEN_FDS_START, &mut address as *mut _ as *mut libc::sockaddr, &mut length) } != 0 {
+        eprintln!("Ignoring systemd's socket: {}", io::Error::last_os_error());
+        return None;
+    }
+    // SAFETY: systemd hands the descriptor over to us, and nothing else takes it.
+    let listener = unsafe {
+        match address.ss_family as libc::c_int {
+            libc::AF_UNIX => Listener::Unix(UnixListener::from_raw_fd(LISTEN_FDS_START)),
+            _ => Listener::Tcp(TcpListener::from_raw_fd(LISTEN_FDS_START)),
+        }
+    };
+    eprintln!("Serving on the socket from systemd");
+    Some(listener)
+}
+
+/// Listens on the Unix socket at `path`, replacing a stale one left by an earlier run.
+pub fn bind_unix(path: &Path) -> io::Result<UnixListener> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    UnixListener::bind(path)
+}
+
+/// Sends `state`, e.g. `READY=1`, to systemd's notification socket; a no-op when not
+/// run by systemd.
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let sent = UnixDatagram::unbound().and_then(|socket| match path.as_bytes().strip_prefix(b"@") {
+        // An abstract socket.
+        Some(name) => socket.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?),
+        None => socket.send_to(state.as_bytes(), &path),
+    });
+    if let Err(e) = sent {
+        eprintln!("Failed to notify systemd ({}): {}", state, e);
+    }
+}
+
+/// Pings systemd's watchdog at half its timeout while the main printer isn't wedged.
+pub fn spawn_watchdog(state: &AppState) {
+    let Some(timeout) = watchdog_timeout() else {
+        return;
+    };
+    let diagnostics = state.diagnostics.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(timeout / 2);
+        let mut was_wedged = false;
+        loop {
+            interval.tick().await;
+            let wedged = diagnostics.snapshot().wedged;
+            if wedged && !was_wedged {
+                eprintln!("Printer wedged, letting the systemd watchdog restart the service");
+            }
+            was_wedged = wedged;
+            if !wedged {
+                notify("WATCHDOG=1");
+            }
+        }
+    });
+}
+
+/// `WatchdogSec=` as systemd passes it, if it's meant for this process.
+fn watchdog_timeout() -> Option<Duration> {
+    let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    if let Some(pid) = std::env::var("WATCHDOG_PID").ok().and_then(|pid| pid.parse::<u32>().ok())
+        && pid != std::process::id()
+    {
+        return None;
+    }
+    Some(Duration::from_micros(usec)).filter(|timeout| !timeout.is_zero())
+}
@@ -0,0 +1,153 @@
+//! Tables with box-drawing borders (the PC437 line characters every ESC/POS printer
+//! has), sized to the paper: columns keep their natural width when everything fits,
+//! otherwise the widest ones give way and their cells wrap.
+
+use crate::receipt::{text_width, wrap};
+use serde_json::Value;
+
+/// The narrowest a column gets before a table counts as too wide for the paper.
+const MIN_COLUMN: usize = 3;
+
+/// Column widths for `rows` within `width` characters, borders and padding included.
+/// `None` if there are too many columns to fit.
+fn column_widths(rows: &[Vec<String>], width: usize) -> Option<Vec<usize>> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    // "│ " before every cell, " " after, and the closing "│".
+    let available = width.checked_sub(3 * columns + 1)?;
+    if columns == 0 || available < columns * MIN_COLUMN {
+        return None;
+    }
+    let mut widths = vec![1; columns];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text_width(cell));
+        }
+    }
+    while widths.iter().sum::<usize>() > available {
+        // Take a character off the widest column until the table fits.
+        let widest = widths.iter().enumerate().max_by_key(|&(i, &w)| (w, std::cmp::Reverse(i))).map(|(i, _)| i)?;
+        widths[widest] -= 1;
+    }
+    Some(widths)
+}
+
+fn border(widths: &[usize], left: char, middle: char, right: char) -> String {
+    let mut line = String::from(left);
+    for (i, width) in widths.iter().enumerate() {
+        if i > 0 {
+            line.push(middle);
+        }
+        line.extend(std::iter::repeat_n('─', width + 2));
+    }
+    line.push(right);
+    line
+}
+
+/// One row of cells, each wrapped to its column, as however many lines the tallest needs.
+fn row_lines(widths: &[usize], row: &[String]) -> Vec<String> {
+    let cells: Vec<Vec<String>> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, &width)| wrap(row.get(i).map_or("", String::as_str), width))
+        .collect();
+    let height = cells.iter().map(Vec::len).max().unwrap_or(1);
+    (0..height)
+        .map(|n| {
+            let mut line = String::new();
+            for (cell, &width) in cells.iter().zip(widths) {
+                let text = cell.get(n).map_or("", String::as_str);
+                line.push_str("│ ");
+                line.push_str(text);
+                line.extend(std::iter::repeat_n(' ', width - text_width(text) + 1));
+            }
+            line.push('│');
+            line
+        })
+        .collect()
+}
+
+/// Draws `rows` as a bordered table at most `width` characters wide, with a rule under
+/// the first row when it's a `header`. `None` if the columns can't fit the paper.
+pub fn lines(rows: &[Vec<String>], header: bool, width: usize) -> Option<Vec<String>> {
+    let widths = column_widths(rows, width)?;
+    let mut lines = vec![border(&widths, '┌', '┬', '┐')];
+    for (i, row) in rows.iter().enumerate() {
+        if i == 1 && header {
+            lines.push(border(&widths, '├', '┼', '┤'));
+        }
+        lines.extend(row_lines(&widths, row));
+    }
+    lines.push(border(&widths, '└', '┴', '┘'));
+    Some(lines)
+}
+
+/// Parses CSV: comma-separated fields, double-quoted where they contain commas, quotes
+/// or line breaks, with `""` for a literal quote. Blank lines are skipped.
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|field| !field.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    row.push(field);
+    if row.iter().any(|field| !field.is_empty()) {
+        rows.push(row);
+    }
+    rows
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// The rows of a `{"table": [...]}` document: either arrays of cells with the header
+/// first, or objects whose keys make up the header. `None` for any other JSON.
+pub fn from_json(value: &Value) -> Option<Vec<Vec<String>>> {
+    let Value::Object(map) = value else {
+        return None;
+    };
+    let Some(Value::Array(items)) = map.get("table").filter(|_| map.len() == 1) else {
+        return None;
+    };
+    if items.iter().all(Value::is_array) {
+        return Some(items.iter().filter_map(Value::as_array).map(|row| row.iter().map(cell).collect()).collect());
+    }
+    if items.iter().all(Value::is_object) {
+        let mut header: Vec<String> = Vec::new();
+        for key in items.iter().filter_map(Value::as_object).flat_map(|item| item.keys()) {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+        let rows = items
+            .iter()
+            .filter_map(Value::as_object)
+            .map(|item| header.iter().map(|key| item.get(key).map(cell).unwrap_or_default()).collect());
+        return Some(std::iter::once(header.clone()).chain(rows).collect());
+    }
+    None
+}
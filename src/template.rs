@@ -0,0 +1,34 @@
+//! Header and footer templates from `HEADER_TEMPLATE` / `FOOTER_TEMPLATE`.
+//!
+//! Placeholders: `{timestamp}`, `{date}`, `{time}`, `{job_id}`, `{job}`, `{source}` and
+//! `{namespace}`. A literal `\n` starts a new line; every line is centered.
+
+use crate::{receipt::{Receipt, wrap}, source::Source};
+use chrono::{DateTime, Local};
+
+pub struct JobInfo<'a> {
+    pub id: u64,
+    pub job: &'a str,
+    pub source: &'a Source,
+    pub at: DateTime<Local>,
+}
+
+fn fill(template: &str, info: &JobInfo) -> String {
+    template
+        .replace("{timestamp}", &info.at.format("%Y-%m-%d %H:%M").to_string())
+        .replace("{date}", &info.at.format("%Y-%m-%d").to_string())
+        .replace("{time}", &info.at.format("%H:%M").to_string())
+        .replace("{job_id}", &info.id.to_string())
+        .replace("{job}", info.job)
+        .replace("{source}", &info.source.to_string())
+        .replace("{namespace}", info.source.namespace())
+}
+
+/// Adds the filled-in template to `receipt`, one centered line per template line.
+pub fn render(receipt: &mut Receipt, template: &str, info: &JobInfo) {
+    for line in fill(template, info).split("\\n") {
+        for chunk in wrap(line, receipt.width()) {
+            receipt.line_center(&chunk);
+        }
+    }
+}
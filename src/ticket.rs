@@ -0,0 +1,91 @@
+use crate::{AppState, receipt::Receipt, source::Source, storage};
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::Local;
+use serde::Serialize;
+use utoipa::ToSchema;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Scale of the ticket number; large enough to read across a counter.
+pub const NUMBER_SCALE: u8 = 4;
+
+/// Last number handed out per namespace, saved as JSON in the data directory. Tickets and
+/// kitchen orders each keep their own.
+#[derive(Clone)]
+pub struct TicketCounter {
+    inner: Arc<Mutex<HashMap<String, u64>>>,
+    path: PathBuf,
+}
+
+impl TicketCounter {
+    pub fn load(path: PathBuf) -> Self {
+        TicketCounter {
+            inner: Arc::new(Mutex::new(storage::load_json(&path))),
+            path,
+        }
+    }
+
+    /// Hands out the namespace's next number.
+    pub fn next(&self, namespace: &str) -> Result<u64, StatusCode> {
+        self.update(namespace, |n| n + 1)
+    }
+
+    /// Updates the namespace's counter with `f` and saves it, returning the new value.
+    fn update(&self, namespace: &str, f: impl FnOnce(u64) -> u64) -> Result<u64, StatusCode> {
+        let mut counters = self.inner.lock().unwrap();
+        let counter = counters.entry(namespace.to_owned()).or_default();
+        *counter = f(*counter);
+        let value = *counter;
+        storage::save_json(&self.path, &*counters).map_err(|e| {
+            eprintln!("Failed to save ticket counter to {}: {}", self.path.display(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(value)
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Ticket {
+    number: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/ticket",
+    tag = "printing",
+    summary = "Print the next queue ticket number",
+    responses((status = 200, description = "Printed, or queued to print.", body = Ticket))
+)]
+pub async fn ticket(State(state): State<AppState>, source: Source) -> Result<Json<Ticket>, StatusCode> {
+    let number = state.tickets.next(source.namespace())?;
+    eprintln!("Ticket #{} for {}", number, source.namespace());
+
+    let mut receipt = Receipt::for_config(&state.config);
+    if let Some(header) = &state.config.ticket_header {
+        receipt.line_center(header);
+        receipt.divider();
+    }
+    receipt.line_large(&format!("{:03}", number), NUMBER_SCALE);
+    receipt.divider();
+    receipt.line_center(&Local::now().format_localized("%a %-d %b %H:%M", state.config.locale).to_string());
+    state.print(receipt, "ticket", &source);
+
+    Ok(Json(Ticket { number }))
+}
+
+/// Starts the namespace's numbering over; the next ticket is 001.
+#[utoipa::path(
+    post,
+    path = "/ticket/reset",
+    tag = "printing",
+    summary = "Reset the ticket counter",
+    responses((status = 204, description = "The next ticket is 001."))
+)]
+pub async fn reset(State(state): State<AppState>, source: Source) -> Result<StatusCode, StatusCode> {
+    state.tickets.update(source.namespace(), |_| 0)?;
+    eprintln!("Ticket counter reset for {}", source.namespace());
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,175 @@
+//! High and low tide predictions from NOAA CO-OPS, with a curve across the day.
+
+use crate::{AppState, config::Units, error::JobError, receipt::{self, Receipt}, source::Source};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeDelta};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::f64::consts::PI;
+
+/// Water levels from low to high for the tide curve.
+const LEVELS: [char; 5] = ['_', '.', '-', '=', '^'];
+
+#[derive(Deserialize, Default, IntoParams)]
+pub struct TidesParams {
+    /// NOAA station ID, e.g. `9414290`; `TIDE_STATION` when missing.
+    station: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PredictionsResponse {
+    #[serde(default)]
+    predictions: Vec<Prediction>,
+}
+
+#[derive(Deserialize)]
+struct Prediction {
+    /// Station local time, e.g. `2026-10-16 04:12`.
+    t: String,
+    /// Height above mean lower low water.
+    v: String,
+    /// `H` or `L`.
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// A high or low water.
+struct Tide {
+    at: NaiveDateTime,
+    height: f64,
+    high: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/tides",
+    tag = "printing",
+    summary = "Print today's high and low tides with a tide curve",
+    params(TidesParams),
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn tides(State(state): State<AppState>, source: Source, Query(params): Query<TidesParams>) -> Result<(), StatusCode> {
+    Ok(print_tides(&state, &source, &params).await?)
+}
+
+pub async fn print_tides(state: &AppState, source: &Source, params: &TidesParams) -> Result<(), JobError> {
+    let station = params
+        .station
+        .clone()
+        .or_else(|| state.config.tide_station.clone())
+        .ok_or(JobError::NotConfigured("TIDE_STATION"))?;
+    let today = Local::now().date_naive();
+    eprintln!("Tides request for station {}", station);
+
+    let tides = fetch_tides(state, &station, today).await.inspect_err(|e| {
+        eprintln!("Failed to fetch tides: {}", e);
+        state.diagnostics.record_error("tides", e.to_string());
+    })?;
+    let todays: Vec<&Tide> = tides.iter().filter(|tide| tide.at.date() == today).collect();
+    if todays.is_empty() {
+        return Err(JobError::Upstream(format!("no tide predictions for station {}", station)));
+    }
+    let unit = match state.config.weather_units {
+        Units::Metric => "m",
+        Units::Imperial => "ft",
+    };
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("TIDES");
+    receipt.line_center(&format!("Station {}", station));
+    receipt.line_center(&today.format_localized("%A, %-d %B", state.config.locale).to_string());
+    receipt.divider();
+    for tide in todays {
+        receipt.line_left(&format!(
+            "{:<5} {}  {:>6.1} {}",
+            if tide.high { "High" } else { "Low" },
+            tide.at.format("%H:%M"),
+            tide.height,
+            unit
+        ));
+    }
+    receipt.divider();
+    for line in render_tide_curve(&tides, today, receipt.width()).lines() {
+        receipt.line_left(line);
+    }
+
+    state.print(receipt, "tides", source);
+    Ok(())
+}
+
+/// Highs and lows from yesterday to tomorrow, so the curve can run from midnight to midnight.
+async fn fetch_tides(state: &AppState, station: &str, today: NaiveDate) -> Result<Vec<Tide>, JobError> {
+    let base = state.config.noaa_tides_url.as_deref().unwrap_or("https://api.tidesandcurrents.noaa.gov").trim_end_matches('/');
+    let (begin, end) = (today - TimeDelta::days(1), today + TimeDelta::days(1));
+    let units = match state.config.weather_units {
+        Units::Metric => "metric",
+        Units::Imperial => "english",
+    };
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/api/prod/datagetter", base),
+        &[
+            ("product", "predictions"),
+            ("interval", "hilo"),
+            ("datum", "MLLW"),
+            ("time_zone", "lst_ldt"),
+            ("format", "json"),
+            ("application", "print-jobber"),
+            ("station", station),
+            ("units", units),
+            ("begin_date", &begin.format("%Y%m%d").to_string()),
+            ("end_date", &end.format("%Y%m%d").to_string()),
+        ],
+    )
+    .map_err(|e| JobError::Upstream(e.to_string()))?;
+    let response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<PredictionsResponse>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+
+    let mut tides: Vec<Tide> = response
+        .predictions
+        .iter()
+        .filter_map(|prediction| {
+            Some(Tide {
+                at: NaiveDateTime::parse_from_str(&prediction.t, "%Y-%m-%d %H:%M").ok()?,
+                height: prediction.v.trim().parse().ok()?,
+                high: prediction.kind == "H",
+            })
+        })
+        .collect();
+    tides.sort_by_key(|tide| tide.at);
+    Ok(tides)
+}
+
+/// The water level across `day`, one column per slice of the day, drawn as a cosine between
+/// each pair of highs and lows. Columns outside the predictions are left blank.
+fn render_tide_curve(tides: &[Tide], day: NaiveDate, width: usize) -> String {
+    let Some(midnight) = day.and_hms_opt(0, 0, 0) else {
+        return String::new();
+    };
+    let lowest = tides.iter().map(|tide| tide.height).fold(f64::INFINITY, f64::min);
+    let highest = tides.iter().map(|tide| tide.height).fold(f64::NEG_INFINITY, f64::max);
+    let range = (highest - lowest).max(f64::EPSILON);
+
+    let mut bar = String::new();
+    for col in 0..width {
+        let at = midnight + TimeDelta::minutes((col * 24 * 60 / width) as i64);
+        let level = tides.windows(2).find(|pair| pair[0].at <= at && at < pair[1].at).map(|pair| {
+            let progress = (at - pair[0].at).num_seconds() as f64 / (pair[1].at - pair[0].at).num_seconds() as f64;
+            let height = pair[0].height + (pair[1].height - pair[0].height) * (1.0 - (progress * PI).cos()) / 2.0;
+            (height - lowest) / range
+        });
+        bar.push(match level {
+            Some(level) => LEVELS[((level * LEVELS.len() as f64) as usize).min(LEVELS.len() - 1)],
+            None => ' ',
+        });
+    }
+
+    format!("{}\n{}\n", receipt::hour_axis(width, &receipt::HOUR_LABELS), bar.trim_end())
+}
@@ -0,0 +1,32 @@
+//! Dates and times as they're printed: names in the `LOCALE` language, the long date
+//! in report headers from `DATE_FORMAT`, and times of day from `TIME_FORMAT`.
+
+use crate::config::Config;
+use chrono::{NaiveDate, NaiveTime};
+
+pub const DEFAULT_DATE_FORMAT: &str = "%A, %-d %B %Y";
+pub const CLOCK_24H: &str = "%H:%M";
+pub const CLOCK_12H: &str = "%-I:%M %p";
+
+/// The long date for report headers, e.g. "Saturday, 15 March 2025" or, with
+/// `LOCALE=de_DE` and `DATE_FORMAT=%A, %-d. %b %Y`, "Montag, 15. Jan 2024".
+pub fn long_date(config: &Config, date: NaiveDate) -> String {
+    date.format_localized(&config.date_format, config.locale).to_string()
+}
+
+/// A time of day, e.g. "07:30" or "7:30 AM".
+pub fn time(config: &Config, time: NaiveTime) -> String {
+    time.format(&config.time_format).to_string()
+}
+
+/// Whether `pattern` can format a date (or, with `date` false, a time of day) without
+/// failing, so a bad setting is caught at startup rather than when printing.
+pub fn valid_pattern(pattern: &str, date: bool) -> bool {
+    use std::fmt::Write;
+    let mut out = String::new();
+    if date {
+        write!(out, "{}", NaiveDate::MIN.format(pattern)).is_ok()
+    } else {
+        write!(out, "{}", NaiveTime::MIN.format(pattern)).is_ok()
+    }
+}
@@ -0,0 +1,203 @@
+//! HTTPS for the REST API, so API keys don't cross the network in cleartext. Serves
+//! the certificate and key from `TLS_CERT` and `TLS_KEY`; when those files don't exist
+//! yet, a self-signed pair for this host is generated and saved there.
+//!
+//! With `TLS_CLIENT_CA` the server also wants a certificate from every client (mutual
+//! TLS): connections without one signed by that CA are refused during the handshake,
+//! and the certificate's common name becomes the request's identity, `cert:<name>`.
+//!
+//! The gRPC API on `GRPC_PORT` is served over TLS the same way whenever it's configured.
+
+use crate::{config::Tls, source::Peer};
+use axum::{extract::connect_info::Connected, serve::IncomingStream};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use std::{fs, io, net::SocketAddr, os::unix::fs::PermissionsExt, path::Path, sync::Arc};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{RootCertStore, ServerConfig, server::WebPkiClientVerifier},
+    server::TlsStream,
+};
+
+/// Handshakes that may be in flight before new connections wait.
+const PENDING_HANDSHAKES: usize = 64;
+
+/// Names the generated certificate is valid for: `localhost`, the host name and its
+/// mDNS `.local` name.
+fn host_names() -> Vec<String> {
+    let mut names = vec!["localhost".to_owned()];
+    let mut buf = [0u8; 256];
+    // SAFETY: gethostname(2) writes at most `buf.len()` bytes into `buf`.
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } == 0 {
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        if let Ok(host) = std::str::from_utf8(&buf[..len])
+            && !host.is_empty()
+            && host != "localhost"
+        {
+            names.push(host.to_owned());
+            if !host.contains('.') {
+                names.push(format!("{}.local", host));
+            }
+        }
+    }
+    names
+}
+
+fn generate(tls: &Tls) -> io::Result<()> {
+    let names = host_names();
+    eprintln!("Generating a self-signed TLS certificate for {}", names.join(", "));
+    let generated = rcgen::generate_simple_self_signed(names).map_err(io::Error::other)?;
+    for path in [&tls.cert, &tls.key] {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    fs::write(&tls.cert, generated.cert.pem())?;
+    fs::write(&tls.key, generated.key_pair.serialize_pem())?;
+    fs::set_permissions(&tls.key, fs::Permissions::from_mode(0o600))
+}
+
+fn invalid(path: &Path, e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+}
+
+fn load(tls: &Tls) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(&tls.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(&tls.cert, e))?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key).map_err(|e| invalid(&tls.key, e))?;
+    let builder = ServerConfig::builder();
+    let builder = match &tls.client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(path).map_err(|e| invalid(path, e))? {
+                roots.add(cert.map_err(|e| invalid(path, e))?).map_err(|e| invalid(path, e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build().map_err(|e| invalid(path, e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+/// The server's TLS setup, generating a self-signed certificate on first run.
+pub fn server_config(tls: &Tls) -> io::Result<ServerConfig> {
+    match (tls.cert.exists(), tls.key.exists()) {
+        (true, true) => {}
+        (false, false) => generate(tls)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("only one of {} and {} exists", tls.cert.display(), tls.key.display()),
+            ));
+        }
+    }
+    load(tls)
+}
+
+/// The common name in the client's certificate, if it sent one.
+fn client_name(stream: &TlsStream<TcpStream>) -> Option<String> {
+    let cert = stream.get_ref().1.peer_certificates()?.first()?;
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    let name = cert.subject().iter_common_name().next()?.as_str().ok()?.to_owned();
+    Some(name)
+}
+
+/// Accepts TLS connections for [`axum::serve`]. Handshakes run in their own tasks,
+/// so a slow or stalled client doesn't hold up everyone else.
+pub struct TlsListener {
+    local_addr: SocketAddr,
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, Peer)>,
+}
+
+impl TlsListener {
+    pub async fn bind(addr: &str, tls: &Tls) -> io::Result<Self> {
+        Self::on(TcpListener::bind(addr).await?, tls)
+    }
+
+    /// Serves TLS on an already open `listener`, e.g. one from systemd.
+    pub fn on(listener: TcpListener, tls: &Tls) -> io::Result<Self> {
+        Self::accepting(listener, server_config(tls)?)
+    }
+
+    /// Serves TLS for the gRPC API: the same certificate and client verification, but
+    /// offering HTTP/2, which gRPC needs.
+    pub fn for_grpc(listener: TcpListener, tls: &Tls) -> io::Result<Self> {
+        let mut config = server_config(tls)?;
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Self::accepting(listener, config)
+    }
+
+    fn accepting(listener: TcpListener, config: ServerConfig) -> io::Result<Self> {
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let local_addr = listener.local_addr()?;
+        let (sender, connections) = mpsc::channel(PENDING_HANDSHAKES);
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => {
+                            let peer = Peer {
+                                addr: remote,
+                                cert_name: client_name(&stream),
+                            };
+                            let _ = sender.send((stream, peer)).await;
+                        }
+                        Err(e) => eprintln!("TLS handshake with {} failed: {}", remote, e),
+                    }
+                });
+            }
+        });
+        Ok(TlsListener { local_addr, connections })
+    }
+
+    /// The connections that completed their handshake, for servers that take a stream.
+    pub fn into_stream(self) -> ReceiverStream<(TlsStream<TcpStream>, Peer)> {
+        ReceiverStream::new(self.connections)
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = Peer;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accept loop only ends with the runtime.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(Peer {
+            addr: self.local_addr,
+            cert_name: None,
+        })
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for Peer {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        stream.remote_addr().clone()
+    }
+}
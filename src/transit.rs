@@ -0,0 +1,71 @@
+//! Upcoming departures from a public transport stop, from a `transport.rest` style
+//! HAFAS API (BVG by default).
+
+use crate::error::JobError;
+use chrono::{DateTime, FixedOffset, Local};
+use serde::Deserialize;
+
+const API_BASE: &str = "https://v6.bvg.transport.rest";
+
+#[derive(Deserialize)]
+struct DeparturesResponse {
+    departures: Vec<Departure>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Departure {
+    /// Expected time including any delay; missing when cancelled.
+    when: Option<DateTime<FixedOffset>>,
+    planned_when: Option<DateTime<FixedOffset>>,
+    /// Seconds late.
+    delay: Option<i64>,
+    direction: Option<String>,
+    line: Option<Line>,
+    #[serde(default)]
+    cancelled: bool,
+}
+
+#[derive(Deserialize)]
+struct Line {
+    name: String,
+}
+
+/// The next `count` departures from `stop` as lines like `08:12 U8 -> Hermannstr. +2`.
+/// `base` replaces the BVG API, e.g. with `https://v6.db.transport.rest`.
+pub async fn departures(stop: &str, count: usize, base: Option<&str>) -> Result<Vec<String>, JobError> {
+    let base = base.unwrap_or(API_BASE).trim_end_matches('/');
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/stops/{}/departures", base, stop),
+        &[("results", count.to_string()), ("duration", "60".to_owned())],
+    )
+    .map_err(|e| JobError::Upstream(e.to_string()))?;
+    let response = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<DeparturesResponse>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+
+    Ok(response
+        .departures
+        .iter()
+        .take(count)
+        .map(|departure| {
+            let at = departure.when.or(departure.planned_when);
+            let mut line = format!(
+                "{} {} -> {}",
+                at.map_or_else(|| "--:--".to_owned(), |at| at.with_timezone(&Local).format("%H:%M").to_string()),
+                departure.line.as_ref().map_or("?", |line| line.name.as_str()),
+                departure.direction.as_deref().unwrap_or("?")
+            );
+            if departure.cancelled {
+                line.push_str(" (cancelled)");
+            } else if let Some(minutes) = departure.delay.map(|delay| delay / 60).filter(|&minutes| minutes > 0) {
+                line.push_str(&format!(" +{}", minutes));
+            }
+            line
+        })
+        .collect())
+}
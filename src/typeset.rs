@@ -0,0 +1,92 @@
+//! Text drawn as raster graphics in the bundled DejaVu Sans Mono, for `?render=raster`:
+//! scripts and symbols the printer's character tables don't have still print. Each
+//! character fills the same cell it would as printed text, so layouts line up either way.
+
+use crate::{
+    raster::Bitmap,
+    receipt::{Style, char_width},
+};
+use ab_glyph::{Font, FontRef, PxScale, point};
+use escpos::utils::JustifyMode;
+use std::sync::OnceLock;
+
+/// A line's height in dots at the printer's default spacing of 1/6 inch.
+pub const LINE_DOTS: usize = 34;
+
+fn font() -> &'static FontRef<'static> {
+    static FONT: OnceLock<FontRef<'static>> = OnceLock::new();
+    // Compiled in, so failing to parse it is a bug.
+    FONT.get_or_init(|| FontRef::try_from_slice(include_bytes!("fonts/DejaVuSansMono.ttf")).expect("bundled DejaVu Sans Mono"))
+}
+
+/// Part of a line in one style, at `scale` times the normal character width and height.
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+    pub scale: (u8, u8),
+}
+
+/// Draws `spans` as one line of a `paper` dots wide receipt, in character cells `cell`
+/// dots wide and `line_height` dots tall, placed as `justify` says.
+pub fn line(spans: &[Span], cell: usize, line_height: usize, paper: usize, justify: JustifyMode) -> Bitmap {
+    let font = font();
+    // Every glyph in a monospaced font has the same advance.
+    let advance = font.h_advance_unscaled(font.glyph_id('M'));
+    let glyph_height = font.height_unscaled() * cell as f32 / advance;
+
+    let columns: usize = spans
+        .iter()
+        .flat_map(|span| span.text.chars().map(|c| char_width(c) * span.scale.0.max(1) as usize))
+        .sum();
+    let tallest = spans.iter().map(|span| span.scale.1.max(1) as usize).max().unwrap_or(1);
+    let mut bitmap = Bitmap::new(paper, line_height * tallest);
+    let pad = paper.saturating_sub(columns * cell);
+    let mut x = match justify {
+        JustifyMode::LEFT => 0,
+        JustifyMode::CENTER => pad / 2,
+        JustifyMode::RIGHT => pad,
+    };
+    for span in spans {
+        let (width, height) = (span.scale.0.max(1) as usize, span.scale.1.max(1) as usize);
+        let scale = PxScale {
+            x: glyph_height * width as f32,
+            y: glyph_height * height as f32,
+        };
+        // Shorter text sits at the bottom of the line, as printed characters do.
+        let top = bitmap.height - line_height * height;
+        // Glyphs centered between the line's top and bottom.
+        let baseline = (line_height * height) as f32 / 2.0 + scale.y / 2.0 + font.descent_unscaled() * scale.y / font.height_unscaled();
+        for c in span.text.chars() {
+            let box_width = char_width(c) * width * cell;
+            let mut ink = Bitmap::new(box_width, line_height * height);
+            let origin = point((box_width - cell * width) as f32 / 2.0, baseline);
+            let glyph = font.glyph_id(c).with_scale_and_position(scale, origin);
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let (px, py) = (bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32);
+                    if coverage >= 0.5 && (0..ink.width as i32).contains(&px) && (0..ink.height as i32).contains(&py) {
+                        ink.set(px as usize, py as usize, true);
+                        // Emphasis doubles the strokes, as the printer's does.
+                        if span.style.bold && px as usize + 1 < ink.width {
+                            ink.set(px as usize + 1, py as usize, true);
+                        }
+                    }
+                });
+            }
+            if span.style.underline {
+                let y = (baseline as usize + 2 * height).min(ink.height - 1);
+                (0..ink.width).for_each(|ux| ink.set(ux, y, true));
+            }
+            for iy in 0..ink.height {
+                for ix in 0..ink.width {
+                    if x + ix < paper && ink.get(ix, iy) != span.style.inverse {
+                        bitmap.set(x + ix, top + iy, true);
+                    }
+                }
+            }
+            x += box_width;
+        }
+    }
+    bitmap
+}
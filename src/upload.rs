@@ -0,0 +1,207 @@
+use crate::{PrintParams, config::Config, csv_lines, json_lines, markdown, raster, receipt::Receipt, render_lines, render_text};
+use axum::{body::Bytes, extract::Multipart, http::StatusCode};
+use escpos::utils::JustifyMode;
+use serde::{Deserialize, de::DeserializeOwned};
+use utoipa::ToSchema;
+
+/// A file part of a `multipart/form-data` print request.
+pub struct Upload {
+    content_type: Option<String>,
+    file_name: Option<String>,
+    data: Bytes,
+}
+
+/// The `multipart/form-data` body of a print request, for the API docs: any number of
+/// `file` parts, printed in order, and print options as fields named like the query
+/// parameters.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct UploadForm {
+    #[schema(value_type = Vec<String>, format = Binary)]
+    file: Vec<Bytes>,
+}
+
+enum Kind {
+    Text,
+    Markdown,
+    Json,
+    Csv,
+    Image,
+}
+
+impl Upload {
+    /// A file read from disk, typed by its extension.
+    pub fn from_file(file_name: &str, data: Vec<u8>) -> Self {
+        Upload {
+            content_type: None,
+            file_name: Some(file_name.to_owned()),
+            data: data.into(),
+        }
+    }
+
+    /// A download from `/fetch`, typed by its content type and then its URL's file name.
+    pub fn fetched(content_type: Option<String>, file_name: Option<&str>, data: Vec<u8>) -> Self {
+        Upload {
+            content_type,
+            file_name: file_name.map(str::to_owned),
+            data: data.into(),
+        }
+    }
+
+    /// Picks a renderer from the part's MIME type, falling back to the file extension
+    /// since browsers often send `.md` files as `application/octet-stream`.
+    fn kind(&self) -> Option<Kind> {
+        let extension = self
+            .file_name
+            .as_deref()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_lowercase());
+        match (self.content_type.as_deref(), extension.as_deref()) {
+            (Some("text/markdown"), _) | (_, Some("md" | "markdown")) => Some(Kind::Markdown),
+            (Some("application/json"), _) | (_, Some("json")) => Some(Kind::Json),
+            (Some("text/csv"), _) | (_, Some("csv")) => Some(Kind::Csv),
+            (Some(mime), _) if mime.starts_with("image/") => Some(Kind::Image),
+            (_, Some("png" | "jpg" | "jpeg" | "gif")) => Some(Kind::Image),
+            (Some(mime), _) if mime.starts_with("text/") => Some(Kind::Text),
+            (None | Some("application/octet-stream"), Some("txt") | None) => Some(Kind::Text),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.file_name.as_deref().unwrap_or("upload")
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+fn field_error(e: axum::extract::multipart::MultipartError) -> StatusCode {
+    eprintln!("Bad multipart upload: {}", e);
+    e.status()
+}
+
+/// Parses a field value the way the query string would, e.g. `floyd-steinberg` for `dither`.
+fn parse_enum<T: DeserializeOwned>(value: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_value(serde_json::Value::String(value.to_owned()))
+}
+
+/// Applies a form field named after a print option; `None` if `name` isn't one.
+fn apply_option(params: &mut PrintParams, name: &str, value: &str) -> Option<bool> {
+    let parsed = match name {
+        "raw" => value.parse().map(|raw| params.raw = raw).is_ok(),
+        "strict" => value.parse().map(|strict| params.strict = strict).is_ok(),
+        "indent" => value.parse().map(|indent| params.indent = indent).is_ok(),
+        "tab_width" => value.parse().map(|tab_width| params.tab_width = tab_width).is_ok(),
+        "trim" => value.parse().map(|trim| params.trim = trim).is_ok(),
+        "squeeze" => value.parse().map(|squeeze| params.squeeze = squeeze).is_ok(),
+        "plain" => value.parse().map(|plain| params.plain = plain).is_ok(),
+        "cut" => value.parse().map(|cut| params.cut = cut).is_ok(),
+        "copies" => value.parse().map(|copies| params.copies = copies).is_ok(),
+        "size" => value.parse().map(|size| params.size = size).is_ok(),
+        "width" => value.parse().map(|width| params.width = Some(width)).is_ok(),
+        "height" => value.parse().map(|height| params.height = Some(height)).is_ok(),
+        "fit" => parse_enum(value).map(|fit| params.fit = fit).is_ok(),
+        "align" => parse_enum(value).map(|align| params.align = align).is_ok(),
+        "rotate" => value.parse().map(|rotate| params.rotate = rotate).is_ok(),
+        "dither" => parse_enum(value).map(|dither| params.dither = dither).is_ok(),
+        "brightness" => value.parse().map(|brightness| params.brightness = brightness).is_ok(),
+        "contrast" => value.parse().map(|contrast| params.contrast = contrast).is_ok(),
+        "logo" => {
+            params.logo = Some(value.to_owned());
+            true
+        }
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+/// Reads every part of the form. Fields named after print options (`raw`, `copies`,
+/// `dither`, ...) override the query parameters; everything else is content to print, in order.
+pub async fn read_parts(mut multipart: Multipart, params: &mut PrintParams) -> Result<Vec<Upload>, StatusCode> {
+    let mut uploads = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(field_error)? {
+        let name = field.name().unwrap_or_default().to_owned();
+        let content_type = field.content_type().map(str::to_owned);
+        let file_name = field.file_name().map(str::to_owned);
+        let data = field.bytes().await.map_err(field_error)?;
+
+        if file_name.is_none() {
+            let value = std::str::from_utf8(&data).unwrap_or_default().trim();
+            match apply_option(params, &name, value) {
+                Some(true) => continue,
+                Some(false) => {
+                    eprintln!("Invalid {} field {:?}", name, value);
+                    return Err(StatusCode::BAD_REQUEST);
+                }
+                None => {}
+            }
+        }
+        uploads.push(Upload {
+            content_type,
+            file_name,
+            data,
+        });
+    }
+    Ok(uploads)
+}
+
+pub fn render(receipt: &mut Receipt, upload: &Upload, params: &PrintParams, config: &Config) -> Result<(), StatusCode> {
+    let locale = config.locale;
+    eprintln!("Rendering upload {} ({} bytes, {:?})", upload.name(), upload.data.len(), upload.content_type);
+    let text = || std::str::from_utf8(&upload.data).or(Err(StatusCode::UNPROCESSABLE_ENTITY));
+    match upload.kind() {
+        Some(Kind::Text) => render_text(receipt, text()?, params, upload.file_name.as_deref(), locale),
+        Some(Kind::Json) => {
+            let lines = json_lines(text()?, receipt.width())?;
+            render_lines(receipt, lines, params, upload.file_name.as_deref(), locale);
+            Ok(())
+        }
+        Some(Kind::Csv) => {
+            let lines = csv_lines(text()?, receipt.width())?;
+            render_lines(receipt, lines, params, upload.file_name.as_deref(), locale);
+            Ok(())
+        }
+        Some(Kind::Markdown) => {
+            markdown::render(receipt, text()?);
+            Ok(())
+        }
+        Some(Kind::Image) => {
+            if !matches!(params.rotate, 0 | 90 | 180 | 270) {
+                eprintln!("Invalid rotation {} (expected 0, 90, 180 or 270)", params.rotate);
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            let options = raster::ImageOptions {
+                width: params.width,
+                paper_width: Some(config.printer_profile.raster_width as u32),
+                height: params.height,
+                fit: params.fit,
+                rotate: params.rotate,
+                dither: params.dither,
+                brightness: params.brightness.clamp(-255, 255),
+                contrast: params.contrast,
+            };
+            let bitmap = raster::decode_image(&upload.data, &options).map_err(|e| {
+                eprintln!("Failed to decode {}: {}", upload.name(), e);
+                StatusCode::UNPROCESSABLE_ENTITY
+            })?;
+            let justify = match params.align {
+                ImageAlign::Left => JustifyMode::LEFT,
+                ImageAlign::Center => JustifyMode::CENTER,
+                ImageAlign::Right => JustifyMode::RIGHT,
+            };
+            receipt.image_aligned(&bitmap, &format!("[image: {}]", upload.name()), justify);
+            Ok(())
+        }
+        None => {
+            eprintln!("Unsupported upload type {:?} for {}", upload.content_type, upload.name());
+            Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        }
+    }
+}
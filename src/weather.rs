@@ -0,0 +1,1181 @@
+use crate::{
+    AppState,
+    astro,
+    config::{Config, Location, Units},
+    error::JobError,
+    raster,
+    receipt::{self, Column, Receipt, render_row},
+    source::Source,
+    timefmt,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike, Utc};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub const BERLIN_LAT: f64 = 52.52;
+pub const BERLIN_LON: f64 = 13.405;
+const MAX_LOCATIONS: usize = 8;
+/// Dots; about 2cm of paper.
+const TEMPERATURE_CHART_HEIGHT: usize = 160;
+
+#[derive(Clone, Deserialize)]
+struct WeatherResponse {
+    utc_offset_seconds: i32,
+    daily: DailyWeather,
+    hourly: HourlyWeather,
+}
+
+#[derive(Clone, Deserialize)]
+struct DailyWeather {
+    time: Vec<String>,
+    temperature_2m_max: Vec<f64>,
+    temperature_2m_min: Vec<f64>,
+    apparent_temperature_max: Vec<f64>,
+    apparent_temperature_min: Vec<f64>,
+    precipitation_probability_max: Vec<u8>,
+    weather_code: Vec<u8>,
+    sunrise: Vec<String>,
+    sunset: Vec<String>,
+    uv_index_max: Vec<f64>,
+    wind_speed_10m_max: Vec<f64>,
+    wind_gusts_10m_max: Vec<f64>,
+    /// Degrees the wind mostly blows from, 0 for north.
+    wind_direction_10m_dominant: Vec<f64>,
+    relative_humidity_2m_mean: Vec<f64>,
+    dew_point_2m_mean: Vec<f64>,
+    /// Rain, showers and snow as water, in mm or inches.
+    precipitation_sum: Vec<f64>,
+    rain_sum: Vec<f64>,
+    /// Fresh snow in cm or inches.
+    snowfall_sum: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveResponse {
+    daily: ArchiveDaily,
+}
+
+#[derive(Deserialize)]
+struct ArchiveDaily {
+    temperature_2m_max: Vec<Option<f64>>,
+    temperature_2m_min: Vec<Option<f64>>,
+    weather_code: Vec<Option<u8>>,
+}
+
+/// Observed weather on the same date a year earlier.
+struct LastYear {
+    high: f64,
+    low: f64,
+    weather_code: u8,
+}
+
+#[derive(Deserialize)]
+struct GeocodingResponse {
+    #[serde(default)]
+    results: Vec<GeocodingResult>,
+}
+
+#[derive(Deserialize)]
+struct GeocodingResult {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// A reading from `WEATHER_INDOOR_URL`, e.g. `{"temperature": 21.4, "humidity": 48}`.
+/// Temperatures are °C unless `unit` is `F`, as most ESP sensor firmware reports them.
+#[derive(Deserialize)]
+struct IndoorReading {
+    temperature: Option<f64>,
+    humidity: Option<f64>,
+    unit: Option<String>,
+}
+
+impl IndoorReading {
+    /// The temperature in `units`.
+    fn temperature(&self, units: Units) -> Option<f64> {
+        match self.unit.as_deref() {
+            Some("F" | "f") => self.temperature.map(|f| units.from_celsius((f - 32.0) * 5.0 / 9.0)),
+            _ => self.temperature.map(|c| units.from_celsius(c)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AirQualityResponse {
+    current: AirQuality,
+}
+
+#[derive(Deserialize)]
+struct AirQuality {
+    european_aqi: Option<f64>,
+    pm2_5: Option<f64>,
+    pm10: Option<f64>,
+    alder_pollen: Option<f64>,
+    birch_pollen: Option<f64>,
+    grass_pollen: Option<f64>,
+    mugwort_pollen: Option<f64>,
+    olive_pollen: Option<f64>,
+    ragweed_pollen: Option<f64>,
+}
+
+#[derive(Clone, Deserialize)]
+struct HourlyWeather {
+    temperature_2m: Vec<f64>,
+    precipitation_probability: Vec<f64>,
+    wind_speed_10m: Vec<f64>,
+    wind_gusts_10m: Vec<f64>,
+    /// Snow on the ground in metres or feet.
+    snow_depth: Vec<f64>,
+}
+
+impl HourlyWeather {
+    fn truncate(&mut self, hours: usize) {
+        self.temperature_2m.truncate(hours);
+        self.precipitation_probability.truncate(hours);
+        self.wind_speed_10m.truncate(hours);
+        self.wind_gusts_10m.truncate(hours);
+        self.snow_depth.truncate(hours);
+    }
+}
+
+// The codes are listed one by one as the WMO table has them.
+#[allow(clippy::manual_range_patterns)]
+fn weather_code_to_description(code: u8) -> &'static str {
+    match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Foggy",
+        51 | 53 | 55 => "Drizzle",
+        61 | 63 | 65 => "Rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 => "Snow",
+        77 => "Snow grains",
+        80 | 81 | 82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => "Unknown",
+    }
+}
+
+/// "2024-01-15T07:30" -> "07:30", or "7:30 AM" with `TIME_FORMAT=12h`.
+fn format_time(config: &Config, iso: &str) -> String {
+    match NaiveDateTime::parse_from_str(iso, "%Y-%m-%dT%H:%M") {
+        Ok(at) => timefmt::time(config, at.time()),
+        Err(_) => iso.to_owned(),
+    }
+}
+
+/// Midnight at the forecast location, as UTC, for the forecast's date.
+fn local_midnight(date: NaiveDate, utc_offset_seconds: i32) -> Option<DateTime<Utc>> {
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc() - TimeDelta::seconds(utc_offset_seconds as i64))
+}
+
+/// Hours since local midnight, e.g. 7.5 for 07:30.
+fn local_hour(t: DateTime<Utc>, midnight: DateTime<Utc>) -> f64 {
+    (t - midnight).num_seconds() as f64 / 3600.0
+}
+
+fn parse_hour(iso: &str) -> f64 {
+    // "2024-01-15T07:30" -> 7.5
+    let time = iso.split('T').nth(1).unwrap_or("12:00");
+    let parts: Vec<&str> = time.split(':').collect();
+    let hour: f64 = parts[0].parse().unwrap_or(12.0);
+    let min: f64 = parts.get(1).and_then(|m| m.parse().ok()).unwrap_or(0.0);
+    hour + min / 60.0
+}
+
+/// Sun times for the report's day, as hours since local midnight.
+struct Daylight {
+    dawn: f64,
+    sunrise: f64,
+    sunset: f64,
+    dusk: f64,
+}
+
+fn render_daylight_bar(daylight: &Daylight, width: usize) -> String {
+    let mut bar = String::new();
+
+    for col in 0..width {
+        let hour = (col as f64 / width as f64) * 24.0;
+        let sr_col = (daylight.sunrise / 24.0 * width as f64) as usize;
+        let ss_col = (daylight.sunset / 24.0 * width as f64) as usize;
+
+        let ch = if col == sr_col {
+            '>'
+        } else if col == ss_col {
+            '<'
+        } else if hour > daylight.sunrise && hour < daylight.sunset {
+            '='
+        } else if hour >= daylight.dawn && hour <= daylight.dusk {
+            '~'
+        } else {
+            '-'
+        };
+        bar.push(ch);
+    }
+
+    format!(
+        "{}\n{}\n{}\n",
+        receipt::hour_axis(width, &receipt::HOUR_LABELS),
+        bar,
+        receipt::hour_axis(width, &[(0, "^night"), (6, "^morn"), (12, "^noon"), (18, "^eve"), (24, "^")])
+    )
+}
+
+/// A one-character picture of a weather code for the outlook grid; see [`OUTLOOK_LEGEND`].
+fn condition_icon(code: u8) -> char {
+    match code {
+        0 | 1 => 'O',
+        2 => 'o',
+        3 => 'c',
+        45 | 48 => '=',
+        51..=57 => ',',
+        61..=67 | 80..=82 => '/',
+        71..=77 | 85 | 86 => '*',
+        95..=99 => '!',
+        _ => '?',
+    }
+}
+
+const OUTLOOK_LEGEND: [&str; 2] = ["O clear  o partly cloudy  c cloudy  = fog", ", drizzle  / rain  * snow  ! storm"];
+
+/// Width of one day in the outlook grid; seven fill 42 of a 48-column receipt.
+const OUTLOOK_COLUMN: usize = 6;
+
+/// Up to seven days side by side, as many as fit in `width`: day, condition icon, high
+/// and low.
+fn render_outlook(daily: &DailyWeather, width: usize) -> Vec<String> {
+    let days = daily.time.len().min(7).min(width / OUTLOOK_COLUMN);
+    let margin = " ".repeat(width.saturating_sub(7 * OUTLOOK_COLUMN) / 2);
+    let row = |cell: &dyn Fn(usize) -> String| {
+        let cells: String = (0..days).map(|day| format!("{:^width$}", cell(day), width = OUTLOOK_COLUMN)).collect();
+        format!("{}{}", margin, cells).trim_end().to_owned()
+    };
+    vec![
+        row(&|day| {
+            NaiveDate::parse_from_str(&daily.time[day], "%Y-%m-%d").map_or_else(|_| "?".to_owned(), |date| date.format("%a").to_string())
+        }),
+        row(&|day| condition_icon(daily.weather_code[day]).to_string()),
+        row(&|day| format!("{:.0}", daily.temperature_2m_max[day])),
+        row(&|day| format!("{:.0}", daily.temperature_2m_min[day])),
+    ]
+}
+
+/// The 8-point compass direction for a bearing in degrees, e.g. `NW` for 315.
+fn compass_point(degrees: f64) -> &'static str {
+    const POINTS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+    POINTS[((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8]
+}
+
+/// An arrow pointing where wind from `degrees` blows to, in characters every code page has.
+fn wind_arrow(degrees: f64) -> &'static str {
+    const ARROWS: [&str; 8] = ["v", "/", "<", "\\", "^", "/", ">", "\\"];
+    ARROWS[((degrees.rem_euclid(360.0) + 22.5) / 45.0) as usize % 8]
+}
+
+/// How the air feels at a dew point in °F.
+fn dew_point_verdict(dew_point: f64) -> &'static str {
+    if dew_point < 40.0 {
+        "dry"
+    } else if dew_point < 60.0 {
+        "comfortable"
+    } else {
+        "muggy"
+    }
+}
+
+/// The day's expected precipitation, e.g. `Amount: 4.2 mm (rain 3.0 mm, snow 1.2 cm)`,
+/// or `None` on a dry day.
+fn precipitation_amounts(daily: &DailyWeather, units: Units) -> Option<String> {
+    let total = daily.precipitation_sum[0];
+    if total <= 0.0 {
+        return None;
+    }
+    let water = |amount: f64| match units {
+        Units::Metric => format!("{:.1} mm", amount),
+        Units::Imperial => format!("{:.2} in", amount),
+    };
+    let snow = |amount: f64| match units {
+        Units::Metric => format!("{:.1} cm", amount),
+        Units::Imperial => format!("{:.1} in", amount),
+    };
+    let mut kinds = Vec::new();
+    if daily.rain_sum[0] > 0.0 {
+        kinds.push(format!("rain {}", water(daily.rain_sum[0])));
+    }
+    if daily.snowfall_sum[0] > 0.0 {
+        kinds.push(format!("snow {}", snow(daily.snowfall_sum[0])));
+    }
+    Some(match kinds.as_slice() {
+        [] => format!("Amount: {}", water(total)),
+        [only] if only.starts_with("rain") => format!("Amount: {} of rain", water(total)),
+        _ => format!("Amount: {} ({})", water(total), kinds.join(", ")),
+    })
+}
+
+/// The deepest snow on the ground today, e.g. `Snow depth: 12 cm`, or `None` without any.
+fn snow_depth(hourly: &HourlyWeather, units: Units) -> Option<String> {
+    let deepest = hourly.snow_depth.iter().copied().fold(0.0, f64::max);
+    if deepest <= 0.0 {
+        return None;
+    }
+    Some(match units {
+        Units::Metric => format!("Snow depth: {:.0} cm", deepest * 100.0),
+        Units::Imperial => format!("Snow depth: {:.0} in", deepest * 12.0),
+    })
+}
+
+/// The hour of the strongest gust, e.g. `15:00`.
+fn peak_gust_hour(gusts: &[f64]) -> Option<String> {
+    let (hour, _) = gusts.iter().enumerate().max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    Some(format!("{:02}:00", hour))
+}
+
+fn render_hourly_temps(temps: &[f64], units: Units, width: usize) -> String {
+    let mut output = String::new();
+
+    // Show temps for key hours (every 3 hours, or every 4 where eight don't fit)
+    let (indent, step) = if width >= 41 { ("  ", 3) } else { ("", 4) };
+    output.push_str(&format!("{}Hour:  ", indent));
+    for h in (0..24).step_by(step) {
+        output.push_str(&format!("{:>4}", h));
+    }
+    output.push('\n');
+    output.push_str(&format!("{}Temp:  ", indent));
+    for h in (0..24).step_by(step) {
+        if h < temps.len() {
+            output.push_str(&format!("{:>3.0}{}", temps[h], units.degrees()));
+        }
+    }
+    output.push('\n');
+
+    output
+}
+
+fn aqi_description(aqi: f64) -> &'static str {
+    // European AQI bands
+    match aqi as u32 {
+        0..=20 => "Good",
+        21..=40 => "Fair",
+        41..=60 => "Moderate",
+        61..=80 => "Poor",
+        81..=100 => "Very poor",
+        _ => "Extremely poor",
+    }
+}
+
+fn render_air_quality(air: &AirQuality) -> String {
+    let fmt = |v: Option<f64>| v.map(|v| format!("{:.0}", v)).unwrap_or("-".to_owned());
+    let mut output = String::new();
+
+    match air.european_aqi {
+        Some(aqi) => output.push_str(&format!("AQI (EU): {:.0} - {}\n", aqi, aqi_description(aqi))),
+        None => output.push_str("AQI (EU): -\n"),
+    }
+    output.push_str(&format!("PM2.5: {} ug/m3     PM10: {} ug/m3\n", fmt(air.pm2_5), fmt(air.pm10)));
+
+    // Pollen is only reported for Europe and only in season, so list whatever is airborne.
+    let pollen: Vec<String> = [
+        ("Alder", air.alder_pollen),
+        ("Birch", air.birch_pollen),
+        ("Grass", air.grass_pollen),
+        ("Mugwort", air.mugwort_pollen),
+        ("Olive", air.olive_pollen),
+        ("Ragweed", air.ragweed_pollen),
+    ]
+    .into_iter()
+    .filter_map(|(name, v)| v.filter(|&v| v >= 1.0).map(|v| format!("{} {:.0}", name, v)))
+    .collect();
+    if pollen.is_empty() {
+        output.push_str("Pollen: none\n");
+    } else {
+        output.push_str(&format!("Pollen: {}\n", pollen.join(", ")));
+    }
+
+    output
+}
+
+/// Linearly interpolates an hourly series at a fractional hour, e.g. 7.5 for 07:30.
+fn value_at(series: &[f64], hour: f64) -> Option<f64> {
+    let lower = hour.floor() as usize;
+    let upper = (hour.ceil() as usize).min(series.len().checked_sub(1)?);
+    let (a, b) = (*series.get(lower)?, series[upper]);
+    Some(a + (b - a) * hour.fract())
+}
+
+fn render_key_hours(config: &Config, hourly: &HourlyWeather, hours: &[NaiveTime], width: usize) -> String {
+    // Drop the indent and narrow the last two columns where the full 36 don't fit.
+    let (indent, wide) = if width >= 36 { (2, 10) } else { (0, 8) };
+    let units = config.weather_units;
+    let columns = [
+        Column::left(indent),
+        Column::left(8),
+        Column::right(6),
+        Column::right(wide),
+        Column::right(wide),
+    ];
+    let mut output = render_row(&columns, &["", "Time", "Temp", "Precip", "Wind"]);
+    output.push('\n');
+
+    for time in hours {
+        let hour = time.hour() as f64 + time.minute() as f64 / 60.0;
+        let cell = |series: &[f64], fmt: &dyn Fn(f64) -> String| value_at(series, hour).map(fmt).unwrap_or("-".to_owned());
+        output.push_str(&render_row(
+            &columns,
+            &[
+                "",
+                &timefmt::time(config, *time),
+                &cell(&hourly.temperature_2m, &|t| format!("{:.0}{}", t, units.degrees())),
+                &cell(&hourly.precipitation_probability, &|p| format!("{:.0}%", p)),
+                &cell(&hourly.wind_speed_10m, &|w| format!("{:.0} {}", w, units.speed())),
+            ],
+        ));
+        output.push('\n');
+    }
+
+    output
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+pub struct WeatherParams {
+    /// A ~10 line summary instead of the full report.
+    #[serde(default)]
+    #[param(default = false)]
+    compact: bool,
+    /// Comma-separated place names for a per-city summary, e.g. `berlin,hamburg`.
+    locations: Option<String>,
+    /// Add the 7-day outlook grid even when `WEATHER_OUTLOOK` is off.
+    #[serde(default)]
+    #[param(default = false)]
+    outlook: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/weather",
+    tag = "printing",
+    summary = "Print the weather forecast",
+    params(WeatherParams),
+    responses((status = 200, description = "Printed, or queued to print."))
+)]
+pub async fn weather(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<WeatherParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_weather(&state, &source, &params).await?)
+}
+
+pub async fn print_weather(state: &AppState, source: &Source, params: &WeatherParams) -> Result<(), JobError> {
+    let receipt = match &params.locations {
+        Some(locations) => multi_location_receipt(state, locations).await?,
+        None => weather_receipt(state, params).await?,
+    };
+    state.print(receipt, "weather", source);
+    Ok(())
+}
+
+/// `https://{host}`, or `OPEN_METEO_URL` when set so tests can serve canned responses.
+fn base_url(state: &AppState, host: &str) -> String {
+    match &state.config.open_meteo_url {
+        Some(url) => url.trim_end_matches('/').to_owned(),
+        None => format!("https://{}", host),
+    }
+}
+
+/// The last good forecast per location, printed (marked stale) when Open-Meteo is unreachable.
+#[derive(Clone, Default)]
+pub struct ForecastCache {
+    inner: Arc<Mutex<HashMap<String, CachedForecast>>>,
+}
+
+struct CachedForecast {
+    fetched_at: DateTime<Local>,
+    weather: WeatherResponse,
+}
+
+/// A forecast, with when it was fetched if it came from the cache.
+struct Fetched {
+    weather: WeatherResponse,
+    stale_since: Option<DateTime<Local>>,
+}
+
+/// Fetches a forecast, retrying with backoff, and falls back to the cached one
+/// for the same location if every attempt fails.
+async fn fetch_forecast(state: &AppState, lat: f64, lon: f64) -> Result<Fetched, JobError> {
+    let key = format!("{},{}", lat, lon);
+    let attempts = state.config.weather_retries + 1;
+    let mut delay = Duration::from_millis(500);
+    let mut attempt = 1;
+    let error = loop {
+        match fetch_forecast_once(state, lat, lon).await {
+            Ok(weather) => {
+                let cached = CachedForecast {
+                    fetched_at: Local::now(),
+                    weather: weather.clone(),
+                };
+                state.forecast_cache.inner.lock().unwrap().insert(key, cached);
+                return Ok(Fetched {
+                    weather,
+                    stale_since: None,
+                });
+            }
+            Err(e) if attempt < attempts => {
+                eprintln!("Weather fetch attempt {}/{} failed ({}), retrying in {:?}", attempt, attempts, e, delay);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => break e,
+        }
+    };
+
+    match state.forecast_cache.inner.lock().unwrap().get(&key) {
+        Some(cached) => {
+            eprintln!("Using forecast cached at {}", cached.fetched_at.format("%H:%M"));
+            Ok(Fetched {
+                weather: cached.weather.clone(),
+                stale_since: Some(cached.fetched_at),
+            })
+        }
+        None => Err(error),
+    }
+}
+
+async fn fetch_forecast_once(state: &AppState, lat: f64, lon: f64) -> Result<WeatherResponse, JobError> {
+    let url = format!(
+        "{}/v1/forecast?latitude={}&longitude={}&daily=temperature_2m_max,temperature_2m_min,apparent_temperature_max,apparent_temperature_min,precipitation_probability_max,weather_code,sunrise,sunset,uv_index_max,wind_speed_10m_max,wind_gusts_10m_max,wind_direction_10m_dominant,relative_humidity_2m_mean,dew_point_2m_mean,precipitation_sum,rain_sum,snowfall_sum&hourly=temperature_2m,precipitation_probability,wind_speed_10m,wind_gusts_10m,snow_depth&temperature_unit={}&wind_speed_unit={}&precipitation_unit={}&timezone=auto&forecast_days=7",
+        base_url(state, "api.open-meteo.com"),
+        lat,
+        lon,
+        state.config.weather_units.temperature_unit(),
+        state.config.weather_units.wind_speed_unit(),
+        state.config.weather_units.precipitation_unit()
+    );
+
+    let client = reqwest::Client::builder()
+        .timeout(state.config.weather_timeout)
+        .build()
+        .map_err(|e| JobError::Upstream(e.to_string()))?;
+    let mut weather = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to fetch weather: {:?}", e);
+            state.diagnostics.record_error("weather", e.to_string());
+            JobError::Upstream(e.without_url().to_string())
+        })?
+        .json::<WeatherResponse>()
+        .await
+        .map_err(|e| {
+            eprintln!("Failed to parse weather response: {:?}", e);
+            state.diagnostics.record_error("weather", e.to_string());
+            JobError::Upstream(e.without_url().to_string())
+        })?;
+    // Seven days are fetched for the outlook, but the hourly sections only cover today.
+    weather.hourly.truncate(24);
+    Ok(weather)
+}
+
+/// Air quality is a nice-to-have, so failures are logged and the section is skipped.
+async fn fetch_air_quality(state: &AppState) -> Option<AirQuality> {
+    let url = format!(
+        "{}/v1/air-quality?latitude={}&longitude={}&current=european_aqi,pm2_5,pm10,alder_pollen,birch_pollen,grass_pollen,mugwort_pollen,olive_pollen,ragweed_pollen&timezone=auto",
+        base_url(state, "air-quality-api.open-meteo.com"), BERLIN_LAT, BERLIN_LON
+    );
+
+    let result = match reqwest::Client::new().get(&url).timeout(state.config.weather_timeout).send().await {
+        Ok(response) => response.json::<AirQualityResponse>().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(response) => Some(response.current),
+        Err(e) => {
+            eprintln!("Failed to fetch air quality: {:?}", e);
+            state.diagnostics.record_error("air_quality", e.to_string());
+            None
+        }
+    }
+}
+
+/// The indoor sensor's current reading. Optional like air quality: a sensor that's
+/// offline just leaves its column blank.
+async fn fetch_indoor(state: &AppState, url: &str) -> Option<IndoorReading> {
+    let result = match reqwest::Client::new().get(url).timeout(state.config.weather_timeout).send().await {
+        Ok(response) => response.json::<IndoorReading>().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(reading) => Some(reading),
+        Err(e) => {
+            eprintln!("Failed to read indoor sensor: {:?}", e);
+            state.diagnostics.record_error("indoor_sensor", e.without_url().to_string());
+            None
+        }
+    }
+}
+
+/// Indoor and outdoor temperature and humidity side by side. Outdoors is the forecast
+/// for the current hour and the day's mean humidity.
+fn render_indoor(indoor: Option<&IndoorReading>, outdoor: Option<(f64, f64)>, units: Units) -> String {
+    const COLUMNS: [Column; 3] = [Column::left(12), Column::right(8), Column::right(10)];
+    let fmt = |v: Option<f64>, unit: &str| v.map(|v| format!("{:.0}{}", v, unit)).unwrap_or("-".to_owned());
+    let mut output = render_row(&COLUMNS, &["", "Inside", "Outside"]);
+    output.push('\n');
+    output.push_str(&render_row(
+        &COLUMNS,
+        &["Temp", &fmt(indoor.and_then(|r| r.temperature(units)), units.degrees()), &fmt(outdoor.map(|(t, _)| t), units.degrees())],
+    ));
+    output.push('\n');
+    output.push_str(&render_row(
+        &COLUMNS,
+        &["Humidity", &fmt(indoor.and_then(|r| r.humidity), "%"), &fmt(outdoor.map(|(_, h)| h), "%")],
+    ));
+    output.push('\n');
+    output
+}
+
+/// Last year's observations for `date` from the historical archive. Like air
+/// quality this is optional, so failures just drop the comparison line.
+async fn fetch_last_year(state: &AppState, date: NaiveDate) -> Option<LastYear> {
+    // Feb 29 has no counterpart last year; compare with Feb 28 instead.
+    let last_year = date
+        .with_year(date.year() - 1)
+        .or_else(|| NaiveDate::from_ymd_opt(date.year() - 1, date.month(), date.day() - 1))?;
+    let url = format!(
+        "{}/v1/archive?latitude={}&longitude={}&start_date={}&end_date={}&daily=temperature_2m_max,temperature_2m_min,weather_code&temperature_unit={}&timezone=auto",
+        base_url(state, "archive-api.open-meteo.com"), BERLIN_LAT, BERLIN_LON, last_year, last_year, state.config.weather_units.temperature_unit()
+    );
+
+    let result = match reqwest::Client::new().get(&url).timeout(state.config.weather_timeout).send().await {
+        Ok(response) => response.json::<ArchiveResponse>().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(response) => {
+            let daily = response.daily;
+            Some(LastYear {
+                high: (*daily.temperature_2m_max.first()?)?,
+                low: (*daily.temperature_2m_min.first()?)?,
+                weather_code: (*daily.weather_code.first()?)?,
+            })
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch last year's weather: {:?}", e);
+            state.diagnostics.record_error("weather_archive", e.to_string());
+            None
+        }
+    }
+}
+
+/// Everything fetched for one weather report. `weather` is missing when
+/// Open-Meteo is down; the report then falls back to what can be computed locally.
+struct Forecast {
+    weather: Option<WeatherResponse>,
+    /// When the forecast was fetched, if Open-Meteo failed and it came from the cache.
+    stale_since: Option<DateTime<Local>>,
+    air: Option<AirQuality>,
+    /// The `WEATHER_INDOOR_URL` sensor's reading; `None` when it isn't configured.
+    indoor: Option<Option<IndoorReading>>,
+    last_year: Option<LastYear>,
+    /// Print the 7-day outlook grid before the footer.
+    outlook: bool,
+    date: NaiveDate,
+    utc_offset_seconds: i32,
+}
+
+impl Forecast {
+    /// Sunrise and sunset from Open-Meteo when available, otherwise computed
+    /// locally. Twilight is always computed locally.
+    fn daylight(&self) -> Option<Daylight> {
+        let midnight = local_midnight(self.date, self.utc_offset_seconds)?;
+        let sun = astro::sun_times(self.date, BERLIN_LAT, BERLIN_LON);
+        let (sunrise, sunset) = match &self.weather {
+            Some(weather) => (parse_hour(&weather.daily.sunrise[0]), parse_hour(&weather.daily.sunset[0])),
+            None => (local_hour(sun.sunrise?, midnight), local_hour(sun.sunset?, midnight)),
+        };
+        // Around midsummer at high latitudes it never gets darker than civil twilight.
+        Some(Daylight {
+            dawn: sun.dawn.map_or(0.0, |t| local_hour(t, midnight)),
+            sunrise,
+            sunset,
+            dusk: sun.dusk.map_or(24.0, |t| local_hour(t, midnight)),
+        })
+    }
+}
+
+/// "07:30" for `hours` since local midnight.
+fn format_hour(config: &Config, hours: f64) -> String {
+    let minutes = (hours * 60.0).round() as u32;
+    match NaiveTime::from_hms_opt(minutes / 60, minutes % 60, 0) {
+        Some(time) => timefmt::time(config, time),
+        // Midnight at the end of the day.
+        None => timefmt::time(config, NaiveTime::MIN),
+    }
+}
+
+/// The building blocks of a weather receipt, rendered in the order listed.
+#[derive(Clone, Copy)]
+enum Section {
+    Header,
+    Temperature,
+    Conditions,
+    Indoor,
+    Air,
+    Hourly,
+    Daylight,
+    Moon,
+    Summary,
+    Advice,
+    Outlook,
+    Footer,
+}
+
+const FULL_LAYOUT: &[Section] = &[
+    Section::Header,
+    Section::Temperature,
+    Section::Conditions,
+    Section::Indoor,
+    Section::Air,
+    Section::Hourly,
+    Section::Daylight,
+    Section::Moon,
+    Section::Outlook,
+    Section::Footer,
+];
+
+const COMPACT_LAYOUT: &[Section] = &[Section::Header, Section::Summary, Section::Advice, Section::Outlook, Section::Footer];
+
+/// The weather part of `/briefing`, which has its own header.
+const BRIEFING_LAYOUT: &[Section] = &[Section::Summary, Section::Advice, Section::Outlook];
+
+/// Used when the forecast couldn't be fetched.
+const OFFLINE_LAYOUT: &[Section] = &[Section::Header, Section::Indoor, Section::Daylight, Section::Moon, Section::Footer];
+
+/// One line of practical advice, picking the most pressing concern of the day.
+fn advice(daily: &DailyWeather, units: Units) -> &'static str {
+    let code = daily.weather_code[0];
+    if matches!(code, 71..=77 | 85 | 86) {
+        "Snow expected - dress warm, watch your step"
+    } else if matches!(code, 95..=99) {
+        "Thunderstorms - stay inside if you can"
+    } else if daily.precipitation_probability_max[0] >= 50 {
+        "Take an umbrella"
+    } else if units.fahrenheit(daily.temperature_2m_max[0]) < 32.0 {
+        "Below freezing all day - bundle up"
+    } else if units.fahrenheit(daily.temperature_2m_max[0]) >= 86.0 {
+        "Hot one - stay hydrated"
+    } else if units.mph(daily.wind_gusts_10m_max[0]) >= 30.0 {
+        "Gusty - hold onto your hat"
+    } else if daily.uv_index_max[0] >= 6.0 {
+        "Strong sun - wear sunscreen"
+    } else {
+        "Enjoy your day"
+    }
+}
+
+fn render_section(section: Section, forecast: &Forecast, state: &AppState, receipt: &mut Receipt) {
+    let weather = forecast.weather.as_ref();
+    let units = state.config.weather_units;
+    let border = "~".repeat(receipt.width());
+    let divider = "-".repeat(receipt.width());
+
+    match section {
+        Section::Header => {
+            receipt.line_left(&border);
+            receipt.line_center("* * * BERLIN * * *");
+            receipt.line_center(&timefmt::long_date(&state.config, forecast.date));
+            receipt.line_left(&border);
+            receipt.line_center("");
+            let description = weather.map_or("Forecast unavailable", |w| weather_code_to_description(w.daily.weather_code[0]));
+            receipt.line_center(&format!("~ {} ~", description));
+            if let Some(fetched_at) = forecast.stale_since {
+                receipt.line_center(&format!("stale (fetched {})", fetched_at.format("%H:%M")));
+            }
+            receipt.line_center("");
+        }
+        Section::Temperature => {
+            let Some(WeatherResponse { daily, .. }) = weather else {
+                return;
+            };
+            receipt.line_left(&divider);
+            receipt.fitted(&format!("High: {:.0}{deg}          Low: {:.0}{deg}", daily.temperature_2m_max[0], daily.temperature_2m_min[0], deg = units.degrees()));
+            receipt.fitted(&format!("Feels: {:.0}{deg} / {:.0}{deg}", daily.apparent_temperature_max[0], daily.apparent_temperature_min[0], deg = units.degrees()));
+            if let Some(last_year) = &forecast.last_year {
+                receipt.fitted(&format!(
+                    "Last year today: {:.0}{deg}/{:.0}{deg}, {}",
+                    last_year.high,
+                    last_year.low,
+                    weather_code_to_description(last_year.weather_code),
+                    deg = units.degrees()
+                ));
+            }
+            receipt.line_left(&divider);
+        }
+        Section::Conditions => {
+            let Some(WeatherResponse { daily, hourly, .. }) = weather else {
+                return;
+            };
+            receipt.fitted(&format!("Precip: {}%       UV Index: {:.0}", daily.precipitation_probability_max[0], daily.uv_index_max[0]));
+            if let Some(amounts) = precipitation_amounts(daily, state.config.weather_units) {
+                receipt.fitted(&amounts);
+            }
+            if let Some(depth) = snow_depth(hourly, state.config.weather_units) {
+                receipt.fitted(&depth);
+            }
+            let direction = daily.wind_direction_10m_dominant[0];
+            let gusts = match peak_gust_hour(&hourly.wind_gusts_10m) {
+                Some(hour) => format!("gusts {:.0} at {}", daily.wind_gusts_10m_max[0], hour),
+                None => format!("gusts {:.0}", daily.wind_gusts_10m_max[0]),
+            };
+            receipt.fitted(&format!(
+                "Wind: {:.0} {} {} {} ({})",
+                daily.wind_speed_10m_max[0],
+                units.speed(),
+                compass_point(direction),
+                wind_arrow(direction),
+                gusts
+            ));
+            let dew_point = daily.dew_point_2m_mean[0];
+            receipt.fitted(&format!(
+                "Humidity: {:.0}%      Dew point: {:.0}{} ({})",
+                daily.relative_humidity_2m_mean[0],
+                dew_point,
+                units.degrees(),
+                dew_point_verdict(units.fahrenheit(dew_point))
+            ));
+            receipt.line_left(&divider);
+            receipt.line_left("");
+        }
+        Section::Indoor => {
+            let Some(indoor) = &forecast.indoor else {
+                return;
+            };
+            let outdoor = weather.and_then(|w| {
+                let now = Utc::now() + TimeDelta::seconds(w.utc_offset_seconds as i64);
+                let temperature = value_at(&w.hourly.temperature_2m, now.hour() as f64 + now.minute() as f64 / 60.0)?;
+                Some((temperature, w.daily.relative_humidity_2m_mean[0]))
+            });
+            receipt.line_center("INDOOR");
+            for line in render_indoor(indoor.as_ref(), outdoor, state.config.weather_units).lines() {
+                receipt.fitted(line);
+            }
+            receipt.line_left(&divider);
+            receipt.line_left("");
+        }
+        Section::Air => {
+            if let Some(air) = &forecast.air {
+                receipt.line_center("AIR");
+                for line in render_air_quality(air).lines() {
+                    receipt.fitted(line);
+                }
+                receipt.line_left(&divider);
+                receipt.line_left("");
+            }
+        }
+        Section::Hourly => {
+            let Some(WeatherResponse { hourly, .. }) = weather else {
+                return;
+            };
+            if state.config.weather_key_hours.is_empty() && hourly.temperature_2m.len() >= 2 {
+                // The full curve as a chart; stdout still gets the every-3-hours table.
+                let temps = &hourly.temperature_2m[..hourly.temperature_2m.len().min(24)];
+                let chart = raster::temperature_chart(temps, units.degrees(), state.config.printer_profile.raster_width, TEMPERATURE_CHART_HEIGHT);
+                receipt.line_center("HOURLY TEMPERATURES");
+                receipt.image(&chart, render_hourly_temps(temps, units, receipt.width()).trim_end());
+            } else {
+                let (title, table) = if state.config.weather_key_hours.is_empty() {
+                    ("HOURLY TEMPERATURES", render_hourly_temps(&hourly.temperature_2m, units, receipt.width()))
+                } else {
+                    ("KEY HOURS", render_key_hours(&state.config, hourly, &state.config.weather_key_hours, receipt.width()))
+                };
+                receipt.line_center(title);
+                for line in table.lines() {
+                    receipt.fitted(line);
+                }
+            }
+            receipt.line_left(&divider);
+            receipt.line_left("");
+        }
+        Section::Daylight => {
+            let Some(daylight) = forecast.daylight() else {
+                return;
+            };
+            receipt.line_center("DAYLIGHT");
+            receipt.line_left(">=day  ~=twilight  -=night");
+            for line in render_daylight_bar(&daylight, receipt.width()).lines() {
+                receipt.line_left(line);
+            }
+            receipt.fitted(&format!("Sunrise: {}    Sunset: {}", format_hour(&state.config, daylight.sunrise), format_hour(&state.config, daylight.sunset)));
+            receipt.fitted(&format!("Dawn: {}       Dusk: {}", format_hour(&state.config, daylight.dawn), format_hour(&state.config, daylight.dusk)));
+            receipt.line_left(&divider);
+            receipt.line_left("");
+        }
+        Section::Moon => {
+            let offset = forecast.utc_offset_seconds;
+            let Some(midnight) = local_midnight(forecast.date, offset) else {
+                return;
+            };
+            let moon = astro::moon_illumination(midnight + TimeDelta::hours(12));
+            let times = astro::moon_times(midnight, BERLIN_LAT, BERLIN_LON);
+            let local_time = |t: Option<DateTime<Utc>>| {
+                t.map(|t| timefmt::time(&state.config, (t + TimeDelta::seconds(offset as i64)).time()))
+                    .unwrap_or("--:--".to_owned())
+            };
+
+            receipt.line_center("MOON");
+            receipt.image(&raster::moon_icon(moon.phase, 64), astro::moon_phase_symbol(moon.phase));
+            receipt.line_center(&format!("{} ({:.0}% lit)", astro::moon_phase_name(moon.phase), moon.fraction * 100.0));
+            receipt.fitted(&format!("Moonrise: {}   Moonset: {}", local_time(times.rise), local_time(times.set)));
+            receipt.line_left("");
+        }
+        Section::Summary => {
+            let Some(WeatherResponse { daily, .. }) = weather else {
+                return;
+            };
+            receipt.fitted(&format!(
+                "High: {:.0}{deg}   Low: {:.0}{deg}   Precip: {}%",
+                daily.temperature_2m_max[0], daily.temperature_2m_min[0], daily.precipitation_probability_max[0], deg = units.degrees()
+            ));
+            receipt.fitted(&format!("Sunrise: {}    Sunset: {}", format_time(&state.config, &daily.sunrise[0]), format_time(&state.config, &daily.sunset[0])));
+            receipt.line_left(&divider);
+        }
+        Section::Advice => {
+            let Some(WeatherResponse { daily, .. }) = weather else {
+                return;
+            };
+            receipt.line_center(advice(daily, state.config.weather_units));
+        }
+        Section::Outlook => {
+            let Some(WeatherResponse { daily, .. }) = weather.filter(|_| forecast.outlook) else {
+                return;
+            };
+            receipt.line_left(&divider);
+            receipt.line_center("7-DAY OUTLOOK");
+            for line in render_outlook(daily, receipt.width()) {
+                receipt.line_left(&line);
+            }
+            for line in OUTLOOK_LEGEND {
+                if receipt::text_width(line) <= receipt.width() {
+                    receipt.line_center(line);
+                } else {
+                    for part in receipt::wrap(line, receipt.width()) {
+                        receipt.line_center(&part);
+                    }
+                }
+            }
+        }
+        Section::Footer => {
+            receipt.line_left(&border);
+        }
+    }
+}
+
+async fn weather_receipt(state: &AppState, params: &WeatherParams) -> Result<Receipt, JobError> {
+    eprintln!("Weather request for Berlin (compact={})", params.compact);
+
+    let indoor_url = state.config.weather_indoor_url.as_deref().filter(|_| !params.compact);
+    let (weather, air, indoor) = tokio::join!(
+        fetch_forecast(state, BERLIN_LAT, BERLIN_LON),
+        async {
+            if state.config.weather_air_quality && !params.compact {
+                fetch_air_quality(state).await
+            } else {
+                None
+            }
+        },
+        async {
+            match indoor_url {
+                Some(url) => Some(fetch_indoor(state, url).await),
+                None => None,
+            }
+        },
+    );
+
+    let outlook = params.outlook || state.config.weather_outlook;
+    let mut forecast = match weather {
+        Ok(Fetched { weather, stale_since }) => Forecast {
+            date: NaiveDate::parse_from_str(&weather.daily.time[0], "%Y-%m-%d").unwrap_or(Local::now().date_naive()),
+            utc_offset_seconds: weather.utc_offset_seconds,
+            weather: Some(weather),
+            stale_since,
+            air,
+            indoor,
+            last_year: None,
+            outlook,
+        },
+        Err(e) => {
+            eprintln!("Forecast unavailable ({}), printing offline report", e);
+            let now = Local::now();
+            Forecast {
+                weather: None,
+                stale_since: None,
+                air,
+                indoor,
+                last_year: None,
+                outlook,
+                date: now.date_naive(),
+                utc_offset_seconds: now.offset().local_minus_utc(),
+            }
+        }
+    };
+
+    if forecast.weather.is_some() && !params.compact {
+        forecast.last_year = fetch_last_year(state, forecast.date).await;
+    }
+
+    let layout = match (&forecast.weather, params.compact) {
+        (None, _) => OFFLINE_LAYOUT,
+        (Some(_), true) => COMPACT_LAYOUT,
+        (Some(_), false) => FULL_LAYOUT,
+    };
+    let mut receipt = Receipt::for_config(&state.config);
+    for &section in layout {
+        render_section(section, &forecast, state, &mut receipt);
+    }
+
+    Ok(receipt)
+}
+
+/// Today's summary and advice for `/briefing`. Unlike the full report there's no offline
+/// fallback; the briefing notes the section as unavailable instead.
+pub async fn briefing_receipt(state: &AppState, outlook: bool) -> Result<Receipt, JobError> {
+    let Fetched { weather, stale_since } = fetch_forecast(state, BERLIN_LAT, BERLIN_LON).await?;
+    let forecast = Forecast {
+        date: NaiveDate::parse_from_str(&weather.daily.time[0], "%Y-%m-%d").unwrap_or(Local::now().date_naive()),
+        utc_offset_seconds: weather.utc_offset_seconds,
+        weather: Some(weather),
+        stale_since,
+        air: None,
+        indoor: None,
+        last_year: None,
+        outlook,
+    };
+    let mut receipt = Receipt::for_config(&state.config);
+    if let Some(fetched_at) = stale_since {
+        receipt.fitted(&format!("(forecast from {})", fetched_at.format("%H:%M")));
+    }
+    for &section in BRIEFING_LAYOUT {
+        render_section(section, &forecast, state, &mut receipt);
+    }
+    Ok(receipt)
+}
+
+/// Looks `name` up in `WEATHER_LOCATIONS`, falling back to Open-Meteo's geocoder.
+pub async fn resolve_location(state: &AppState, name: &str) -> Result<Location, JobError> {
+    let name = name.to_lowercase();
+    if let Some(location) = state.config.weather_locations.iter().find(|l| l.name == name) {
+        return Ok(location.clone());
+    }
+
+    let url = reqwest::Url::parse_with_params(
+        &format!("{}/v1/search", base_url(state, "geocoding-api.open-meteo.com")),
+        &[("name", name.as_str()), ("count", "1")],
+    )
+    .map_err(|e| JobError::Upstream(e.to_string()))?;
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?
+        .json::<GeocodingResponse>()
+        .await
+        .map_err(|e| JobError::Upstream(e.without_url().to_string()))?;
+    match response.results.first() {
+        Some(result) => Ok(Location {
+            name,
+            lat: result.latitude,
+            lon: result.longitude,
+        }),
+        None => Err(JobError::Upstream(format!("unknown location {}", name))),
+    }
+}
+
+async fn fetch_city(state: AppState, name: String) -> Result<Fetched, JobError> {
+    let location = resolve_location(&state, &name).await?;
+    fetch_forecast(&state, location.lat, location.lon).await
+}
+
+/// One short block per city on a single receipt. Cities that fail are listed as
+/// unavailable; the job only fails if none could be fetched.
+async fn multi_location_receipt(state: &AppState, locations: &str) -> Result<Receipt, JobError> {
+    let names: Vec<String> = locations
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .take(MAX_LOCATIONS)
+        .map(str::to_owned)
+        .collect();
+    eprintln!("Weather request for {}", names.join(","));
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, name) in names.iter().enumerate() {
+        let state = state.clone();
+        let name = name.clone();
+        tasks.spawn(async move { (i, fetch_city(state, name).await) });
+    }
+    let mut results: Vec<Option<Result<Fetched, JobError>>> = names.iter().map(|_| None).collect();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((i, result)) => results[i] = Some(result),
+            // The city is left out as unavailable; the others still print.
+            Err(e) => eprintln!("Weather lookup task failed: {}", e),
+        }
+    }
+
+    let mut receipt = Receipt::for_config(&state.config);
+    let border = "~".repeat(receipt.width());
+    receipt.line_left(&border);
+    receipt.line_center("* * * WEATHER * * *");
+    receipt.line_center(&timefmt::long_date(&state.config, Local::now().date_naive()));
+    receipt.line_left(&border);
+
+    let mut fetched = 0;
+    let mut last_error = String::from("no locations given");
+    for (name, result) in names.iter().zip(results) {
+        match result {
+            Some(Ok(Fetched { weather, stale_since })) => {
+                fetched += 1;
+                match stale_since {
+                    Some(fetched_at) => receipt.line_left(&format!("{} (stale, fetched {})", name.to_uppercase(), fetched_at.format("%H:%M"))),
+                    None => receipt.line_left(&name.to_uppercase()),
+                }
+                let daily = &weather.daily;
+                receipt.line_left(&format!("  {}", weather_code_to_description(daily.weather_code[0])));
+                receipt.fitted(&format!(
+                    "  High: {:.0}{deg}   Low: {:.0}{deg}   Precip: {}%",
+                    daily.temperature_2m_max[0], daily.temperature_2m_min[0], daily.precipitation_probability_max[0],
+                    deg = state.config.weather_units.degrees()
+                ));
+            }
+            Some(Err(e)) => {
+                eprintln!("Failed to fetch weather for {}: {}", name, e);
+                receipt.line_left(&name.to_uppercase());
+                receipt.fitted(&format!("  unavailable: {}", e));
+                last_error = e.to_string();
+            }
+            None => {
+                receipt.line_left(&name.to_uppercase());
+                receipt.line_left("  unavailable");
+            }
+        }
+        receipt.divider();
+    }
+
+    if fetched == 0 {
+        return Err(JobError::Upstream(last_error));
+    }
+    receipt.line_left(&border);
+    Ok(receipt)
+}
@@ -0,0 +1,92 @@
+use crate::{AppState, receipt::Receipt, source::Source};
+use axum::{Json, extract::State, http::StatusCode};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+pub enum Security {
+    #[default]
+    #[serde(rename = "WPA", alias = "wpa", alias = "WPA2", alias = "wpa2")]
+    Wpa,
+    #[serde(rename = "WEP", alias = "wep")]
+    Wep,
+    /// An open network.
+    #[serde(rename = "nopass", alias = "none", alias = "open")]
+    Open,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WifiRequest {
+    ssid: String,
+    /// Needed unless `security` is `nopass`.
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    #[schema(inline)]
+    security: Security,
+    #[serde(default)]
+    hidden: bool,
+    /// Also print the password in plain text, for devices that can't scan.
+    #[serde(default)]
+    show_password: bool,
+}
+
+/// Backslash-escapes the characters that delimit fields in a `WIFI:` payload.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The de facto standard payload phone cameras recognise as a network to join.
+fn payload(request: &WifiRequest) -> String {
+    let security = match request.security {
+        Security::Wpa => "WPA",
+        Security::Wep => "WEP",
+        Security::Open => "nopass",
+    };
+    let mut payload = format!("WIFI:T:{};S:{};", security, escape(&request.ssid));
+    if request.security != Security::Open {
+        payload.push_str(&format!("P:{};", escape(&request.password)));
+    }
+    if request.hidden {
+        payload.push_str("H:true;");
+    }
+    payload.push(';');
+    payload
+}
+
+#[utoipa::path(
+    post,
+    path = "/wifi",
+    tag = "printing",
+    summary = "Print a Wi-Fi login card with a QR code",
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 400, description = "No SSID, or no password for a secured network."),
+    )
+)]
+pub async fn wifi(State(state): State<AppState>, source: Source, Json(request): Json<WifiRequest>) -> Result<(), StatusCode> {
+    let needs_password = request.security != Security::Open;
+    if request.ssid.is_empty() || (needs_password && request.password.is_empty()) {
+        eprintln!("WiFi request without an SSID or password");
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    eprintln!("WiFi card for {:?}", request.ssid);
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center("WIFI");
+    receipt.qr_code(&payload(&request), 8);
+    receipt.line_center("");
+    receipt.line_center(&request.ssid);
+    if request.show_password && needs_password {
+        receipt.line_center(&request.password);
+    }
+    state.print(receipt, "wifi", &source);
+    Ok(())
+}
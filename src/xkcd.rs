@@ -0,0 +1,118 @@
+//! `/xkcd`: the latest comic, or `?num=`, dithered to the paper width with its title
+//! above and alt text below.
+
+use crate::{
+    AppState,
+    error::JobError,
+    raster::{self, Dither, ImageOptions},
+    receipt::{Receipt, wrap},
+    source::Source,
+};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+const API_BASE: &str = "https://xkcd.com";
+
+#[derive(Deserialize, IntoParams)]
+pub struct XkcdParams {
+    /// The comic's number; the latest when missing.
+    num: Option<u32>,
+    /// How to turn the image's greys into dots.
+    #[serde(default = "default_dither")]
+    #[param(inline, default = "atkinson")]
+    dither: Dither,
+}
+
+fn default_dither() -> Dither {
+    Dither::Atkinson
+}
+
+#[derive(Deserialize)]
+struct Comic {
+    num: u32,
+    safe_title: String,
+    img: String,
+    alt: String,
+    year: String,
+    month: String,
+    day: String,
+}
+
+/// The API base, or `XKCD_URL` when set.
+fn api_base(state: &AppState) -> &str {
+    state.config.xkcd_url.as_deref().unwrap_or(API_BASE).trim_end_matches('/')
+}
+
+#[utoipa::path(
+    get,
+    path = "/xkcd",
+    tag = "printing",
+    summary = "Print an XKCD comic",
+    description = "The latest comic, or the one numbered `num`: its title and date, the image dithered to the paper \
+        width, and the alt text wrapped underneath. Also available as the `xkcd` scheduled job, which prints \
+        the latest.",
+    params(XkcdParams),
+    responses(
+        (status = 200, description = "Printed, or queued to print."),
+        (status = 502, description = "XKCD failed, has no comic `num`, or its image can't be decoded."),
+    )
+)]
+pub async fn xkcd(
+    State(state): State<AppState>,
+    source: Source,
+    Query(params): Query<XkcdParams>,
+) -> Result<(), StatusCode> {
+    Ok(print_xkcd(&state, &source, params.num, params.dither).await?)
+}
+
+async fn get(url: &str) -> Result<reqwest::Response, JobError> {
+    reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| JobError::Upstream(e.to_string()))
+}
+
+pub async fn print_xkcd(state: &AppState, source: &Source, num: Option<u32>, dither: Dither) -> Result<(), JobError> {
+    eprintln!("XKCD request for {}", num.map_or("the latest comic".to_owned(), |num| format!("#{}", num)));
+    let url = match num {
+        Some(num) => format!("{}/{}/info.0.json", api_base(state), num),
+        None => format!("{}/info.0.json", api_base(state)),
+    };
+    let fetched = async {
+        let comic = get(&url).await?.json::<Comic>().await.map_err(|e| JobError::Upstream(e.to_string()))?;
+        let image = get(&comic.img).await?.bytes().await.map_err(|e| JobError::Upstream(e.to_string()))?;
+        Ok((comic, image))
+    };
+    let (comic, image) = fetched.await.inspect_err(|e: &JobError| {
+        eprintln!("Failed to fetch XKCD: {}", e);
+        state.diagnostics.record_error("xkcd", e.to_string());
+    })?;
+
+    let raster_width = state.config.printer_profile.raster_width as u32;
+    let options = ImageOptions {
+        width: Some(raster_width),
+        paper_width: Some(raster_width),
+        dither,
+        ..Default::default()
+    };
+    let bitmap = raster::decode_image(&image, &options)
+        .map_err(|e| JobError::Upstream(format!("undecodable image for XKCD #{}: {}", comic.num, e)))?;
+
+    let mut receipt = Receipt::for_config(&state.config);
+    receipt.line_center(&format!("XKCD #{}", comic.num));
+    for line in wrap(&comic.safe_title, receipt.width()) {
+        receipt.line_center(&line);
+    }
+    receipt.line_center(&format!("{}-{:0>2}-{:0>2}", comic.year, comic.month, comic.day));
+    receipt.divider();
+    receipt.image(&bitmap, &format!("[comic: {}]", comic.safe_title));
+    receipt.divider();
+    receipt.wrapped(&comic.alt);
+
+    state.print(receipt, "xkcd", source);
+    Ok(())
+}
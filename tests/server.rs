@@ -0,0 +1,2716 @@
+use axum::{
+    Router,
+    http::{StatusCode, header::CONTENT_TYPE},
+    routing::get,
+};
+use print_jobber::{
+    AppState,
+    config::Config,
+    mock::{MockDriver, serve, spawn_server, spawn_server_on, spawn_server_with_printers},
+    printer::PrinterDriver,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+const FORECAST: &str = include_str!("fixtures/forecast.json");
+const RECIPE_PAGE: &str = include_str!("fixtures/recipe.html");
+const ARTICLE_PAGE: &str = include_str!("fixtures/article.html");
+
+/// Serves canned Open-Meteo responses while `up` is set; anything else (air quality, archive) 404s.
+async fn spawn_open_meteo(up: Arc<AtomicBool>) -> String {
+    let forecast = move || async move {
+        if up.load(Ordering::SeqCst) {
+            Ok(([(CONTENT_TYPE, "application/json")], FORECAST))
+        } else {
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    };
+    serve(Router::new().route("/v1/forecast", get(forecast))).await
+}
+
+#[tokio::test]
+async fn print_wraps_text_and_cuts() {
+    let (url, driver) = spawn_server(&[]).await;
+    let body = "The quick brown fox jumps over the lazy dog and keeps running far away";
+    let response = reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("The quick brown fox jumps over the lazy dog and\nkeeps running far away\n"));
+    // GS V: paper cut
+    assert!(driver.jobs()[0].windows(2).any(|w| w == [0x1d, b'V']));
+}
+
+#[tokio::test]
+async fn print_answers_with_the_queued_jobs() {
+    let (url, driver) = spawn_server(&[]).await;
+    let response = reqwest::Client::new().post(format!("{}/?copies=2&cut=false", url)).body("one\ntwo").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let printed: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(printed["jobs"].as_array().unwrap().len(), 2);
+    assert_eq!(printed["jobs"][0]["id"], 1);
+    assert_eq!(printed["jobs"][1]["id"], 2);
+    assert_eq!(printed["jobs"][0]["lines"], 2);
+    assert!(printed["jobs"][0]["paper_mm"].as_f64().unwrap() > 0.0);
+    assert_eq!(printed["printers"], serde_json::json!(["default"]));
+    assert_eq!(printed["scheduled_for"], serde_json::Value::Null);
+    assert_eq!(printed["cut"], false);
+    assert_eq!(printed["copies"], 2);
+    assert_eq!(printed["plain"], false);
+    driver.wait_for_jobs(2).await;
+}
+
+#[tokio::test]
+async fn chunked_bodies_print_line_by_line_as_they_stream_in() {
+    use std::io::{Read, Write};
+    let (url, driver) = spawn_server(&[]).await;
+    let mut stream = std::net::TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+    let head = "PUT / HTTP/1.1\r\nHost: printer\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n";
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(b"10\r\nbuild started\nco\r\n").unwrap();
+
+    // The whole line prints before the body ends, uncut; the partial one waits.
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("build started\n"));
+    assert!(!jobs[0].contains("co"));
+    assert!(!driver.jobs()[0].windows(2).any(|w| w == [0x1d, b'V']));
+
+    stream.write_all(b"9\r\nmpiled\nok\r\n0\r\n\r\n").unwrap();
+    // The end of the body ends the last line, and the job with a cut.
+    let jobs = driver.wait_for_jobs(3).await;
+    assert!(jobs[1].contains("compiled\n"));
+    assert!(jobs[2].contains("ok\n"));
+    assert!(driver.jobs()[2].windows(2).any(|w| w == [0x1d, b'V']));
+    let response = tokio::task::spawn_blocking(move || {
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    })
+    .await
+    .unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains(r#""lines":3"#));
+}
+
+#[tokio::test]
+async fn print_breaks_words_longer_than_a_line_unless_strict() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    let url_text = format!("see https://example.com/{}", "x".repeat(49));
+    let response = client.post(format!("{}?strict=true", url)).body(url_text.clone()).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(driver.jobs().is_empty());
+
+    assert_eq!(client.post(&url).body(url_text).send().await.unwrap().status(), 200);
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains(&format!("see\nhttps://example.com/{}\n{}\n", "x".repeat(28), "x".repeat(21))));
+}
+
+#[tokio::test]
+async fn indent_keeps_leading_whitespace_on_wrapped_lines() {
+    let (url, driver) = spawn_server(&[]).await;
+    let body = "Packing:\n  - a warm jacket, gloves, the blue scarf and a thermos\n  - tickets";
+    assert_eq!(reqwest::Client::new().post(format!("{}?indent=true", url)).body(body).send().await.unwrap().status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("Packing:\n  - a warm jacket, gloves, the blue scarf and a\n  thermos\n  - tickets\n"));
+}
+
+#[tokio::test]
+async fn raw_text_expands_tabs_and_squeezes_blank_lines() {
+    let (url, driver) = spawn_server(&[]).await;
+    let body = "Item\tQty\nTea\t2   \n\n\n\nab\tc";
+    let query = "raw=true&tab_width=4&trim=true&squeeze=true";
+    assert_eq!(reqwest::Client::new().post(format!("{}?{}", url, query)).body(body).send().await.unwrap().status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("Item    Qty\nTea 2\n\nab  c\n"));
+}
+
+#[tokio::test]
+async fn huge_tab_widths_are_capped() {
+    let (url, driver) = spawn_server(&[]).await;
+    for format in ["text", "ansi"] {
+        let query = format!("raw=true&format={}&tab_width=1000000000", format);
+        assert_eq!(reqwest::Client::new().post(format!("{}?{}", url, query)).body("a\tb").send().await.unwrap().status(), 200);
+    }
+
+    let jobs = driver.wait_for_jobs(2).await;
+    for job in jobs {
+        assert!(job.contains(&format!("a{}b", " ".repeat(15))));
+    }
+}
+
+#[tokio::test]
+async fn ansi_output_maps_styles_and_drops_colours() {
+    let (url, driver) = spawn_server(&[]).await;
+    let body = "\x1b[1;38;5;4mbuild\x1b[0m \x1b[4mREADME\x1b[24m \x1b[31mtarget\x1b[0m\x1b[K";
+    let response = reqwest::Client::new().post(format!("{}?format=ansi&plain=true", url)).body(body).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    // ESC E 1 (bold), with underline and inverse off; colour 4 must not turn on underline.
+    assert!(jobs[0].contains("\x1bE\x01\x1b-\x00\x1dB\x00build"));
+    assert!(jobs[0].contains("\x1b-\x01\x1dB\x00README\x1bE\x00\x1b-\x00\x1dB\x00 target\n"));
+    assert!(!jobs[0].contains("[31m"));
+}
+
+#[tokio::test]
+async fn weather_renders_forecast() {
+    let open_meteo = spawn_open_meteo(Arc::new(AtomicBool::new(true))).await;
+    let (url, driver) = spawn_server(&[("OPEN_METEO_URL", &open_meteo)]).await;
+    let response = reqwest::get(format!("{}/weather", url)).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("Friday, 16 October 2026"));
+    assert!(receipt.contains("~ Rain ~"));
+    assert!(receipt.contains("High: 55F          Low: 41F"));
+    assert!(receipt.contains("Sunrise: 07:34    Sunset: 18:12"));
+    assert!(receipt.contains("Wind: 12 mph NW \\ (gusts 25 at 15:00)"));
+    assert!(receipt.contains("Humidity: 78%      Dew point: 45F (comfortable)"));
+    assert!(receipt.contains("Amount: 0.17 in of rain"));
+    assert!(!receipt.contains("Snow depth"));
+    // The hourly temperatures as a 576x160 dot chart.
+    assert!(driver.jobs()[0].windows(8).any(|w| w == b"\x1dv0\x00\x48\x00\xa0\x00"));
+    assert!(!receipt.contains("  Temp:  "));
+    assert!(!receipt.contains("Last year today"));
+    assert!(!receipt.contains("AIR"));
+
+    // Metric asks Open-Meteo for Celsius, km/h and millimetres and labels them so.
+    let query = Arc::new(std::sync::Mutex::new(String::new()));
+    let seen = query.clone();
+    let forecast = move |axum::extract::RawQuery(q): axum::extract::RawQuery| async move {
+        *seen.lock().unwrap() = q.unwrap_or_default();
+        ([(CONTENT_TYPE, "application/json")], FORECAST)
+    };
+    let open_meteo = serve(Router::new().route("/v1/forecast", get(forecast))).await;
+    let (url, driver) = spawn_server(&[("OPEN_METEO_URL", open_meteo.as_str()), ("WEATHER_UNITS", "metric")]).await;
+    assert_eq!(reqwest::get(format!("{}/weather", url)).await.unwrap().status(), 200);
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    let query = query.lock().unwrap().clone();
+    for unit in ["temperature_unit=celsius", "wind_speed_unit=kmh", "precipitation_unit=mm"] {
+        assert!(query.contains(unit), "{} missing from {}", unit, query);
+    }
+    assert!(receipt.contains("High: 55C          Low: 41C"));
+    assert!(receipt.contains("Wind: 12 km/h NW"));
+    assert!(receipt.contains("Dew point: 45C (muggy)"));
+    assert!(!receipt.contains("mph"));
+}
+
+#[tokio::test]
+async fn weather_gives_up_on_slow_air_quality_and_archive_lookups() {
+    let slow = || async {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let open_meteo = serve(
+        Router::new()
+            .route("/v1/forecast", get(|| async { ([(CONTENT_TYPE, "application/json")], FORECAST) }))
+            .route("/v1/air-quality", get(slow))
+            .route("/v1/archive", get(slow)),
+    )
+    .await;
+    let (url, driver) = spawn_server(&[
+        ("OPEN_METEO_URL", open_meteo.as_str()),
+        ("WEATHER_AIR_QUALITY", "1"),
+        ("WEATHER_TIMEOUT_SECONDS", "1"),
+    ])
+    .await;
+    let started = std::time::Instant::now();
+    assert_eq!(reqwest::get(format!("{}/weather", url)).await.unwrap().status(), 200);
+    // One timeout for air quality, fetched alongside the forecast, and one for the archive.
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("High: 55F"));
+    assert!(!receipt.contains("AIR") && !receipt.contains("Last year today"));
+}
+
+#[tokio::test]
+async fn weather_dates_and_times_follow_locale_and_formats() {
+    let open_meteo = spawn_open_meteo(Arc::new(AtomicBool::new(true))).await;
+    let (url, driver) = spawn_server(&[
+        ("OPEN_METEO_URL", open_meteo.as_str()),
+        ("LOCALE", "de_DE"),
+        ("DATE_FORMAT", "%A, %-d. %b %Y"),
+        ("TIME_FORMAT", "12h"),
+    ])
+    .await;
+    assert_eq!(reqwest::get(format!("{}/weather", url)).await.unwrap().status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("Freitag, 16. Okt 2026"));
+    assert!(receipt.contains("Sunrise: 7:34 AM    Sunset: 6:12 PM"));
+}
+
+#[tokio::test]
+async fn weather_prints_the_indoor_sensor_beside_the_forecast() {
+    let open_meteo = spawn_open_meteo(Arc::new(AtomicBool::new(true))).await;
+    let sensor = serve(Router::new().route("/", get(|| async { axum::Json(serde_json::json!({"temperature": 21.5, "humidity": 48})) }))).await;
+    let (url, driver) = spawn_server(&[("OPEN_METEO_URL", open_meteo.as_str()), ("WEATHER_INDOOR_URL", sensor.as_str())]).await;
+    assert_eq!(reqwest::get(format!("{}/weather", url)).await.unwrap().status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("INDOOR"));
+    assert!(receipt.contains("              Inside   Outside\n"));
+    assert!(receipt.contains("Temp             71F"));
+    assert!(receipt.contains("Humidity         48%       78%\n"));
+}
+
+#[tokio::test]
+async fn weather_outlook_prints_a_seven_day_grid() {
+    let open_meteo = spawn_open_meteo(Arc::new(AtomicBool::new(true))).await;
+    let (url, driver) = spawn_server(&[("OPEN_METEO_URL", &open_meteo)]).await;
+    let response = reqwest::get(format!("{}/weather?compact=true&outlook=true", url)).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("7-DAY OUTLOOK"));
+    assert!(receipt.contains("    Fri   Sat   Sun   Mon   Tue   Wed   Thu\n"));
+    assert!(receipt.contains("     /     o     O     c     /     *     O\n"));
+    assert!(receipt.contains("    55    58    61    57    50    49    52\n"));
+    assert!(receipt.contains("    41    43    46    44    38    36    39\n"));
+}
+
+#[tokio::test]
+async fn sky_lists_iss_passes_and_the_planets() {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let passes = serde_json::json!({
+        "info": {"satid": 25544, "passescount": 1},
+        "passes": [{
+            "startAz": 310.5, "startAzCompass": "NW", "startUTC": now + 3600,
+            "maxAz": 220.1, "maxAzCompass": "SW", "maxEl": 67.3, "maxUTC": now + 3780,
+            "endAz": 135.0, "endAzCompass": "SE", "endUTC": now + 4020,
+            "mag": -3.1, "duration": 420
+        }]
+    });
+    let n2yo = serve(Router::new().route(
+        "/rest/v1/satellite/visualpasses/25544/{lat}/{lon}/{alt}/{days}/{seconds}/{key}",
+        get(move || async move { axum::Json(passes) }),
+    ))
+    .await;
+    let (url, driver) = spawn_server(&[("N2YO_URL", &n2yo), ("N2YO_API_KEY", "secret")]).await;
+    assert_eq!(reqwest::get(format!("{}/sky", url)).await.unwrap().status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("TONIGHT'S SKY"));
+    assert!(receipt.contains("  NW -> SE  max 67"));
+    assert!(receipt.contains("  mag -3.1"));
+    for planet in ["Mercury", "Venus", "Mars", "Jupiter", "Saturn"] {
+        assert!(receipt.contains(&format!("{:<9} rise ", planet)), "{} missing", planet);
+    }
+}
+
+#[tokio::test]
+async fn tides_lists_highs_and_lows_with_a_curve() {
+    let (yesterday, today, tomorrow) = {
+        let today = chrono::Local::now().date_naive();
+        (today.pred_opt().unwrap(), today, today.succ_opt().unwrap())
+    };
+    let predictions = serde_json::json!({"predictions": [
+        {"t": format!("{} 22:10", yesterday), "v": "0.412", "type": "L"},
+        {"t": format!("{} 04:12", today), "v": "5.123", "type": "H"},
+        {"t": format!("{} 10:30", today), "v": "-0.310", "type": "L"},
+        {"t": format!("{} 16:45", today), "v": "4.870", "type": "H"},
+        {"t": format!("{} 22:58", today), "v": "1.204", "type": "L"},
+        {"t": format!("{} 05:01", tomorrow), "v": "5.350", "type": "H"},
+    ]});
+    let noaa = serve(Router::new().route("/api/prod/datagetter", get(move || async move { axum::Json(predictions) }))).await;
+
+    let (url, _driver) = spawn_server(&[("NOAA_TIDES_URL", &noaa)]).await;
+    assert_eq!(reqwest::get(format!("{}/tides", url)).await.unwrap().status(), 503);
+
+    let (url, driver) = spawn_server(&[("NOAA_TIDES_URL", &noaa), ("TIDE_STATION", "9414290")]).await;
+    assert_eq!(reqwest::get(format!("{}/tides", url)).await.unwrap().status(), 200);
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("Station 9414290"));
+    assert!(receipt.contains("High  04:12     5.1 ft\n"));
+    assert!(receipt.contains("Low   10:30    -0.3 ft\n"));
+    assert!(!receipt.contains("22:10"));
+    // Half-hour columns: two highs, with yesterday's and tomorrow's tides shaping the ends.
+    assert!(receipt.contains("..-==^^^^^^^==--.._______..--==^^^^^^===---.....\n"));
+}
+
+#[tokio::test]
+async fn habits_print_a_weekly_checkbox_grid() {
+    let (url, _driver) = spawn_server(&[]).await;
+    assert_eq!(reqwest::get(format!("{}/habits", url)).await.unwrap().status(), 503);
+
+    let (url, driver) = spawn_server(&[("HABITS", "Stretch,Read 20 pages before bed")]).await;
+    assert_eq!(reqwest::get(format!("{}/habits", url)).await.unwrap().status(), 200);
+    let jobs = driver.wait_for_jobs(1).await;
+    let row = jobs[0].lines().find_map(|line| line.strip_prefix("\x1ba\0Stretch")).unwrap();
+    assert_eq!(row.matches("[ ]").count(), 7);
+    assert_eq!(row.len() + "Stretch".len(), 48);
+    assert!(jobs[0].contains(" Mo  Tu  We  Th  Fr  Sa  Su"));
+}
+
+#[tokio::test]
+async fn github_prints_the_contribution_grid_and_streaks() {
+    let (url, _driver) = spawn_server(&[]).await;
+    assert_eq!(reqwest::get(format!("{}/github/octocat", url)).await.unwrap().status(), 503);
+
+    // Eleven busy days, a gap, then a day each up to yesterday.
+    let today = chrono::Local::now().date_naive();
+    let mut weeks: Vec<Vec<serde_json::Value>> = Vec::new();
+    for offset in -20..=0 {
+        let date = today + chrono::TimeDelta::days(offset);
+        let (count, level) = match offset {
+            -20..=-10 => (4, "FOURTH_QUARTILE"),
+            -8..=-1 => (1, "FIRST_QUARTILE"),
+            _ => (0, "NONE"),
+        };
+        if weeks.is_empty() || chrono::Datelike::weekday(&date) == chrono::Weekday::Sun {
+            weeks.push(Vec::new());
+        }
+        weeks.last_mut().unwrap().push(serde_json::json!({"date": date, "contributionCount": count, "contributionLevel": level}));
+    }
+    let weeks: Vec<_> = weeks.into_iter().map(|days| serde_json::json!({"contributionDays": days})).collect();
+    let calendar = serde_json::json!({"data": {"user": {"contributionsCollection": {"contributionCalendar": {
+        "totalContributions": 52,
+        "weeks": weeks,
+    }}}}});
+    let github = serve(Router::new().route(
+        "/graphql",
+        axum::routing::post(move |headers: axum::http::HeaderMap| async move {
+            assert_eq!(headers["authorization"], "Bearer secret");
+            axum::Json(calendar)
+        }),
+    ))
+    .await;
+    let (url, driver) = spawn_server(&[("GITHUB_TOKEN", "secret"), ("GITHUB_URL", github.as_str())]).await;
+    assert_eq!(reqwest::get(format!("{}/github/octocat", url)).await.unwrap().status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("octocat"));
+    assert!(receipt.contains("Less . - + * # More"));
+    assert!(receipt.lines().any(|line| line.strip_prefix("\x1ba\0We ").is_some_and(|cells| cells.contains("#"))));
+    assert!(receipt.contains("Contributions: 52"));
+    assert!(receipt.contains("Current streak: 8 days"));
+    assert!(receipt.contains("Longest streak: 11 days"));
+    let best = (today - chrono::TimeDelta::days(10)).format("%-d %b %Y");
+    assert!(receipt.contains(&format!("Best day: {} (4)", best)));
+}
+
+#[tokio::test]
+async fn xkcd_prints_the_comic_scaled_to_the_paper_with_its_alt_text() {
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(96, 24, image::Luma([0]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let images = serve(Router::new().route("/comics/standards.png", get(move || async move { png }))).await;
+    let comic = |num: u32, title: &str| {
+        serde_json::json!({
+            "num": num, "safe_title": title, "title": title, "img": format!("{}/comics/standards.png", images),
+            "alt": "Fortunately, the charging one has been solved now that we've all standardized on mini-USB.",
+            "year": "2013", "month": "7", "day": "24",
+        })
+    };
+    let (latest, older) = (comic(2000, "Latest"), comic(927, "Standards"));
+    let xkcd = serve(
+        Router::new()
+            .route("/info.0.json", get(move || async move { axum::Json(latest) }))
+            .route("/927/info.0.json", get(move || async move { axum::Json(older) })),
+    )
+    .await;
+    let (url, driver) = spawn_server(&[("XKCD_URL", &xkcd)]).await;
+    assert_eq!(reqwest::get(format!("{}/xkcd?num=927", url)).await.unwrap().status(), 200);
+    assert_eq!(reqwest::get(format!("{}/xkcd?num=1", url)).await.unwrap().status(), 502);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("XKCD #927"));
+    assert!(jobs[0].contains("Standards"));
+    assert!(jobs[0].contains("2013-07-24"));
+    // 96x24 scaled up to the 576-dot paper width: 72 bytes per row, 144 rows.
+    assert!(driver.jobs()[0].windows(8).any(|w| w == b"\x1dv0\x00\x48\x00\x90\x00"));
+    assert!(jobs[0].contains("Fortunately, the charging one has been solved\n"));
+    assert!(jobs[0].contains("now that we've all standardized on mini-USB.\n"));
+}
+
+#[tokio::test]
+async fn apod_prints_the_picture_and_a_shortened_explanation() {
+    let (url, _driver) = spawn_server(&[]).await;
+    assert_eq!(reqwest::get(format!("{}/apod", url)).await.unwrap().status(), 503);
+
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(48, 48, image::Luma([128]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let images = serve(Router::new().route("/image/nebula.png", get(move || async move { png }))).await;
+    let apod = serde_json::json!({
+        "date": "2024-01-15", "title": "The Horsehead Nebula", "media_type": "image",
+        "url": format!("{}/image/nebula.png", images),
+        "explanation": "One of the most identifiable nebulae in the sky, the Horsehead Nebula in Orion, is part of a large, dark, molecular cloud.",
+    });
+    let nasa = serve(Router::new().route(
+        "/planetary/apod",
+        get(move |axum::extract::Query(query): axum::extract::Query<HashMap<String, String>>| async move {
+            assert_eq!(query["api_key"], "key");
+            assert_eq!(query["date"], "2024-01-15");
+            axum::Json(apod)
+        }),
+    ))
+    .await;
+    let (url, driver) = spawn_server(&[("NASA_API_KEY", "key"), ("NASA_URL", &nasa), ("APOD_MAX_CHARS", "60")]).await;
+    assert_eq!(reqwest::get(format!("{}/apod?date=2024-01-15", url)).await.unwrap().status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("Monday, 15 January 2024"));
+    assert!(jobs[0].contains("The Horsehead Nebula"));
+    assert!(driver.jobs()[0].windows(8).any(|w| w == b"\x1dv0\x00\x48\x00\x40\x02"));
+    assert!(jobs[0].contains("One of the most identifiable nebulae in the sky,\n"));
+    assert!(jobs[0].contains("the...\n"));
+    assert!(!jobs[0].contains("Horsehead Nebula in Orion"));
+}
+
+#[tokio::test]
+async fn packages_print_only_status_changes() {
+    let tag = Arc::new(std::sync::Mutex::new("InTransit"));
+    let current = tag.clone();
+    let aftership = serve(Router::new().route(
+        "/v4/trackings/ups/1Z999AA10123456784",
+        get(move |headers: axum::http::HeaderMap| async move {
+            assert_eq!(headers["as-api-key"], "key");
+            let tag = *current.lock().unwrap();
+            axum::Json(serde_json::json!({"data": {"tracking": {
+                "slug": "ups", "tag": tag, "expected_delivery": "2024-01-18T12:00:00",
+                "checkpoints": [{"message": "Departed facility", "city": "Memphis", "country_name": "USA"}],
+            }}}))
+        }),
+    ))
+    .await;
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-packages-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let (url, driver) = spawn_server(&[
+        ("TRACKING_API_KEY", "key"),
+        ("TRACKING_NUMBERS", "Headphones=1Z999AA10123456784:UPS,0000000000:dhl"),
+        ("TRACKING_URL", &aftership),
+        ("DATA_DIR", data_dir.to_str().unwrap()),
+    ])
+    .await;
+    let check = || async { reqwest::get(format!("{}/packages", url)).await.unwrap().json::<serde_json::Value>().await.unwrap() };
+
+    assert_eq!(check().await["changed"], 1);
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("Headphones\n"));
+    assert!(jobs[0].contains("  Carrier: ups\n"));
+    assert!(jobs[0].contains("  Status: In transit\n"));
+    assert!(jobs[0].contains("  Location: Memphis, USA\n"));
+    assert!(jobs[0].contains("  ETA: 2024-01-18\n"));
+    assert!(jobs[0].contains("  Departed facility\n"));
+
+    assert_eq!(check().await["changed"], 0);
+    *tag.lock().unwrap() = "OutForDelivery";
+    assert_eq!(check().await["changed"], 1);
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[1].contains("  Status: Out for delivery\n"));
+    assert_eq!(driver.jobs().len(), 2);
+    let _ = std::fs::remove_dir_all(&data_dir);
+}
+
+#[tokio::test]
+async fn fx_prints_rates_with_the_daily_change() {
+    let frankfurter = serve(Router::new().route(
+        "/{range}",
+        get(|axum::extract::Query(query): axum::extract::Query<HashMap<String, String>>| async move {
+            assert_eq!(query["from"], "EUR");
+            assert_eq!(query["to"], "USD,JPY");
+            axum::Json(serde_json::json!({"base": "EUR", "rates": {
+                "2024-01-12": {"USD": 1.0950, "JPY": 158.50},
+                "2024-01-15": {"USD": 1.0972, "JPY": 160.08},
+            }}))
+        }),
+    ))
+    .await;
+    let (url, driver) = spawn_server(&[("FX_URL", &frankfurter)]).await;
+    assert_eq!(reqwest::get(format!("{}/fx?base=eur&symbols=usd,jpy", url)).await.unwrap().status(), 200);
+    assert_eq!(reqwest::get(format!("{}/fx?symbols=dollars", url)).await.unwrap().status(), 400);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("1 EUR on 15 January 2024"));
+    assert!(jobs[0].contains("USD           1.0972     +0.0022    +0.20%  ^\n"));
+    assert!(jobs[0].contains("JPY           160.08     +1.5800    +1.00%  ^\n"));
+}
+
+#[tokio::test]
+async fn countdowns_print_days_until_events_and_birthdays() {
+    let today = chrono::Local::now().date_naive();
+    let day = |offset: i64| today + chrono::TimeDelta::days(offset);
+    let birthdays = std::env::temp_dir().join(format!("print-jobber-birthdays-{}.vcf", std::process::id()));
+    std::fs::write(
+        &birthdays,
+        format!(
+            "BEGIN:VCARD\r\nFN:Ada\r\nBDAY:{}\r\nEND:VCARD\r\nBEGIN:VCARD\r\nFN:Grace\r\nBDAY:--{}\r\nEND:VCARD\r\n",
+            chrono::Datelike::with_year(&day(3), chrono::Datelike::year(&day(3)) - 30).unwrap().format("%Y%m%d"),
+            day(0).format("%m%d"),
+        ),
+    )
+    .unwrap();
+    let (url, driver) = spawn_server(&[
+        ("COUNTDOWNS", &format!("Launch={},Past={},Far away={}", day(37), day(-1), day(400))),
+        ("BIRTHDAYS_FILE", birthdays.to_str().unwrap()),
+    ])
+    .await;
+    assert_eq!(reqwest::get(format!("{}/countdowns", url)).await.unwrap().status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    let receipt = &jobs[0];
+    let today_at = receipt.find("Today: Grace's birthday").unwrap();
+    let ada_at = receipt.find("3 days until Ada's birthday (turns 30)").unwrap();
+    let launch_at = receipt.find("37 days until Launch").unwrap();
+    assert!(today_at < ada_at && ada_at < launch_at);
+    assert!(!receipt.contains("Past") && !receipt.contains("Far away"));
+    std::fs::remove_file(birthdays).unwrap();
+}
+
+#[tokio::test]
+async fn briefing_combines_sections_and_notes_failed_ones() {
+    let today = chrono::Local::now().date_naive();
+    let day = |offset: i64| (today + chrono::TimeDelta::days(offset)).format("%Y%m%d").to_string();
+    let weekday = ["MO", "TU", "WE", "TH", "FR", "SA", "SU"][chrono::Datelike::weekday(&today).num_days_from_monday() as usize];
+    let calendar = format!(
+        "BEGIN:VCALENDAR\r\n\
+         BEGIN:VEVENT\r\nSUMMARY:Dentist\r\nLOCATION:Main St\\, 4\r\nDTSTART:{today}T090000\r\nDTEND:{today}T093000\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nSUMMARY:Team\r\n  sync\r\nDTSTART:{start}T080000\r\nRRULE:FREQ=WEEKLY;BYDAY={weekday}\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nSUMMARY:Holiday\r\nDTSTART;VALUE=DATE:{today}\r\nDTEND;VALUE=DATE:{tomorrow}\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nSUMMARY:Tomorrow only\r\nDTSTART:{tomorrow}T100000\r\nEND:VEVENT\r\n\
+         BEGIN:VEVENT\r\nSUMMARY:Skipped today\r\nDTSTART:{start}T120000\r\nRRULE:FREQ=DAILY\r\nEXDATE:{today}T120000\r\nEND:VEVENT\r\n\
+         END:VCALENDAR\r\n",
+        today = day(0),
+        tomorrow = day(1),
+        start = day(-14),
+        weekday = weekday,
+    );
+    let upstream = serve(
+        Router::new()
+            .route("/cal.ics", get(move || async move { calendar }))
+            .route("/todo.md", get(|| async { "# Todo\n\n- [x] laundry\n- [ ] pay rent\n  - [ ] call landlord\n" }))
+            .route("/v0/topstories.json", get(|| async { StatusCode::INTERNAL_SERVER_ERROR })),
+    )
+    .await;
+    let open_meteo = spawn_open_meteo(Arc::new(AtomicBool::new(true))).await;
+    let (url, driver) = spawn_server(&[
+        ("OPEN_METEO_URL", open_meteo.as_str()),
+        ("CALENDAR_URLS", &format!("{}/cal.ics", upstream)),
+        ("TODO_URL", &format!("{}/todo.md", upstream)),
+        ("HN_URL", &format!("{}/v0", upstream)),
+    ])
+    .await;
+    assert_eq!(reqwest::get(format!("{}/briefing", url)).await.unwrap().status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    let receipt = &jobs[0];
+    assert!(receipt.contains("High: 55F   Low: 41F"));
+    let agenda = ["All day      Holiday", "08:00        Team sync", "09:00-09:30  Dentist @ Main St, 4"];
+    let positions: Vec<usize> = agenda.iter().map(|line| receipt.find(line).unwrap_or_else(|| panic!("missing {:?}", line))).collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(!receipt.contains("Tomorrow only") && !receipt.contains("Skipped today"));
+    assert!(receipt.contains("[ ] pay rent") && receipt.contains("[ ] call landlord") && !receipt.contains("laundry"));
+    assert!(receipt.contains("HEADLINES (unavailable)"));
+    assert_eq!(driver.jobs().len(), 1);
+}
+
+#[tokio::test]
+async fn briefing_sections_follow_the_configured_order() {
+    let quotes = std::env::temp_dir().join(format!("print-jobber-quotes-{}.txt", std::process::id()));
+    std::fs::write(&quotes, "Simplicity is prerequisite for reliability. -- Edsger Dijkstra\n").unwrap();
+    let rss = "<rss><channel><title>Feed</title>\
+        <item><title><![CDATA[Bridge reopens]]></title></item>\
+        <item><title>Rates &amp; rents</title></item>\
+        <item><title>Third story</title></item></channel></rss>";
+    let departures = r#"{"departures": [
+        {"when": "2026-10-16T08:14:00+02:00", "plannedWhen": "2026-10-16T08:12:00+02:00", "delay": 120, "direction": "Hermannstr.", "line": {"name": "U8"}},
+        {"when": null, "plannedWhen": "2026-10-16T08:20:00+02:00", "direction": "Wittenau", "line": {"name": "U8"}, "cancelled": true}
+    ]}"#;
+    let upstream = serve(
+        Router::new()
+            .route("/rss", get(move || async move { rss }))
+            .route("/stops/900100003/departures", get(move || async move { ([(CONTENT_TYPE, "application/json")], departures) })),
+    )
+    .await;
+    let sections = format!(
+        "quote:file={},feeds:url={}/rss:count=2:title=World,transit:stop=900100003:url={},bogus,todo",
+        quotes.display(),
+        upstream,
+        upstream
+    );
+    let (url, driver) = spawn_server(&[("BRIEFING_SECTIONS", sections.as_str())]).await;
+    assert_eq!(reqwest::get(format!("{}/briefing", url)).await.unwrap().status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    let order = ["QUOTE", "WORLD", "DEPARTURES", "TO DO (unavailable)"];
+    let positions: Vec<usize> = order.iter().map(|title| receipt.find(title).unwrap_or_else(|| panic!("missing {:?}", title))).collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(receipt.contains("\"Simplicity is prerequisite for reliability.\"") && receipt.contains("- Edsger Dijkstra"));
+    assert!(receipt.contains("* Bridge reopens") && receipt.contains("* Rates & rents") && !receipt.contains("Third story"));
+    assert!(receipt.contains("U8 -> Hermannstr. +2") && receipt.contains("U8 -> Wittenau (cancelled)"));
+    assert!(!receipt.contains("WEATHER") && !receipt.contains("HEADLINES"));
+    std::fs::remove_file(quotes).unwrap();
+}
+
+#[tokio::test]
+async fn plugins_render_blocks_and_can_only_fetch_allowed_hosts() {
+    let upstream = serve(Router::new().route("/count", get(|| async { ([(CONTENT_TYPE, "application/json")], r#"{"count": 42}"#) }))).await;
+    let dir = std::env::temp_dir().join(format!("print-jobber-plugins-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("counter.lua"),
+        r#"function render(params)
+            local data = json(fetch(params.url .. "/count"))
+            return {
+                {type = "large", text = params.label or "COUNT"},
+                {type = "divider"},
+                {type = "text", text = "Visitors: " .. math.floor(data.count)},
+                {type = "center", text = params.body or "no body"},
+            }
+        end"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("snoop.lua"), r#"function render() return {{type = "text", text = fetch("http://localhost:1/")}} end"#).unwrap();
+    std::fs::write(dir.join("spin.lua"), "function render() while true do end end").unwrap();
+    std::fs::write(dir.join("escape.lua"), r#"function render() return {{type = "text", text = io.read()}} end"#).unwrap();
+
+    let (url, driver) = spawn_server(&[
+        ("PLUGINS_DIR", dir.to_str().unwrap()),
+        ("PLUGIN_HOSTS", "127.0.0.1"),
+        ("PLUGIN_TIMEOUT_SECONDS", "1"),
+    ])
+    .await;
+    let client = reqwest::Client::new();
+    let status = |path: &str| client.get(format!("{}{}", url, path)).send();
+    assert_eq!(status(&format!("/plugins/counter?label=HITS&url={}", upstream)).await.unwrap().status(), 200);
+    let post = client.post(format!("{}/plugins/counter?url={}", url, upstream)).body("from the body").send().await.unwrap();
+    assert_eq!(post.status(), 200);
+    assert_eq!(status("/plugins/snoop").await.unwrap().status(), 502);
+    assert_eq!(status("/plugins/spin").await.unwrap().status(), 502);
+    assert_eq!(status("/plugins/escape").await.unwrap().status(), 502);
+    assert_eq!(status("/plugins/missing").await.unwrap().status(), 404);
+
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[0].contains("HITS") && jobs[0].contains("Visitors: 42") && jobs[0].contains("no body"));
+    assert!(jobs[1].contains("COUNT") && jobs[1].contains("from the body"));
+    assert_eq!(driver.jobs().len(), 2);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn exec_prints_allowlisted_command_output() {
+    let wide = "x".repeat(60);
+    let commands = format!("hello=echo disk usage,wide=echo {},fail=ls /nonexistent,slow=sleep 5,flood=yes,bad entry", wide);
+    let (url, driver) = spawn_server(&[
+        ("EXEC_COMMANDS", commands.as_str()),
+        ("EXEC_TIMEOUT_SECONDS", "1"),
+        ("BRIEFING_SECTIONS", "exec:name=hello:title=Disk"),
+    ])
+    .await;
+    let client = reqwest::Client::new();
+    let exec = |name: &str| client.post(format!("{}/exec/{}", url, name)).send();
+    assert_eq!(exec("hello").await.unwrap().status(), 200);
+    assert_eq!(exec("wide").await.unwrap().status(), 200);
+    assert_eq!(exec("fail").await.unwrap().status(), 502);
+    assert_eq!(exec("slow").await.unwrap().status(), 502);
+    assert_eq!(exec("rm").await.unwrap().status(), 404);
+    assert_eq!(reqwest::get(format!("{}/briefing", url)).await.unwrap().status(), 200);
+
+    let jobs = driver.wait_for_jobs(3).await;
+    assert!(jobs[0].contains("disk usage\n"));
+    assert!(jobs[1].contains(&format!("{}\n{}\n", "x".repeat(48), "x".repeat(12))));
+    assert!(jobs[2].contains("DISK") && jobs[2].contains("disk usage"));
+    assert_eq!(driver.jobs().len(), 3);
+
+    // A command flooding stdout is stopped once it passes the limit, not at the timeout.
+    let started = std::time::Instant::now();
+    assert_eq!(exec("flood").await.unwrap().status(), 502);
+    assert!(started.elapsed() < Duration::from_millis(900));
+
+    // Per-request layout options apply: font B fits the wide line.
+    assert_eq!(exec("wide?printer_font=b").await.unwrap().status(), 200);
+    assert!(driver.wait_for_jobs(4).await[3].contains(&format!("{}\n", wide)));
+    assert_eq!(driver.jobs().len(), 4);
+}
+
+#[tokio::test]
+async fn weather_falls_back_to_cached_forecast() {
+    let up = Arc::new(AtomicBool::new(true));
+    let open_meteo = spawn_open_meteo(up.clone()).await;
+    let (url, driver) = spawn_server(&[("OPEN_METEO_URL", &open_meteo), ("WEATHER_RETRIES", "0")]).await;
+
+    reqwest::get(format!("{}/weather", url)).await.unwrap();
+    assert!(!driver.wait_for_jobs(1).await[0].contains("stale"));
+
+    up.store(false, Ordering::SeqCst);
+    let response = reqwest::get(format!("{}/weather", url)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let receipt = &driver.wait_for_jobs(2).await[1];
+    assert!(receipt.contains("stale (fetched "));
+    assert!(receipt.contains("High: 55F"));
+}
+
+#[tokio::test]
+async fn recipe_prints_ingredients_and_steps() {
+    let site = serve(Router::new().route("/pancakes", get(|| async { axum::response::Html(RECIPE_PAGE) }))).await;
+    let (url, driver) = spawn_server(&[]).await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/recipe", url))
+        .json(&HashMap::from([("url", format!("{}/pancakes", site))]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("FLUFFY PANCAKES\n"));
+    assert!(receipt.contains("Serves: 4\n"));
+    assert!(receipt.contains("[ ] 1 tbsp sugar & a pinch of salt\n"));
+    assert!(receipt.contains("1. Whisk the flour, sugar and salt together in a\n"));
+    assert!(receipt.contains("   large bowl, then make a well in the middle.\n"));
+    assert!(receipt.contains("3. Fry ladlefuls in a hot buttered pan.\n"));
+}
+
+#[tokio::test]
+async fn recipe_without_structured_data_is_unprocessable() {
+    let site = serve(Router::new().route("/", get(|| async { axum::response::Html("<p>no recipe here</p>") }))).await;
+    let (url, _driver) = spawn_server(&[]).await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/recipe", url))
+        .json(&HashMap::from([("url", site)]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+}
+
+#[tokio::test]
+async fn article_prints_main_text_without_boilerplate() {
+    let site = serve(Router::new().route("/post", get(|| async { axum::response::Html(ARTICLE_PAGE) }))).await;
+    let (url, driver) = spawn_server(&[]).await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/article", url))
+        .json(&serde_json::json!({ "url": format!("{}/post", site), "max_chars": 150 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let receipt = &driver.wait_for_jobs(1).await[0];
+    assert!(receipt.contains("Why Receipt Printers Are Great\n"));
+    assert!(receipt.contains("by Ada Example\n"));
+    assert!(receipt.contains("Receipt printers are cheap, fast and"));
+    assert!(receipt.contains("Thermal paper needs no ink &"));
+    assert!(receipt.contains("more characters online]"));
+    assert!(!receipt.contains("Short caption"));
+    assert!(!receipt.contains("navigation"));
+    assert!(!receipt.contains("Sponsored"));
+    assert!(!receipt.contains("Copyright"));
+}
+
+#[tokio::test]
+async fn job_metadata_line_names_the_source_unless_turned_off() {
+    let (url, driver) = spawn_server(&[("JOB_METADATA", "1"), ("API_KEYS", "kitchen:secret"), ("HEADER_TEMPLATE", "HELLO")]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).bearer_auth("secret").body("first").send().await.unwrap();
+    driver.wait_for_jobs(1).await;
+    client.post(format!("{}/?no_header=true", url)).bearer_auth("secret").body("second").send().await.unwrap();
+
+    let jobs = driver.wait_for_jobs(2).await;
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let first = &jobs[0][jobs[0].find(&today).unwrap()..];
+    let first = first.lines().next().unwrap();
+    assert!(first.contains(" kitchen "), "{:?}", first);
+    let id = first.split_whitespace().rev().nth(1).unwrap();
+    let hash = first.split_whitespace().last().unwrap();
+    assert_eq!(first.chars().count(), 48);
+    assert_eq!(id, "#1");
+    assert!(hash.len() == 6 && hash.chars().all(|c| c.is_ascii_hexdigit()));
+    assert!(jobs[0].find(&today).unwrap() < jobs[0].find("HELLO").unwrap());
+    assert!(!jobs[1].contains(&today) && jobs[1].contains("HELLO") && jobs[1].contains("second"));
+}
+
+#[tokio::test]
+async fn footer_qr_code_links_to_the_job_content() {
+    let (url, driver) = spawn_server(&[("JOB_QR_URL", "http://printer.local:3000/"), ("SOURCE_FOOTER", "1")]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("Shopping list:\n  eggs\n  milk").send().await.unwrap();
+    client.post(format!("{}/?plain=true", url)).body("no footer").send().await.unwrap();
+
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[0].contains("http://printer.local:3000/jobs/1/content"));
+    assert!(!jobs[1].contains("/content"));
+    let content = reqwest::get(format!("{}/jobs/1/content", url)).await.unwrap();
+    assert_eq!(content.status(), 200);
+    assert_eq!(content.text().await.unwrap(), "Shopping list:\neggs\nmilk\n");
+    assert_eq!(reqwest::get(format!("{}/jobs/99/content", url)).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn paused_queue_holds_jobs_until_resumed() {
+    let (url, driver) = spawn_server(&[("API_KEYS", "cli:secret")]).await;
+    let client = reqwest::Client::new();
+    let ready = || async { client.get(format!("{}/readyz", url)).send().await.unwrap() };
+    assert_eq!(ready().await.status(), 200);
+
+    client.post(format!("{}/queue/pause", url)).bearer_auth("secret").send().await.unwrap();
+    client.post(&url).bearer_auth("secret").body("held").send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(driver.jobs().is_empty());
+    let readiness = ready().await;
+    assert_eq!(readiness.status(), 503);
+    let readiness: serde_json::Value = readiness.json().await.unwrap();
+    assert_eq!(readiness["paused"], true);
+    assert_eq!(readiness["queued"], 1);
+
+    client.post(format!("{}/queue/resume", url)).bearer_auth("secret").send().await.unwrap();
+    assert!(driver.wait_for_jobs(1).await[0].contains("held\n"));
+    assert_eq!(ready().await.status(), 200);
+
+    // The old /admin routes still pause and resume.
+    let response = client.post(format!("{}/admin/pause", url)).bearer_auth("secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    client.post(&url).bearer_auth("secret").body("held again").send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(driver.jobs().len(), 1);
+    assert_eq!(ready().await.status(), 503);
+
+    let response = client.post(format!("{}/admin/resume", url)).bearer_auth("secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(driver.wait_for_jobs(2).await[1].contains("held again\n"));
+    assert_eq!(ready().await.status(), 200);
+}
+
+#[tokio::test]
+async fn jobs_past_their_expiry_are_dropped_unprinted() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    client.post(format!("{}/admin/pause", url)).send().await.unwrap();
+    client.post(format!("{}/?expires_in=1", url)).body("lunch is ready").send().await.unwrap();
+    client.post(format!("{}/?expires_in=600", url)).body("still wanted").send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    client.post(format!("{}/admin/resume", url)).send().await.unwrap();
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("still wanted"));
+    let history: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history[1]["status"], "expired");
+    assert_eq!(driver.jobs().len(), 1);
+}
+
+#[tokio::test]
+async fn clearing_the_queue_cancels_waiting_jobs() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    client.post(format!("{}/queue/pause", url)).send().await.unwrap();
+    client.post(&url).body("flood 1").send().await.unwrap();
+    client.post(format!("{}/?delay_seconds=3600", url)).body("flood 2").send().await.unwrap();
+
+    let cleared = |query: &'static str| {
+        let client = client.clone();
+        let url = url.clone();
+        async move {
+            let response = client.delete(format!("{}/queue{}", url, query)).send().await.unwrap();
+            response.json::<serde_json::Value>().await.unwrap()["dropped"].clone()
+        }
+    };
+    assert_eq!(cleared("?source=key:someone-else").await, 0);
+    assert_eq!(cleared("").await, 2);
+
+    client.post(format!("{}/queue/resume", url)).send().await.unwrap();
+    client.post(&url).body("after").send().await.unwrap();
+    assert!(driver.wait_for_jobs(1).await[0].contains("after"));
+    let history: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history[1]["status"], "cancelled");
+    assert_eq!(history[2]["status"], "cancelled");
+    assert_eq!(driver.jobs().len(), 1);
+}
+
+#[tokio::test]
+async fn a_full_queue_turns_jobs_away_with_429() {
+    let (url, driver) = spawn_server(&[("MAX_QUEUED_JOBS", "2")]).await;
+    let client = reqwest::Client::new();
+    client.post(format!("{}/queue/pause", url)).send().await.unwrap();
+    client.post(&url).body("one").send().await.unwrap();
+    client.post(format!("{}/?delay_seconds=3600", url)).body("two").send().await.unwrap();
+
+    let response = client.post(&url).body("three").send().await.unwrap();
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["x-queue-depth"], "2");
+    assert_eq!(response.headers()["x-queue-limit"], "2");
+    assert!(response.headers().contains_key("retry-after"));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "queue_full");
+
+    client.post(format!("{}/queue/resume", url)).send().await.unwrap();
+    driver.wait_for_jobs(1).await;
+    let response = client.post(&url).body("four").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(driver.wait_for_jobs(2).await[1].contains("four"));
+}
+
+#[tokio::test]
+async fn recent_jobs_can_be_reprinted() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("torn receipt").send().await.unwrap();
+    client.post(&url).body("second job").send().await.unwrap();
+
+    let last: serde_json::Value = client.post(format!("{}/reprint-last", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(last["reprint_of"], 2);
+    let first = client.post(format!("{}/jobs/1/reprint", url)).send().await.unwrap();
+    assert_eq!(first.status(), 200);
+    assert_eq!(client.post(format!("{}/jobs/99/reprint", url)).send().await.unwrap().status(), 404);
+
+    let jobs = driver.wait_for_jobs(4).await;
+    assert!(jobs[2].contains("second job"));
+    assert!(jobs[3].contains("torn receipt"));
+}
+
+#[tokio::test]
+async fn archived_jobs_reprint_identically_after_a_restart() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-archive-{}", std::process::id()));
+    let vars = [("DATA_DIR", data_dir.to_str().unwrap()), ("ARCHIVE_JOBS", "1")];
+    let client = reqwest::Client::new();
+    let (url, driver) = spawn_server(&vars).await;
+    client.post(&url).body("kept forever").send().await.unwrap();
+    client.post(&url).body("kept forever").send().await.unwrap();
+    let first = driver.wait_for_jobs(2).await;
+    // Identical receipts share one archived file.
+    assert_eq!(std::fs::read_dir(data_dir.join("archive/objects")).unwrap().count(), 1);
+
+    let (url, driver) = spawn_server(&vars).await;
+    let content = client.get(format!("{}/jobs/1/content", url)).send().await.unwrap().text().await.unwrap();
+    assert!(content.contains("kept forever"));
+    let reprinted: serde_json::Value = client.post(format!("{}/jobs/1/reprint", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(reprinted["reprint_of"], 1);
+    assert_eq!(reprinted["id"], 3);
+    assert_eq!(driver.wait_for_jobs(1).await[0], first[0]);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn redaction_masks_secrets_before_they_are_printed_or_archived() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-redact-{}", std::process::id()));
+    let vars = [
+        ("DATA_DIR", data_dir.to_str().unwrap()),
+        ("ARCHIVE_JOBS", "1"),
+        ("REDACT", "cards,tokens,emails"),
+        ("REDACT_PATTERNS", "door code \\d{4}"),
+    ];
+    let (url, driver) = spawn_server(&vars).await;
+    let body = "Card 4111 1111 1111 1111\nOrder 1234 5678 9012 3456\nKey sk_live_abcdefghij12345\nMail jo@example.com\ndoor code 4821";
+    reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    let printed = driver.wait_for_jobs(1).await.remove(0);
+    // Cards keep their last four digits; numbers failing the Luhn check aren't cards.
+    assert!(printed.contains("Card **** **** **** 1111\n"));
+    assert!(printed.contains("Order 1234 5678 9012 3456\n"));
+    assert!(printed.contains("Key **_****_***************\n"));
+    assert!(printed.contains("Mail **@*******.***\n"));
+    assert!(printed.contains("**** **** ****\n"));
+    for secret in ["4111 1111", "abcdefghij", "jo@example", "4821"] {
+        assert!(!printed.contains(secret));
+    }
+    let objects: Vec<_> = std::fs::read_dir(data_dir.join("archive/objects")).unwrap().collect();
+    let object = std::fs::read_dir(objects[0].as_ref().unwrap().path()).unwrap().next().unwrap().unwrap();
+    let archived = std::fs::read_to_string(object.path()).unwrap();
+    assert!(archived.contains("**** **** **** 1111") && !archived.contains("4821"));
+
+    // The audit log keeps the first line, masked the same way.
+    let audit: serde_json::Value = reqwest::get(format!("{}/audit", url)).await.unwrap().json().await.unwrap();
+    assert_eq!(audit[0]["first_line"], "Card **** **** **** 1111");
+    let log = std::fs::read_to_string(data_dir.join("audit.jsonl")).unwrap();
+    assert!(!log.contains("4111 1111"));
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn jobs_can_be_searched_across_restarts() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-search-{}", std::process::id()));
+    let vars = [("DATA_DIR", data_dir.to_str().unwrap()), ("ARCHIVE_JOBS", "1")];
+    let client = reqwest::Client::new();
+    let (url, driver) = spawn_server(&vars).await;
+    client.post(&url).body("Pancake recipe\nflour, eggs, milk").send().await.unwrap();
+    client.post(&url).body("Shopping list\nmilk").send().await.unwrap();
+    driver.wait_for_jobs(2).await;
+
+    let (url, driver) = spawn_server(&vars).await;
+    client.post(&url).body("Another recipe\nsoup").send().await.unwrap();
+    driver.wait_for_jobs(1).await;
+    let search = |query: &'static str| {
+        let (client, url) = (client.clone(), url.clone());
+        async move {
+            let jobs: serde_json::Value = client.get(format!("{}/jobs{}", url, query)).send().await.unwrap().json().await.unwrap();
+            jobs.as_array().unwrap().iter().map(|job| (job["id"].as_u64().unwrap(), job["status"].as_str().unwrap().to_owned())).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(search("?q=RECIPE").await, [(3, "printed".to_owned()), (1, "archived".to_owned())]);
+    assert_eq!(search("?q=milk&limit=1&offset=1").await, [(1, "archived".to_owned())]);
+    assert_eq!(search("?source=http").await.len(), 3);
+    assert!(search("?source=kitchen").await.is_empty());
+    assert!(search("?from=2999-01-01T00:00:00Z").await.is_empty());
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn stats_total_the_job_history_and_print_a_report() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("one\ntwo\nthree").send().await.unwrap();
+    client.post(&url).body("four").send().await.unwrap();
+    driver.wait_for_jobs(2).await;
+    // The worker records the outcome just after writing to the printer.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats: serde_json::Value = client.get(format!("{}/stats?days=3&print=true", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stats["jobs"], 2);
+    assert_eq!(stats["lines"], 4);
+    assert_eq!(stats["printed"], 2);
+    assert_eq!(stats["failure_rate"], 0.0);
+    assert_eq!(stats["per_day"].as_array().unwrap().len(), 3);
+    assert_eq!(stats["per_day"][2]["jobs"], 2);
+    assert_eq!(stats["top_sources"][0]["source"], "http");
+    assert!(stats["paper_mm"].as_f64().unwrap() > 0.0);
+    assert_eq!(client.get(format!("{}/stats?days=0", url)).send().await.unwrap().status(), 400);
+    assert_eq!(client.get(format!("{}/stats?days=366", url)).send().await.unwrap().status(), 200);
+    assert_eq!(client.get(format!("{}/stats?days=367", url)).send().await.unwrap().status(), 400);
+    assert_eq!(client.get(format!("{}/stats?days=9223372036854775807", url)).send().await.unwrap().status(), 400);
+
+    let report = &driver.wait_for_jobs(3).await[2];
+    assert!(report.contains("PRINTER REPORT"));
+    assert!(report.contains("Jobs: 2    Lines: 4"));
+    assert!(report.contains("Printed: 2    Failed: 0 (0.0%)"));
+    assert!(report.contains("  http (2)"));
+}
+
+#[tokio::test]
+async fn named_printers_print_with_their_own_profiles() {
+    let vars = [
+        ("PRINTERS", "kitchen=usb:profile=star-tsp100,bar=usb:profile=generic-58mm"),
+        ("MIRROR_PRINTERS", "default,kitchen,bar"),
+    ];
+    let (url, main, named) = spawn_server_with_printers(&vars, &["kitchen", "bar"]).await;
+    let body = "The quick brown fox jumps over the lazy dog and keeps running far away";
+    reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+
+    let main_job = &main.wait_for_jobs(1).await[0];
+    assert!(main_job.contains("The quick brown fox jumps over the lazy dog and\nkeeps running far away"));
+    assert!(main.jobs()[0].windows(2).any(|w| w == [0x1d, b'V']));
+
+    // Star line mode: ESC d 2 to cut instead of GS V.
+    let kitchen_job = &named[0].wait_for_jobs(1).await[0];
+    assert!(kitchen_job.contains("The quick brown fox jumps over the lazy dog and\nkeeps running far away"));
+    let kitchen = &named[0].jobs()[0];
+    assert!(kitchen.ends_with(&[0x1b, b'd', 2]));
+    assert!(!kitchen.windows(2).any(|w| w == [0x1d, b'V']));
+
+    // 32 columns on 58mm paper, and no cutter to send a cut to.
+    let bar_job = &named[1].wait_for_jobs(1).await[0];
+    assert!(bar_job.contains("The quick brown fox jumps over\nthe lazy dog and\nkeeps running far away"));
+    assert!(!named[1].jobs()[0].windows(2).any(|w| w == [0x1d, b'V']));
+
+    // Dividers are cut to the narrower paper rather than wrapped.
+    reqwest::get(format!("{}/diagnostics?print=true", url)).await.unwrap();
+    let bar_job = &named[1].wait_for_jobs(2).await[1];
+    assert!(bar_job.contains(&format!("{}\n", "-".repeat(32))));
+    assert!(!bar_job.contains(&"-".repeat(33)));
+}
+
+#[tokio::test]
+async fn mirrored_jobs_print_everywhere_and_report_each_printer() {
+    let (url, main, named) = spawn_server_with_printers(&[("MIRROR_PRINTERS", "default,office")], &["office"]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("shopping list").send().await.unwrap();
+    assert!(main.wait_for_jobs(1).await[0].contains("shopping list"));
+    assert!(named[0].wait_for_jobs(1).await[0].contains("shopping list"));
+
+    client.post(format!("{}/?mirror=office,attic", url)).body("office only").send().await.unwrap();
+    assert!(named[0].wait_for_jobs(2).await[1].contains("office only"));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(main.jobs().len(), 1);
+
+    let history: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history[0]["status"], "printed");
+    assert_eq!(history[0]["targets"][0]["printer"], "office");
+    assert_eq!(history[0]["targets"][0]["status"], "printed");
+    assert_eq!(history[0]["targets"][1]["printer"], "attic");
+    assert_eq!(history[0]["targets"][1]["status"], "failed");
+    assert_eq!(history[0]["targets"][1]["error"], "no such printer");
+    assert_eq!(history[1]["targets"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn jobs_fail_over_to_the_backup_printer() {
+    let (url, main, named) = spawn_server_with_printers(&[("PRINTER_FAILOVER", "backup")], &["backup"]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("first").send().await.unwrap();
+    assert!(main.wait_for_jobs(1).await[0].contains("first"));
+
+    main.set_failing(true);
+    client.post(&url).body("second").send().await.unwrap();
+    assert!(named[0].wait_for_jobs(1).await[0].contains("second"));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let history: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history[0]["status"], "printed");
+    assert_eq!(history[0]["failover"], "backup");
+    assert_eq!(history[0]["targets"][0]["printer"], "default");
+    assert_eq!(history[0]["targets"][0]["status"], "failed");
+    assert_eq!(history[0]["targets"][1]["printer"], "backup");
+    assert_eq!(history[0]["targets"][1]["status"], "printed");
+    assert!(history[1]["failover"].is_null());
+    assert!(history[1].get("targets").is_none());
+}
+
+#[tokio::test]
+async fn relayed_jobs_print_downstream_and_report_back() {
+    let (downstream, office) = spawn_server(&[("API_KEYS", "ingress:secret"), ("HEADER_TEMPLATE", "OFFICE")]).await;
+    let relays = format!("office={} secret,attic=http://127.0.0.1:9", downstream);
+    let (url, _) = spawn_server(&[("RELAYS", relays.as_str()), ("HEADER_TEMPLATE", "INGRESS")]).await;
+    let client = reqwest::Client::new();
+    let printed: serde_json::Value = client.post(format!("{}/?printer=office", url)).body("hello office").send().await.unwrap().json().await.unwrap();
+    let id = printed["jobs"][0]["id"].clone();
+
+    let job = office.wait_for_jobs(1).await.remove(0);
+    assert!(job.contains("INGRESS") && job.contains("hello office"));
+    assert!(!job.contains("OFFICE"));
+    let there: serde_json::Value = client.get(format!("{}/jobs", downstream)).bearer_auth("secret").send().await.unwrap().json().await.unwrap();
+    assert_eq!(there[0]["job"], "relay");
+    assert_eq!(there[0]["upstream_id"], id);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let history: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history[0]["status"], "printed");
+
+    client.post(format!("{}/?printer=attic", url)).body("nobody there").send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let history: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(history[0]["status"], "failed");
+}
+
+#[tokio::test]
+async fn agents_pull_jobs_for_printers_behind_them() {
+    let (url, _) = spawn_server(&[("PRINTERS", "kitchen=agent")]).await;
+    let client = reqwest::Client::new();
+    let history = || async { client.get(format!("{}/jobs", url)).send().await.unwrap().json::<serde_json::Value>().await.unwrap() };
+    client.post(format!("{}/?printer=kitchen", url)).body("nobody home").send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(history().await[0]["status"], "failed");
+
+    let polling = tokio::spawn(client.get(format!("{}/agent/kitchen/next?wait=5", url)).send());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client.post(format!("{}/?printer=kitchen", url)).body("soup's on").send().await.unwrap();
+    let job = polling.await.unwrap().unwrap();
+    assert_eq!(job.status(), StatusCode::OK);
+    let number = job.headers()["x-agent-job"].to_str().unwrap().to_owned();
+    assert!(String::from_utf8_lossy(&job.bytes().await.unwrap()).contains("soup's on"));
+    assert_eq!(history().await[0]["status"], "queued");
+
+    let report = client.post(format!("{}/agent/kitchen/jobs/{}", url, number)).json(&serde_json::json!({})).send().await.unwrap();
+    assert_eq!(report.status(), StatusCode::NO_CONTENT);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(history().await[0]["status"], "printed");
+
+    let idle = client.get(format!("{}/agent/kitchen/next?wait=0", url)).send().await.unwrap();
+    assert_eq!(idle.status(), StatusCode::NO_CONTENT);
+    let unknown = client.get(format!("{}/agent/attic/next?wait=0", url)).send().await.unwrap();
+    assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn routing_rules_and_the_printer_parameter_pick_the_printer() {
+    let (url, main, named) = spawn_server_with_printers(
+        &[
+            ("API_KEYS", "alerts:alert-token,tablet:tablet-token"),
+            ("PRINTER_ROUTES", "tag:urgent=office,key:alerts=office,job:print=kitchen"),
+        ],
+        &["kitchen", "office"],
+    )
+    .await;
+    let (kitchen, office) = (&named[0], &named[1]);
+    let client = reqwest::Client::new();
+    let post = |query: &str, token: &str, body: &'static str| client.post(format!("{}/{}", url, query)).bearer_auth(token).body(body).send();
+    post("", "tablet-token", "groceries").await.unwrap();
+    assert!(kitchen.wait_for_jobs(1).await[0].contains("groceries"));
+    post("", "alert-token", "disk full").await.unwrap();
+    assert!(office.wait_for_jobs(1).await[0].contains("disk full"));
+    post("?tag=urgent", "tablet-token", "call back").await.unwrap();
+    assert!(office.wait_for_jobs(2).await[1].contains("call back"));
+    post("?printer=default", "alert-token", "overridden").await.unwrap();
+    assert!(main.wait_for_jobs(1).await[0].contains("overridden"));
+    assert_eq!(kitchen.jobs().len(), 1);
+}
+
+#[tokio::test]
+async fn fetch_downloads_allowed_urls_and_prints_them_by_type() {
+    let site = serve(
+        Router::new()
+            .route("/notes.md", get(|| async { ([(CONTENT_TYPE, "text/markdown")], "# Shopping\n\n- eggs\n") }))
+            .route("/plain", get(|| async { "just text" }))
+            .route("/page", get(|| async { ([(CONTENT_TYPE, "text/html")], "<p>hi</p>") }))
+            .route("/huge.txt", get(|| async { "x".repeat(4096) }))
+            .route("/away", get(|| async { axum::response::Redirect::temporary("http://example.com/") })),
+    )
+    .await;
+    let (url, driver) = spawn_server(&[("FETCH_HOSTS", "127.0.0.1"), ("FETCH_MAX_BYTES", "1024")]).await;
+    let client = reqwest::Client::new();
+    let fetch = |target: String| {
+        let client = client.clone();
+        let url = url.clone();
+        async move { client.post(format!("{}/fetch", url)).query(&[("url", target)]).send().await.unwrap().status() }
+    };
+
+    assert_eq!(fetch(format!("{}/notes.md", site)).await, 200);
+    assert_eq!(fetch(format!("{}/plain", site)).await, 200);
+    assert_eq!(fetch(format!("{}/page", site)).await, 415);
+    assert_eq!(fetch(format!("{}/huge.txt", site)).await, 413);
+    assert_eq!(fetch(format!("{}/away", site)).await, 502);
+    assert_eq!(fetch("http://example.com/notes.md".to_owned()).await, 403);
+    assert_eq!(fetch("file:///etc/passwd".to_owned()).await, 403);
+
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[0].contains("SHOPPING\n"));
+    assert!(jobs[0].contains("- eggs\n"));
+    assert!(jobs[1].contains("just text"));
+    assert_eq!(driver.jobs().len(), 2);
+}
+
+#[tokio::test]
+async fn delayed_print_waits_for_the_scheduler() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    let both = client.post(format!("{}/?delay_seconds=1&schedule_at=18:00", url)).body("x").send().await.unwrap();
+    assert_eq!(both.status(), 400);
+
+    let response = client.post(format!("{}/?delay_seconds=1", url)).body("later").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let jobs: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(jobs[0]["status"], "scheduled");
+    assert!(driver.jobs().is_empty());
+
+    // The scheduler ticks every few seconds.
+    for _ in 0..80 {
+        if !driver.jobs().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(String::from_utf8_lossy(&driver.jobs()[0]).contains("later\n"));
+}
+
+#[tokio::test]
+async fn quiet_hours_hold_jobs_unless_overridden() {
+    let now = chrono::Local::now();
+    let quiet = format!(
+        "{}-{}",
+        (now - chrono::TimeDelta::hours(1)).format("%H:%M"),
+        (now + chrono::TimeDelta::hours(1)).format("%H:%M")
+    );
+    let (url, driver) = spawn_server(&[("QUIET_HOURS", &quiet)]).await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("can wait").send().await.unwrap();
+    let jobs: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(jobs[0]["status"], "scheduled");
+    let until = chrono::DateTime::parse_from_rfc3339(jobs[0]["scheduled_for"].as_str().unwrap()).unwrap();
+    assert_eq!(until.format("%H:%M").to_string(), (now + chrono::TimeDelta::hours(1)).format("%H:%M").to_string());
+
+    client.post(format!("{}/?override_quiet=true", url)).body("urgent").send().await.unwrap();
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("urgent\n"));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(driver.jobs().len(), 1);
+}
+
+#[tokio::test]
+async fn reminders_are_saved_and_print_when_due() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-reminders-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let client = reqwest::Client::new();
+    let reminders = format!("{}/reminders", url);
+
+    let past = client.post(&reminders).json(&serde_json::json!({"message": "late", "at": "2001-01-01T00:00:00Z"})).send().await.unwrap();
+    assert_eq!(past.status(), 400);
+
+    let soon = (chrono::Local::now() + chrono::TimeDelta::seconds(1)).to_rfc3339();
+    let created = client.post(&reminders).json(&serde_json::json!({"message": "water the plants", "at": soon})).send().await.unwrap();
+    assert_eq!(created.status(), 201);
+    let weekly: serde_json::Value = client
+        .post(&reminders)
+        .json(&serde_json::json!({"message": "bins", "at": "23:59", "recurrence": "weekly"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let weekly_url = format!("{}/{}", reminders, weekly["id"]);
+    let updated = client.put(&weekly_url).json(&serde_json::json!({"message": "recycling", "at": "23:58"})).send().await.unwrap();
+    assert_eq!(updated.status(), 200);
+    assert!(std::fs::read_to_string(data_dir.join("reminders.json")).unwrap().contains("recycling"));
+    assert_eq!(client.delete(&weekly_url).send().await.unwrap().status(), 204);
+    assert_eq!(client.get(&weekly_url).send().await.unwrap().status(), 404);
+
+    // The scheduler ticks every few seconds.
+    for _ in 0..80 {
+        if !driver.jobs().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let ticket = String::from_utf8_lossy(&driver.jobs()[0]).into_owned();
+    assert!(ticket.contains("REMINDER") && ticket.contains("water the plants"));
+    let left: Vec<serde_json::Value> = client.get(&reminders).send().await.unwrap().json().await.unwrap();
+    assert!(left.is_empty());
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn tickets_count_up_until_reset() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-tickets-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap()), ("TICKET_HEADER", "BAKE SALE")]).await;
+    let client = reqwest::Client::new();
+    let ticket = format!("{}/ticket", url);
+
+    for expected in [1, 2] {
+        let body: serde_json::Value = client.post(&ticket).send().await.unwrap().json().await.unwrap();
+        assert_eq!(body["number"], expected);
+    }
+    assert_eq!(client.post(format!("{}/reset", ticket)).send().await.unwrap().status(), 204);
+    let body: serde_json::Value = client.post(&ticket).send().await.unwrap().json().await.unwrap();
+    assert_eq!(body["number"], 1);
+
+    let jobs = driver.wait_for_jobs(3).await;
+    assert!(jobs[1].contains("BAKE SALE\n"));
+    // GS ! 0x33: 4x width and height around the number.
+    assert!(jobs[1].contains("\x1d!\x33002\n"));
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn notes_print_together_as_one_digest() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-notes-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let client = reqwest::Client::new();
+    let notes = format!("{}/notes", url);
+
+    assert_eq!(client.post(&notes).json(&serde_json::json!({"text": "  "})).send().await.unwrap().status(), 400);
+    for text in ["call the plumber", "buy stamps"] {
+        let created = client.post(&notes).json(&serde_json::json!({"text": text})).send().await.unwrap();
+        assert_eq!(created.status(), 201);
+    }
+    assert!(std::fs::read_to_string(data_dir.join("notes.json")).unwrap().contains("buy stamps"));
+
+    let print = || async { client.post(format!("{}/print", notes)).send().await.unwrap().json::<serde_json::Value>().await.unwrap() };
+    assert_eq!(print().await["printed"], 2);
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("NOTES"));
+    assert!(jobs[0].find("call the plumber").unwrap() < jobs[0].find("buy stamps").unwrap());
+
+    assert_eq!(print().await["printed"], 0);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(driver.jobs().len(), 1);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn pomodoro_prints_a_session_ticket_and_can_remind_of_the_break() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-pomodoro-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let client = reqwest::Client::new();
+    let pomodoro = format!("{}/pomodoro", url);
+
+    assert_eq!(client.post(&pomodoro).json(&serde_json::json!({"task": ""})).send().await.unwrap().status(), 400);
+    let session: serde_json::Value = client
+        .post(&pomodoro)
+        .json(&serde_json::json!({"task": "Write report", "intervals": 3, "break": true}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("Write report") && jobs[0].contains("3 x 25 min"));
+    assert!(jobs[0].contains("[ ] [ ] [ ]\n") && !jobs[0].contains("[ ] [ ] [ ] [ ]"));
+
+    let reminder: serde_json::Value = client.get(format!("{}/reminders/{}", url, session["break_reminder"])).send().await.unwrap().json().await.unwrap();
+    assert_eq!(reminder["message"], "Break time - Write report");
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn debug_render_returns_annotated_bytes_without_printing() {
+    let (url, driver) = spawn_server(&[("FOOTER_TEMPLATE", "job #{job_id}")]).await;
+    let client = reqwest::Client::new();
+    let response = client.post(format!("{}/debug/render?format=ansi", url)).body("\x1b[1mBold\x1b[0m text").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let dump = response.text().await.unwrap();
+    assert!(dump.starts_with("0000  1b 40"), "{}", dump);
+    assert!(dump.contains("ESC @ — initialize"));
+    assert!(dump.contains("ESC E 1 — bold on") && dump.contains("ESC E 0 — bold off"));
+    assert!(dump.contains("42 6f 6c 64") && dump.contains("\"Bold\""));
+    assert!(dump.contains("\" text\" LF"));
+    assert!(dump.contains("\"job #0\" LF"));
+    assert!(dump.contains("GS V 65 0 — feed 0 dots, then full cut"));
+
+    let copies = client.post(format!("{}/debug/render?copies=2&cut=false", url)).body("x").send().await.unwrap().text().await.unwrap();
+    assert!(copies.contains("# job 1 of 2") && copies.contains("# job 2 of 2"));
+    assert!(!copies.contains("cut"));
+
+    let (url, _) = spawn_server(&[("PRINTER_PROFILE", "star-tsp100")]).await;
+    let dump = client.post(format!("{}/debug/render", url)).body("Star").send().await.unwrap().text().await.unwrap();
+    assert!(dump.contains("ESC GS t 1 — code page 1") && dump.contains("\"Star\" LF") && dump.contains("ESC d 2 — full cut"));
+
+    // A 2D code command whose length is shorter than its own header.
+    let (url, _) = spawn_server(&[]).await;
+    let response = client.post(format!("{}/debug/render?raw=true", url)).body("\x1d(k\x00\x001P").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.text().await.unwrap().contains("GS ( k — QR code data \"\""));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(driver.jobs().is_empty());
+}
+
+#[tokio::test]
+async fn multipart_upload_renders_each_file_by_type() {
+    let (url, driver) = spawn_server(&[]).await;
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(16, 2, image::Luma([0]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let form = reqwest::multipart::Form::new()
+        .text("copies", "2")
+        .text("cut", "false")
+        .part("notes", reqwest::multipart::Part::text("# Shopping\n\n- eggs\n- **milk**\n").file_name("list.md"))
+        .part("logo", reqwest::multipart::Part::bytes(png).file_name("logo.png").mime_str("image/png").unwrap());
+    let response = reqwest::Client::new().post(&url).multipart(form).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(2).await;
+    assert_eq!(jobs[0], jobs[1]);
+    assert!(jobs[0].contains("SHOPPING\n") && jobs[0].contains("- eggs\n") && jobs[0].contains("- milk\n"));
+    // GS v 0, 2 bytes wide, 2 rows, all black.
+    assert!(jobs[0].contains("\x1dv0\0\x02\0\x02\0\u{fffd}\u{fffd}\u{fffd}\u{fffd}"));
+    assert!(!jobs[0].contains("\x1dVA"));
+}
+
+#[tokio::test]
+async fn markdown_task_lists_print_as_nested_checkboxes() {
+    let (url, driver) = spawn_server(&[]).await;
+    let note = "## Today\n\n- [ ] Groceries\n\t- [x] eggs\n\t- [ ] milk\n- [X] Call Sam\n- plain\n";
+    let form = reqwest::multipart::Form::new().part("note", reqwest::multipart::Part::text(note).file_name("2026-10-16.md"));
+    let response = reqwest::Client::new().post(&url).multipart(form).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    for line in ["[ ] Groceries\n", "  [X] eggs\n", "  [ ] milk\n", "[X] Call Sam\n", "- plain\n"] {
+        assert!(jobs[0].contains(&format!("\x1ba\0{}", line)), "missing {:?}", line);
+    }
+}
+
+#[tokio::test]
+async fn markdown_tables_get_box_drawing_borders() {
+    let (url, driver) = spawn_server(&[]).await;
+    let note = "| Item | Qty |\n|:-----|----:|\n| **Coffee** | 2 |\n| Pipe \\| fitting |\n\nAfter.\n";
+    let form = reqwest::multipart::Form::new().part("note", reqwest::multipart::Part::text(note).file_name("order.md"));
+    let response = reqwest::Client::new().post(&url).multipart(form).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    // ┌ and ├ in PC437.
+    assert!(driver.jobs()[0].contains(&0xDA) && driver.jobs()[0].contains(&0xC3));
+    assert!(jobs[0].contains(" Coffee ") && jobs[0].contains(" Pipe | fitting "));
+    assert!(!jobs[0].contains("---") && !jobs[0].contains("**"));
+    assert!(jobs[0].contains("After."));
+}
+
+#[tokio::test]
+async fn multipart_upload_rejects_unknown_types() {
+    let (url, _driver) = spawn_server(&[]).await;
+    let form = reqwest::multipart::Form::new()
+        .part("file", reqwest::multipart::Part::bytes(vec![0, 1, 2]).file_name("a.zip").mime_str("application/zip").unwrap());
+    let response = reqwest::Client::new().post(&url).multipart(form).send().await.unwrap();
+    assert_eq!(response.status(), 415);
+}
+
+#[tokio::test]
+async fn paginated_text_gets_headers_and_partial_cuts() {
+    let (url, driver) = spawn_server(&[]).await;
+    let body = (1..=5).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+    let response = reqwest::Client::new()
+        .post(format!("{}/?page_lines=2&title=Minutes&page_cut=true", url))
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let job = &driver.wait_for_jobs(1).await[0];
+    assert!(job.contains("Minutes") && job.contains("page 1/3") && job.contains("page 3/3"));
+    assert!(job.find("line 2\n") < job.find("page 2/3"));
+    // GS V A 1: a partial cut between consecutive pages.
+    assert_eq!(job.matches("\x1dVA\x01").count(), 2);
+}
+
+#[tokio::test]
+async fn json_bodies_are_pretty_printed_and_wrapped() {
+    let (url, driver) = spawn_server(&[]).await;
+    let body = r#"{"event":"push","commits":[{"id":42,"message":"Fix the thing that broke when the other thing was fixed"}],"ok":true,"tags":[]}"#;
+    let response = reqwest::Client::new()
+        .post(&url)
+        .header("content-type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let job = &driver.wait_for_jobs(1).await[0];
+    let expected = [
+        "{",
+        "  \"event\": \"push\",",
+        "  \"commits\": [",
+        "    {",
+        "      \"id\": 42,",
+        "      \"message\": \"Fix the thing that broke when ",
+        "        the other thing was fixed\"",
+        "    }",
+        "  ],",
+        "  \"ok\": true,",
+        "  \"tags\": []",
+        "}",
+    ];
+    assert!(job.contains(&expected.join("\n")), "{:?}", job);
+
+    let invalid = reqwest::Client::new().post(format!("{}/?format=json", url)).body("{nope").send().await.unwrap();
+    assert_eq!(invalid.status(), 422);
+}
+
+#[tokio::test]
+async fn wifi_card_escapes_the_qr_payload() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    let missing = client.post(format!("{}/wifi", url)).json(&serde_json::json!({"ssid": "Guest"})).send().await.unwrap();
+    assert_eq!(missing.status(), 400);
+
+    let response = client
+        .post(format!("{}/wifi", url))
+        .json(&serde_json::json!({"ssid": "Guest;Net", "password": "p:ss", "security": "wpa2"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let job = &driver.wait_for_jobs(1).await[0];
+    assert!(job.contains(r"WIFI:T:WPA;S:Guest\;Net;P:p\:ss;;"));
+    assert!(job.contains("Guest;Net\n") && !job.contains("p:ss\n"));
+}
+
+#[tokio::test]
+async fn contact_card_encodes_a_vcard() {
+    let (url, driver) = spawn_server(&[]).await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/contact", url))
+        .json(&serde_json::json!({"name": "Ada Lovelace", "email": "ada@example.com", "phone": "+44 20 7946 0000"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let job = &driver.wait_for_jobs(1).await[0];
+    assert!(job.contains("\x1d!\x11Ada Lovelace\n"));
+    assert!(job.contains("N:Lovelace;Ada;;;\r\nFN:Ada Lovelace\r\nTEL;TYPE=CELL:+44 20 7946 0000\r\nEMAIL:ada@example.com\r\nEND:VCARD"));
+}
+
+#[tokio::test]
+async fn invoice_totals_line_items_with_tax() {
+    let (url, driver) = spawn_server(&[]).await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/invoice", url))
+        .json(&serde_json::json!({
+            "seller": {"name": "Corner Cafe", "address": ["1 Main St"]},
+            "number": "0042",
+            "currency": "EUR",
+            "items": [
+                {"description": "Flat white", "quantity": 2, "unit_price": 3.4},
+                {"description": "Almond croissant", "unit_price": 2.95}
+            ],
+            "tax_rate": 7,
+            "footer": "Thank you!"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let job = &driver.wait_for_jobs(1).await[0];
+    assert!(job.contains("Flat white                 2      3.40      6.80\n"));
+    assert!(job.contains("Almond croissant           1      2.95      2.95\n"));
+    assert!(job.contains("Subtotal                                9.75 EUR\n"));
+    assert!(job.contains("Tax 7%                                  0.68 EUR\n"));
+    assert!(job.contains("TOTAL                                  10.43 EUR\n"));
+
+    for item in [
+        serde_json::json!({"description": "Huge", "quantity": 1e308, "unit_price": 1e308}),
+        serde_json::json!({"description": "Billions", "quantity": 1e5, "unit_price": 1e5}),
+        serde_json::json!({"description": "Refund", "quantity": -1, "unit_price": 3}),
+    ] {
+        let invoice = serde_json::json!({"seller": {"name": "Corner Cafe"}, "items": [item]});
+        let response = reqwest::Client::new().post(format!("{}/invoice", url)).json(&invoice).send().await.unwrap();
+        assert_eq!(response.status(), 422);
+    }
+    let invoice = serde_json::json!({"seller": {"name": "Corner Cafe"}, "items": [{"description": "Tea", "unit_price": 2}], "tax_rate": 1e300});
+    let response = reqwest::Client::new().post(format!("{}/invoice", url)).json(&invoice).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+}
+
+#[tokio::test]
+async fn invoices_are_laid_out_for_narrow_paper() {
+    let (url, driver) = spawn_server(&[("PRINTER_PROFILE", "generic-58mm")]).await;
+    let response = reqwest::Client::new()
+        .post(format!("{}/invoice", url))
+        .json(&serde_json::json!({
+            "seller": {"name": "Corner Cafe"},
+            "currency": "EUR",
+            "items": [{"description": "Flat white", "quantity": 2, "unit_price": 3.4}],
+            "footer": "Thank you for visiting, see you again soon!"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let job = &driver.wait_for_jobs(1).await[0];
+    assert!(job.contains("Flat white     2    3.40    6.80\n"));
+    assert!(job.contains("TOTAL                   6.80 EUR\n"));
+    assert!(job.contains(&format!("{}\n", "-".repeat(32))));
+    assert!(!job.contains(&"-".repeat(33)));
+    assert!(job.contains("Thank you for visiting, see you\n"));
+    assert!(job.contains("again soon!\n"));
+}
+
+#[tokio::test]
+async fn kitchen_orders_number_themselves_and_beep() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-orders-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let order = serde_json::json!({
+        "table": "7",
+        "items": [{"name": "Burger", "quantity": 2, "modifiers": ["no onions"]}],
+        "beep": true
+    });
+    let body: serde_json::Value = reqwest::Client::new()
+        .post(format!("{}/order", url))
+        .json(&order)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["number"], 1);
+
+    let job = &driver.wait_for_jobs(1).await[0];
+    // GS ! 0x01: double height for the items.
+    assert!(job.contains("\x1d!\x012 x Burger\n"));
+    assert!(job.contains("    - no onions\n"));
+    assert!(job.contains("\x1bB\x03\x02"));
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn batch_requests_print_each_document_separately() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    let response = client.post(format!("{}/?batch=true", url)).body("first\x0csecond\n\x0c\n").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client
+        .post(format!("{}/?batch=true", url))
+        .json(&serde_json::json!(["third", {"n": 4}]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(4).await;
+    assert!(jobs[0].contains("first\n") && !jobs[0].contains("second"));
+    assert!(jobs[1].contains("second\n"));
+    assert!(jobs[2].contains("third\n"));
+    assert!(jobs[3].contains("  \"n\": 4\n"));
+}
+
+#[tokio::test]
+async fn templates_wrap_jobs_unless_plain() {
+    let (url, driver) = spawn_server(&[
+        ("HEADER_TEMPLATE", "OFFICE PRINTER\\n{date}"),
+        ("FOOTER_TEMPLATE", "job {job_id} ({job})"),
+    ])
+    .await;
+    let client = reqwest::Client::new();
+    client.post(&url).body("decorated").send().await.unwrap();
+    let jobs = driver.wait_for_jobs(1).await;
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    assert!(jobs[0].find("OFFICE PRINTER\n") < jobs[0].find(&today));
+    assert!(jobs[0].find(&today) < jobs[0].find("decorated\n"));
+    assert!(jobs[0].contains("job 1 (print)\n"));
+
+    client.post(format!("{}/?plain=true", url)).body("bare").send().await.unwrap();
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[1].contains("bare\n") && !jobs[1].contains("OFFICE PRINTER"));
+}
+
+#[tokio::test]
+async fn banner_picks_the_largest_scale_that_fits() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    client.post(format!("{}/banner?border=true", url)).body("CLOSED").send().await.unwrap();
+    client.post(format!("{}/banner", url)).body("BACK IN FIFTEEN MINUTES").send().await.unwrap();
+
+    let jobs = driver.wait_for_jobs(2).await;
+    // GS ! 0x77: 8x width and height.
+    assert!(jobs[0].contains("\x1d!\x77CLOSED\n") && jobs[0].contains(&"#".repeat(48)));
+    // "FIFTEEN" and "MINUTES" need 7 columns, so 6x (8 columns per line).
+    assert!(jobs[1].contains("\x1d!\x55BACK IN\n") && jobs[1].contains("\x1d!\x55FIFTEEN\n"));
+}
+
+#[tokio::test]
+async fn figlet_format_renders_ascii_art_within_the_paper_width() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    client.post(format!("{}/?format=figlet&font=block", url)).body("Hi").send().await.unwrap();
+    client.post(format!("{}/?format=figlet", url)).body("Happy birthday").send().await.unwrap();
+
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[0].contains("#...# ###\n#...#  #\n#####  #\n#...#  #\n#...# ###\n".replace('.', " ").as_str()));
+    let art: Vec<&str> = jobs[1].lines().filter(|line| line.contains('_') || line.contains('|')).collect();
+    assert!(art.len() > 6, "expected two rows of lettering, got {:?}", art);
+    assert!(art.iter().all(|line| line.chars().count() <= 48));
+}
+
+#[tokio::test]
+async fn image_uploads_can_be_dithered() {
+    let (url, driver) = spawn_server(&[]).await;
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(8, 8, image::Luma([170]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let client = reqwest::Client::new();
+    for dither in ["threshold", "floyd-steinberg"] {
+        let form = reqwest::multipart::Form::new()
+            .text("dither", dither)
+            .part("photo", reqwest::multipart::Part::bytes(png.clone()).file_name("grey.png"));
+        assert_eq!(client.post(&url).multipart(form).send().await.unwrap().status(), 200);
+    }
+
+    driver.wait_for_jobs(2).await;
+    let rows = |job: &[u8]| -> Vec<u8> {
+        let start = job.windows(3).position(|w| w == b"\x1dv0").expect("raster image") + 8;
+        job[start..start + 8].to_vec()
+    };
+    let jobs = driver.jobs();
+    // Light grey is white under a plain threshold, but dithering scatters some black dots.
+    assert!(rows(&jobs[0]).iter().all(|&row| row == 0));
+    assert!(rows(&jobs[1]).iter().any(|&row| row != 0));
+}
+
+#[tokio::test]
+async fn image_uploads_can_be_rotated_scaled_and_aligned() {
+    let (url, driver) = spawn_server(&[]).await;
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(16, 4, image::Luma([0]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let client = reqwest::Client::new();
+    let upload = |fields: &[(&'static str, &'static str)]| {
+        let mut form = reqwest::multipart::Form::new();
+        for (name, value) in fields {
+            form = form.text(*name, *value);
+        }
+        client.post(&url).multipart(form.part("logo", reqwest::multipart::Part::bytes(png.clone()).file_name("logo.png"))).send()
+    };
+    assert_eq!(upload(&[("rotate", "90"), ("width", "8"), ("align", "left")]).await.unwrap().status(), 200);
+    assert_eq!(upload(&[("width", "8"), ("height", "8"), ("fit", "cover")]).await.unwrap().status(), 200);
+    assert_eq!(upload(&[("rotate", "45")]).await.unwrap().status(), 400);
+
+    let jobs = driver.wait_for_jobs(2).await;
+    // ESC a 0 (left), then GS v 0 with 1 byte per row: 4x16 rotated, doubled to 8x32.
+    assert!(jobs[0].contains("\x1ba\x00\x1dv0\x00\x01\x00\x20\x00"));
+    // Cover crops 16x4 down to fill the 8x8 box.
+    assert!(jobs[1].contains("\x1dv0\x00\x01\x00\x08\x00"));
+}
+
+#[tokio::test]
+async fn stored_logos_print_at_the_top_of_jobs() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-assets-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(16, 2, image::Luma([0]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let client = reqwest::Client::new();
+    let logo = format!("{}/assets/shop", url);
+
+    assert_eq!(client.put(&logo).body(png).send().await.unwrap().status(), 201);
+    assert_eq!(client.put(format!("{}/assets/shop.png", url)).body("x").send().await.unwrap().status(), 400);
+    let list: serde_json::Value = client.get(format!("{}/assets", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list, serde_json::json!([{"name": "shop", "width": 16, "height": 2}]));
+
+    assert_eq!(client.post(format!("{}?logo=shop", url)).body("Open today").send().await.unwrap().status(), 200);
+    assert_eq!(client.post(format!("{}?logo=nope", url)).body("Open today").send().await.unwrap().status(), 422);
+    let jobs = driver.wait_for_jobs(1).await;
+    let image = jobs[0].find("\x1dv0\x00\x02\x00\x02\x00").expect("logo raster");
+    assert!(image < jobs[0].find("Open today").unwrap());
+
+    assert_eq!(client.delete(&logo).send().await.unwrap().status(), 204);
+    assert_eq!(client.delete(&logo).send().await.unwrap().status(), 404);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn logos_in_nv_memory_print_by_key() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-nv-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let mut png = Vec::new();
+    image::GrayImage::from_pixel(16, 2, image::Luma([0]))
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let client = reqwest::Client::new();
+    let logo = format!("{}/assets/shop", url);
+
+    assert_eq!(client.put(format!("{}/nv", logo)).send().await.unwrap().status(), 404);
+    assert_eq!(client.put(&logo).body(png.clone()).send().await.unwrap().status(), 201);
+    let stored: serde_json::Value = client.put(format!("{}/nv", logo)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stored["nv_key"], "A0");
+    assert_eq!(client.post(format!("{}?logo=shop", url)).body("Open today").send().await.unwrap().status(), 200);
+    // Replacing the logo deletes the printer's stale copy.
+    assert_eq!(client.put(&logo).body(png).send().await.unwrap().status(), 201);
+
+    let jobs = driver.wait_for_jobs(3).await;
+    // GS 8 L with 11 + 4 bytes: raster, key A0, one colour, 16x2 dots.
+    assert!(jobs[0].contains("\x1d8L\x0f\x00\x00\x000C0A0\x01\x10\x00\x02\x001"));
+    assert!(jobs[1].contains("\x1d(L\x06\x000EA0\x01\x01"));
+    assert!(!jobs[1].contains("\x1dv0"));
+    assert!(jobs[2].contains("\x1d(L\x04\x000BA0"));
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn text_switches_code_pages_as_needed() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    let body = "Grüße – 5 €\nZażółć\nПривет 🙂";
+    assert_eq!(client.post(format!("{}?plain=true", url)).body(body).send().await.unwrap().status(), 200);
+
+    driver.wait_for_jobs(1).await;
+    let job = &driver.jobs()[0];
+    let contains = |needle: &[u8]| job.windows(needle.len()).any(|w| w == needle);
+    // PC437 ü and ß, a dash for the en dash, then PC858 for the euro sign.
+    assert!(contains(b"Gr\x1bt\x00\x81\xe1e - 5 \x1bt\x13\xd5"));
+    // Z and a stay ASCII; ż needs PC852 and ó is there too.
+    assert!(contains(b"Za\x1bt\x12\xbe\xa2"));
+    assert!(contains(b"\x1bt\x11\x8f"));
+    assert!(contains(b" ?\n"));
+}
+
+#[tokio::test]
+async fn paper_usage_counts_down_the_roll() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-paper-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap()), ("PAPER_ROLL_METERS", "0.1")]).await;
+    let client = reqwest::Client::new();
+    assert_eq!(client.post(&url).body("one\ntwo\nthree").send().await.unwrap().status(), 200);
+    driver.wait_for_jobs(1).await;
+
+    let mut printed = serde_json::Value::Null;
+    for _ in 0..20 {
+        printed = client.get(format!("{}/printer/status", url)).send().await.unwrap().json().await.unwrap();
+        if printed["paper"]["jobs"] == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    // Three 1/6" lines plus the feed to the cutter, out of a 100mm roll.
+    assert_eq!(printed["paper"]["used_mm"], 28.0);
+    assert_eq!(printed["paper"]["remaining_mm"], 72.0);
+    assert_eq!(printed["paper"]["low"], false);
+    assert_eq!(printed["queue"]["queued"], 0);
+
+    let reset: serde_json::Value = client.post(format!("{}/printer/roll-reset", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(reset["used_mm"], 0.0);
+    assert!(reset["since"].is_string());
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn audit_log_records_prints_and_refusals_in_a_hash_chain() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-audit-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("DATA_DIR", data_dir.to_str().unwrap()), ("API_KEYS", "kiosk:secret")]).await;
+    let client = reqwest::Client::new();
+
+    let refused = client.post(&url).bearer_auth("wrong").body("Sneaky\nnote").send().await.unwrap();
+    assert_eq!(refused.status(), 401);
+    let printed = client.post(&url).bearer_auth("secret").body("\nShopping list\nmilk").send().await.unwrap();
+    assert_eq!(printed.status(), 200);
+    client.get(format!("{}/jobs", url)).bearer_auth("secret").send().await.unwrap();
+    driver.wait_for_jobs(1).await;
+
+    let audit = |query: &str| client.get(format!("{}/audit{}", url, query)).bearer_auth("secret").send();
+    let entries: serde_json::Value = audit("").await.unwrap().json().await.unwrap();
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 2, "only printing requests and refusals: {:?}", entries);
+    assert_eq!(entries[0]["who"], "key:kiosk");
+    assert_eq!(entries[0]["first_line"], "Shopping list");
+    assert_eq!(entries[0]["bytes"], 19);
+    assert_eq!(entries[1]["status"], 401);
+    assert!(entries[1]["who"].as_str().unwrap().starts_with("http"));
+    let kiosk: serde_json::Value = audit("?key=kiosk").await.unwrap().json().await.unwrap();
+    assert_eq!(kiosk.as_array().unwrap().len(), 1);
+
+    let verify = || client.get(format!("{}/audit/verify", url)).bearer_auth("secret").send();
+    let result: serde_json::Value = verify().await.unwrap().json().await.unwrap();
+    assert_eq!(result["valid"], true);
+    let log = data_dir.join("audit.jsonl");
+    let tampered = std::fs::read_to_string(&log).unwrap().replace("Sneaky", "Benign");
+    std::fs::write(&log, tampered).unwrap();
+    let result: serde_json::Value = verify().await.unwrap().json().await.unwrap();
+    assert_eq!(result["valid"], false);
+    assert_eq!(result["broken_at"], 1);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn completion_callbacks_are_signed() {
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = received.clone();
+    let receiver = serve(Router::new().route(
+        "/done",
+        axum::routing::post(move |headers: axum::http::HeaderMap, body: String| async move {
+            let signature = headers["x-print-jobber-signature"].to_str().unwrap().to_owned();
+            sink.lock().unwrap().push((signature, body));
+        }),
+    ))
+    .await;
+    let (url, driver) = spawn_server(&[("CALLBACK_SECRET", "s3cret")]).await;
+    let client = reqwest::Client::new();
+
+    let bad = client.post(format!("{}?callback_url=not-a-url", url)).body("hi").send().await.unwrap();
+    assert_eq!(bad.status(), 400);
+    let response = client.post(format!("{}?callback_url={}/done", url, receiver)).body("hi").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    driver.wait_for_jobs(1).await;
+
+    for _ in 0..40 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let (signature, body) = received.lock().unwrap().first().cloned().expect("callback");
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["status"], "printed");
+    assert!(body["id"].is_u64());
+    assert!(signature.starts_with("sha256=") && signature.len() == 7 + 64);
+}
+
+#[tokio::test]
+async fn low_paper_pushes_an_alert() {
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = received.clone();
+    let ntfy = serve(Router::new().route(
+        "/printer",
+        axum::routing::post(move |headers: axum::http::HeaderMap, body: String| async move {
+            sink.lock().unwrap().push((headers["title"].to_str().unwrap().to_owned(), body));
+        }),
+    ))
+    .await;
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-alerts-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[
+        ("DATA_DIR", data_dir.to_str().unwrap()),
+        ("PAPER_ROLL_METERS", "0.03"),
+        ("NTFY_URL", &format!("{}/printer", ntfy)),
+    ])
+    .await;
+    let client = reqwest::Client::new();
+    for _ in 0..2 {
+        assert_eq!(client.post(&url).body("one\ntwo\nthree").send().await.unwrap().status(), 200);
+    }
+    driver.wait_for_jobs(2).await;
+
+    for _ in 0..40 {
+        if !received.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let alerts = received.lock().unwrap().clone();
+    // 28mm of a 30mm roll: below 10% after the first job, and only alerted once.
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].0, "Paper running low");
+    assert!(alerts[0].1.contains("2mm"));
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn discover_lists_usb_printer_candidates() {
+    let (url, _driver) = spawn_server(&[]).await;
+    let response = reqwest::get(format!("{}/printer/discover", url)).await.unwrap();
+    // Without USB access (e.g. in a container) enumeration itself fails.
+    if response.status() == 503 {
+        return;
+    }
+    assert_eq!(response.status(), 200);
+    let candidates: serde_json::Value = response.json().await.unwrap();
+    for candidate in candidates.as_array().unwrap() {
+        let id = candidate["id"].as_str().unwrap();
+        assert_eq!(id.len(), 9);
+        assert_eq!(&id[4..5], ":");
+        assert!(candidate["vendor"].is_string() || candidate["printer_class"] == true);
+    }
+}
+
+#[tokio::test]
+async fn printer_profile_sets_width_cutter_and_code_pages() {
+    let (url, driver) = spawn_server(&[
+        ("PRINTER_PROFILES", "kiosk:base=generic-58mm:cutter=none:code_pages=PC437"),
+        ("PRINTER_PROFILE", "kiosk"),
+    ])
+    .await;
+    let body = "The quick brown fox jumps over the lazy dog and keeps running far away for 5€";
+    let response = reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("The quick brown fox jumps over\nthe lazy dog and keeps running\nfar away for 5?\n"));
+    let bytes = &driver.jobs()[0];
+    // No GS V cut, and no ESC t switch away from PC437 (table 0).
+    assert!(!bytes.windows(2).any(|w| w == [0x1d, b'V']));
+    assert!(!bytes.windows(3).any(|w| w[..2] == [0x1b, b't'] && w[2] != 0));
+}
+
+/// Polls `/diagnostics` for up to ten seconds until `done` accepts the report.
+async fn wait_for_report(url: &str, done: impl Fn(&serde_json::Value) -> bool) -> serde_json::Value {
+    for _ in 0..100 {
+        let report: serde_json::Value = reqwest::get(format!("{}/diagnostics", url)).await.unwrap().json().await.unwrap();
+        if done(&report) {
+            return report;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("diagnostics never reached the expected state");
+}
+
+#[tokio::test]
+async fn keepalive_reconnects_a_printer_that_stops_responding() {
+    let driver = MockDriver::new();
+    let (url, _) = spawn_server_on(driver.clone(), &[("PRINTER_KEEPALIVE_SECONDS", "1")], &[]).await;
+    driver.set_failing(true);
+    let report = wait_for_report(&url, |report| report["reconnects"].as_u64() >= Some(1)).await;
+    assert_eq!(report["printer_responding"], false);
+
+    driver.set_failing(false);
+    let report = wait_for_report(&url, |report| report["printer_responding"] == true).await;
+    assert!(report["reconnects"].as_u64() >= Some(1));
+}
+
+#[tokio::test]
+async fn keepalive_leaves_the_printer_alone_during_quiet_hours() {
+    let now = chrono::Local::now();
+    let quiet = format!(
+        "{}-{}",
+        (now - chrono::TimeDelta::hours(1)).format("%H:%M"),
+        (now + chrono::TimeDelta::hours(1)).format("%H:%M")
+    );
+    let driver = MockDriver::new();
+    let (url, _) = spawn_server_on(driver.clone(), &[("PRINTER_KEEPALIVE_SECONDS", "1"), ("QUIET_HOURS", quiet.as_str())], &[]).await;
+    driver.set_failing(true);
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+
+    let report: serde_json::Value = reqwest::get(format!("{}/diagnostics", url)).await.unwrap().json().await.unwrap();
+    assert!(report["printer_responding"].is_null());
+    assert_eq!(report["reconnects"], 0);
+}
+
+#[tokio::test]
+async fn autodetect_uses_the_width_of_the_model_the_printer_reports() {
+    let driver = MockDriver::answering(b"_POS-58\0");
+    let (url, _) = spawn_server_on(driver.clone(), &[("PRINTER_AUTODETECT", "1")], &[]).await;
+    // The GS I 67 model name query goes out on its own before any job.
+    assert_eq!(driver.jobs()[0], [0x1d, b'I', 0x43]);
+
+    let body = "The quick brown fox jumps over the lazy dog and keeps running far away";
+    let response = reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[1].contains("The quick brown fox jumps over\nthe lazy dog and keeps running\n"));
+
+    let report: serde_json::Value = reqwest::get(format!("{}/diagnostics", url)).await.unwrap().json().await.unwrap();
+    assert_eq!(report["printer_model"], "POS-58");
+
+    // A printer that doesn't answer keeps the configured 80mm profile.
+    let (url, driver) = spawn_server(&[("PRINTER_AUTODETECT", "1")]).await;
+    reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[1].contains("The quick brown fox jumps over the lazy dog and\nkeeps running far away"));
+}
+
+#[tokio::test]
+async fn font_b_and_line_spacing_fit_more_on_a_receipt() {
+    let (url, driver) = spawn_server(&[("PRINTER_PROFILE", "generic-58mm"), ("PRINTER_FONT", "b")]).await;
+    let body = "The quick brown fox jumps over the lazy dog and keeps running far away";
+    reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    let jobs = driver.wait_for_jobs(1).await;
+    // Font B fits 42 columns on 58mm paper instead of 32.
+    assert!(jobs[0].contains("The quick brown fox jumps over the lazy\ndog and keeps running far away"));
+    let bytes = &driver.jobs()[0];
+    assert!(bytes.windows(3).any(|w| w == [0x1b, b'M', 1]));
+    // Back to font A before the cut.
+    assert!(bytes.windows(3).any(|w| w == [0x1b, b'M', 0]));
+
+    // Per job: font A and tighter lines.
+    let response = reqwest::Client::new()
+        .post(format!("{}/?printer_font=a&line_spacing=24", url))
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[1].contains("The quick brown fox jumps over\nthe lazy dog and keeps running\n"));
+    let bytes = &driver.jobs()[1];
+    assert!(!bytes.windows(3).any(|w| w == [0x1b, b'M', 1]));
+    assert!(bytes.windows(3).any(|w| w == [0x1b, b'3', 24]));
+    assert!(bytes.windows(2).any(|w| w == [0x1b, b'2']));
+}
+
+#[tokio::test]
+async fn kanji_profiles_print_cjk_text_at_double_width() {
+    let (url, driver) = spawn_server(&[
+        ("PRINTER_PROFILES", "jp:base=generic-58mm:kanji=shift_jis"),
+        ("PRINTER_PROFILE", "jp"),
+    ])
+    .await;
+    let body = "Café 東京 Tokyo 日本語のテキストはとても長いので折り返す必要があります";
+    let response = reqwest::Client::new().post(&url).body(body).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    driver.wait_for_jobs(1).await;
+
+    let bytes = &driver.jobs()[0];
+    // FS C 1 selects Shift JIS; é still comes from the code table.
+    assert!(bytes.windows(3).any(|w| w == [0x1c, b'C', 1]));
+    assert!(bytes.windows(2).any(|w| w == b"\x82 "));
+    // FS &, 東 in Shift JIS, ... FS . before the ASCII that follows.
+    assert!(bytes.windows(4).any(|w| w == [0x1c, b'&', 0x93, 0x8c]));
+    assert!(bytes.windows(3).any(|w| w == [0x1c, b'.', b' ']));
+
+    // 16 ideographs fill the 32 columns of 58mm paper.
+    let content = reqwest::get(format!("{}/jobs/1/content", url)).await.unwrap().text().await.unwrap();
+    assert!(content.contains("Café 東京 Tokyo\n日本語のテキストはとても長いので\n折り返す必要があります\n"));
+}
+
+#[tokio::test]
+async fn render_raster_draws_text_the_code_pages_lack() {
+    let (url, driver) = spawn_server(&[("PRINTER_PROFILE", "generic-80mm")]).await;
+    let client = reqwest::Client::new();
+    let response = client.post(format!("{}/?render=auto", url)).body("Hello\nПривет, мир").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    driver.wait_for_jobs(1).await;
+    let bytes = &driver.jobs()[0];
+    // The ASCII line stays text; the Cyrillic one, which PC437 doesn't have, is a
+    // 576 x 34 dot image instead of question marks.
+    assert!(bytes.windows(6).any(|w| w == b"Hello\n"));
+    let image = bytes.windows(8).position(|w| w == [0x1d, b'v', b'0', 0, 72, 0, 34, 0]).expect("a raster line");
+    assert!(bytes[image + 8..image + 8 + 72 * 34].iter().any(|&b| b != 0));
+    assert!(!bytes.windows(2).any(|w| w == b"??"));
+    let content = reqwest::get(format!("{}/jobs/1/content", url)).await.unwrap().text().await.unwrap();
+    assert!(content.contains("Hello\nПривет, мир\n"));
+
+    // Every line with render=raster.
+    client.post(format!("{}/?render=raster", url)).body("Hello").send().await.unwrap();
+    driver.wait_for_jobs(2).await;
+    let bytes = &driver.jobs()[1];
+    assert!(!bytes.windows(5).any(|w| w == b"Hello"));
+    assert!(bytes.windows(3).any(|w| w == [0x1d, b'v', b'0']));
+}
+
+#[tokio::test]
+async fn star_profile_sends_star_line_mode_commands() {
+    let (url, driver) = spawn_server(&[("PRINTER_PROFILE", "star-tsp650")]).await;
+    let response = reqwest::Client::new().post(&url).body("Grüße für 5€").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    driver.wait_for_jobs(1).await;
+    let bytes = &driver.jobs()[0];
+    // ESC GS t 4: PC858 for the euro sign, ESC d 2: feed and full cut, and no ESC/POS GS V.
+    assert!(bytes.windows(4).any(|w| w == [0x1b, 0x1d, b't', 4]));
+    assert!(bytes.ends_with(&[0x1b, b'd', 2]));
+    assert!(!bytes.windows(2).any(|w| w == [0x1d, b'V']));
+}
+
+#[tokio::test]
+async fn spool_directory_prints_dropped_files() {
+    let spool = std::env::temp_dir().join(format!("print-jobber-spool-{}", std::process::id()));
+    std::fs::create_dir_all(&spool).unwrap();
+    let (_url, driver) = spawn_server(&[("SPOOL_DIR", spool.to_str().unwrap())]).await;
+    std::fs::write(spool.join("note.txt"), "Saved from an old app").unwrap();
+    std::fs::write(spool.join("report.pdf"), "%PDF-1.4").unwrap();
+
+    // Files are picked up once their size has held still for one poll.
+    for _ in 0..50 {
+        if spool.join("done/note.txt").exists() && spool.join("failed/report.pdf").exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(spool.join("done/note.txt").exists());
+    assert!(spool.join("failed/report.pdf").exists());
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("Saved from an old app"));
+    assert!(!spool.join("note.txt").exists());
+    std::fs::remove_dir_all(spool).unwrap();
+}
+
+#[tokio::test]
+async fn send_subcommand_posts_a_file_to_the_server() {
+    let (url, driver) = spawn_server(&[("API_KEYS", "cli:secret")]).await;
+    let file = std::env::temp_dir().join(format!("print-jobber-send-{}.txt", std::process::id()));
+    std::fs::write(&file, "piped   from\tthe shell").unwrap();
+
+    let args = |key: &str| ["--raw", "--server", &url, "--key", key, file.to_str().unwrap()].map(String::from);
+    assert_eq!(print_jobber::send::run(args("wrong")).await, 1);
+    assert_eq!(print_jobber::send::run(args("secret")).await, 0);
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("piped   from    the shell\n"));
+    assert_eq!(print_jobber::send::run(["--bogus".to_owned()]).await, 2);
+    std::fs::remove_file(file).unwrap();
+}
+
+/// A gRPC client for a server on `port`, once it's listening.
+async fn grpc_client(port: u16) -> tonic::client::Grpc<tonic::transport::Channel> {
+    let endpoint = format!("http://127.0.0.1:{}", port);
+    for _ in 0..50 {
+        match tonic::transport::Channel::from_shared(endpoint.clone()).unwrap().connect().await {
+            Ok(channel) => return tonic::client::Grpc::new(channel),
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+    panic!("gRPC server never came up");
+}
+
+#[tokio::test]
+async fn grpc_submits_jobs_to_the_shared_queue() {
+    use print_jobber::grpc::{GetJobStatusRequest, Job, SubmitJobReply, SubmitJobRequest};
+    use tonic::{codec::ProstCodec, codegen::http::uri::PathAndQuery};
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-grpc-{}", std::process::id()));
+    let (url, driver) = spawn_server(&[("GRPC_PORT", &port.to_string()), ("DATA_DIR", data_dir.to_str().unwrap())]).await;
+    let mut client = grpc_client(port).await;
+
+    client.ready().await.unwrap();
+    let request = SubmitJobRequest {
+        text: "hello over grpc".to_owned(),
+        raw: false,
+        job: String::new(),
+    };
+    let path = PathAndQuery::from_static("/print_jobber.PrintJobber/SubmitJob");
+    let reply: SubmitJobReply = client
+        .unary(tonic::Request::new(request), path, ProstCodec::default())
+        .await
+        .unwrap()
+        .into_inner();
+    let jobs = driver.wait_for_jobs(1).await;
+    assert!(jobs[0].contains("hello over grpc"));
+
+    client.ready().await.unwrap();
+    let path = PathAndQuery::from_static("/print_jobber.PrintJobber/GetJobStatus");
+    let job: Job = client
+        .unary(tonic::Request::new(GetJobStatusRequest { id: reply.id }), path, ProstCodec::default())
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(job.id, reply.id);
+    assert_eq!(job.job, "grpc");
+    assert_eq!(job.source, "grpc");
+
+    client.ready().await.unwrap();
+    let path = PathAndQuery::from_static("/print_jobber.PrintJobber/GetJobStatus");
+    let missing = client
+        .unary::<_, Job, _>(tonic::Request::new(GetJobStatusRequest { id: 9999 }), path, ProstCodec::default())
+        .await
+        .unwrap_err();
+    assert_eq!(missing.code(), tonic::Code::NotFound);
+
+    let audit: serde_json::Value = reqwest::get(format!("{}/audit", url)).await.unwrap().json().await.unwrap();
+    assert_eq!(audit[0]["path"], "/print_jobber.PrintJobber/SubmitJob");
+    assert_eq!(audit[0]["who"], "grpc:127.0.0.1");
+    assert_eq!(audit[0]["first_line"], "hello over grpc");
+    assert_eq!(audit[0]["status"], 200);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn grpc_calls_without_a_key_are_refused_and_audited() {
+    use print_jobber::grpc::{SubmitJobReply, SubmitJobRequest};
+    use tonic::{codec::ProstCodec, codegen::http::uri::PathAndQuery};
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-grpc-keys-{}", std::process::id()));
+    let vars = [("GRPC_PORT", port.to_string()), ("API_KEYS", "cli:secret".to_owned()), ("DATA_DIR", data_dir.display().to_string())];
+    let (url, driver) = spawn_server(&vars.each_ref().map(|(name, value)| (*name, value.as_str()))).await;
+    let mut client = grpc_client(port).await;
+    client.ready().await.unwrap();
+    let request = SubmitJobRequest {
+        text: "sneaking in".to_owned(),
+        raw: false,
+        job: String::new(),
+    };
+    let path = PathAndQuery::from_static("/print_jobber.PrintJobber/SubmitJob");
+    let refused = client
+        .unary::<_, SubmitJobReply, _>(tonic::Request::new(request), path, ProstCodec::default())
+        .await
+        .unwrap_err();
+    assert_eq!(refused.code(), tonic::Code::Unauthenticated);
+
+    let audit: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}/audit", url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(audit.as_array().unwrap().len(), 1);
+    assert_eq!(audit[0]["path"], "/print_jobber.PrintJobber/SubmitJob");
+    assert_eq!(audit[0]["status"], 401);
+    assert_eq!(audit[0]["who"], "grpc:127.0.0.1");
+    assert!(driver.jobs().is_empty());
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn openapi_spec_documents_the_real_parameters_and_needs_no_key() {
+    let (url, _driver) = spawn_server(&[("API_KEYS", "cli:secret")]).await;
+    let client = reqwest::Client::new();
+
+    let response = client.get(format!("{}/openapi.json", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let spec: serde_json::Value = response.json().await.unwrap();
+    let parameters = |path: &str, method: &str| -> Vec<String> {
+        let operation = &spec["paths"][path][method];
+        assert!(operation.is_object(), "{} {} is missing from openapi.json", method, path);
+        operation["parameters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|parameter| parameter["name"].as_str().unwrap().to_owned())
+            .collect()
+    };
+    for name in ["width", "dither", "tab_width", "copies", "schedule_at"] {
+        assert!(parameters("/", "post").contains(&name.to_owned()), "POST / doesn't list {}", name);
+        assert!(parameters("/exec/{name}", "post").contains(&name.to_owned()), "/exec/{{name}} doesn't list {}", name);
+    }
+    let fetch = parameters("/fetch", "post");
+    assert!(["url", "width", "dither"].iter().all(|name| fetch.contains(&name.to_string())), "/fetch lists {:?}", fetch);
+    assert_eq!(parameters("/agent/{printer}/jobs/{job}", "post"), ["printer", "job"]);
+    assert!(spec["paths"]["/readyz"]["get"]["security"].as_array().is_some_and(|security| security[0] == serde_json::json!({})));
+    assert!(spec["paths"]["/jobs"]["get"]["responses"].get("401").is_some());
+    assert!(spec["paths"]["/xkcd"]["get"]["responses"].get("429").is_some());
+
+    // Swagger UI is served from the binary, so it works without internet access.
+    let docs = client.get(format!("{}/docs/", url)).send().await.unwrap();
+    assert_eq!(docs.status(), 200);
+    assert!(!docs.text().await.unwrap().contains("unpkg.com"));
+    let initializer = client.get(format!("{}/docs/swagger-initializer.js", url)).send().await.unwrap();
+    assert!(initializer.text().await.unwrap().contains("/openapi.json"));
+    let css = client.get(format!("{}/docs/swagger-ui.css", url)).send().await.unwrap();
+    assert_eq!(css.status(), 200);
+    let jobs = client.get(format!("{}/jobs", url)).send().await.unwrap();
+    assert_eq!(jobs.status(), 401);
+}
+
+#[tokio::test]
+async fn cors_preflight_is_answered_for_allowed_origins() {
+    let (url, _driver) = spawn_server(&[("API_KEYS", "web:secret"), ("CORS_ORIGINS", "http://dashboard.lan")]).await;
+    let client = reqwest::Client::new();
+    let preflight = |origin: &'static str| {
+        client
+            .request(reqwest::Method::OPTIONS, format!("{}/", url))
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "authorization,content-type")
+            .send()
+    };
+
+    let allowed = preflight("http://dashboard.lan").await.unwrap();
+    assert_eq!(allowed.status(), 200);
+    assert_eq!(allowed.headers()["access-control-allow-origin"], "http://dashboard.lan");
+    assert!(allowed.headers()["access-control-allow-methods"].to_str().unwrap().contains("POST"));
+
+    let other = preflight("http://elsewhere.example").await.unwrap();
+    assert!(other.headers().get("access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn tls_generates_a_self_signed_certificate_and_serves_https() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-tls-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&data_dir);
+    let vars: HashMap<&str, String> = [("TLS", "1".to_owned()), ("DATA_DIR", data_dir.display().to_string())].into();
+    let mut config = Config::from_vars(|name| vars.get(name).cloned());
+    let tls = config.tls.take().unwrap();
+    let state = AppState::new(config, Some(PrinterDriver::Mock(MockDriver::new())));
+
+    let listener = print_jobber::tls::TlsListener::bind("127.0.0.1:0", &tls).await.unwrap();
+    let addr = axum::serve::Listener::local_addr(&listener).unwrap().addr;
+    let app = print_jobber::router(state).into_make_service_with_connect_info::<print_jobber::source::Peer>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    assert!(data_dir.join("tls/cert.pem").exists());
+    assert!(data_dir.join("tls/key.pem").exists());
+
+    let client = reqwest::Client::builder().danger_accept_invalid_certs(true).build().unwrap();
+    let response = client.get(format!("https://localhost:{}/jobs", addr.port())).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(client.get(format!("http://localhost:{}/jobs", addr.port())).send().await.is_err());
+
+    // A second start reuses the saved pair.
+    let cert = std::fs::read(data_dir.join("tls/cert.pem")).unwrap();
+    print_jobber::tls::server_config(&tls).unwrap();
+    assert_eq!(std::fs::read(data_dir.join("tls/cert.pem")).unwrap(), cert);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+/// Makes a fresh `data_dir` with a CA in `ca.pem`, and returns a client certificate it
+/// signed for `kitchen`.
+fn client_certificate(data_dir: &std::path::Path) -> (rcgen::Certificate, rcgen::KeyPair) {
+    use rcgen::{BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+
+    let _ = std::fs::remove_dir_all(data_dir);
+    std::fs::create_dir_all(data_dir).unwrap();
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.distinguished_name.push(DnType::CommonName, "Home CA");
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    std::fs::write(data_dir.join("ca.pem"), ca.pem()).unwrap();
+    let client_key = KeyPair::generate().unwrap();
+    let mut client_params = CertificateParams::new(Vec::new()).unwrap();
+    client_params.distinguished_name.push(DnType::CommonName, "kitchen");
+    client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    (client_params.signed_by(&client_key, &ca, &ca_key).unwrap(), client_key)
+}
+
+#[tokio::test]
+async fn mutual_tls_requires_a_client_certificate_and_audits_its_name() {
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-mtls-{}", std::process::id()));
+    let (client_cert, client_key) = client_certificate(&data_dir);
+
+    let vars: HashMap<&str, String> = [
+        ("DATA_DIR", data_dir.display().to_string()),
+        ("TLS_CLIENT_CA", data_dir.join("ca.pem").display().to_string()),
+    ]
+    .into();
+    let mut config = Config::from_vars(|name| vars.get(name).cloned());
+    let tls = config.tls.take().unwrap();
+    let driver = MockDriver::new();
+    let state = AppState::new(config, Some(PrinterDriver::Mock(driver.clone())));
+    print_jobber::spawn_background(&state);
+    let listener = print_jobber::tls::TlsListener::bind("127.0.0.1:0", &tls).await.unwrap();
+    let port = axum::serve::Listener::local_addr(&listener).unwrap().addr.port();
+    let app = print_jobber::router(state).into_make_service_with_connect_info::<print_jobber::source::Peer>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let url = format!("https://localhost:{}", port);
+
+    let anonymous = reqwest::Client::builder().danger_accept_invalid_certs(true).build().unwrap();
+    assert!(anonymous.post(&url).body("no cert").send().await.is_err());
+
+    let identity = reqwest::Identity::from_pem(format!("{}{}", client_cert.pem(), client_key.serialize_pem()).as_bytes()).unwrap();
+    let client = reqwest::Client::builder().danger_accept_invalid_certs(true).identity(identity).build().unwrap();
+    let printed = client.post(&url).body("from the kitchen").send().await.unwrap();
+    assert_eq!(printed.status(), 200);
+    driver.wait_for_jobs(1).await;
+
+    let jobs: serde_json::Value = client.get(format!("{}/jobs", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(jobs[0]["source"], "cert:kitchen");
+    let audit: serde_json::Value = client.get(format!("{}/audit", url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(audit[0]["who"], "cert:kitchen");
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn grpc_uses_tls_and_refuses_clients_without_a_certificate() {
+    use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName, pem::PemObject};
+    use tokio::io::AsyncReadExt;
+    use tokio_rustls::{TlsConnector, rustls};
+
+    let data_dir = std::env::temp_dir().join(format!("print-jobber-grpc-tls-{}", std::process::id()));
+    let (client_cert, client_key) = client_certificate(&data_dir);
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let ca = data_dir.join("ca.pem").display().to_string();
+    let (_url, _driver) = spawn_server(&[("GRPC_PORT", &port.to_string()), ("DATA_DIR", &data_dir.display().to_string()), ("TLS_CLIENT_CA", &ca)]).await;
+    let mut tcp = None;
+    for _ in 0..50 {
+        match tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+            Ok(connected) => {
+                tcp = Some(connected);
+                break;
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    }
+    drop(tcp.expect("gRPC server never came up"));
+
+    // Trusts the certificate the server generated, and asks for HTTP/2 as gRPC does.
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_file(data_dir.join("tls/cert.pem")).unwrap()).unwrap();
+    let connect = |identity: Option<(CertificateDer<'static>, PrivateKeyDer<'static>)>| {
+        let builder = rustls::ClientConfig::builder().with_root_certificates(roots.clone());
+        let mut config = match identity {
+            Some((cert, key)) => builder.with_client_auth_cert(vec![cert], key).unwrap(),
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        async move {
+            let tcp = tokio::net::TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+            let mut tls = TlsConnector::from(std::sync::Arc::new(config)).connect(ServerName::try_from("localhost").unwrap(), tcp).await?;
+            // The server opens HTTP/2 with its settings, or alerts that the client has no certificate.
+            let mut settings = [0u8; 9];
+            tls.read_exact(&mut settings).await.map(|_| settings)
+        }
+    };
+
+    assert!(connect(None).await.is_err());
+    let key = PrivateKeyDer::try_from(client_key.serialize_der()).unwrap();
+    let settings = connect(Some((client_cert.der().clone(), key))).await.unwrap();
+    // An HTTP/2 SETTINGS frame.
+    assert_eq!(settings[3], 0x4);
+    std::fs::remove_dir_all(data_dir).unwrap();
+}
+
+#[tokio::test]
+async fn gzip_and_deflate_bodies_are_decompressed() {
+    use flate2::{
+        Compression,
+        write::{GzEncoder, ZlibEncoder},
+    };
+    use std::io::Write;
+
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(b"squeezed through gzip").unwrap();
+    let mut deflate = ZlibEncoder::new(Vec::new(), Compression::default());
+    deflate.write_all(b"squeezed through deflate").unwrap();
+
+    for (encoding, body) in [("gzip", gzip.finish().unwrap()), ("deflate", deflate.finish().unwrap())] {
+        let response = client.post(&url).header("content-encoding", encoding).body(body).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let jobs = driver.wait_for_jobs(2).await;
+    assert!(jobs[0].contains("squeezed through gzip"));
+    assert!(jobs[1].contains("squeezed through deflate"));
+
+    let brotli = client.post(&url).header("content-encoding", "br").body("not really").send().await.unwrap();
+    assert_eq!(brotli.status(), 415);
+}
+
+#[tokio::test]
+async fn tables_get_box_drawing_borders_sized_to_the_paper() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+
+    let csv = "item,qty,note\nCoffee,2,\"oat milk, no sugar\"\nCroissant,1,\n";
+    let response = client.post(format!("{}/?format=csv", url)).body(csv).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let table = serde_json::json!({"table": [{"name": "Ada", "score": 3}, {"name": "Grace", "score": 5}]});
+    let response = client.post(&url).json(&table).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let jobs = driver.wait_for_jobs(2).await;
+    // ┌, ─, ┬ and ├ in PC437.
+    let raw = &driver.jobs()[0];
+    for byte in [0xDA, 0xC4, 0xC2, 0xC3] {
+        assert!(raw.contains(&byte), "missing box-drawing byte {:#x}", byte);
+    }
+    assert!(jobs[0].contains(" item "));
+    assert!(jobs[0].contains(" oat milk, no sugar "));
+    assert!(jobs[1].contains(" Grace "));
+    assert!(!jobs[1].contains("\"table\""));
+
+    let wide = vec!["x"; 20].join(",");
+    let response = client.post(format!("{}/?format=csv", url)).body(wide).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+}
+
+#[tokio::test]
+async fn serves_on_a_unix_socket_replacing_a_stale_one() {
+    let path = std::env::temp_dir().join(format!("print-jobber-{}.sock", std::process::id()));
+    drop(print_jobber::systemd::bind_unix(&path).unwrap());
+    assert!(path.exists());
+    let listener = print_jobber::systemd::bind_unix(&path).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let listener = tokio::net::UnixListener::from_std(listener).unwrap();
+
+    let driver = MockDriver::new();
+    let state = AppState::new(Config::from_vars(|_| None), Some(PrinterDriver::Mock(driver.clone())));
+    print_jobber::spawn_background(&state);
+    let app = print_jobber::router(state).into_make_service_with_connect_info::<print_jobber::source::Peer>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let client = reqwest::Client::builder().unix_socket(path.clone()).build().unwrap();
+    let printed = client.post("http://localhost/").body("over the socket").send().await.unwrap();
+    assert_eq!(printed.status(), 200);
+    assert!(driver.wait_for_jobs(1).await[0].contains("over the socket"));
+    let jobs: serde_json::Value = client.get("http://localhost/jobs").send().await.unwrap().json().await.unwrap();
+    assert_eq!(jobs[0]["source"], "http:127.0.0.1");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn wide_text_can_be_turned_to_run_along_the_paper() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+
+    let row = |cell: &str| [cell; 12].join(",");
+    let csv: Vec<String> = std::iter::once(row("heading")).chain((0..20).map(|i| row(&format!("value {:02}", i)))).collect();
+    let response = client.post(format!("{}/?format=csv&rotate=90", url)).body(csv.join("\n")).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.post(format!("{}/?rotate=180", url)).body("upside down").send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    driver.wait_for_jobs(1).await;
+    let job = &driver.jobs()[0];
+    // GS v 0 headers: bytes per row across the paper, then rows along it.
+    let images: Vec<(usize, usize)> = job
+        .windows(8)
+        .filter(|w| w.starts_with(b"\x1dv0"))
+        .map(|w| (u16::from_le_bytes([w[4], w[5]]) as usize, u16::from_le_bytes([w[6], w[7]]) as usize))
+        .collect();
+    // 24 lines with the borders, in strips of up to 16 that fit across 576 dots.
+    assert_eq!(images.len(), 2);
+    assert_eq!(images[0].0, 16 * 34 / 8);
+    assert!(images[1].0 < images[0].0);
+    // Each strip as long as a table far wider than 48 characters, in 12-dot cells.
+    assert!(images.iter().all(|&(_, rows)| rows == images[0].1 && rows > 120 * 12));
+    // Right-aligned, so the first line meets the paper's edge.
+    assert!(job.windows(5).any(|w| w == b"\x1ba\x02\x1dv"));
+    assert!(!job.windows(8).any(|w| w == b"value 01"));
+}
+
+#[tokio::test]
+async fn text_can_be_printed_in_larger_characters() {
+    let (url, driver) = spawn_server(&[]).await;
+    let client = reqwest::Client::new();
+
+    let sign = "Meeting in progress, please knock before coming in";
+    let response = client.post(format!("{}/?size=2x2", url)).body(sign).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.post(format!("{}/?size=9x1", url)).body(sign).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let jobs = driver.wait_for_jobs(1).await;
+    // GS ! with double width and height, then back to normal.
+    let text = &jobs[0];
+    let start = text.find("\x1d!\x11").expect("double size");
+    let end = text.find("\x1d!\x00").expect("normal size");
+    // Wrapped at 24 characters, half the paper's 48.
+    let lines: Vec<&str> = text[start + 3..end].lines().collect();
+    assert_eq!(lines, ["Meeting in progress,", "please knock before", "coming in"]);
+}